@@ -243,6 +243,17 @@ blueprint!{
             return self.vaults[&addresses[0]].amount() * self.vaults[&addresses[1]].amount()
         }
 
+        /// Gets the reserves of the two tokens held by this liquidity pool.
+        /// 
+        /// # Returns:
+        /// 
+        /// `HashMap<ResourceAddress, Decimal>` - A hashmap of the amount of each token in the reserves of the pool.
+        pub fn reserves(&self) -> HashMap<ResourceAddress, Decimal> {
+            return self.vaults.iter()
+                .map(|(address, vault)| (*address, vault.amount()))
+                .collect::<HashMap<ResourceAddress, Decimal>>();
+        }
+
         /// Calculates the amount of output that can be given for for a given amount of input.
         /// 
         /// This method calculates the amount of output tokens that would be received for a given amount of an input
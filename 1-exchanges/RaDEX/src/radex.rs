@@ -99,6 +99,35 @@ blueprint!{
             );
         }
 
+        /// Gets the reserves of the liquidity pool for the given pair of tokens.
+        /// 
+        /// This method is used to allow callers, such as investment vehicles holding tracking tokens, to value their
+        /// share of a liquidity pool at the pool's current reserves.
+        /// 
+        /// This method performs a number of checks before the reserves are obtained:
+        /// 
+        /// * **Check 1:** Checks that there does exist a liquidity pool for the given pair of tokens.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `address1` (ResourceAddress) - The resource address of the first token.
+        /// * `address2` (ResourceAddress) - The resource address of the second token.
+        /// 
+        /// # Returns:
+        /// 
+        /// * `HashMap<ResourceAddress, Decimal>` - A hashmap of the amount of each token in the reserves of the pool.
+        pub fn pool_reserves(
+            &self,
+            address1: ResourceAddress,
+            address2: ResourceAddress
+        ) -> HashMap<ResourceAddress, Decimal> {
+            // Checking if there does exist a liquidity pool for the given pair of tokens
+            self.assert_pool_exists(address1, address2, String::from("DEX Pool Reserves"));
+
+            let sorted_addresses: (ResourceAddress, ResourceAddress) = sort_addresses(address1, address2);
+            return self.liquidity_pools[&sorted_addresses].reserves();
+        }
+
         /// Creates a new liquidity pool in the DEX.
         /// 
         /// This method is used to create a new liquidity pool between the two provided tokens on RaDEX.
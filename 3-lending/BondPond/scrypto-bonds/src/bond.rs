@@ -1,4 +1,5 @@
 use scrypto::prelude::*;
+use crate::pricing::{bond_count, BondTerms};
 
 
 #[derive(NonFungibleData)]
@@ -6,9 +7,19 @@ pub struct IssuerData {
     pub bond_id: u64,
 }
 
+// Each bond is a non fungible so that we know how many coupons were already claimed with it
+#[derive(NonFungibleData)]
+pub struct BondData {
+    pub bond_id: u64,
+    #[scrypto(mutable)]
+    pub coupons_claimed: u64,
+}
+
 blueprint! {
 
     // Bond Definition
+    // coupon_epoch is the number of epochs between two coupon payments
+    // maturity_epoch is the epoch when the face value can be redeemed
     struct BondToken {
         token_supply: Vault,
         repayment_vault: Vault,
@@ -19,6 +30,12 @@ blueprint! {
         issue_price: Decimal, 
         issuer_badge: ResourceAddress, // Issuer holds identity NFT
         issue_epoch: u64,
+        internal_admin_badge: Vault, // Used to update the claimed coupons, burn redeemed bonds and mark defaults
+        bond_address: ResourceAddress,
+        bonds_issued: u64, // Bonds taken out of the supply by the issuer
+        bonds_redeemed: u64,
+        coupons_claimed: u64, // Total number of coupons claimed by all bonds
+        default_epoch: Option<u64>, // Set when the issuer did not provide enough funds for a due payment
     }
 
     impl BondToken {
@@ -38,16 +55,26 @@ blueprint! {
                 .burnable(rule!(deny_all), LOCKED)
                 .restrict_withdraw(rule!(deny_all), LOCKED)
                 .initial_supply(issuer_badge_data);    
+
+            let internal_admin_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Bond Internal Admin Badge")
+                .initial_supply(dec!("1"));
             
-            // We only allow the issuer to burn the token
+            // We only allow the issuer to burn the token, the component burns the redeemed bonds
             let burn_rule: AccessRule = rule!( 
-                require(issuer_badge.resource_address()) 
+                require(issuer_badge.resource_address()) || require(internal_admin_badge.resource_address())
             );
 
+            let mut bond_data = Vec::new();
+            for i in 0..supply {
+                bond_data.push((NonFungibleId::from_u64(i as u64),
+                    BondData { bond_id: bond_id, coupons_claimed: 0 }));
+            }
+
             // Bond Token Supply Bucket
-            let new_bond_bucket: Bucket = ResourceBuilder::new_fungible()
+            let new_bond_bucket: Bucket = ResourceBuilder::new_non_fungible()
                 .metadata("name", "BondToken")
-                .divisibility(DIVISIBILITY_NONE)
                 .metadata(
                     "description",
                     "A bond token used to recieve the principle and to be resold",
@@ -57,15 +84,24 @@ blueprint! {
                 .metadata("coupon_epoch", coupon_epoch.to_string())
                 .metadata("maturity_epoch", maturity_epoch.to_string())
                 .metadata("coupon_rate", coupon_rate.to_string())
+                .metadata("defaulted", "false")
                 .burnable(
                     burn_rule.clone(), 
                     MUTABLE(burn_rule.clone())
                 )
-                .initial_supply(supply);
+                .updateable_non_fungible_data(rule!(require(internal_admin_badge.resource_address())), LOCKED)
+                .updateable_metadata(rule!(require(internal_admin_badge.resource_address())), LOCKED)
+                .initial_supply(bond_data);
             
             let issue_epoch:u64 = Runtime::current_epoch();
+
+            let rules: AccessRules = AccessRules::new()
+                .method("withdraw_bonds", rule!(require(issuer_badge.resource_address())))
+                .method("withdraw_surplus", rule!(require(issuer_badge.resource_address())))
+                .default(rule!(allow_all));
             
             let component = Self {
+                bond_address: new_bond_bucket.resource_address(),
                 token_supply: Vault::with_bucket(new_bond_bucket),
                 repayment_vault: Vault::new(RADIX_TOKEN),
                 face_value: face_value,
@@ -75,18 +111,196 @@ blueprint! {
                 issue_price: issue_price, 
                 issuer_badge: issuer_badge.resource_address(),
                 issue_epoch: issue_epoch,
+                internal_admin_badge: Vault::with_bucket(internal_admin_badge),
+                bonds_issued: 0,
+                bonds_redeemed: 0,
+                coupons_claimed: 0,
+                default_epoch: None,
             }
             .instantiate()
+            .add_access_check(rules)
             .globalize();
 
             return (component, issuer_badge);
         }
-        
-        // // Withdraw principal if available, or maturity amount
-        // // TODO
-        // pub fn withdraw() -> Bucket {
 
-        // }
+        // Issuer takes bonds out of the supply to sell them on the market.
+        // Coupons that are already due are not paid to bonds issued after their period
+        pub fn withdraw_bonds(&mut self, amount: Decimal) -> Bucket {
+            let bonds: Bucket = self.token_supply.take(amount);
+            let coupons_due: u64 = self.coupons_due();
+            for bond in bonds.non_fungibles::<BondData>() {
+                let mut data: BondData = bond.data();
+                data.coupons_claimed = coupons_due;
+                self.internal_admin_badge.authorize(|| bond.update_data(data));
+            }
+            let count: u64 = bond_count(bonds.amount());
+            self.bonds_issued += count;
+            self.coupons_claimed += coupons_due * count;
+            return bonds;
+        }
+
+        // Issuer escrows or tops up the XRD used to pay coupons and face value
+        pub fn deposit_repayment(&mut self, payment: Bucket) {
+            assert!(payment.resource_address() == RADIX_TOKEN, "Repayments are made in XRD");
+            self.repayment_vault.put(payment);
+        }
+
+        // Issuer can take back whatever is not needed to pay the remaining coupons and face values
+        pub fn withdraw_surplus(&mut self) -> Bucket {
+            assert!(self.default_epoch.is_none(), "Bond is in default, funds belong to the holders");
+            let obligations: Decimal = self.remaining_obligations();
+            if self.repayment_vault.amount() <= obligations {
+                return Bucket::new(RADIX_TOKEN);
+            }
+            return self.repayment_vault.take(self.repayment_vault.amount() - obligations);
+        }
+
+        // Holders claim every coupon that is due and was not claimed yet with their bonds.
+        // Each coupon can be claimed only once per bond
+        pub fn claim_coupons(&mut self, bond_proof: Proof) -> Bucket {
+            assert!(bond_proof.resource_address() == self.bond_address, "Not a bond of this issue");
+            assert!(self.default_epoch.is_none(), "Bond is in default, redeem the bonds for a share of the repayment vault");
+
+            let mut payment: Bucket = Bucket::new(RADIX_TOKEN);
+            if self.check_default() {
+                return payment;
+            }
+
+            let coupons_due: u64 = self.coupons_due();
+            for bond in bond_proof.non_fungibles::<BondData>() {
+                payment.put(self.pay_coupons(&bond, coupons_due));
+            }
+            return payment;
+        }
+
+        // At maturity holders get the face value and the remaining coupons and their bonds are burned.
+        // If the bond defaulted, holders get a share of the repayment vault instead, pro rata to what their bonds
+        // are still owed: the face value and the coupons they did not claim
+        pub fn redeem(&mut self, bonds: Bucket) -> Bucket {
+            assert!(bonds.resource_address() == self.bond_address, "Not a bond of this issue");
+            assert!(!bonds.is_empty(), "Did not provide any bonds");
+            self.check_default();
+            assert!(self.default_epoch.is_some() || Runtime::current_epoch() >= self.maturity_epoch, "Bond has not matured yet");
+
+            let bond_amount: Decimal = bonds.amount();
+            let payment: Bucket;
+            if self.default_epoch.is_some() {
+                let total_coupons: u64 = self.total_coupons();
+                let mut unclaimed_coupons: u64 = 0;
+                for bond in bonds.non_fungibles::<BondData>() {
+                    unclaimed_coupons += total_coupons - bond.data().coupons_claimed;
+                }
+                let entitlement: Decimal = self.coupon_amount() * Decimal::from(unclaimed_coupons)
+                    + self.face_value * bond_amount;
+                let outstanding: Decimal = self.remaining_obligations();
+                payment = self.repayment_vault.take(self.repayment_vault.amount() * entitlement / outstanding);
+                // The redeemed bonds are settled, their coupons no longer count as owed
+                self.coupons_claimed += unclaimed_coupons;
+            } else {
+                let mut coupons: Bucket = Bucket::new(RADIX_TOKEN);
+                let coupons_due: u64 = self.coupons_due();
+                for bond in bonds.non_fungibles::<BondData>() {
+                    coupons.put(self.pay_coupons(&bond, coupons_due));
+                }
+                coupons.put(self.repayment_vault.take(self.face_value * bond_amount));
+                payment = coupons;
+            }
+
+            self.bonds_redeemed += bond_count(bond_amount);
+            self.internal_admin_badge.authorize(|| bonds.burn());
+            return payment;
+        }
+
+        // Anyone can check if the issuer missed a payment. The default is recorded on the component
+        // and in the metadata of the bond resource
+        pub fn check_default(&mut self) -> bool {
+            if self.default_epoch.is_some() {
+                return true;
+            }
+            if self.repayment_vault.amount() >= self.due_obligations() {
+                return false;
+            }
+
+            let current_epoch: u64 = Runtime::current_epoch();
+            self.default_epoch = Some(current_epoch);
+
+            let bond_resource_manager: &ResourceManager = borrow_resource_manager!(self.bond_address);
+            let mut metadata: HashMap<String, String> = bond_resource_manager.metadata();
+            metadata.insert("defaulted".to_string(), "true".to_string());
+            metadata.insert("default_epoch".to_string(), current_epoch.to_string());
+            metadata.insert("missed_coupon".to_string(), self.coupons_due().to_string());
+            self.internal_admin_badge.authorize(|| bond_resource_manager.update_metadata(metadata));
+
+            info!("Bond {} defaulted at epoch {}", self.bond_address, current_epoch);
+            return true;
+        }
+
+        pub fn get_bond_address(&self) -> ResourceAddress {
+            return self.bond_address;
+        }
+
+        // Terms used by the market to convert between price and yield
+        pub fn get_terms(&self) -> BondTerms {
+            return BondTerms {
+                face_value: self.face_value,
+                coupon_rate: self.coupon_rate,
+                coupon_epoch: self.coupon_epoch,
+                maturity_epoch: self.maturity_epoch,
+                issue_epoch: self.issue_epoch,
+            };
+        }
+
+        // Returns (coupons due so far, total coupons, coupon per bond, face value, default epoch)
+        pub fn get_bond_status(&self) -> (u64, u64, Decimal, Decimal, Option<u64>) {
+            return (self.coupons_due(), self.total_coupons(), self.coupon_amount(), self.face_value, self.default_epoch);
+        }
+
+        // Pays the coupons of one bond that were not claimed yet, as long as the repayment vault allows it
+        fn pay_coupons(&mut self, bond: &NonFungible<BondData>, coupons_due: u64) -> Bucket {
+            let mut data: BondData = bond.data();
+            if data.coupons_claimed >= coupons_due {
+                return Bucket::new(RADIX_TOKEN);
+            }
+
+            let unclaimed: u64 = coupons_due - data.coupons_claimed;
+            let payment: Bucket = self.repayment_vault.take(self.coupon_amount() * Decimal::from(unclaimed));
+            data.coupons_claimed = coupons_due;
+            self.coupons_claimed += unclaimed;
+            self.internal_admin_badge.authorize(|| bond.update_data(data));
+            return payment;
+        }
+
+        fn coupon_amount(&self) -> Decimal {
+            return self.face_value * self.coupon_rate;
+        }
+
+        fn total_coupons(&self) -> u64 {
+            return (self.maturity_epoch - self.issue_epoch) / self.coupon_epoch;
+        }
+
+        // Number of coupon periods that have ended so far
+        fn coupons_due(&self) -> u64 {
+            let elapsed: u64 = Runtime::current_epoch() - self.issue_epoch;
+            return std::cmp::min(elapsed / self.coupon_epoch, self.total_coupons());
+        }
+
+        // What the issuer must have in the repayment vault right now
+        fn due_obligations(&self) -> Decimal {
+            let unclaimed_coupons: u64 = self.bonds_issued * self.coupons_due() - self.coupons_claimed;
+            let mut obligations: Decimal = self.coupon_amount() * Decimal::from(unclaimed_coupons);
+            if Runtime::current_epoch() >= self.maturity_epoch {
+                obligations += self.face_value * Decimal::from(self.bonds_issued - self.bonds_redeemed);
+            }
+            return obligations;
+        }
+
+        // What the issuer must pay until maturity
+        fn remaining_obligations(&self) -> Decimal {
+            let unclaimed_coupons: u64 = self.bonds_issued * self.total_coupons() - self.coupons_claimed;
+            return self.coupon_amount() * Decimal::from(unclaimed_coupons)
+                + self.face_value * Decimal::from(self.bonds_issued - self.bonds_redeemed);
+        }
 
     }
 }
\ No newline at end of file
@@ -1,24 +1,40 @@
 use scrypto::prelude::*;
 use crate::bond::BondToken;
-use crate::order::Order;
+use crate::order::{Order, OrderSide};
+use crate::pricing::{bond_count, BondTerms, Quote};
 
+// The ticket keeps the address of its order, which is dropped from the book once it is filled or cancelled
 #[derive(NonFungibleData)]
-pub struct SellTicket {
+pub struct OrderTicket {
     order_number: u64,
-    price: Decimal,
     bond_id: u64,
+    side: OrderSide,
+    order_address: ComponentAddress,
+}
+
+// An order in the book with its current clean price
+struct BookEntry {
+    price: Decimal,
+    order_id: u64,
+    remaining: u64,
+    escrow: Decimal,
+    address: ComponentAddress,
 }
 
 // No AMMs, future work.
+// Orders are matched with price-time priority: the best price first, then the oldest order.
+// Trades execute at the price of the order that was in the book first and the buyer pays
+// the accrued interest since the last coupon on top of the clean price
 blueprint! {
     struct BondMarket {
         bonds: LazyMap<u64, ComponentAddress>, // Issuer details w Vault of Bonds, Vault of XRD to repay
-        market: HashMap<u64, HashMap<u64, (Decimal, ComponentAddress)>>, // Bond id mapped to Order details
+        market: HashMap<u64, HashMap<u64, ComponentAddress>>, // Bond id mapped to its open orders
         dead_vaults: Vec<Vault>, // For getting rid of dead vaults
         order_count: u64,   // For getting count of orders
         issuer_count: u64,  // For getting number of bonds issued in market
         account_count: u64, // For keeping track of credit rating NFT IDs
         internal_admin_badge: Vault,
+        order_ticket: ResourceAddress, // Badge to withdraw the proceeds or cancel an order
     }
 
     impl BondMarket {
@@ -35,6 +51,13 @@ blueprint! {
                 )
                 .initial_supply(dec!("1"));
 
+            let order_ticket: ResourceAddress = ResourceBuilder::new_non_fungible()
+                .metadata("name", "Order badge to withdraw money or bonds when filled")
+                .mintable(rule!(require(internal_admin_badge.resource_address())), LOCKED)
+                .burnable(rule!(require(internal_admin_badge.resource_address())), LOCKED)
+                .restrict_withdraw(rule!(require(internal_admin_badge.resource_address())), LOCKED)
+                .no_initial_supply();
+
             // TODO: Add access rules 
 
             // Bond Market Instantiation
@@ -46,6 +69,7 @@ blueprint! {
                 issuer_count: 0,
                 account_count: 0,
                 internal_admin_badge: Vault::with_bucket(internal_admin_badge),
+                order_ticket: order_ticket,
             }
             .instantiate()
             .globalize();
@@ -58,7 +82,11 @@ blueprint! {
         pub fn issue_bond(&mut self, face_value: Decimal, coupon_epoch: u64, 
             maturity_epoch: u64, coupon_rate: Decimal, issue_price: Decimal, supply:u32) -> Bucket {
             
-            // BACKLOG: Add coupon epoch check
+            let current_epoch: u64 = Runtime::current_epoch();
+            assert!(maturity_epoch > current_epoch, "Maturity must be in the future");
+            assert!(coupon_epoch > 0 && coupon_epoch <= maturity_epoch - current_epoch, 
+                "Coupon period must be positive and end before maturity");
+            assert!(face_value > Decimal::zero(), "Face value must be positive");
 
             let (new_bond_component, issuer_badge) = BondToken::instantiate_bond(
                 self.issuer_count, face_value, coupon_epoch, maturity_epoch,
@@ -71,117 +99,282 @@ blueprint! {
             return issuer_badge;
         }
 
-        pub fn sell_bond(&mut self, bonds: Bucket, price: Decimal) -> Bucket {
+        // Get the bond component where holders claim coupons and redeem the bonds
+        pub fn get_bond_component(&self, bond_id: u64) -> ComponentAddress {
+            return self.bonds.get(&bond_id).expect("No bond issued with that id");
+        }
 
+        // Place an ask for the bonds, quoted as a clean price or as a yield to maturity.
+        // Returns the order ticket used to withdraw the XRD or cancel the order
+        pub fn place_ask(&mut self, bonds: Bucket, quote: Quote) -> Bucket {
             assert!(bonds.amount() > Decimal::zero(), "Did not provide any bonds");
+            let bond_address: ResourceAddress = bonds.resource_address();
+            let bond_id: u64 = self.get_bond_id(bond_address);
+            let quantity: u64 = bond_count(bonds.amount());
+            self.assert_tradable(bond_id);
 
-            let order_count:u64 = self.order_count;
+            return self.add_order(bond_id, OrderSide::Ask, quote, bonds, quantity, bond_address);
+        }
 
-            // Get the bond details: Particularly care about bond_address, and bond_id
-            let bond_address = bonds.resource_address();
-            let bond_resource_manager: &ResourceManager = borrow_resource_manager!(bond_address);
-            let bond_metadata:HashMap<String, String> = bond_resource_manager.metadata();
-            let bond_id_str: String = bond_metadata.get("bond_id").unwrap().to_string();
-            let bond_id: u64 = bond_id_str.parse::<u64>().unwrap();
-            
-            // Create seller badge NFT data encoding
-            let mut seller_badge_data = Vec::new();
-            seller_badge_data.push((NonFungibleId::random(),
-                SellTicket { order_number: order_count, 
-                             price: price, 
-                             bond_id: bond_id }));
-
-            // Create seller badge NFT based on seller_badge_data
-            let seller_badge: Bucket = ResourceBuilder::new_non_fungible()
-                .metadata("name", "Sell order badge to withdraw money when sold")
-                .burnable(rule!(require(self.internal_admin_badge.resource_address())), LOCKED)
-                .restrict_withdraw(rule!(require(self.internal_admin_badge.resource_address())), LOCKED)
-                .initial_supply(seller_badge_data);
-
-            // Add bond to market listing            
-            self.market.insert(bond_id, 
-                HashMap::from([(order_count, (price,Order::new(price, bonds, seller_badge.resource_address(), order_count)))]));   
-            self.order_count += 1;
-            return seller_badge;
+        // Place a bid for `quantity` bonds, quoted as a clean price or as a yield to maturity.
+        // The payment must cover the clean price and the accrued interest, whatever is left can be withdrawn
+        pub fn place_bid(&mut self, bond_address: ResourceAddress, payment: Bucket, quantity: u64, quote: Quote) -> Bucket {
+            assert!(payment.resource_address() == RADIX_TOKEN, "Bonds are paid in XRD");
+            assert!(quantity > 0, "Did not ask for any bonds");
+            let bond_id: u64 = self.get_bond_id(bond_address);
+            self.assert_tradable(bond_id);
+
+            return self.add_order(bond_id, OrderSide::Bid, quote, payment, quantity, bond_address);
         }
 
-        pub fn withdraw_sell(&mut self, sell_proof: Proof) -> Bucket {
+        // Fixed price ask
+        pub fn sell_bond(&mut self, bonds: Bucket, price: Decimal) -> Bucket {
+            return self.place_ask(bonds, Quote::Price(price));
+        }
 
-            // Get sell_id from proof of posession of seller badge NFT
-            let sell_ticket: SellTicket = sell_proof.non_fungible::<>().data();
+        // Buy from the asks in the book until the payment is exhausted, returns the bonds and the change
+        pub fn buy_bond(&mut self, bond_address: ResourceAddress, payment: Bucket) -> (Bucket, Bucket) {
+            let bond_id: u64 = self.get_bond_id(bond_address);
+            self.assert_tradable(bond_id);
+            let (bond_component, terms) = self.get_bond_terms(bond_id);
+            let epoch: u64 = Runtime::current_epoch();
+            let accrued: Decimal = terms.accrued_interest(epoch);
 
-            
-            // Assert user's proof contains one of the sold market stuff
-            assert!(self.market.contains_key(&sell_ticket.bond_id), "No bond found with that proof");
-            
-            let bond_listings: &HashMap<u64, (Decimal, ComponentAddress)> = &*self.market.get(&sell_ticket.bond_id).unwrap();
+            let mut purchased_bonds: Bucket = Bucket::new(bond_address);
+            let mut remaining_payment: Bucket = payment;
+            let mut filled: Vec<u64> = Vec::new();
+            let (_, asks) = self.get_book(bond_id, &terms, epoch);
+            for ask in asks {
+                let dirty_price: Decimal = ask.price + accrued;
+                let affordable: u64 = bond_count((remaining_payment.amount() / dirty_price).floor());
+                let quantity: u64 = std::cmp::min(affordable, ask.remaining);
+                if quantity == 0 {
+                    break;
+                }
+
+                let order: Order = ask.address.into();
+                let payment_taken: Bucket = remaining_payment.take(dirty_price * Decimal::from(quantity));
+                purchased_bonds.put(self.fill_ask(&order, bond_component, payment_taken, quantity));
+                info!("Bought {} bonds at clean price {} + accrued interest {}", quantity, ask.price, accrued);
+                if quantity == ask.remaining {
+                    filled.push(ask.order_id);
+                }
+            }
+            self.remove_orders(bond_id, filled);
 
-            let (_, corresponding_order_address) = bond_listings.get(&sell_ticket.order_number).unwrap();
-            let corresponding_order: Order = (*corresponding_order_address).into();
+            return (purchased_bonds, remaining_payment);
+        }
 
-            let unclaimed_funds: Bucket = corresponding_order.withdraw(sell_proof);
-            return unclaimed_funds;
+        // Anyone can match the book, yield quotes move with time so they can cross without a new order
+        pub fn match_orders(&mut self, bond_address: ResourceAddress) {
+            let bond_id: u64 = self.get_bond_id(bond_address);
+            self.match_book(bond_id);
         }
 
-        fn get_bond_id(&self, bond_address: ResourceAddress) -> u64 {
-            // Retrieve bond_id based on the bond resourceadress
-            let bond_resource_manager: &ResourceManager = borrow_resource_manager!(bond_address);
-            let bond_id: u64 = bond_resource_manager.metadata().get("bond_id").unwrap().parse::<u64>().unwrap();
+        // Withdraw the XRD of a filled ask or the bonds of a filled bid
+        pub fn withdraw_order(&mut self, ticket_proof: Proof) -> Bucket {
+            let order: Order = self.get_ticket_order(&ticket_proof).into();
+            return self.internal_admin_badge.authorize(|| order.withdraw());
+        }
 
-            // Assert specified bond contains one of the sold market bonds
-            assert!(self.market.contains_key(&bond_id), "No bond found with that address");
+        // Cancel what is left of an order and get back the escrowed bonds or XRD
+        pub fn cancel_order(&mut self, ticket_proof: Proof) -> Bucket {
+            let ticket: OrderTicket = self.get_ticket(&ticket_proof);
+            self.remove_orders(ticket.bond_id, vec![ticket.order_number]);
+            let order: Order = ticket.order_address.into();
+            return self.internal_admin_badge.authorize(|| order.cancel());
+        }
 
-            return bond_id;
+        // Converts a quote to (clean price, yield to maturity, accrued interest) at the current epoch
+        pub fn get_quote(&self, bond_address: ResourceAddress, quote: Quote) -> (Decimal, Decimal, Decimal) {
+            let bond_id: u64 = self.get_bond_id(bond_address);
+            let (_, terms) = self.get_bond_terms(bond_id);
+            let epoch: u64 = Runtime::current_epoch();
+            let price: Decimal = terms.quote_price(quote, epoch);
+            return (price, terms.yield_to_maturity(price, epoch), terms.accrued_interest(epoch));
         }
 
-        fn get_bond_id_and_listings(&self, bond_address: ResourceAddress) -> (u64, BTreeMap<Decimal, ComponentAddress>) {
-            // Get the bond_id from the resource address
-            let bond_id = self.get_bond_id(bond_address);
+        // Returns the open orders as (order number, side, clean price, yield to maturity, remaining bonds),
+        // bids and asks in matching priority
+        pub fn list_orders(&self, bond_address: ResourceAddress) -> Vec<(u64, OrderSide, Decimal, Decimal, u64)> {
+            let bond_id: u64 = self.get_bond_id(bond_address);
+            let (_, terms) = self.get_bond_terms(bond_id);
+            let epoch: u64 = Runtime::current_epoch();
+            let (bids, asks) = self.get_book(bond_id, &terms, epoch);
+
+            info!(" ========================================== ");
+            info!(" Bond {} - accrued interest {}", bond_id, terms.accrued_interest(epoch));
+            info!(" ========================================== ");
+            let mut orders: Vec<(u64, OrderSide, Decimal, Decimal, u64)> = Vec::new();
+            for (side, entries) in [(OrderSide::Bid, bids), (OrderSide::Ask, asks)] {
+                for entry in entries {
+                    let ytm: Decimal = terms.yield_to_maturity(entry.price, epoch);
+                    info!(" | {:?} #{} | {} bonds | price {} | yield {}", side, entry.order_id, entry.remaining, entry.price, ytm);
+                    orders.push((entry.order_id, side, entry.price, ytm, entry.remaining));
+                }
+            }
+            return orders;
+        }
 
-            // Get the listing of that specific bond
-            let bond_listings: &HashMap<u64, (Decimal, ComponentAddress)> = &*self.market.get(&bond_id).unwrap();
+        fn add_order(&mut self, bond_id: u64, side: OrderSide, quote: Quote, escrow: Bucket, quantity: u64, 
+            bond_address: ResourceAddress) -> Bucket {
+            let order_number: u64 = self.order_count;
+            let order_address: ComponentAddress = Order::new(side, quote, escrow, quantity, bond_address, 
+                self.internal_admin_badge.resource_address(), order_number);
 
-            // Sort the bond order listings by price with BTreeMap
-            let listings_sorted: BTreeMap<Decimal, ComponentAddress> = 
-                                bond_listings.iter().map(|(_,v)| (v.0,v.1)).collect();
-            
-            return (bond_id,listings_sorted);
-        } 
+            let ticket: Bucket = self.internal_admin_badge.authorize(|| {
+                borrow_resource_manager!(self.order_ticket).mint_non_fungible(
+                    &NonFungibleId::from_u64(order_number),
+                    OrderTicket { order_number: order_number, bond_id: bond_id, side: side, order_address: order_address })
+            });
 
-        pub fn buy_bond(&mut self, bond_address: ResourceAddress, payment: Bucket) -> Bucket {
-           
-            // Get bond_id and sorted listings in the market for that bond
-            let (_bond_id,listings_sorted) = self.get_bond_id_and_listings(bond_address);
+            if !self.market.contains_key(&bond_id) {
+                self.market.insert(bond_id, HashMap::new());
+            }
+            self.market.get_mut(&bond_id).unwrap().insert(order_number, order_address);
+            self.order_count += 1;
 
-            // All the bonds purchased
-            let mut purchased_bonds: Bucket = Bucket::new(bond_address);
+            self.match_book(bond_id);
+            return ticket;
+        }
 
-            let mut remaining_payment: Bucket = payment;
-            // Buy up the bonds until the payment is exhausted
-            for (_price, order_addr) in listings_sorted {
-                let order: Order = order_addr.into();
-                let (bonds, change) = order.buy_bond(remaining_payment); 
-                remaining_payment = change;
-                purchased_bonds.put(bonds);
+        // Cross the best bid and the best ask as long as they overlap
+        fn match_book(&mut self, bond_id: u64) {
+            if !self.market.contains_key(&bond_id) {
+                return;
             }
+            let (bond_component, terms) = self.get_bond_terms(bond_id);
+            let bond: BondToken = bond_component.into();
+            let epoch: u64 = Runtime::current_epoch();
+            if epoch >= terms.maturity_epoch || bond.check_default() {
+                return;
+            }
+            let accrued: Decimal = terms.accrued_interest(epoch);
+
+            // The book is read once, the entries are updated locally as orders are filled
+            let (mut bids, mut asks) = self.get_book(bond_id, &terms, epoch);
+            let (mut bid_index, mut ask_index) = (0, 0);
+            let mut filled: Vec<u64> = Vec::new();
+            while bid_index < bids.len() && ask_index < asks.len() && bids[bid_index].price >= asks[ask_index].price {
+                let (best_bid, best_ask) = (&mut bids[bid_index], &mut asks[ask_index]);
+
+                // The order that was in the book first sets the price
+                let price: Decimal = if best_bid.order_id < best_ask.order_id { best_bid.price } else { best_ask.price };
+                let dirty_price: Decimal = price + accrued;
+
+                // A bid that cannot pay for one more bond is skipped
+                let affordable: u64 = bond_count((best_bid.escrow / dirty_price).floor());
+                let quantity: u64 = std::cmp::min(affordable, std::cmp::min(best_bid.remaining, best_ask.remaining));
+                if quantity == 0 {
+                    bid_index += 1;
+                    continue;
+                }
+
+                let bid: Order = best_bid.address.into();
+                let ask: Order = best_ask.address.into();
+                let cost: Decimal = dirty_price * Decimal::from(quantity);
+                let payment: Bucket = self.internal_admin_badge.authorize(|| bid.take_escrow(cost));
+                let bonds: Bucket = self.fill_ask(&ask, bond_component, payment, quantity);
+                self.internal_admin_badge.authorize(|| bid.settle(bonds, quantity));
+                info!("Matched {} bonds at clean price {} + accrued interest {}", quantity, price, accrued);
+
+                best_bid.escrow -= cost;
+                best_bid.remaining -= quantity;
+                best_ask.remaining -= quantity;
+                if best_bid.remaining == 0 {
+                    filled.push(best_bid.order_id);
+                    bid_index += 1;
+                }
+                if best_ask.remaining == 0 {
+                    filled.push(best_ask.order_id);
+                    ask_index += 1;
+                }
+            }
+            self.remove_orders(bond_id, filled);
+        }
 
-            return purchased_bonds;
+        // Filled and cancelled orders leave the book, their tickets still reach them to withdraw the proceeds
+        fn remove_orders(&mut self, bond_id: u64, order_ids: Vec<u64>) {
+            if let Some(orders) = self.market.get_mut(&bond_id) {
+                for order_id in order_ids {
+                    orders.remove(&order_id);
+                }
+            }
         }
 
+        // Pays the seller, including the coupons due on the bonds while they were listed, and returns the bonds
+        fn fill_ask(&self, ask: &Order, bond_component: ComponentAddress, payment: Bucket, quantity: u64) -> Bucket {
+            return self.internal_admin_badge.authorize(|| {
+                ask.collect_coupons(bond_component);
+                let bonds: Bucket = ask.take_escrow(Decimal::from(quantity));
+                ask.settle(payment, quantity);
+                bonds
+            });
+        }
 
-        // pub fn list_orders(&self, bond_address: ResourceAddress) {
+        // Open orders of a bond with their current clean price, bids by descending price and asks by ascending price,
+        // then by order number. Bids that cannot pay for one bond at their own price are left out
+        fn get_book(&self, bond_id: u64, terms: &BondTerms, epoch: u64) -> (Vec<BookEntry>, Vec<BookEntry>) {
+            let mut bids: Vec<BookEntry> = Vec::new();
+            let mut asks: Vec<BookEntry> = Vec::new();
+            let accrued: Decimal = terms.accrued_interest(epoch);
+
+            if let Some(orders) = self.market.get(&bond_id) {
+                for (_, order_address) in orders.iter() {
+                    let order: Order = (*order_address).into();
+                    let (side, quote, order_id, remaining, escrow) = order.get_info();
+                    if remaining == 0 {
+                        continue;
+                    }
+                    let price: Decimal = terms.quote_price(quote, epoch);
+                    let entry = BookEntry { price: price, order_id: order_id, remaining: remaining, escrow: escrow, address: *order_address };
+                    match side {
+                        OrderSide::Bid => if escrow >= price + accrued { bids.push(entry) },
+                        OrderSide::Ask => asks.push(entry),
+                    }
+                }
+            }
 
-        //     // Get bond_id and sorted listings in the market for that bond
-        //     let (bond_id,listings_sorted) = self.get_bond_id_and_listings(bond_address);
+            bids.sort_by(|a, b| b.price.cmp(&a.price).then(a.order_id.cmp(&b.order_id)));
+            asks.sort_by(|a, b| a.price.cmp(&b.price).then(a.order_id.cmp(&b.order_id)));
+            return (bids, asks);
+        }
 
-        //     info!(" ========================================== ");
-        //     info!("");
-        //     info!("");
-        //     info!(" ========================================== ");
-        //     info!(" |''''''''''''''''''''''''''''''''''''''''|");
+        fn get_ticket(&self, ticket_proof: &Proof) -> OrderTicket {
+            assert!(ticket_proof.resource_address() == self.order_ticket, "Invalid order ticket");
+            return ticket_proof.non_fungible::<OrderTicket>().data();
+        }
 
+        // Only the market mints tickets, so the order address they carry can be trusted
+        fn get_ticket_order(&self, ticket_proof: &Proof) -> ComponentAddress {
+            return self.get_ticket(ticket_proof).order_address;
+        }
 
-        // }
+        fn get_bond_terms(&self, bond_id: u64) -> (ComponentAddress, BondTerms) {
+            let bond_component: ComponentAddress = self.bonds.get(&bond_id).unwrap();
+            let bond: BondToken = bond_component.into();
+            return (bond_component, bond.get_terms());
+        }
 
+        fn assert_tradable(&self, bond_id: u64) {
+            let (bond_component, terms) = self.get_bond_terms(bond_id);
+            let bond: BondToken = bond_component.into();
+            // A missed payment counts even if nobody has recorded the default yet
+            assert!(!bond.check_default(), "Bond is in default");
+            assert!(Runtime::current_epoch() < terms.maturity_epoch, "Bond has matured");
+        }
+
+        fn get_bond_id(&self, bond_address: ResourceAddress) -> u64 {
+            // Retrieve bond_id based on the bond resourceadress
+            let bond_resource_manager: &ResourceManager = borrow_resource_manager!(bond_address);
+            let bond_id: u64 = bond_resource_manager.metadata().get("bond_id").unwrap().parse::<u64>().unwrap();
+
+            // Assert the bond was issued in this market, the metadata alone can be copied by any resource
+            let bond_component: ComponentAddress = self.bonds.get(&bond_id).expect("No bond found with that address");
+            let bond: BondToken = bond_component.into();
+            assert!(bond.get_bond_address() == bond_address, "Not a bond issued in this market");
+
+            return bond_id;
+        }
     }
 }
\ No newline at end of file
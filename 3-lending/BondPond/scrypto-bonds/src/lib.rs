@@ -1,7 +1,8 @@
 use scrypto::prelude::*;
 mod bond;
-mod order;
+pub mod order;
 mod bond_market;
+pub mod pricing;
 
 blueprint! {
     struct Hello {
@@ -1,37 +1,53 @@
 use scrypto::prelude::*;
+use crate::bond::BondToken;
+use crate::pricing::Quote;
+
+#[derive(Debug, Clone, Copy, PartialEq, TypeId, Encode, Decode, Describe)]
+pub enum OrderSide {
+    Bid,
+    Ask,
+}
 
 blueprint! {
+    // A resting order in the bond market.
+    // Asks escrow bonds and receive XRD, bids escrow XRD and receive bonds
     #[derive(Debug, sbor::Decode, sbor::Encode, sbor::Describe, sbor::TypeId)]
     pub struct  Order {
-        pub price: Decimal, // Price per bond
-        bond_store: Vault, // Contains n buckets
-        bond_count: u32,
-        tvl: Decimal,
-        payment: Vault,
+        side: OrderSide,
+        quote: Quote,
+        escrow: Vault, // Bonds for asks, XRD for bids
+        proceeds: Vault, // XRD for asks, bonds for bids
+        remaining: u64, // Bonds left to sell or buy
         bond_address: ResourceAddress,
-        withdraw_nft: ResourceAddress,
-        order_id: u64,
+        order_id: u64, // Also used for time priority
     }
 
     impl Order {
-        
-        pub fn new(price: Decimal, bonds: Bucket, withdraw_nft: ResourceAddress, order_id: u64) -> ComponentAddress {
 
+        pub fn new(side: OrderSide, quote: Quote, escrow: Bucket, quantity: u64, bond_address: ResourceAddress,
+            market_badge: ResourceAddress, order_id: u64) -> ComponentAddress {
+
+            // Only the market can fill, withdraw and cancel, it checks the order tickets
             let rules: AccessRules = AccessRules::new()
-                .method("withdraw", rule!(require(withdraw_nft)));
-            
-            let bond_amount_dec:Decimal = bonds.amount();
-            let bond_amount:u32 = bond_amount_dec.to_string().parse::<u32>().unwrap();
-            let bond_address = bonds.resource_address();
+                .method("take_escrow", rule!(require(market_badge)))
+                .method("settle", rule!(require(market_badge)))
+                .method("collect_coupons", rule!(require(market_badge)))
+                .method("withdraw", rule!(require(market_badge)))
+                .method("cancel", rule!(require(market_badge)))
+                .default(rule!(allow_all));
+
+            let proceeds_address: ResourceAddress = match side {
+                OrderSide::Ask => RADIX_TOKEN,
+                OrderSide::Bid => bond_address,
+            };
 
             let component = Self {
-                price: price,
-                bond_store: Vault::with_bucket(bonds),
-                bond_count: bond_amount,
-                tvl: price * Decimal::from(bond_amount),
-                payment: Vault::new(RADIX_TOKEN),
+                side: side,
+                quote: quote,
+                escrow: Vault::with_bucket(escrow),
+                proceeds: Vault::new(proceeds_address),
+                remaining: quantity,
                 bond_address: bond_address,
-                withdraw_nft: withdraw_nft,
                 order_id: order_id,
             }
             .instantiate()
@@ -41,47 +57,44 @@ blueprint! {
             return component;
         }
 
-        pub fn withdraw(&mut self, proof: Proof) -> Bucket {
-            if proof.resource_address() == self.withdraw_nft {
-                return self.payment.take_all();
-            } else {
-                return Bucket::new(RADIX_TOKEN);
-            }
+        // Returns (side, quote, order_id, remaining, escrow amount)
+        pub fn get_info(&self) -> (OrderSide, Quote, u64, u64, Decimal) {
+            return (self.side, self.quote, self.order_id, self.remaining, self.escrow.amount());
         }
 
-        // Return the (bond, change) 
-        pub fn buy_bond(&mut self, mut payment: Bucket) -> (Bucket, Bucket) {
-            // If insufficient funds to buy this bond, return empty bucket and change
-            if payment.amount() < self.price {
-                return (Bucket::new(self.bond_address), payment);
-            }
+        pub fn take_escrow(&mut self, amount: Decimal) -> Bucket {
+            return self.escrow.take(amount);
+        }
 
+        // Records a fill of `quantity` bonds paid with `assets`
+        pub fn settle(&mut self, assets: Bucket, quantity: u64) {
+            assert!(quantity <= self.remaining, "Fill larger than the order");
+            self.proceeds.put(assets);
+            self.remaining -= quantity;
+        }
 
-            let purchase_amount;
-            let purchase_number;
-            if payment.amount() > self.tvl {
-                purchase_amount = self.tvl;
-                purchase_number = Decimal::from(self.bond_count);
-            } else{
-                purchase_number = (payment.amount() / self.price).floor();
-                purchase_amount = purchase_number * self.price;
+        // Coupons that became due while the bonds were listed belong to the seller
+        pub fn collect_coupons(&mut self, bond_component: ComponentAddress) {
+            if self.side != OrderSide::Ask || self.escrow.is_empty() {
+                return;
             }
-
-            let payment_taken: Bucket = payment.take(purchase_amount);
-            self.payment.put(payment_taken);
-            return (self.bond_store.take(purchase_number), payment);
+            let bond: BondToken = bond_component.into();
+            let coupons: Bucket = bond.claim_coupons(self.escrow.create_proof());
+            self.proceeds.put(coupons);
         }
 
-        pub fn is_this_bond(&self, bond: ResourceAddress) -> bool {
-            return bond==self.bond_address;
+        pub fn withdraw(&mut self) -> Bucket {
+            return self.proceeds.take_all();
         }
 
-        pub fn get_price(&self) -> Decimal {
-            return self.price;
+        // Returns the unfilled escrow, the proceeds stay available through withdraw
+        pub fn cancel(&mut self) -> Bucket {
+            self.remaining = 0;
+            return self.escrow.take_all();
         }
 
-
+        pub fn is_this_bond(&self, bond: ResourceAddress) -> bool {
+            return bond==self.bond_address;
+        }
     }
-
-
-}
\ No newline at end of file
+}
@@ -0,0 +1,168 @@
+use scrypto::prelude::*;
+
+// Terms of a bond needed to convert between price and yield.
+// Rates are expressed per coupon period, so a bond quoted at a yield equal
+// to its coupon rate trades at face value right after a coupon payment
+#[derive(Debug, Clone, TypeId, Encode, Decode, Describe)]
+pub struct BondTerms {
+    pub face_value: Decimal,
+    pub coupon_rate: Decimal,
+    pub coupon_epoch: u64,
+    pub maturity_epoch: u64,
+    pub issue_epoch: u64,
+}
+
+// Bonds are non fungibles, so an amount of bonds is a whole number of them
+pub fn bond_count(amount: Decimal) -> u64 {
+    let count: Decimal = amount.floor();
+    assert!(count == amount, "Bond amount must be a whole number");
+    assert!(count >= Decimal::zero() && count <= Decimal::from(u64::MAX), "Bond amount out of range");
+    return count.to_string().parse::<u64>().expect("Bond amount out of range");
+}
+
+// A bid or an ask is either quoted as a clean price per bond or as a yield to maturity
+#[derive(Debug, Clone, Copy, PartialEq, TypeId, Encode, Decode, Describe)]
+pub enum Quote {
+    Price(Decimal),
+    Yield(Decimal),
+}
+
+impl BondTerms {
+
+    pub fn coupon(&self) -> Decimal {
+        return self.face_value * self.coupon_rate;
+    }
+
+    pub fn total_coupons(&self) -> u64 {
+        return (self.maturity_epoch - self.issue_epoch) / self.coupon_epoch;
+    }
+
+    // Number of coupons that are due at the given epoch
+    pub fn coupons_paid(&self, epoch: u64) -> u64 {
+        return std::cmp::min((epoch - self.issue_epoch) / self.coupon_epoch, self.total_coupons());
+    }
+
+    // Part of the current coupon period that has elapsed
+    fn accrual_fraction(&self, epoch: u64) -> Decimal {
+        if self.coupons_paid(epoch) >= self.total_coupons() {
+            return Decimal::zero();
+        }
+        return Decimal::from((epoch - self.issue_epoch) % self.coupon_epoch) / Decimal::from(self.coupon_epoch);
+    }
+
+    // Interest earned by the seller since the last coupon, paid by the buyer on top of the clean price
+    pub fn accrued_interest(&self, epoch: u64) -> Decimal {
+        return self.coupon() * self.accrual_fraction(epoch);
+    }
+
+    // Present value of the remaining coupons and the face value, discounted at the yield per coupon period.
+    // The elapsed part of the current period is accounted for with simple interest
+    pub fn dirty_price(&self, yield_rate: Decimal, epoch: u64) -> Decimal {
+        if epoch >= self.maturity_epoch {
+            return self.face_value;
+        }
+
+        let remaining_coupons: u64 = self.total_coupons() - self.coupons_paid(epoch);
+        let discount: Decimal = Decimal::one() + yield_rate;
+        let mut compound: Decimal = Decimal::one();
+        let mut present_value: Decimal = Decimal::zero();
+        for _ in 0..remaining_coupons {
+            compound = compound * discount;
+            present_value += self.coupon() / compound;
+        }
+        present_value += self.face_value / compound;
+
+        return present_value * (Decimal::one() + self.accrual_fraction(epoch) * yield_rate);
+    }
+
+    pub fn clean_price(&self, yield_rate: Decimal, epoch: u64) -> Decimal {
+        return self.dirty_price(yield_rate, epoch) - self.accrued_interest(epoch);
+    }
+
+    // The price decreases when the yield increases, so we search the yield by bisection
+    pub fn yield_to_maturity(&self, clean_price: Decimal, epoch: u64) -> Decimal {
+        let mut low: Decimal = dec!("-0.5");
+        let mut high: Decimal = dec!("2");
+        for _ in 0..64 {
+            let mid: Decimal = (low + high) / dec!("2");
+            if self.clean_price(mid, epoch) > clean_price {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        return (low + high) / dec!("2");
+    }
+
+    // Clean price of a quote at the given epoch
+    pub fn quote_price(&self, quote: Quote, epoch: u64) -> Decimal {
+        match quote {
+            Quote::Price(price) => price,
+            Quote::Yield(yield_rate) => self.clean_price(yield_rate, epoch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scrypto::prelude::*;
+    use crate::pricing::BondTerms;
+
+    // 10 coupons of 5 on a face value of 100, one every 10 epochs
+    fn terms() -> BondTerms {
+        BondTerms {
+            face_value: dec!("100"),
+            coupon_rate: dec!("0.05"),
+            coupon_epoch: 10,
+            maturity_epoch: 100,
+            issue_epoch: 0,
+        }
+    }
+
+    fn assert_close(actual: Decimal, expected: Decimal) {
+        let diff = actual - expected;
+        assert!(diff.abs() < dec!("0.000000001"), "{}, {}", actual, expected);
+    }
+
+    #[test]
+    fn test_accrued_interest() {
+        assert_eq!(terms().accrued_interest(0), Decimal::zero());
+        assert_eq!(terms().accrued_interest(5), dec!("2.5"));
+        assert_eq!(terms().accrued_interest(10), Decimal::zero());
+        assert_eq!(terms().accrued_interest(28), dec!("4"));
+        // Nothing accrues once the last coupon is due
+        assert_eq!(terms().accrued_interest(100), Decimal::zero());
+        assert_eq!(terms().accrued_interest(105), Decimal::zero());
+    }
+
+    #[test]
+    fn test_dirty_price() {
+        // At a yield equal to the coupon rate the bond trades at par on a coupon date
+        assert_close(terms().dirty_price(dec!("0.05"), 0), dec!("100"));
+        assert_close(terms().dirty_price(dec!("0.05"), 50), dec!("100"));
+        // At a zero yield the price is the sum of the payments left
+        assert_close(terms().dirty_price(Decimal::zero(), 0), dec!("150"));
+        assert_close(terms().dirty_price(Decimal::zero(), 60), dec!("120"));
+        // Half way through a period the price grows with simple interest
+        assert_close(terms().dirty_price(dec!("0.05"), 5), dec!("102.5"));
+        // One coupon left: 105 / 1.1, then half a period at 10%
+        assert_close(terms().dirty_price(dec!("0.1"), 90), dec!("95.454545454545454545"));
+        assert_close(terms().dirty_price(dec!("0.1"), 95), dec!("100.227272727272727272"));
+        assert_close(terms().dirty_price(dec!("0.1"), 100), dec!("100"));
+    }
+
+    #[test]
+    fn test_clean_price() {
+        assert_close(terms().clean_price(dec!("0.05"), 5), dec!("100"));
+        assert_close(terms().clean_price(dec!("0.1"), 95), dec!("97.727272727272727272"));
+    }
+
+    #[test]
+    fn test_yield_to_maturity() {
+        assert_close(terms().yield_to_maturity(dec!("100"), 0), dec!("0.05"));
+        assert_close(terms().yield_to_maturity(dec!("100"), 5), dec!("0.05"));
+        assert_close(terms().yield_to_maturity(dec!("150"), 0), Decimal::zero());
+        assert_close(terms().yield_to_maturity(dec!("95.454545454545454545"), 90), dec!("0.1"));
+        assert_close(terms().yield_to_maturity(dec!("97.727272727272727272"), 95), dec!("0.1"));
+    }
+}
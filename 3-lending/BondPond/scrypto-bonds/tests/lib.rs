@@ -1,32 +1,234 @@
 use radix_engine::ledger::*;
+use radix_engine::model::Receipt;
 use radix_engine::transaction::*;
 use scrypto::prelude::*;
+use scrypto_bonds::order::OrderSide;
+use scrypto_bonds::pricing::Quote;
+
+struct User {
+    pk: EcdsaPublicKey,
+    sk: EcdsaPrivateKey,
+    account: ComponentAddress,
+}
+
+struct TestEnv {
+    issuer: User,
+    buyer: User,
+    market: ComponentAddress,
+    order_ticket: ResourceAddress,
+    bond_component: ComponentAddress,
+    bond: ResourceAddress,
+}
+
+fn new_user(executor: &mut TransactionExecutor<InMemorySubstateStore>) -> User {
+    let (pk, sk, account) = executor.new_account();
+    User { pk, sk, account }
+}
+
+fn execute(executor: &mut TransactionExecutor<InMemorySubstateStore>, user: &User, builder: &mut TransactionBuilder) -> Receipt {
+    let transaction = builder
+        .call_method_with_all_resources(user.account, "deposit_batch")
+        .build(executor.get_nonce([user.pk]))
+        .sign([&user.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+/// Issues 10 bonds with a face value of 100 and a 5% coupon every 10 epochs until epoch 100.
+/// The issuer takes all of them out of the supply but does not deposit any repayment
+fn setup(executor: &mut TransactionExecutor<InMemorySubstateStore>) -> TestEnv {
+    let issuer = new_user(executor);
+    let buyer = new_user(executor);
+    let package = executor.publish_package(compile_package!()).unwrap();
+
+    let receipt = execute(executor, &issuer, TransactionBuilder::new()
+        .call_function(package, "BondMarket", "instantiate_bond_market", args![]));
+    assert!(receipt.result.is_ok());
+    let market = receipt.new_component_addresses[0];
+    let order_ticket = receipt.new_resource_addresses[1];
+
+    let receipt = execute(executor, &issuer, TransactionBuilder::new()
+        .call_method(market, "issue_bond", args![dec!("100"), 10u64, 100u64, dec!("0.05"), dec!("100"), 10u32]));
+    assert!(receipt.result.is_ok());
+    let bond_component = receipt.new_component_addresses[0];
+    let issuer_badge = receipt.new_resource_addresses[0];
+    let bond = receipt.new_resource_addresses[2];
+
+    let receipt = execute(executor, &issuer, TransactionBuilder::new()
+        .create_proof_from_account(issuer_badge, issuer.account)
+        .call_method(bond_component, "withdraw_bonds", args![dec!("10")]));
+    assert!(receipt.result.is_ok());
+
+    TestEnv { issuer, buyer, market, order_ticket, bond_component, bond }
+}
+
+fn deposit_repayment(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, amount: Decimal) {
+    let receipt = execute(executor, &env.issuer, TransactionBuilder::new()
+        .withdraw_from_account_by_amount(amount, RADIX_TOKEN, env.issuer.account)
+        .take_from_worktop(RADIX_TOKEN, |builder, bucket_id| {
+            builder.call_method(env.bond_component, "deposit_repayment", args![Bucket(bucket_id)])
+        }));
+    assert!(receipt.result.is_ok());
+}
+
+fn place_ask(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, quantity: u64, quote: Quote) -> Receipt {
+    execute(executor, &env.issuer, TransactionBuilder::new()
+        .withdraw_from_account_by_amount(Decimal::from(quantity), env.bond, env.issuer.account)
+        .take_from_worktop(env.bond, |builder, bucket_id| {
+            builder.call_method(env.market, "place_ask", args![Bucket(bucket_id), quote])
+        }))
+}
+
+fn place_bid(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, payment: Decimal, quantity: u64, quote: Quote) -> Receipt {
+    execute(executor, &env.buyer, TransactionBuilder::new()
+        .withdraw_from_account_by_amount(payment, RADIX_TOKEN, env.buyer.account)
+        .take_from_worktop(RADIX_TOKEN, |builder, bucket_id| {
+            builder.call_method(env.market, "place_bid", args![env.bond, Bucket(bucket_id), quantity, quote])
+        }))
+}
+
+/// Calls a market method with the user's order ticket, each test user holds a single ticket
+fn call_with_ticket(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, user: &User, method: &str) -> Receipt {
+    execute(executor, user, TransactionBuilder::new()
+        .create_proof_from_account(env.order_ticket, user.account)
+        .create_proof_from_auth_zone(env.order_ticket, |builder, proof_id| {
+            builder.call_method(env.market, method, args![Proof(proof_id)])
+        }))
+}
+
+fn list_orders(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv) -> Vec<(u64, Decimal, u64)> {
+    let receipt = execute(executor, &env.buyer, TransactionBuilder::new()
+        .call_method(env.market, "list_orders", args![env.bond]));
+    assert!(receipt.result.is_ok());
+    let orders: Vec<(u64, OrderSide, Decimal, Decimal, u64)> =
+        scrypto_decode(&receipt.outputs[0].raw[..]).unwrap();
+    orders.into_iter().map(|(order_id, _, price, _, remaining)| (order_id, price, remaining)).collect()
+}
 
 #[test]
-fn test_hello() {
-    // Set up environment.
+fn test_filled_orders_leave_the_book_and_can_be_withdrawn() {
     let mut ledger = InMemorySubstateStore::with_bootstrap();
     let mut executor = TransactionExecutor::new(&mut ledger, false);
-    let (pk, sk, account) = executor.new_account();
-    let package = executor.publish_package(compile_package!()).unwrap();
+    let env = setup(&mut executor);
+    deposit_repayment(&mut executor, &env, dec!("1500"));
+
+    assert!(place_ask(&mut executor, &env, 5, Quote::Price(dec!("100"))).result.is_ok());
+    assert_eq!(list_orders(&mut executor, &env), vec![(0, dec!("100"), 5)]);
+
+    // No interest has accrued at issuance, the bid is filled at the ask's price and the change stays in escrow
+    assert!(place_bid(&mut executor, &env, dec!("600"), 5, Quote::Price(dec!("110"))).result.is_ok());
+    assert!(list_orders(&mut executor, &env).is_empty());
+
+    // Both tickets still reach their orders once they have left the book
+    assert!(call_with_ticket(&mut executor, &env, &env.issuer, "withdraw_order").result.is_ok());
+    assert!(call_with_ticket(&mut executor, &env, &env.buyer, "withdraw_order").result.is_ok());
+    assert!(call_with_ticket(&mut executor, &env, &env.buyer, "cancel_order").result.is_ok());
+}
+
+#[test]
+fn test_cancelled_orders_leave_the_book() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+    deposit_repayment(&mut executor, &env, dec!("1500"));
+
+    assert!(place_ask(&mut executor, &env, 5, Quote::Price(dec!("100"))).result.is_ok());
+    assert!(call_with_ticket(&mut executor, &env, &env.issuer, "cancel_order").result.is_ok());
+    assert!(list_orders(&mut executor, &env).is_empty());
+
+    // A bid at the old ask's price no longer finds anything to match
+    assert!(place_bid(&mut executor, &env, dec!("600"), 5, Quote::Price(dec!("100"))).result.is_ok());
+    assert_eq!(list_orders(&mut executor, &env), vec![(1, dec!("100"), 5)]);
+}
+
+#[test]
+fn test_missed_coupon_stops_trading_before_default_is_recorded() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // Nothing is due during the first coupon period
+    executor.substate_store_mut().set_epoch(5);
+    assert!(place_ask(&mut executor, &env, 1, Quote::Price(dec!("100"))).result.is_ok());
+
+    // The first coupon is due and the repayment vault is empty, nobody has called check_default
+    executor.substate_store_mut().set_epoch(10);
+    assert!(place_ask(&mut executor, &env, 1, Quote::Price(dec!("100"))).result.is_err());
+    assert!(place_bid(&mut executor, &env, dec!("200"), 1, Quote::Price(dec!("100"))).result.is_err());
+
+    // The failed orders did not record the default, so topping up the coupons now keeps the bond tradable
+    deposit_repayment(&mut executor, &env, dec!("50"));
+    assert!(place_ask(&mut executor, &env, 1, Quote::Price(dec!("100"))).result.is_ok());
+}
+
+fn get_balance(executor: &mut TransactionExecutor<InMemorySubstateStore>, user: &User, resource: ResourceAddress) -> Decimal {
+    let transaction = TransactionBuilder::new()
+        .call_method(user.account, "balance", args![resource])
+        .build(executor.get_nonce([user.pk]))
+        .sign([&user.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    scrypto_decode(&receipt.outputs[0].raw[..]).unwrap()
+}
+
+fn redeem(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, user: &User) -> Receipt {
+    execute(executor, user, TransactionBuilder::new()
+        .withdraw_from_account(env.bond, user.account)
+        .take_from_worktop(env.bond, |builder, bucket_id| {
+            builder.call_method(env.bond_component, "redeem", args![Bucket(bucket_id)])
+        }))
+}
+
+fn assert_close(actual: Decimal, expected: Decimal) {
+    assert!(actual - expected < dec!("0.000001") && expected - actual < dec!("0.000001"), "expected {} got {}", expected, actual);
+}
+
+#[test]
+fn test_redeem_after_default_is_pro_rata_to_unclaimed_entitlement() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // The issuer funds the first coupon only and sells half of the bonds to the buyer
+    deposit_repayment(&mut executor, &env, dec!("50"));
+    let receipt = execute(&mut executor, &env.issuer, TransactionBuilder::new()
+        .withdraw_from_account_by_amount(dec!("5"), env.bond, env.issuer.account)
+        .call_method_with_all_resources(env.buyer.account, "deposit_batch"));
+    assert!(receipt.result.is_ok());
+
+    // Only the buyer claims the first coupon
+    executor.substate_store_mut().set_epoch(10);
+    let receipt = execute(&mut executor, &env.buyer, TransactionBuilder::new()
+        .create_proof_from_account(env.bond, env.buyer.account)
+        .create_proof_from_auth_zone(env.bond, |builder, proof_id| {
+            builder.call_method(env.bond_component, "claim_coupons", args![Proof(proof_id)])
+        }));
+    assert!(receipt.result.is_ok());
+
+    // The second coupon is missed, the 25 left in the vault is all the holders get
+    executor.substate_store_mut().set_epoch(20);
+    let buyer_xrd = get_balance(&mut executor, &env.buyer, RADIX_TOKEN);
+    let issuer_xrd = get_balance(&mut executor, &env.issuer, RADIX_TOKEN);
+
+    // The buyer's bonds are owed 5 * (9 * 5 + 100) = 725 of the 1475 still owed,
+    // the issuer's bonds 5 * (10 * 5 + 100) = 750
+    assert!(redeem(&mut executor, &env, &env.buyer).result.is_ok());
+    assert_close(get_balance(&mut executor, &env.buyer, RADIX_TOKEN) - buyer_xrd, dec!("25") * dec!("725") / dec!("1475"));
+
+    assert!(redeem(&mut executor, &env, &env.issuer).result.is_ok());
+    assert_close(get_balance(&mut executor, &env.issuer, RADIX_TOKEN) - issuer_xrd, dec!("25") * dec!("750") / dec!("1475"));
+}
+
+#[test]
+fn test_redeem_at_maturity_pays_face_value_and_unclaimed_coupons() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+    deposit_repayment(&mut executor, &env, dec!("1500"));
+
+    assert!(redeem(&mut executor, &env, &env.issuer).result.is_err());
 
-    // Test the `instantiate_hello` function.
-    let transaction1 = TransactionBuilder::new()
-        .call_function(package, "Hello", "instantiate_hello", args![])
-        .build(executor.get_nonce([pk]))
-        .sign([&sk]);
-    let receipt1 = executor.validate_and_execute(&transaction1).unwrap();
-    println!("{:?}\n", receipt1);
-    assert!(receipt1.result.is_ok());
-
-    // Test the `free_token` method.
-    let component = receipt1.new_component_addresses[0];
-    let transaction2 = TransactionBuilder::new()
-        .call_method(component, "free_token", args![])
-        .call_method_with_all_resources(account, "deposit_batch")
-        .build(executor.get_nonce([pk]))
-        .sign([&sk]);
-    let receipt2 = executor.validate_and_execute(&transaction2).unwrap();
-    println!("{:?}\n", receipt2);
-    assert!(receipt2.result.is_ok());
+    executor.substate_store_mut().set_epoch(100);
+    let issuer_xrd = get_balance(&mut executor, &env.issuer, RADIX_TOKEN);
+    assert!(redeem(&mut executor, &env, &env.issuer).result.is_ok());
+    assert_close(get_balance(&mut executor, &env.issuer, RADIX_TOKEN) - issuer_xrd, dec!("1500"));
 }
@@ -29,12 +29,21 @@
 //! - Check the maximum credit and current credit allowance.
 //! - Request an installment credit.
 //! - Take the installment credit badge after the request has passed.
+//! - Take a time-limited credit attestation NFT to carry their score to other lenders.
+//! - Get their Credit SBT ID to hand to the protocols reading their credit history.
+//! - Grant (or revoke) other protocols the consent to read their detailed credit history through the service.
 //!
 //! 3. **Lending protocols**: Listed lending protocols can use this blueprint for on-chain credit service. Through the blueprint's method, *Lending protocols* are allowed to:
 //! - Automatically evaluate user's credit score through late (or on-time) repayment frequency. 
 //! - Edit user's current debt or the credit's due time.
 //! - Let protocol users use the installment credit badge to change credit into installment type (Require no-debt credit status).
 //! - Let protocol users stop using installment credit and change the credit back into revolving type.
+//!
+//! 4. **Third-party protocols**: Any protocol, listed or not, can verify a credit attestation NFT presented by an user
+//! and read the detailed credit history of the users who consented to it through the service.
+//!
+//! The Credit SBT data is stored on ledger like any other NFT data, so it is public to anyone reading the ledger state directly.
+//! The history access list is a record of the user's consent that protocols are expected to honour, not a privacy guarantee.
 
 use scrypto::prelude::*;
 use ground_id::*;
@@ -106,7 +115,32 @@ pub struct CreditData {
     /// 
     /// Default repaid amount is 0.
     // #[scrypto(mutable)]
-    pub repaid_amount_accumulated: Decimal
+    pub repaid_amount_accumulated: Decimal,
+    /// Number of debts (or installment periods) the user has repaid on-time.
+    /// 
+    /// Default on-time repayment count is 0.
+    pub on_time_repayments: u64,
+    /// Number of debts (or installment periods) the user was late on repayment.
+    /// 
+    /// Default late repayment count is 0.
+    pub late_repayments: u64
+}
+
+impl CreditData {
+    /// The user's total outstanding debt: the current debt, debt interest and extra debt from late repayment.
+    pub fn outstanding_debt(&self) -> Decimal {
+        self.current_debt + self.debt_interest + self.extra_debt
+    }
+
+    /// The ratio of on-time repayments over all repayments. Default ratio is 1 when the user hasn't repaid any debt yet.
+    pub fn on_time_ratio(&self) -> Decimal {
+        let total = self.on_time_repayments + self.late_repayments;
+        if total == 0 {
+            Decimal::ONE
+        } else {
+            Decimal::from(self.on_time_repayments) / Decimal::from(total)
+        }
+    }
 }
 
 /// Type of the credit.
@@ -215,6 +249,41 @@ pub struct InstallmentCredit {
     pub data: InstallmentCreditData
 }
 
+/// The NFT summarizing an user's credit, signed by the credit service.
+/// 
+/// ## Uses:
+/// The credit attestation is for users to carry their credit score to lenders outside of the listed protocols.
+/// 
+/// The attestation can only be minted by the credit service component, so its resource address works as the service's signature.
+/// 
+/// Any component can verify the attestation through the "verify_credit_attestation" method without being a listed protocol. 
+/// 
+/// The attestation is only valid until its expiry epoch, after that user has to take a new one.
+#[derive(NonFungibleData)]
+pub struct CreditAttestation {
+    /// The user's Credit SBT ID.
+    pub credit_sbt_id: NonFungibleId,
+    /// Store the attested credit summary.
+    pub data: CreditAttestationData
+}
+
+/// A workaround way for restrictive proof.
+#[derive(TypeId, Encode, Decode, Describe, Clone, Copy)]
+pub struct CreditAttestationData {
+    /// User's credit score when the attestation was issued.
+    pub credit_score: Decimal,
+    /// User's total outstanding debt (include the debt interest and the extra debt on late repayment) when the attestation was issued.
+    pub outstanding_debt: Decimal,
+    /// User's on-time repayment ratio when the attestation was issued.
+    pub on_time_ratio: Decimal,
+    /// Number of repayments the on-time ratio is calculated from.
+    pub repayment_count: u64,
+    /// The epoch the attestation was issued.
+    pub issued_epoch: u64,
+    /// The epoch the attestation expires.
+    pub expiry_epoch: u64
+}
+
 blueprint! {
 
     struct GroundCredit {
@@ -254,7 +323,17 @@ blueprint! {
         /// **Format**: 
         /// 
         /// `LazyMap<Identity SBT ID, Credit SBT ID>`
-        credit_list: LazyMap<NonFungibleId, NonFungibleId>
+        credit_list: LazyMap<NonFungibleId, NonFungibleId>,
+        /// Credit Attestation Resource Address
+        credit_attestation: ResourceAddress,
+        /// The number of epochs a credit attestation is valid for.
+        attestation_validity: u64,
+        /// The protocols each user consented to read their detailed credit history, in addition to the listed protocols.
+        /// 
+        /// **Format**: 
+        /// 
+        /// `LazyMap<Credit SBT ID, Vec<protocol controller badge address>>`
+        history_access: LazyMap<NonFungibleId, Vec<ResourceAddress>>
 
     }
 
@@ -296,11 +375,18 @@ blueprint! {
                 .no_initial_supply();
 
             let installment_credit_badge = ResourceBuilder::new_non_fungible()
-                .metadata("name", name +"'s Installment Credit Badge")
+                .metadata("name", name.clone() +"'s Installment Credit Badge")
                 .mintable(rule!(require(controller_badge.resource_address())), LOCKED)
                 .burnable(rule!(require(controller_badge.resource_address())), MUTABLE(rule!(require(controller_badge.resource_address()))))
                 .no_initial_supply();
 
+            let credit_attestation = ResourceBuilder::new_non_fungible()
+                .metadata("name", name + "'s Credit Attestation")
+                .mintable(rule!(require(controller_badge.resource_address())), LOCKED)
+                .burnable(rule!(allow_all), LOCKED)
+                .restrict_withdraw(rule!(deny_all), LOCKED)
+                .no_initial_supply();
+
             let rules = AccessRules::new()
                 .method("issue_new_credit_sbt", rule!(require(admin_badge)))
                 .method("review_installment_credit_request", rule!(require(admin_badge)))
@@ -309,6 +395,7 @@ blueprint! {
                 .method("blacklist", rule!(require(admin_badge)))
                 .method("whitelist", rule!(require(admin_badge)))
                 .method("change_credit_scoring_rate", rule!(require(admin_badge)))
+                .method("change_attestation_validity", rule!(require(admin_badge)))
                 .default(rule!(allow_all));
 
             let comp = Self {
@@ -324,7 +411,10 @@ blueprint! {
                 blacklist: Vec::new(),
                 credit_scoring_rates: credit_scoring_rates,
                 authorized_protocol: authorized_protocol,
-                credit_list: LazyMap::new()
+                credit_list: LazyMap::new(),
+                credit_attestation: credit_attestation,
+                attestation_validity: 500,
+                history_access: LazyMap::new()
 
             }
             .instantiate()
@@ -373,7 +463,9 @@ blueprint! {
                                 debt_interest: Decimal::zero(),
                                 due_time: 0,
                                 extra_debt: Decimal::zero(),
-                                repaid_amount_accumulated: Decimal::zero()
+                                repaid_amount_accumulated: Decimal::zero(),
+                                on_time_repayments: 0,
+                                late_repayments: 0
                             }
                         }
                 )
@@ -411,7 +503,9 @@ blueprint! {
                                 debt_interest: Decimal::zero(),
                                 due_time: 0,
                                 extra_debt: Decimal::zero(),
-                                repaid_amount_accumulated: Decimal::zero()
+                                repaid_amount_accumulated: Decimal::zero(),
+                                on_time_repayments: 0,
+                                late_repayments: 0
                             }
 
                         }
@@ -572,6 +666,172 @@ blueprint! {
 
         }
       
+        /// This method is for users to take a credit attestation NFT summarizing their credit.
+        /// 
+        /// The attestation included user's credit score, total outstanding debt and on-time repayment ratio and is valid until the expiry epoch.
+        /// 
+        /// The attestation cannot be transfered to other wallets, user can present its proof to any lender to verify.
+        /// ### Input: 
+        /// - id_proof: The Proof of the user's Identity SBT.
+        /// - credit_sbt: the Proof of the user's Credit SBT.
+        /// ### Output: 
+        /// The credit attestation NFT.
+        pub fn issue_credit_attestation(&self, id_proof: Proof, credit_sbt: Proof) -> Bucket {
+
+            let (id_proof, credit_sbt) = self.check_id_and_credit(id_proof, credit_sbt);
+
+            let credit = credit_sbt.non_fungible::<Credit>();
+
+            let data = credit.data().data;
+
+            id_proof.drop(); credit_sbt.drop();
+
+            let current = Runtime::current_epoch();
+
+            let attestation_data = CreditAttestationData {
+                credit_score: data.credit_score,
+                outstanding_debt: data.outstanding_debt(),
+                on_time_ratio: data.on_time_ratio(),
+                repayment_count: data.on_time_repayments + data.late_repayments,
+                issued_epoch: current,
+                expiry_epoch: current + self.attestation_validity
+            };
+
+            info!("You got new Credit Attestation, valid until epoch {}", attestation_data.expiry_epoch);
+
+            self.controller_badge.authorize(|| {
+                borrow_resource_manager!(self.credit_attestation)
+                    .mint_non_fungible(
+                        &NonFungibleId::random(),
+                        CreditAttestation {
+                            credit_sbt_id: credit.id(),
+                            data: attestation_data
+                        }
+                )
+            })
+        }
+
+        /// This method is for any protocol to verify an user's credit attestation. The protocol doesn't need to be listed.
+        /// ### Input: 
+        /// - attestation: the Proof of the user's Credit Attestation.
+        /// ### Output: 
+        /// The attested credit summary, the method will fail if the attestation is not issued by this service or has expired.
+        pub fn verify_credit_attestation(&self, attestation: Proof) -> CreditAttestationData {
+
+            assert!(attestation.resource_address() == self.credit_attestation, "Wrong resource!");
+
+            let data = attestation.non_fungible::<CreditAttestation>().data().data;
+
+            attestation.drop();
+
+            assert!(Runtime::current_epoch() < data.expiry_epoch, "The credit attestation has expired!");
+
+            data
+
+        }
+
+        /// This method is for users to get their Credit SBT ID, to hand to the protocols reading their credit history.
+        /// ### Input: 
+        /// - id_proof: The Proof of the user's Identity SBT.
+        /// ### Output: 
+        /// The user's Credit SBT ID.
+        pub fn get_credit_id(&self, id_proof: Proof) -> NonFungibleId {
+
+            let id_proof = self.check_id(id_proof);
+
+            let sbt_id = id_proof.non_fungible::<Identity>().id();
+
+            id_proof.drop();
+
+            self.credit_list.get(&sbt_id).expect("You don't have a credit SBT")
+
+        }
+
+        /// This method is for users to consent to a protocol reading their detailed credit history through the "get_credit_history" method.
+        /// 
+        /// Listed protocols can always read the credit history.
+        /// 
+        /// The Credit SBT data is public on ledger, the consent only decides which protocols the service hands it to.
+        /// ### Input: 
+        /// - id_proof: The Proof of the user's Identity SBT.
+        /// - credit_sbt: the Proof of the user's Credit SBT.
+        /// - protocol_controller_address: the protocol controller badge resource address.
+        pub fn grant_history_access(&mut self, id_proof: Proof, credit_sbt: Proof, protocol_controller_address: ResourceAddress) {
+
+            let (id_proof, credit_sbt) = self.check_id_and_credit(id_proof, credit_sbt);
+
+            let credit_id = credit_sbt.non_fungible::<Credit>().id();
+
+            id_proof.drop(); credit_sbt.drop();
+
+            let mut access = self.history_access.get(&credit_id).unwrap_or(Vec::new());
+
+            assert!(!access.contains(&protocol_controller_address), "This protocol can already read your credit history.");
+
+            access.push(protocol_controller_address);
+
+            self.history_access.insert(credit_id, access);
+
+            info!("The protocol with controller badge address {} can now read your credit history", protocol_controller_address);
+
+        }
+
+        /// This method is for users to withdraw their consent to a protocol reading their detailed credit history.
+        /// ### Input: 
+        /// - id_proof: The Proof of the user's Identity SBT.
+        /// - credit_sbt: the Proof of the user's Credit SBT.
+        /// - protocol_controller_address: the protocol controller badge resource address.
+        pub fn revoke_history_access(&mut self, id_proof: Proof, credit_sbt: Proof, protocol_controller_address: ResourceAddress) {
+
+            let (id_proof, credit_sbt) = self.check_id_and_credit(id_proof, credit_sbt);
+
+            let credit_id = credit_sbt.non_fungible::<Credit>().id();
+
+            id_proof.drop(); credit_sbt.drop();
+
+            let mut access = self.history_access.get(&credit_id).unwrap_or(Vec::new());
+
+            let index = access.iter().position(|x| *x == protocol_controller_address);
+
+            match index {
+                None => {panic!("This protocol can't read your credit history.")}
+                Some(x) => {
+
+                    access.remove(x);
+
+                    self.history_access.insert(credit_id, access);
+
+                    info!("The protocol with controller badge address {} can no longer read your credit history", protocol_controller_address);
+
+                }
+            }
+
+        }
+
+        /// This method is for protocols to read an user's detailed credit history.
+        /// 
+        /// Only listed protocols and the protocols the user consented to can read the credit history through this method.
+        /// 
+        /// It doesn't hide the data, a protocol that doesn't honour the user's consent can still read the Credit SBT data from the ledger.
+        /// ### Input: 
+        /// - credit_id: the user's Credit SBT ID.
+        /// - protocol_proof: the protocol controller's proof.
+        /// ### Output: 
+        /// The user's credit data.
+        pub fn get_credit_history(&self, credit_id: NonFungibleId, protocol_proof: Proof) -> CreditData {
+
+            let protocol = protocol_proof.resource_address();
+
+            protocol_proof.drop();
+
+            let granted = self.history_access.get(&credit_id).unwrap_or(Vec::new()).contains(&protocol);
+
+            assert!(granted || self.authorized_protocol.contains(&protocol), "This protocol is not allowed to read the credit history.");
+
+            borrow_resource_manager!(self.credit_sbt).get_non_fungible_data::<Credit>(&credit_id).data
+
+        }
+
         /// This method is for users to request an installment loan.
         /// 
         /// ### Input: 
@@ -1189,5 +1449,15 @@ blueprint! {
         pub fn credit_scoring_rate(&self) -> CreditScoringRates {
             self.credit_scoring_rates
         }
+
+        /// The method for the service operators to change the number of epochs a credit attestation is valid for.
+        pub fn change_attestation_validity(&mut self, attestation_validity: u64) {
+            assert!(attestation_validity > 0, "Wrong data!");
+            self.attestation_validity = attestation_validity
+        }
+
+        pub fn credit_attestation(&self) -> ResourceAddress {
+            self.credit_attestation
+        }
     }
 }
\ No newline at end of file
@@ -1056,6 +1056,10 @@ blueprint! {
                     credit_proof = self.update_debt(credit_proof, self.controller_badge.create_proof(), new_debt, new_debt_interest, new_extra_debt);
 
                     if new_debt + new_debt_interest + new_extra_debt == Decimal::ZERO {
+
+                        if extra_debt == Decimal::ZERO {
+                            credit_proof = self.record_on_time_repayment(credit_proof, self.controller_badge.create_proof());
+                        }
                         
                         credit_proof = self.update_debt_time(credit_proof, self.controller_badge.create_proof(), 0, 0);
                         info!("You have repaid all your current debt.")
@@ -1257,7 +1261,8 @@ blueprint! {
             };
 
             if !late {
-                data.credit_score += credit_scoring_rates.monthly.restore_rate
+                data.credit_score += credit_scoring_rates.monthly.restore_rate;
+                data.on_time_repayments += 1
             };
 
             if installment_data.period_counter < installment_data.period_max {
@@ -1423,6 +1428,7 @@ blueprint! {
                     Credit {
                         data: CreditData {
                             credit_score: new_score,
+                            late_repayments: old_data.late_repayments + 1,
                             ..old_data
                         }
                     }
@@ -1435,6 +1441,35 @@ blueprint! {
 
         }
     
+        /// This method is for lending protocol to record an on-time repayment of an user.
+        /// 
+        /// The method can only be self called. (Unless Scrypto allow create a different resource with the same address as the protocol's controller badge).
+        /// ### Input: 
+        /// - credit_proof: the user's credit proof.
+        /// - protocol_proof: the protocol controller's proof.
+        /// ### Output: 
+        /// increase the user's on-time repayment count.
+        pub fn record_on_time_repayment(&self, credit_proof: Proof, protocol_proof: Proof) -> Proof {
+
+            self.check_protocol(protocol_proof);
+            let credit = credit_proof.non_fungible::<Credit>();
+            let data = credit.data().data;
+
+            self.controller_badge.authorize(|| {
+                credit.update_data(
+                    Credit {
+                        data: CreditData {
+                            on_time_repayments: data.on_time_repayments + 1,
+                            ..data
+                        }
+                    }
+                )
+            });
+
+            credit_proof
+
+        }
+
         /// This method is for lending protocol to update repaid data and restore credit score of an user (if passed the maximum credit amount).
         /// 
         /// The method can only be self called. (Unless Scrypto allow create a different resource with the same address as the protocol's controller badge).
@@ -6,6 +6,7 @@
 use radix_engine::{ledger::*, transaction::*, model::Receipt};
 use scrypto_unit::*;
 use scrypto::prelude::*;
+use ground_finance::ground_credit::CreditData;
 mod neuracle_time_gateway;
 
 const PACKAGE: &str = "ground_test";
@@ -522,6 +523,103 @@ impl<'a, L: SubstateStore> GroundTestEnv<'a, L> {
 
     // }
 
+    fn get_credit_id(&mut self, name: &str) -> NonFungibleId {
+
+        self.env.acting_as(name);
+
+        let (user, private_key) = self.env.get_current_user();
+    
+        let transaction = TransactionBuilder::new()
+        .call_method(user.account, "create_proof", vec![scrypto_encode(&self.id_sbt)])
+        .pop_from_auth_zone(|continue_transaction, proof_id| {
+            continue_transaction
+            .call_method(self.ground_credit, "get_credit_id", vec![scrypto_encode(&Proof(proof_id))])
+        })
+        .call_method_with_all_resources(user.account, "deposit_batch")
+        .build(self.env.executor.get_nonce([user.key]))
+        .sign([private_key]);
+            
+        let mut receipt = self.env.executor.validate_and_execute(&transaction).unwrap();
+        println!("GET CREDIT SBT ID, RECEPIT: {:?}", receipt);
+        assert!(receipt.result.is_ok());
+
+        return_of_call_method(&mut receipt, "get_credit_id")
+
+    }
+
+    /// Create a controller badge for a protocol that is not listed on the credit service.
+    fn new_protocol_badge(&mut self, name: &str) -> ResourceAddress {
+
+        self.env.create_user(name);
+        self.env.acting_as(name);
+
+        let (user, private_key) = self.env.get_current_user();
+
+        let transaction = TransactionBuilder::new()
+        .new_badge_fixed(HashMap::new(), dec!("1"))
+        .call_method_with_all_resources(user.account, "deposit_batch")
+        .build(self.env.executor.get_nonce([user.key]))
+        .sign([private_key]);
+
+        let receipt = self.env.executor.validate_and_execute(&transaction).unwrap();
+        println!("NEW PROTOCOL CONTROLLER BADGE, RECEPIT: {:?}", receipt);
+        assert!(receipt.result.is_ok());
+
+        receipt.new_resource_addresses[0]
+
+    }
+
+    /// Call "grant_history_access" or "revoke_history_access" for the protocol.
+    fn change_history_access(&mut self, name: &str, method: &str, protocol_badge: ResourceAddress) -> Receipt {
+
+        self.env.acting_as(name);
+
+        let (user, private_key) = self.env.get_current_user();
+    
+        let transaction = TransactionBuilder::new()
+        .call_method(user.account, "create_proof", vec![scrypto_encode(&self.id_sbt)])
+        .pop_from_auth_zone(|continue_transaction, proof_id| {
+            continue_transaction
+            .call_method(user.account, "create_proof", vec![scrypto_encode(&self.credit_sbt)])
+            .pop_from_auth_zone(|continue_transaction2, proof_id2| {
+                continue_transaction2
+                .call_method(self.ground_credit, method, vec![scrypto_encode(&Proof(proof_id)), scrypto_encode(&Proof(proof_id2)), scrypto_encode(&protocol_badge)])
+            })
+        })
+        .call_method_with_all_resources(user.account, "deposit_batch")
+        .build(self.env.executor.get_nonce([user.key]))
+        .sign([private_key]);
+            
+        let receipt = self.env.executor.validate_and_execute(&transaction).unwrap();
+        println!("{} FOR PROTOCOL {}, RECEPIT: {:?}", method.to_uppercase(), protocol_badge, receipt);
+
+        receipt
+
+    }
+
+    fn get_credit_history(&mut self, name: &str, credit_id: NonFungibleId, protocol_badge: ResourceAddress) -> Receipt {
+
+        self.env.acting_as(name);
+
+        let (user, private_key) = self.env.get_current_user();
+    
+        let transaction = TransactionBuilder::new()
+        .call_method(user.account, "create_proof", vec![scrypto_encode(&protocol_badge)])
+        .pop_from_auth_zone(|continue_transaction, proof_id| {
+            continue_transaction
+            .call_method(self.ground_credit, "get_credit_history", vec![scrypto_encode(&credit_id), scrypto_encode(&Proof(proof_id))])
+        })
+        .call_method_with_all_resources(user.account, "deposit_batch")
+        .build(self.env.executor.get_nonce([user.key]))
+        .sign([private_key]);
+            
+        let receipt = self.env.executor.validate_and_execute(&transaction).unwrap();
+        println!("READ CREDIT HISTORY OF CREDIT SBT {} AS PROTOCOL {}, RECEPIT: {:?}", credit_id, protocol_badge, receipt);
+
+        receipt
+
+    }
+
     fn lending_use_dao(&mut self) {
 
         self.env.acting_as("tester");
@@ -994,4 +1092,57 @@ fn test_compensation() {
 
     test_env.compensation("lender2")
 
-}
\ No newline at end of file
+}
+
+/// ## Ground Credit blueprint's credit history access test:
+/// The test will do the following:
+/// 
+/// - Create a credit user and a protocol that is not listed on the credit service.
+/// - Test that the protocol can only read the credit history through the service while the user consents to it.
+/// - Test that the user cannot grant the same protocol twice or revoke a protocol that was never granted.
+#[test]
+fn test_credit_history_access() {
+
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let env = TestEnv::new(&mut ledger);
+    let mut test_env = new_test_env(env);
+
+    test_env.update_neuracle_time(Some("10000000".to_string()));
+
+    test_env.new_credit_user("credit_user", dec!("30"));
+
+    let credit_id = test_env.get_credit_id("credit_user");
+
+    let protocol_badge = test_env.new_protocol_badge("protocol");
+
+    println!("READ THE CREDIT HISTORY WITHOUT THE USER'S CONSENT, THIS SHOULD FAIL");
+    let receipt = test_env.get_credit_history("protocol", credit_id.clone(), protocol_badge);
+    assert!(receipt.result.is_err());
+
+    println!("REVOKE A PROTOCOL THAT WAS NEVER GRANTED, THIS SHOULD FAIL");
+    let receipt = test_env.change_history_access("credit_user", "revoke_history_access", protocol_badge);
+    assert!(receipt.result.is_err());
+
+    let receipt = test_env.change_history_access("credit_user", "grant_history_access", protocol_badge);
+    assert!(receipt.result.is_ok());
+
+    println!("GRANT THE SAME PROTOCOL TWICE, THIS SHOULD FAIL");
+    let receipt = test_env.change_history_access("credit_user", "grant_history_access", protocol_badge);
+    assert!(receipt.result.is_err());
+
+    println!("READ THE CREDIT HISTORY WITH THE USER'S CONSENT");
+    let mut receipt = test_env.get_credit_history("protocol", credit_id.clone(), protocol_badge);
+    assert!(receipt.result.is_ok());
+
+    let data: CreditData = return_of_call_method(&mut receipt, "get_credit_history");
+    assert!(data.current_debt == Decimal::zero());
+    assert!(data.on_time_repayments == 0 && data.late_repayments == 0);
+
+    let receipt = test_env.change_history_access("credit_user", "revoke_history_access", protocol_badge);
+    assert!(receipt.result.is_ok());
+
+    println!("READ THE CREDIT HISTORY AFTER THE USER WITHDREW CONSENT, THIS SHOULD FAIL");
+    let receipt = test_env.get_credit_history("protocol", credit_id, protocol_badge);
+    assert!(receipt.result.is_err());
+
+}
@@ -1,5 +1,5 @@
 use scrypto::prelude::*;
-use crate::locked_loan_collateral::{LockedLoanCollateral, NFTLoan, LoanContract};
+use crate::locked_loan_collateral::{LockedLoanCollateral, NFTLoan, LoanContract, AmortizationSchedule, ScheduledInstallment};
 use crate::deposit_contributors::DepositContributors;
 use crate::trusted_network::{TrustedPartnerNetwork, TrustedPartnerNetworkMember};
 use std::cmp;
@@ -69,6 +69,8 @@ blueprint! {
     /// misses an installment
     /// * `max_loan_duration`: the maximum duration of a loan in epochs
     /// * `installment_frequency`: the number of epochs between each installment payment
+    /// * `grace_period`: the number of epochs after the due date of an installment during which the borrower
+    /// can still pay it without a late payment penalty
     /// * `max_loan_percentage`: This is the maximum percentage of the collateral that can be borrowed. 0
     /// means that the borrower should provide 100% of the collateral.
     /// * `trusted_network_memberships`: this is the list of trusted network memberships that the lender
@@ -94,6 +96,7 @@ blueprint! {
         missed_installment_penalization : Decimal, // measured in percent
         max_loan_duration: u64,  // measure in epochs
         installment_frequency : u64,
+        grace_period : u64, // measured in epochs
         max_loan_percentage : Decimal, // percentage of the collateral that can be borrowed. 0 means that the borrower should provide 100% of the collateral 
 
         // trusted network membership nft
@@ -129,7 +132,8 @@ blueprint! {
             let access_rules: AccessRules = AccessRules::new()
             .method("add_trusted_partner_network_member_badge", rule!(require(auth_token.resource_address())))
             .method("recommend_for_promotion", rule!(require(auth_token.resource_address())))
-            .method("mint_custom_lending_offer", rule!(require(auth_token.resource_address())));
+            .method("mint_custom_lending_offer", rule!(require(auth_token.resource_address())))
+            .method("set_grace_period", rule!(require(auth_token.resource_address())));
 
             let (deposit_component, nft) = DepositContributors::instantiate(initial_liquidity, auth_token.resource_address());
 
@@ -141,6 +145,7 @@ blueprint! {
                 loans : HashMap::new(),
                 liquidated_collaterals : Vec::new(),
                 installment_frequency : 480, // there are 1440 minutes in a day * 30 / 90 minutes (which is the max for the epoch)
+                grace_period : 48, // ~ 3 days
                 max_loan_percentage : dec!("0.1"), // by default clients can only get 1% more than what they deposit. They need to build up trust with a lending partner network in order to borrow more
                 interest_rate : dec!("0.01"), // default interest rate is 1% per installment
                 missed_installment_penalization : dec!("0.05"), // if a borrower doesn't pay his installment on time, a 5% 
@@ -227,10 +232,11 @@ blueprint! {
         /// * `collateral`: the collateral that the borrower is providing to the lender
         /// * `amount`: the amount of tokens to be loaned
         /// * `duration`: the duration of the loan in seconds
+        /// * `amortization_schedule`: the way the loan is repaid - equal installments, equal principal or interest only with a balloon payment
         /// * `network_client_nft`: this is the NFT that the borrower has received from the trusted network. It
         /// is used to determine the borrower's fidelity level and thus the maximum loan percentage that the
         /// borrower can get.
-        pub fn take_loan(&mut self, collateral : Bucket, amount : Decimal, duration : u64, mut network_client_nft : Bucket) -> (Bucket, Bucket) 
+        pub fn take_loan(&mut self, collateral : Bucket, amount : Decimal, duration : u64, amortization_schedule : AmortizationSchedule, mut network_client_nft : Bucket) -> (Bucket, Bucket) 
         {
            let (collateral_evaluation, collateral) = self.evaluate_collateral(collateral);
            assert!(collateral_evaluation > Decimal::zero(), "no collateral provided or it couldn't be evaluated");
@@ -254,12 +260,10 @@ blueprint! {
             let (loaned_tokens, allocation_id) = self.deposit.withdraw_funds(amount); // will assert if funds are not enough
             ComponentAuthZone::pop().drop();
 
-            let nb_installments = Decimal::from(duration / self.installment_frequency);
-
             let current_epoch = scrypto::prelude::Runtime::current_epoch();
             let loan_contract = LoanContract::create(current_epoch, duration, self.installment_frequency,
                                                                         self.interest_rate, self.missed_installment_penalization,
-                                                                        amount / nb_installments, amount, collateral_evaluation);
+                                                                        amortization_schedule, self.grace_period, amount, collateral_evaluation);
  
             let (locked_collateral, loan_nft) = LockedLoanCollateral::instantiate(collateral, amount, loan_contract, allocation_id.clone(), self.auth_vault.resource_address());
             self.loans.insert(loan_nft.non_fungible::<crate::locked_loan_collateral::NFTLoan>().id(), locked_collateral);
@@ -299,7 +303,7 @@ blueprint! {
 
         // Function mostly intented for uses cases like having a friend in need and you want to lend him some money 
         // You make him a custom offer and then he can accept it or not 
-        pub fn take_loan_with_offer(&mut self, collateral : Bucket, amount : Decimal, duration : u64, special_offer : Bucket) -> (Bucket, Bucket) 
+        pub fn take_loan_with_offer(&mut self, collateral : Bucket, amount : Decimal, duration : u64, amortization_schedule : AmortizationSchedule, special_offer : Bucket) -> (Bucket, Bucket) 
         {
             let (collateral_evaluation, collateral
             ) = self.evaluate_collateral(collateral);
@@ -326,13 +330,10 @@ blueprint! {
             let (loaned_tokens, allocation_id) = self.deposit.withdraw_funds(amount); // will assert if funds are not enough
             ComponentAuthZone::pop().drop();
             
-            let nb_installments = Decimal::from(duration / self.installment_frequency);
-
-            
             let current_epoch = scrypto::prelude::Runtime::current_epoch();
             let loan_contract = LoanContract::create(current_epoch, duration, self.installment_frequency,
                                                                         interest_rate, self.missed_installment_penalization,
-                                                                        amount / nb_installments, amount, collateral_evaluation);
+                                                                        amortization_schedule, self.grace_period, amount, collateral_evaluation);
 
             let (locked_collateral, loan_nft) = LockedLoanCollateral::instantiate(collateral, amount, loan_contract, allocation_id, self.auth_vault.resource_address());
             self.loans.insert(loan_nft.non_fungible::<crate::locked_loan_collateral::NFTLoan>().id(), locked_collateral);
@@ -418,6 +419,25 @@ blueprint! {
             return (collateral, client_nft, loan_nft, tokens) // return empty buckets
         }
 
+        /// > Returns the installments that the borrower still has to pay for a loan, including the part
+        /// already paid for the current installment. The schedule is recomputed after every prepayment
+        /// 
+        /// Arguments:
+        /// 
+        /// * `loan_id`: The ID of the loan NFT
+        pub fn get_loan_schedule(&self, loan_id : NonFungibleId) -> Vec<ScheduledInstallment> {
+            assert!(self.loans.contains_key(&loan_id), "loan not found");
+            return self.loans.get(&loan_id).unwrap().get_remaining_schedule()
+        }
+
+        /// > Sets the number of epochs after the due date during which installments can be paid without penalty.
+        /// Only applies to new loans, the grace period of existing loans is part of their contract
+        /// The grace period must end before the next installment is due, otherwise installments could be paid late without any penalty
+        pub fn set_grace_period(&mut self, grace_period : u64) {
+            assert!(grace_period < self.installment_frequency, "the grace period must be shorter than the installment frequency");
+            self.grace_period = grace_period;
+        }
+
         /// > If a loan can be liquidated, then remove it from the `loans` map and burn the client's NFT
         pub fn liquidate_loans(&mut self) 
         {
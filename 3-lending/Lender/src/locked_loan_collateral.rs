@@ -1,9 +1,101 @@
 use scrypto::prelude::*;
 use std::cmp;
 
+/// The way the borrowed amount is spread over the installments of a loan
+/// 
+/// Variants:
+/// 
+/// * `EqualInstallment`: every installment has the same amount, the interest part decreases and the
+/// principal part increases over time (annuity)
+/// * `EqualPrincipal`: every installment repays the same amount of principal plus the interest on the
+/// outstanding principal, so installments decrease over time
+/// * `InterestOnly`: installments only cover the interest and the whole principal is repaid with the
+/// last installment (balloon payment)
+#[derive(Debug, Describe, Encode, Decode, TypeId, Clone, Copy, PartialEq)]
+pub enum AmortizationSchedule {
+    EqualInstallment,
+    EqualPrincipal,
+    InterestOnly
+}
+
+impl AmortizationSchedule {
+    /// It builds the list of installments needed to repay `principal` in `nb_installments` installments,
+    /// the first one being due at `first_due_epoch` and the next ones every `installment_frequency` epochs
+    /// 
+    /// Arguments:
+    /// 
+    /// * `principal`: the outstanding amount that needs to be repaid
+    /// * `interest_rate`: the interest rate applied on the outstanding principal for each installment period
+    /// * `first_due_epoch`: the epoch when the first installment is due
+    /// * `installment_frequency`: the number of epochs between two installments
+    /// * `nb_installments`: the number of installments
+    /// 
+    /// Returns:
+    /// 
+    /// The installments in the order they are due. The last installment always repays the remaining principal
+    pub fn build(&self, principal : Decimal, interest_rate : Decimal, first_due_epoch : u64, installment_frequency : u64, nb_installments : u64) -> Vec<ScheduledInstallment> {
+        let mut installments : Vec<ScheduledInstallment> = Vec::new();
+        if nb_installments == 0 || principal <= Decimal::zero() {
+            return installments
+        }
+
+        // annuity payment: P * r * (1 + r)^n / ((1 + r)^n - 1)
+        let mut equal_payment = principal / Decimal::from(nb_installments);
+        if interest_rate > Decimal::zero() {
+            let mut compound = Decimal::one();
+            for _ in 0..nb_installments {
+                compound = compound * (Decimal::one() + interest_rate);
+            }
+            equal_payment = principal * interest_rate * compound / (compound - Decimal::one());
+        }
+
+        let mut balance = principal;
+        for i in 0..nb_installments {
+            let interest = balance * interest_rate;
+            let mut principal_part = match self {
+                AmortizationSchedule::EqualInstallment => equal_payment - interest,
+                AmortizationSchedule::EqualPrincipal => principal / Decimal::from(nb_installments),
+                AmortizationSchedule::InterestOnly => Decimal::zero(),
+            };
+            if i == nb_installments - 1 || principal_part > balance {
+                principal_part = balance; // the last installment settles whatever is left
+            }
+            balance -= principal_part;
+
+            installments.push(ScheduledInstallment {
+                due_epoch : first_due_epoch + i * installment_frequency,
+                principal : principal_part,
+                interest : interest,
+                amount : principal_part + interest,
+                amount_paid : Decimal::zero()
+            });
+        }
+        return installments
+    }
+}
+
+/// A single installment of a loan schedule
+/// 
+/// Properties:
+/// 
+/// * `due_epoch`: The epoch when the installment must be paid
+/// * `principal`: The part of the installment that repays the borrowed amount
+/// * `interest`: The part of the installment that pays the interest
+/// * `amount`: The total amount of the installment (principal + interest)
+/// * `amount_paid`: The amount that was already paid for this installment
+#[derive(Debug, Describe, Encode, Decode, TypeId, Clone)]
+pub struct ScheduledInstallment
+{
+    pub due_epoch : u64,
+    pub principal : Decimal,
+    pub interest : Decimal,
+    pub amount : Decimal,
+    pub amount_paid : Decimal
+}
+
 /// A loan contract is a record of the terms of a loan, including the start date, duration, installment
-/// frequency, interest rate, late payment penalty rate, amount per installment, amount borrowed, and
-/// collateral evaluation.
+/// frequency, interest rate, late payment penalty rate, amortization schedule, grace period, amount borrowed,
+/// and collateral evaluation. The amounts of the installments are given by the schedule.
 /// 
 /// Properties:
 /// 
@@ -12,9 +104,10 @@ use std::cmp;
 /// * `installment_frequency`: The frequency of the installments. For example, if the frequency is 1,
 /// then the installments are paid every epoch. If the frequency is 2, then the installments are paid
 /// every 2 epochs.
-/// * `interest_rate`: The interest rate per epoch.
+/// * `interest_rate`: The interest rate per installment period, applied on the outstanding principal.
 /// * `late_payment_penalty_rate`: The penalty rate per epoch when the installment is overdue.
-/// * `amount_per_installment`: The amount that the borrower has to pay per installment.
+/// * `amortization_schedule`: The way the borrowed amount is spread over the installments.
+/// * `grace_period`: The number of epochs after the due date during which an installment can be paid without penalty.
 /// * `amount_borrowed`: The amount of money the borrower wants to borrow.
 /// * `collateral_evaluation`: The value of the collateral at the time of the loan.
 #[derive(Debug, Describe, Encode, Decode, TypeId, Clone)]
@@ -23,9 +116,10 @@ pub struct LoanContract
     start_date : u64,
     duration : u64, // in epochs
     installment_frequency : u64, // in epochs
-    interest_rate : Decimal, // per epoch
+    interest_rate : Decimal, // per installment period
     late_payment_penalty_rate : Decimal, // per epoch when installment is overdue
-    amount_per_installment : Decimal, 
+    amortization_schedule : AmortizationSchedule,
+    grace_period : u64, // in epochs
     amount_borrowed : Decimal,
     collateral_evaluation : Decimal
 }
@@ -33,14 +127,22 @@ pub struct LoanContract
 // cannot be modified once created
 impl LoanContract {
     pub fn create(start_date : u64, duration : u64, installment_frequency : u64, 
-        interest_rate : Decimal, late_payment_penalty_rate : Decimal, amount_per_installment : Decimal, 
-        amount_borrowed : Decimal, collateral_evaluation : Decimal) -> LoanContract {
+        interest_rate : Decimal, late_payment_penalty_rate : Decimal, amortization_schedule : AmortizationSchedule, 
+        grace_period : u64, amount_borrowed : Decimal, collateral_evaluation : Decimal) -> LoanContract {
+        assert!(installment_frequency > 0 && duration >= installment_frequency, "the loan duration must cover at least one installment");
+
         return LoanContract {
-            start_date, duration, installment_frequency, interest_rate,late_payment_penalty_rate, amount_per_installment, 
+            start_date, duration, installment_frequency, interest_rate,late_payment_penalty_rate, amortization_schedule, grace_period,
             amount_borrowed, collateral_evaluation
         }
     }
 
+    /// The schedule of the loan as agreed when the contract was created
+    pub fn get_initial_schedule(&self) -> Vec<ScheduledInstallment> {
+        return self.amortization_schedule.build(self.amount_borrowed, self.interest_rate, 
+            self.start_date + self.installment_frequency, self.installment_frequency, self.get_nb_installments())
+    }
+
     pub fn get_start_date(&self) -> u64 {
         return self.start_date
     }
@@ -50,14 +152,20 @@ impl LoanContract {
     pub fn get_installment_frequency(&self) -> u64 {
         return self.installment_frequency
     }
+    pub fn get_nb_installments(&self) -> u64 {
+        return self.duration / self.installment_frequency
+    }
     pub fn get_interest_rate(&self) -> Decimal {
         return self.interest_rate
     }
     pub fn get_late_payment_penalty_rate(&self) -> Decimal {
         return self.late_payment_penalty_rate
     }
-    pub fn get_amount_per_installment(&self) -> Decimal {
-        return self.amount_per_installment
+    pub fn get_amortization_schedule(&self) -> AmortizationSchedule {
+        return self.amortization_schedule
+    }
+    pub fn get_grace_period(&self) -> u64 {
+        return self.grace_period
     }
     #[allow(dead_code)]
     pub fn get_amount_borrowed(&self) -> Decimal {
@@ -66,6 +174,48 @@ impl LoanContract {
     pub fn get_collateral_evaluation(&self) -> Decimal {
         return self.collateral_evaluation
     }
+
+    /// It calculates the penalty for late payments by iterating over all the installments that are
+    /// still not paid after their due date plus the grace period. Once the grace period is exceeded, the penalty is
+    /// applied from the due date of the installment and only for the unpaid part of the installment
+    /// 
+    /// Arguments:
+    /// 
+    /// * `remaining_schedule`: the installments that were not fully paid yet, in the order they are due
+    /// * `last_update`: the epoch until which penalties were already accrued
+    /// * `current_epoch`: the current epoch number
+    /// 
+    /// Returns:
+    /// 
+    /// The amount of tokens that the borrower has to pay to the lender for being late in order to pay the loan.
+    pub fn get_late_installment_penalty(&self, remaining_schedule : &[ScheduledInstallment], last_update : u64, current_epoch : u64) -> Decimal
+    {
+        let mut penalty = Decimal::zero();
+
+        for installment in remaining_schedule.iter() {
+            if current_epoch <= installment.due_epoch + self.grace_period {
+                break; // installments are ordered by due date, the next ones are not late either
+            }
+
+            // penalties until the last update were already added to the accrued penalties
+            let penalty_start = cmp::max(installment.due_epoch, last_update);
+            let unpaid_amount = installment.amount - installment.amount_paid;
+            penalty += Decimal::from(current_epoch - penalty_start) * self.late_payment_penalty_rate * unpaid_amount;
+        }
+
+        return penalty
+    }
+
+    /// It recomputes the remaining installments for the outstanding principal, keeping the same due dates.
+    /// Called after a prepayment so that the borrower doesn't pay interest on the principal he already returned
+    pub fn reschedule(&self, remaining_schedule : &[ScheduledInstallment], outstanding_principal : Decimal) -> Vec<ScheduledInstallment> {
+        if remaining_schedule.is_empty() {
+            return Vec::new()
+        }
+
+        return self.amortization_schedule.build(outstanding_principal, self.interest_rate,
+            remaining_schedule[0].due_epoch, self.installment_frequency, remaining_schedule.len() as u64)
+    }
 }
 
 
@@ -76,12 +226,16 @@ impl LoanContract {
 /// * `unlock_threshold`: The amount of tokens that need to be refunded before the loan is unlocked.
 /// * `refunded_tokens`: The amount of tokens that have been refunded to the lender.
 /// * `last_update`: The last time the loan status was updated.
+/// * `outstanding_principal`: The part of the borrowed amount that was not repaid yet.
+/// * `accrued_penalties`: The late payment penalties that were not paid yet.
 #[derive(Debug, Describe, Encode, Decode, TypeId, Clone)]
 pub struct LoanRefundStatus
 {
     pub unlock_threshold : Decimal,
     pub refunded_tokens : Decimal,
     pub last_update : u64,
+    pub outstanding_principal : Decimal,
+    pub accrued_penalties : Decimal,
 }
 
 #[derive(NonFungibleData)]
@@ -93,9 +247,10 @@ pub struct NFTLoan
 }
 
 // LockedLoanCollateral is a structure that encapsulated the logic and calculation for repaying loans and keeps the deposit locked until the amount required for unlock is met
-// The threshold is computed based on the interest rate and is adapted each time a new deposit is made based on early/late installment payments
-// If the borrower didn't manage to restore the threshold amount until the installment date, then he will have to pay penalty fees 
+// The threshold is computed from the amortization schedule of the loan and is adapted each time a new deposit is made based on early/late installment payments
+// If the borrower didn't manage to pay an installment until its due date plus the grace period, then he will have to pay penalty fees 
 // to account for the additional time the lender is without his funds
+// Tokens deposited beyond the installments of the current period are a prepayment of the principal and the remaining installments are recomputed
 // Because we have collateral we increase the debt even more as a penalty method if the borrower doesn't meet his deadlines until the loan passes its deadline
 // and the collateral evaluation is less than the remaining debt. We liquidate the loan when the borrower does not have any more reasons to continue repaying because the 
 // amount he must repay is larger than the collateral
@@ -106,6 +261,7 @@ pub struct NFTLoan
 /// * `collateral`: The vault that contains the collateral that the borrower deposited.
 /// * `loan_contract`: LoanContract - the loan contract that contains the loan data
 /// * `loan_refund_status`: LoanRefundStatus - the status of the loan refund
+/// * `remaining_schedule`: The installments that were not fully paid yet, in the order they are due
 /// * `lender_badge_resource`: The address of the lender's badge resource.
 /// * `borrower_nft_resource`: ResourceAddress - the vault can only be unlocked with the badge if all
 /// the money were restored or if the loan expired
@@ -117,6 +273,7 @@ pub struct LockedLoanCollateral
     collateral : Vault,
     loan_contract : LoanContract,
     loan_refund_status : LoanRefundStatus,
+    remaining_schedule : Vec<ScheduledInstallment>,
     lender_badge_resource : ResourceAddress,
     borrower_nft_resource : ResourceAddress,
     loan_contract_nft_id : NonFungibleId
@@ -144,13 +301,21 @@ impl LockedLoanCollateral
         .updateable_non_fungible_data(rule!(require(lender_badge)), LOCKED) // nobody can change the lottery numbers once created
         .no_initial_supply();
 
-        let max_tokens_for_unlock = amount_borrowed + amount_borrowed * custom_loan_contract.get_interest_rate();
+        let schedule = custom_loan_contract.get_initial_schedule();
+        let max_tokens_for_unlock = schedule.iter().fold(Decimal::zero(), |sum, installment| sum + installment.amount);
+        let loan_refund_status = LoanRefundStatus { 
+            unlock_threshold: max_tokens_for_unlock, 
+            refunded_tokens: Decimal::zero(), 
+            last_update : custom_loan_contract.get_start_date(),
+            outstanding_principal : amount_borrowed,
+            accrued_penalties : Decimal::zero()
+        };
 
         let borrower_nft = auth_token.authorize(|| {
             borrow_resource_manager!(borrower_badge_resource)
                 .mint_non_fungible(&loan_id, NFTLoan { 
                     loan_contract : custom_loan_contract.clone(),
-                    loan_refund_status : LoanRefundStatus { unlock_threshold: max_tokens_for_unlock, refunded_tokens: Decimal::zero(), last_update : custom_loan_contract.get_start_date() }
+                    loan_refund_status : loan_refund_status.clone()
                 })
         });
 
@@ -159,7 +324,8 @@ impl LockedLoanCollateral
             auth_vault : Vault::with_bucket(auth_token),
             lender_badge_resource : lender_badge,
             loan_contract: custom_loan_contract.clone(),
-            loan_refund_status : LoanRefundStatus { unlock_threshold: max_tokens_for_unlock, refunded_tokens: Decimal::zero(), last_update : custom_loan_contract.get_start_date() },
+            loan_refund_status : loan_refund_status,
+            remaining_schedule : schedule,
             borrower_nft_resource: borrower_badge_resource,
             loan_contract_nft_id : loan_id
         };
@@ -167,76 +333,15 @@ impl LockedLoanCollateral
         return (component, borrower_nft)
     }
     
-    fn get_num_installments_to_date(&self) -> u64 {
-        let duration = cmp::min( scrypto::prelude::Runtime::current_epoch() - self.loan_contract.get_start_date(), self.loan_contract.get_duration());
-        return duration / self.loan_contract.get_installment_frequency()
-    }
-
-    /// It calculates the penalty for late payments by iterating over all the installments and adding the
-    /// penalty for each installment that is late
-    /// 
-    /// Arguments:
-    /// 
-    /// * `current_epoch`: the current epoch number
-    /// 
-    /// Returns:
-    /// 
-    /// The amount of tokens that the borrower has to pay to the lender for being late in order to pay the loan.
-    fn get_late_installment_penalty(&self, current_epoch : u64) -> Decimal
-    {
-        let mut sum_epochs_late = Decimal::zero();
-
-        let epochs_since_last_update = current_epoch - self.loan_refund_status.last_update;
-
-        for i in 0..self.get_num_installments_to_date() {
-            if self.loan_refund_status.refunded_tokens >= self.loan_contract.get_amount_per_installment() * Decimal::from(i) {
-                continue;
-            }
-
-            let cur_installment_end_epoch = self.loan_contract.get_start_date() + i * self.loan_contract.get_installment_frequency();
-            sum_epochs_late += Decimal::from(current_epoch - cur_installment_end_epoch);
-            // if we made a payment ealier and we already computed the amount lost we use the epochs_since_last_update so that we don't apply penaly for them
-            if cur_installment_end_epoch < current_epoch -  epochs_since_last_update {
-                sum_epochs_late -= current_epoch - cur_installment_end_epoch - epochs_since_last_update;
-            }
-        }
-
-        // TODO: if a borrower payed half of an installment amount and he is late, we should apply the penalty only for that half and not for the whole amount
-        return sum_epochs_late * self.loan_contract.get_late_payment_penalty_rate() * self.loan_contract.get_amount_per_installment();
-    }
-
-    /// It calculates the amount of tokens that the user saved by paying installments in advance (the unlock threshold will be reduced)
-    /// 
-    /// Arguments:
-    /// 
-    /// * `current_epoch`: the current epoch
-    /// * `num_deposited_tokens`: the amount of tokens deposited by the user
-    /// 
-    /// Returns:
-    /// 
-    /// The amount of tokens that the user saved by paying installments in advance.
-    fn get_advance_payment_savings(&self, current_epoch : u64, num_deposited_tokens : Decimal) -> Decimal {
-        if current_epoch - self.loan_contract.get_start_date() > self.loan_contract.get_duration() {
-            return Decimal::zero()
-        }
-
-        let nb_repayed_installments = (self.loan_refund_status.refunded_tokens + num_deposited_tokens)  / self.loan_contract.get_amount_per_installment();
-        let nb_installments_to_date = Decimal::from(self.get_num_installments_to_date());
-
-        if nb_repayed_installments <= nb_installments_to_date {
-            return Decimal::zero()
-        }
-        let nb_installments_already_refunded = num_deposited_tokens / self.loan_contract.get_amount_per_installment();
-        let nb_installments_payed_in_advance = nb_installments_already_refunded - cmp::max(nb_installments_already_refunded, nb_installments_to_date);
-
-        if nb_installments_payed_in_advance > Decimal::zero() {
-            return nb_installments_payed_in_advance * Decimal::from(self.loan_contract.get_duration() - current_epoch) * self.loan_contract.get_interest_rate();
-        }
-        return Decimal::zero()
+    /// The threshold is what was already refunded plus everything that the borrower still owes
+    fn update_unlock_threshold(&mut self) {
+        let remaining_debt = self.remaining_schedule.iter().fold(Decimal::zero(), |sum, installment| sum + installment.amount - installment.amount_paid);
+        self.loan_refund_status.unlock_threshold = self.loan_refund_status.refunded_tokens + self.loan_refund_status.accrued_penalties + remaining_debt;
     }
 
-
     /// Function called when the borrower want to repay the loan
+    /// The deposited tokens are used in the following order: late payment penalties, installments that are due
+    /// up to the current installment period and finally a prepayment of the principal, which recomputes the remaining schedule
     /// It takes in a number of tokens deposited by the borrower, a proof of the lender's badge, and a proof
     /// of the loan NFT, and returns a tuple of the updated loan refund status and the number of tokens that
     /// overflowed the refund threshold
@@ -253,16 +358,42 @@ impl LockedLoanCollateral
         assert!(loan_nft.non_fungible::<NFTLoan>().id() == self.loan_contract_nft_id, "loan nft id does not match with the current loan id");
 
         let current_epoch : u64 = scrypto::prelude::Runtime::current_epoch();
+        self.loan_refund_status.accrued_penalties += self.loan_contract.get_late_installment_penalty(&self.remaining_schedule,
+            self.loan_refund_status.last_update, current_epoch);
+
+        // penalties are paid first
+        let mut remaining_tokens = num_deposited_tokens;
+        let paid_penalties = cmp::min(remaining_tokens, self.loan_refund_status.accrued_penalties);
+        self.loan_refund_status.accrued_penalties -= paid_penalties;
+        remaining_tokens -= paid_penalties;
+
+        // then the installments that are due, including the one for the current installment period
+        let current_period_end = current_epoch + self.loan_contract.get_installment_frequency();
+        while remaining_tokens > Decimal::zero() && !self.remaining_schedule.is_empty() && self.remaining_schedule[0].due_epoch <= current_period_end {
+            let unpaid_amount = self.remaining_schedule[0].amount - self.remaining_schedule[0].amount_paid;
+            if remaining_tokens < unpaid_amount {
+                self.remaining_schedule[0].amount_paid += remaining_tokens;
+                remaining_tokens = Decimal::zero();
+                break;
+            }
+
+            remaining_tokens -= unpaid_amount;
+            self.loan_refund_status.outstanding_principal -= self.remaining_schedule[0].principal;
+            self.remaining_schedule.remove(0);
+        }
 
-        let mut deposited_tokens_overflow = Decimal::zero();
-        if self.loan_refund_status.refunded_tokens + num_deposited_tokens > self.loan_refund_status.unlock_threshold {
-            deposited_tokens_overflow = self.loan_refund_status.refunded_tokens + num_deposited_tokens - self.loan_refund_status.unlock_threshold;
+        // whatever is left is a prepayment of the principal
+        if remaining_tokens > Decimal::zero() && !self.remaining_schedule.is_empty() {
+            let prepaid_principal = cmp::min(remaining_tokens, self.loan_refund_status.outstanding_principal);
+            self.loan_refund_status.outstanding_principal -= prepaid_principal;
+            remaining_tokens -= prepaid_principal;
+            self.remaining_schedule = self.loan_contract.reschedule(&self.remaining_schedule, self.loan_refund_status.outstanding_principal);
         }
-            
-        // update the status of the refund
-        self.loan_refund_status.unlock_threshold += self.get_late_installment_penalty(current_epoch) - self.get_advance_payment_savings(current_epoch, num_deposited_tokens);
+
+        let deposited_tokens_overflow = remaining_tokens;
         self.loan_refund_status.refunded_tokens += num_deposited_tokens - deposited_tokens_overflow;
-        self.loan_refund_status.last_update = current_epoch; // we don't really need this but it makes things easier to calculate
+        self.loan_refund_status.last_update = current_epoch; // penalties are computed since the last update
+        self.update_unlock_threshold();
 
         return (self.loan_refund_status.clone(), deposited_tokens_overflow)
     }
@@ -350,6 +481,11 @@ impl LockedLoanCollateral
     }
 
 
+    /// The installments that still need to be paid, in the order they are due
+    pub fn get_remaining_schedule(&self) -> Vec<ScheduledInstallment> {
+        return self.remaining_schedule.clone();
+    }
+
     pub fn get_id(&self) -> NonFungibleId { 
         return self.loan_contract_nft_id.clone();
     }
@@ -362,4 +498,100 @@ impl LockedLoanCollateral
         return self.can_be_liquidated();
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use scrypto::prelude::*;
+    use crate::locked_loan_collateral::{AmortizationSchedule, LoanContract, ScheduledInstallment};
+
+    // 1000 borrowed at epoch 0 over 4 installments of 480 epochs, 1% interest per installment,
+    // 0.1% penalty per late epoch and a grace period of 48 epochs
+    fn contract(amortization_schedule : AmortizationSchedule) -> LoanContract {
+        LoanContract::create(0, 1920, 480, dec!("0.01"), dec!("0.001"), amortization_schedule, 48, dec!("1000"), dec!("2000"))
+    }
+
+    fn amounts(schedule : &[ScheduledInstallment]) -> Vec<Decimal> {
+        schedule.iter().map(|installment| installment.amount).collect()
+    }
+
+    #[test]
+    fn test_equal_principal_schedule() {
+        let schedule = contract(AmortizationSchedule::EqualPrincipal).get_initial_schedule();
+        assert_eq!(schedule.iter().map(|installment| installment.due_epoch).collect::<Vec<u64>>(), vec![480, 960, 1440, 1920]);
+        assert!(schedule.iter().all(|installment| installment.principal == dec!("250")));
+        assert_eq!(amounts(&schedule), vec![dec!("260"), dec!("257.5"), dec!("255"), dec!("252.5")]);
+    }
+
+    #[test]
+    fn test_interest_only_schedule() {
+        let schedule = contract(AmortizationSchedule::InterestOnly).get_initial_schedule();
+        assert_eq!(amounts(&schedule), vec![dec!("10"), dec!("10"), dec!("10"), dec!("1010")]);
+        assert_eq!(schedule[3].principal, dec!("1000"));
+    }
+
+    #[test]
+    fn test_equal_installment_schedule() {
+        let schedule = AmortizationSchedule::EqualInstallment.build(dec!("1000"), Decimal::zero(), 10, 10, 4);
+        assert_eq!(amounts(&schedule), vec![dec!("250"); 4]);
+
+        // the annuity is 1000 * 0.1 * 1.1^2 / (1.1^2 - 1) = 576.19...
+        let schedule = AmortizationSchedule::EqualInstallment.build(dec!("1000"), dec!("0.1"), 10, 10, 2);
+        assert_eq!(schedule[0].interest, dec!("100"));
+        assert_eq!(schedule[0].principal + schedule[1].principal, dec!("1000"));
+        let diff = schedule[0].amount - schedule[1].amount;
+        assert!(diff < dec!("0.000001") && diff > dec!("-0.000001"), "{}, {}", schedule[0].amount, schedule[1].amount);
+        assert!(schedule[0].amount > dec!("576.19") && schedule[0].amount < dec!("576.20"));
+    }
+
+    #[test]
+    fn test_empty_schedule() {
+        assert!(AmortizationSchedule::EqualInstallment.build(dec!("1000"), dec!("0.01"), 10, 10, 0).is_empty());
+        assert!(AmortizationSchedule::EqualPrincipal.build(Decimal::zero(), dec!("0.01"), 10, 10, 4).is_empty());
+    }
+
+    #[test]
+    fn test_reschedule_after_prepayment() {
+        let contract = contract(AmortizationSchedule::EqualPrincipal);
+        let schedule = contract.get_initial_schedule();
+
+        // the first installment was paid and 150 of the principal was prepaid, leaving 600 over 3 installments
+        let schedule = contract.reschedule(&schedule[1..], dec!("600"));
+        assert_eq!(schedule.iter().map(|installment| installment.due_epoch).collect::<Vec<u64>>(), vec![960, 1440, 1920]);
+        assert!(schedule.iter().all(|installment| installment.principal == dec!("200")));
+        assert_eq!(amounts(&schedule), vec![dec!("206"), dec!("204"), dec!("202")]);
+
+        assert!(contract.reschedule(&[], dec!("600")).is_empty());
+    }
+
+    #[test]
+    fn test_no_penalty_during_grace_period() {
+        let contract = contract(AmortizationSchedule::EqualPrincipal);
+        let schedule = contract.get_initial_schedule();
+        assert_eq!(contract.get_late_installment_penalty(&schedule, 0, 480), Decimal::zero());
+        assert_eq!(contract.get_late_installment_penalty(&schedule, 0, 528), Decimal::zero());
+    }
+
+    #[test]
+    fn test_penalty_runs_from_due_date_once_grace_period_is_exceeded() {
+        let contract = contract(AmortizationSchedule::EqualPrincipal);
+        let mut schedule = contract.get_initial_schedule();
+
+        // 49 epochs late on an installment of 260
+        assert_eq!(contract.get_late_installment_penalty(&schedule, 0, 529), dec!("12.74"));
+        // penalties up to the last update were already accrued
+        assert_eq!(contract.get_late_installment_penalty(&schedule, 500, 529), dec!("7.54"));
+        // only the unpaid part of the installment is penalized
+        schedule[0].amount_paid = dec!("60");
+        assert_eq!(contract.get_late_installment_penalty(&schedule, 0, 529), dec!("9.8"));
+    }
+
+    #[test]
+    fn test_penalty_on_several_late_installments() {
+        let contract = contract(AmortizationSchedule::EqualPrincipal);
+        let schedule = contract.get_initial_schedule();
+
+        // 529 epochs late on the first installment and 49 on the second, the third is not due yet
+        assert_eq!(contract.get_late_installment_penalty(&schedule, 0, 1009), dec!("137.54") + dec!("12.6175"));
+    }
+}
@@ -1,9 +1,22 @@
 use scrypto::prelude::*;
 
-//The centralRepo allows users to create lending offers. The offers are stored in the offers vector until no longer needed
+#[derive(Debug, Clone, sbor::Decode, sbor::Encode, sbor::Describe, sbor::TypeId)]
+// Listing of a seclending component, the lots and loans counters are updated by the component itself
+pub struct OfferInfo {
+  lot: Vec<(ResourceAddress, Decimal)>, // tokens and quantities of one lot
+  lots_available: u64,
+  active_loans: u64,
+  collat_amount: Decimal,
+  collat_resource_address: ResourceAddress,
+  cost_amount_per_hour: Decimal,
+  fee_resource_address: ResourceAddress,
+  max_borrow_time_in_hours: u64,
+}
+
+//The centralRepo allows users to create lending offers. The offers are stored in the offers map until no longer needed
 blueprint! {
   struct CentralRepository {
-    offers: Vec<(Decimal, ResourceAddress, Decimal, Decimal, u64, ComponentAddress, ResourceAddress, ResourceAddress)>,
+    offers: HashMap<ComponentAddress, OfferInfo>,
     package_address: PackageAddress,
     internal_badge: Vault,
     admin_badge_addr: ResourceAddress,
@@ -13,7 +26,7 @@ blueprint! {
   impl CentralRepository {
     pub fn instantiate(package_address: PackageAddress) -> (ComponentAddress, Bucket) {
       // internal badge to mint the admins badge
-      let Internal_badge = ResourceBuilder::new_fungible().initial_supply(1);
+      let internal_badge = ResourceBuilder::new_fungible().initial_supply(1);
       
       // Admin badge which will be given to each sec lending component
       let admin_badge_addr = ResourceBuilder::new_fungible()
@@ -28,7 +41,7 @@ blueprint! {
           .initial_supply(1);
       
       let component_address = Self {
-        offers: Vec::new(),
+        offers: HashMap::new(),
         package_address: package_address,
         internal_badge: Vault::with_bucket(internal_badge),
         admin_badge_addr: admin_badge_addr,
@@ -44,26 +57,56 @@ blueprint! {
       (component_address, oracle_admin_badge)
     }
     
-    // when a lender want to create a new offer, it provide information to create the new seclending component, and return the lender badge to the creator
-    pub fn new_offer(&mut self, tokens: Bucket, collat_amount: Decimal, collat_resource_address: ResourceAddress ,cost_amount_per_hour: Decimal, fee_resource_address: ResourceAddress,max_borrow_time_in_hours: u64) -> (ComponentAddress, Bucket) {
+    // when a lender want to create a new offer, it provide a basket of tokens split in lots and the terms of one lot to create the new seclending component, 
+    // and return the lender badge to the creator
+    pub fn new_offer(&mut self, tokens: Vec<Bucket>, lots: u64, collat_amount: Decimal, collat_resource_address: ResourceAddress ,cost_amount_per_hour: Decimal, fee_resource_address: ResourceAddress,max_borrow_time_in_hours: u64) -> (ComponentAddress, Bucket) {
+      assert!(lots > 0, "the basket should have at least one lot");
       let admin_badge = self.internal_badge.authorize(|| borrow_resource_manager!(self.admin_badge_addr).mint(Decimal::ONE));
       let my_address : ComponentAddress = Runtime::actor().component_address().unwrap();
-      let tokens_amount = tokens.amount();
-      let tokens_address = tokens.resource_address();
-      let args = args![tokens, collat_amount, cost_amount_per_hour, max_borrow_time_in_hours, admin_badge, my_address, self.oracle_admin_badge_address, collat_resource_address, fee_resource_address];
+      let lot: Vec<(ResourceAddress, Decimal)> = tokens.iter().map(|t| (t.resource_address(), t.amount() / lots)).collect();
+      let args = args![tokens, lots, collat_amount, cost_amount_per_hour, max_borrow_time_in_hours, admin_badge, my_address, self.oracle_admin_badge_address, collat_resource_address, fee_resource_address];
       let result = Runtime::call_function(self.package_address, "SecurityLending", "instantiate", args);
       let option_result = scrypto_decode(&result);
       let (comp, badge) : (ComponentAddress, Bucket) = option_result.unwrap();
-      self.offers.push((tokens_amount, tokens_address, collat_amount, cost_amount_per_hour, max_borrow_time_in_hours, comp, collat_resource_address, fee_resource_address));
+      self.offers.insert(comp, OfferInfo {
+        lot: lot,
+        lots_available: lots,
+        active_loans: 0,
+        collat_amount: collat_amount,
+        collat_resource_address: collat_resource_address,
+        cost_amount_per_hour: cost_amount_per_hour,
+        fee_resource_address: fee_resource_address,
+        max_borrow_time_in_hours: max_borrow_time_in_hours,
+      });
       (comp, badge)
     }
 
-    // when a seclending component receive an accept or cancel order, it remove itself from the central component, to not be listed anymore 
+    // a seclending component calls this method each time a lot is borrowed, returned or defaulted, or when the offer is closed
+    pub fn update_offer(&mut self, addr: ComponentAddress, lots_available: u64, active_loans: u64, admin_badge: Proof) {
+      assert!(admin_badge.resource_address() == self.admin_badge_addr, "The badge provided is incorrect");
+      let offer = self.offers.get_mut(&addr).expect("unknown offer");
+      offer.lots_available = lots_available;
+      offer.active_loans = active_loans;
+      admin_badge.drop();
+    }
+
+    // when a seclending component is closed and all its loans are over, it remove itself from the central component, to not be listed anymore 
     pub fn remove_offer(&mut self, addr: ComponentAddress, admin_badge: Bucket) {
       assert!(admin_badge.amount().is_positive(), "The badge is empty");
       assert!(admin_badge.resource_address() == self.admin_badge_addr, "The badge provided is incorrect");
-      self.offers = self.offers.drain(..).filter(|o| o.5 != addr).collect();
+      self.offers.remove(&addr);
       admin_badge.burn();
     }
+
+    // Lists the offers, optionally only the ones lending a given token, with a term in the given range of hours, or with lots available
+    pub fn get_offers(&self, token: Option<ResourceAddress>, min_hours: Option<u64>, max_hours: Option<u64>, open_only: bool) -> Vec<(ComponentAddress, OfferInfo)> {
+      self.offers.iter()
+        .filter(|(_, o)| token.map_or(true, |t| o.lot.iter().any(|(address, _)| *address == t)))
+        .filter(|(_, o)| min_hours.map_or(true, |h| o.max_borrow_time_in_hours >= h))
+        .filter(|(_, o)| max_hours.map_or(true, |h| o.max_borrow_time_in_hours <= h))
+        .filter(|(_, o)| !open_only || o.lots_available > 0)
+        .map(|(addr, o)| (*addr, o.clone()))
+        .collect()
+    }
   }
-}
\ No newline at end of file
+}
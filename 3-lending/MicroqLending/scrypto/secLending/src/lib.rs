@@ -2,25 +2,58 @@ use scrypto::prelude::*;
 
 #[derive(Debug, PartialEq, sbor::Decode, sbor::Encode, sbor::Describe, sbor::TypeId)]
 // When instantiated, the components functions react differently depending on the State the component is in. 
+// An open offer rents its lots to any number of renters in parallel, a closed offer only waits for its active loans.
 enum State {
-    StateWaitForRenter,
-    StateRenting,
-    StateReturned,
-    StateDefaulted,
-    StateSettled,
-    StateCancelled,
+    StateOpen,
+    StateClosed,
 }
 
-// Used to identify the borrower, and help the frontend to list the relevant component
-fn create_borrower_badge(component_address: ComponentAddress) -> Bucket {
-    ResourceBuilder::new_fungible()
+#[derive(Debug, Clone, Copy, PartialEq, sbor::Decode, sbor::Encode, sbor::Describe, sbor::TypeId)]
+// State of a single loan, a loan can be renewed as long as it is renting
+pub enum LoanState {
+    LoanRenting,
+    LoanReturned,
+    LoanDefaulted,
+}
+
+#[derive(Debug, Clone, sbor::Decode, sbor::Encode, sbor::Describe, sbor::TypeId)]
+// A loan of one lot of the basket
+struct Loan {
+    term_start: u64, // unix time (ms) when the current term started
+    term_end: u64, // unix time (ms) when the lot must be returned, unless the loan is renewed
+    term_fee: Decimal, // fee paid for the current term
+    renewals: u64,
+    state: LoanState,
+}
+
+#[derive(NonFungibleData)]
+// Given to the renter, the loan NFT is needed to renew the loan and to get the collateral back
+pub struct LoanNft {
+    offer: ComponentAddress,
+    #[scrypto(mutable)]
+    term_end: u64,
+}
+
+// Used to identify the borrowers, each loan is a NFT of this resource, and help the frontend to list the relevant component
+fn create_loan_nft_resource(admin_badge_address: ResourceAddress) -> ResourceAddress {
+    ResourceBuilder::new_non_fungible()
         .metadata("name", "borrower badge")
         .metadata("central_exchange", "MiCroqLending")
-        .metadata("component_address", component_address.to_string())
-        .divisibility(DIVISIBILITY_NONE)
-        .burnable(rule!(allow_all), LOCKED)
+        .mintable(rule!(require(admin_badge_address)), LOCKED)
+        .burnable(rule!(require(admin_badge_address)), LOCKED)
+        .updateable_non_fungible_data(rule!(require(admin_badge_address)), LOCKED)
         .updateable_metadata(rule!(allow_all), LOCKED)
-        .initial_supply(1)
+        .no_initial_supply()
+}
+
+// Adds the securityLending component address to the loan NFTs, so the frontend can find the offers a user borrows from
+fn update_loan_nft_resource(loan_nft_address: ResourceAddress, component_address: ComponentAddress) {
+    let resource_manager: &ResourceManager = borrow_resource_manager!(loan_nft_address);
+    resource_manager.update_metadata(HashMap::from([
+      (String::from("name"), String::from("borrower badge")),
+      (String::from("central_exchange"), String::from("MiCroqLending")),
+      (String::from("component_address"), component_address.to_string())
+    ]));
 }
 
 // Used to identify the lender
@@ -42,23 +75,28 @@ fn update_lender_badge(lender_badge_address: ResourceAddress, component_address:
     ]));
 }
 
-// Allows user to lend tokens in exchange for a fee and with a collateral. User can specify the maximum duration of lending. We use real-world time coming from an oracle (the owner of the central repository)
+// Allows user to lend a basket of tokens in exchange for a fee and with a collateral. The basket is split in lots that are rented in parallel,
+// each renter gets a loan NFT. User can specify the duration of a term, renters can renew their loan for a new term before it expires.
+// We use real-world time coming from an oracle (the owner of the central repository)
 blueprint! {
     struct SecurityLending {
-      token_address: ResourceAddress, // Address of the token to borrow
-      token_quantity: Decimal, // Quantity of token available to borrow
-      collat_amount: Decimal, // Collateral amount to be provided
-      cost_amount_per_hour: Decimal, // Fee for borrowing the security
-      max_borrow_time_in_hours: u64, // Maximum amount before the lender can declare default
-      unix_borrow_start: u64, // Time when the offer has been accepted and the borrowing started
+      token_vaults: HashMap<ResourceAddress, Vault>, // vaults to store the tokens that are not rented
+      lot: HashMap<ResourceAddress, Decimal>, // Quantity of each token in one lot
+      lots: u64, // Number of lots in the basket
+      collat_amount: Decimal, // Collateral amount to be provided for one lot
+      cost_amount_per_hour: Decimal, // Fee for borrowing one lot
+      max_borrow_time_in_hours: u64, // Duration of a term, after that the lender can declare default unless the loan is renewed
       state: State, // Status of the component
       fee_resource_address: ResourceAddress, // Address of the token used for the fees (usually Radix)
       collat_resource_address: ResourceAddress, // Address of the token used for collateral (usually Radix)
-      max_lending_cost_amount: Decimal, // max borrow time * cost
-      token_vault: Vault, // vault to store the borrowed tokens
+      max_lending_cost_amount: Decimal, // max borrow time * cost, paid for each term
       collat_vault: Vault, // vault to store the collateral
       lending_fee_vault: Vault, // vault to store the fees
-      admin_badge: Vault, // vault to store the badge, which allow the component to unreference itself from the central component
+      reserved_fees: Decimal, // fees of the current terms, part of them is refunded if the lot is returned early
+      loans: HashMap<NonFungibleId, Loan>, // loans by loan NFT id
+      loan_nft: ResourceAddress, // address of the loan NFTs given to the renters
+      loan_count: u64,
+      admin_badge: Vault, // vault to store the badge, which allow the component to update and unreference itself in the central component
       lender: ResourceAddress, // address of the lender badge
       central_repo: ComponentAddress, // address of the central component
       oracle_admin_badge_address: ResourceAddress, // address of the oracle badge
@@ -69,7 +107,7 @@ blueprint! {
     }
 
     impl SecurityLending {
-        pub fn instantiate(tokens: Bucket, collat_amount: Decimal, cost_amount_per_hour: Decimal, max_borrow_time_in_hours: u64, admin_badge: Bucket,central_repo: ComponentAddress ,oracle_admin_badge_address: ResourceAddress, collat_resource_address: ResourceAddress, fee_resource_address: ResourceAddress,) -> (ComponentAddress, Bucket) {
+        pub fn instantiate(tokens: Vec<Bucket>, lots: u64, collat_amount: Decimal, cost_amount_per_hour: Decimal, max_borrow_time_in_hours: u64, admin_badge: Bucket,central_repo: ComponentAddress ,oracle_admin_badge_address: ResourceAddress, collat_resource_address: ResourceAddress, fee_resource_address: ResourceAddress,) -> (ComponentAddress, Bucket) {
             assert!(
                 collat_amount.is_positive() || collat_amount.is_zero(),
                 "collateral amount should be positive"
@@ -78,8 +116,22 @@ blueprint! {
                 cost_amount_per_hour.is_positive() || cost_amount_per_hour.is_zero(),
                 "cost amount should be positive"
             );
+            assert!(lots > 0, "the basket should have at least one lot");
+            assert!(max_borrow_time_in_hours > 0, "the term should last at least one hour");
+            assert!(!tokens.is_empty(), "the basket should contain at least one token");
             
             let lender_badge = create_lender_badge();
+            let loan_nft = create_loan_nft_resource(admin_badge.resource_address());
+
+            // each lot gets the same share of every token of the basket
+            let mut token_vaults: HashMap<ResourceAddress, Vault> = HashMap::new();
+            let mut lot: HashMap<ResourceAddress, Decimal> = HashMap::new();
+            for bucket in tokens {
+                assert!(bucket.amount().is_positive(), "the basket should not contain empty buckets");
+                assert!(!token_vaults.contains_key(&bucket.resource_address()), "each token should be provided once");
+                lot.insert(bucket.resource_address(), bucket.amount() / lots);
+                token_vaults.insert(bucket.resource_address(), Vault::with_bucket(bucket));
+            }
             
             // guard oracle methods should
             let access_rules = AccessRules::new()
@@ -88,20 +140,23 @@ blueprint! {
                  .default(rule!(allow_all));
 
             let component_address = Self {
-                token_address: tokens.resource_address(),
-                token_quantity: tokens.amount(),
+                token_vaults: token_vaults,
+                lot: lot,
+                lots: lots,
                 collat_amount,
                 cost_amount_per_hour,
                 max_borrow_time_in_hours,
-                unix_borrow_start: 0,
-                state: State::StateWaitForRenter,
+                state: State::StateOpen,
                 collat_resource_address: collat_resource_address,
                 fee_resource_address: fee_resource_address, 
                 max_lending_cost_amount: cost_amount_per_hour * max_borrow_time_in_hours, 
                 
-                token_vault: Vault::with_bucket(tokens),
                 collat_vault: Vault::new(collat_resource_address),
                 lending_fee_vault: Vault::new(fee_resource_address),
+                reserved_fees: Decimal::zero(),
+                loans: HashMap::new(),
+                loan_nft: loan_nft,
+                loan_count: 0,
                 admin_badge: Vault::with_bucket(admin_badge),
                 lender: lender_badge.resource_address(),
                 central_repo: central_repo,
@@ -115,15 +170,47 @@ blueprint! {
                 access_rules
             ).globalize();
             update_lender_badge(lender_badge.resource_address(), component_address);
+            update_loan_nft_resource(loan_nft, component_address);
             (component_address, lender_badge)
         }
+
+        // Number of lots that are not rented
+        pub fn available_lots(&self) -> u64 {
+            if self.state != State::StateOpen {
+                return 0;
+            }
+            return self.lots - self.active_loans();
+        }
+
+        pub fn active_loans(&self) -> u64 {
+            self.loans.values().filter(|l| l.state == LoanState::LoanRenting).count() as u64
+        }
+
+        // Returns (term start, term end, fee of the current term, renewals, state) of a loan
+        pub fn get_loan(&self, loan_id: NonFungibleId) -> (u64, u64, Decimal, u64, LoanState) {
+            let loan = self.loans.get(&loan_id).expect("unknown loan");
+            (loan.term_start, loan.term_end, loan.term_fee, loan.renewals, loan.state)
+        }
         
-        fn remove_from_central_repository(&mut self) {
-          let admin_badge = self.admin_badge.take_all();
+        // Keeps the central repository up to date so it can list the open and active offers. 
+        // Once closed without active loans, the offer unreferences itself
+        fn update_central_repository(&mut self) {
           let my_address : ComponentAddress = Runtime::actor().component_address().unwrap();
-          Runtime::call_method(self.central_repo, "remove_offer", args![my_address,admin_badge]);
+          if self.state == State::StateClosed && self.active_loans() == 0 {
+            if !self.admin_badge.is_empty() {
+              let admin_badge = self.admin_badge.take_all();
+              Runtime::call_method(self.central_repo, "remove_offer", args![my_address,admin_badge]);
+            }
+            return;
+          }
+          let admin_proof = self.admin_badge.create_proof();
+          Runtime::call_method(self.central_repo, "update_offer", args![my_address, self.available_lots(), self.active_loans(), admin_proof]);
         }
 
+        fn hours_to_ms(hours: u64) -> u64 {
+            // 3600000 is miliseconds per hour
+            3600000 * hours
+        }
        
         // unix time is supposed to only be valid for 10 minutes so we can get close to the real lending time
         // This method is guarded. Therefore, only the owner of the oracle_badge can call it.
@@ -139,77 +226,151 @@ blueprint! {
             self.unix_time_is_valid = false;
         }
 
-        // The cancellation is protected by the borrower badge, which is destroyed once called
-        // This method call the central component to remove this component from the list of active offer
-        pub fn cancel(&mut self, badge: Bucket) -> Bucket {
+        // The lender closes the offer, no new renters are accepted. The lots that are not rented are given back with the
+        // fees earned so far, the lots that are rented can be settled once returned
+        pub fn cancel(&mut self, badge: Proof) -> (Vec<Bucket>, Bucket) {
             assert!(badge.resource_address() == self.lender, "Only the lender can cancel the contract");
-            assert!(self.state == State::StateWaitForRenter, "You can only cancel a contract if the renter has not yet been found");
-            self.state = State::StateCancelled;
-            badge.burn();
-            self.remove_from_central_repository();
-            self.token_vault.take_all()
+            assert!(self.state == State::StateOpen, "The offer is already closed");
+            self.state = State::StateClosed;
+            badge.drop();
+            self.update_central_repository();
+            self.take_idle_tokens_and_fees()
         }
         
-        // The default is protected by the oracle timer and by the borrower badge, which is destroyed once called
-        pub fn default(&mut self, badge: Bucket) -> (Bucket, Bucket) {
+        // The default is protected by the oracle timer and by the lender badge. The lender gets the collateral of a loan
+        // that was neither returned nor renewed before the end of its term
+        pub fn default(&mut self, badge: Proof, loan_id: NonFungibleId) -> Bucket {
             assert!(self.unix_time_is_valid == true, "Ask the oracle to update the time first");
             assert!(badge.resource_address() == self.lender, "Only the lender can default the contract");
-            assert!(self.state == State::StateRenting, "Contract can't be defaulted");
-            // Check if the max_borrow_time in hours is already reached. 3600000 is miliseconds per hour
-            assert!((self.unix_borrow_start + 3600000 * self.max_borrow_time_in_hours) > self.unix_time, "Contract not in default, yet");
-            self.state = State::StateDefaulted;
-            badge.burn();
-            (self.collat_vault.take_all(), self.lending_fee_vault.take_all())
+            badge.drop();
+            let loan = self.loans.get_mut(&loan_id).expect("unknown loan");
+            assert!(loan.state == LoanState::LoanRenting, "Contract can't be defaulted");
+            assert!(self.unix_time > loan.term_end, "Contract not in default, yet");
+            loan.state = LoanState::LoanDefaulted;
+            // the lot is lost, the fee of the term is earned by the lender
+            self.reserved_fees -= loan.term_fee;
+            self.lots -= 1;
+            let collat = self.collat_vault.take(self.collat_amount);
+            self.update_central_repository();
+            collat
         }
         
-        // The settlement is protected by the borrower badge, which is destroyed once called
-        pub fn settle(&mut self, badge: Bucket) -> (Bucket, Bucket) {
+        // The settlement gives back to the lender the returned lots and the fees earned so far, the offer must be closed first
+        pub fn settle(&mut self, badge: Proof) -> (Vec<Bucket>, Bucket) {
             assert!(badge.resource_address() == self.lender, "Only the lender can settle the contract");
-            assert!(self.state == State::StateReturned, "Token has not been returned yet");
-            self.state = State::StateSettled;
-            badge.burn();
-            (self.token_vault.take_all(), self.lending_fee_vault.take_all())
+            assert!(self.state == State::StateClosed, "Cancel the offer before settling it");
+            badge.drop();
+            self.take_idle_tokens_and_fees()
+        }
+
+        // The lender collects the fees that are no longer reserved. The fee of a term stays reserved, even once the term
+        // has ended, until the lot is returned, the loan is defaulted or the loan is renewed into a new term
+        pub fn collect_fees(&mut self, badge: Proof) -> Bucket {
+            assert!(badge.resource_address() == self.lender, "Only the lender can collect the fees");
+            badge.drop();
+            self.lending_fee_vault.take(self.lending_fee_vault.amount() - self.reserved_fees)
+        }
+
+        fn take_idle_tokens_and_fees(&mut self) -> (Vec<Bucket>, Bucket) {
+            let tokens: Vec<Bucket> = self.token_vaults.values_mut().map(|v| v.take_all()).collect();
+            let fees = self.lending_fee_vault.take(self.lending_fee_vault.amount() - self.reserved_fees);
+            (tokens, fees)
         }
         
-        // The borrower badge doesn't really protect this method, because we don't care who is reimbursing.
-        // we'll burn the badge to not encumber the wallet of the borrower
-        pub fn return_asset(&mut self, mut token: Bucket, borrower_badge: Bucket) -> (Bucket, Bucket, Bucket) {
+        // The renter gives back one lot with the loan NFT, which is burned, and gets the collateral back
+        // with the fee of the hours of the current term that were not used
+        pub fn return_asset(&mut self, mut tokens: Vec<Bucket>, loan_nft: Bucket) -> (Bucket, Vec<Bucket>, Bucket) {
+            assert!(self.unix_time_is_valid == true, "Ask the oracle to update the time first");
+            assert!(loan_nft.resource_address() == self.loan_nft && loan_nft.amount() == Decimal::one(), "invalid loan NFT");
+            let loan_id = loan_nft.non_fungible::<LoanNft>().id();
+            let loan = self.loans.get_mut(&loan_id).expect("unknown loan");
+            assert!(loan.state == LoanState::LoanRenting, "Token cannot be returned anymore");
+
+            for token in tokens.iter_mut() {
+                let quantity = *self.lot.get(&token.resource_address()).expect("incorrect token type");
+                assert!(quantity <= token.amount(), "not enough tokens");
+                self.token_vaults.get_mut(&token.resource_address()).unwrap().put(token.take(quantity));
+            }
+            // every token of the lot must have been returned
+            for (address, quantity) in self.lot.iter() {
+                assert!(tokens.iter().any(|t| t.resource_address() == *address) || quantity.is_zero(), "not all tokens of the lot were returned");
+            }
+            loan.state = LoanState::LoanReturned;
+
+            // Calculates how much of the fee of the current term is given back to the renter
+            let term_ms = Self::hours_to_ms(self.max_borrow_time_in_hours);
+            let unused_ms = if loan.term_end > self.unix_time { std::cmp::min(loan.term_end - self.unix_time, term_ms) } else { 0 };
+            let lending_fee_leftover = self.lending_fee_vault.take(loan.term_fee * unused_ms / term_ms);
+            self.reserved_fees -= loan.term_fee;
+
+            self.admin_badge.authorize(|| loan_nft.burn());
+            let collat = self.collat_vault.take(self.collat_amount);
+            self.update_central_repository();
+            (collat, tokens, lending_fee_leftover)
+        }
+
+        // The renter rolls the loan into a new term before the current one expires by paying the fee of the next term.
+        // The new term starts when the current one ends. A closed offer can't be renewed
+        pub fn renew(&mut self, loan_nft: Proof, mut lending_fee: Bucket) -> Bucket {
             assert!(self.unix_time_is_valid == true, "Ask the oracle to update the time first");
-            assert!(self.state == State::StateRenting, "Token cannot be returned anymore");
-            assert!(token.resource_address() == self.token_vault.resource_address(), "incorrect token type");
-            assert!(self.token_quantity <= token.amount(), "not enough tokens");
-            self.state = State::StateReturned;
-            self.token_vault.put(token.take(self.token_quantity));
-
-            // Calculates how much of the lending_fee will be given back to lender 
-            let hours_passed = ((self.unix_time - self.unix_borrow_start) / 3600000) as u64;
-            let mut percentage_of_max_hours = hours_passed / self.max_borrow_time_in_hours;
-            if percentage_of_max_hours > 1{percentage_of_max_hours = 1}
-            let lending_fee_leftover = self.lending_fee_vault.take(self.lending_fee_vault.amount() * percentage_of_max_hours);
-            borrower_badge.burn();
-            (self.collat_vault.take_all(), token, lending_fee_leftover)
+            assert!(self.state == State::StateOpen, "The offer is closed, the loan cannot be renewed");
+            assert!(loan_nft.resource_address() == self.loan_nft, "invalid loan NFT");
+            assert!(lending_fee.resource_address() == self.lending_fee_vault.resource_address(), "incorrect cost currency");
+            assert!(lending_fee.amount() >= self.max_lending_cost_amount, "need more to cover borrowing the cost");
+            let nft = loan_nft.non_fungible::<LoanNft>();
+            let loan = self.loans.get_mut(&nft.id()).expect("unknown loan");
+            assert!(loan.state == LoanState::LoanRenting, "Loan cannot be renewed anymore");
+            assert!(self.unix_time < loan.term_end, "The term has expired, the loan cannot be renewed");
+
+            // the fee of the current term is earned by the lender
+            self.reserved_fees = self.reserved_fees - loan.term_fee + self.max_lending_cost_amount;
+            loan.term_start = loan.term_end;
+            loan.term_end = loan.term_end + Self::hours_to_ms(self.max_borrow_time_in_hours);
+            loan.term_fee = self.max_lending_cost_amount;
+            loan.renewals += 1;
+            let term_end = loan.term_end;
+            self.lending_fee_vault.put(lending_fee.take(self.max_lending_cost_amount));
+
+            self.admin_badge.authorize(|| nft.update_data(LoanNft { offer: Runtime::actor().component_address().unwrap(), term_end: term_end }));
+            loan_nft.drop();
+            lending_fee
         }
         
-        // The borrower call this method to accept the offer, he receive a badge in return to help the frontend to keep track of it
-        // This method call the central component to remove this component from the list of active offer
-        pub fn borrow(&mut self, mut collat: Bucket, mut lending_fee: Bucket) -> (Bucket, Bucket, Bucket, Bucket) {
+        // The borrower call this method to rent one lot, he receive a loan NFT in return to help the frontend to keep track of it
+        // This method call the central component to update the number of lots available
+        pub fn borrow(&mut self, mut collat: Bucket, mut lending_fee: Bucket) -> (Vec<Bucket>, Bucket, Bucket, Bucket) {
             assert!(self.unix_time_is_valid == true, "Ask the oracle to update the time so the contract knows when you start borrowing");
-            assert!(self.collat_vault.resource_address() == collat.resource_address(), "collateral resource is of wrong type");
-            assert!(self.lending_fee_vault.resource_address() == lending_fee.resource_address(), "lending fee resource is of wrong type");
-            assert!(self.state == State::StateWaitForRenter, "Token cannot be borrowed anymore");
+            assert!(self.state == State::StateOpen, "Token cannot be borrowed anymore");
+            assert!(self.available_lots() > 0, "All the lots are rented");
             assert!(collat.resource_address() == self.collat_vault.resource_address(), "incorrect collateral type");
             assert!(lending_fee.resource_address() == self.lending_fee_vault.resource_address(), "incorrect cost currency");
             assert!(collat.amount() >= self.collat_amount, "not enough collateral");
-            assert!(lending_fee.amount() >= (self.cost_amount_per_hour * self.max_borrow_time_in_hours), "need more to cover borrowing the cost");
-            self.unix_borrow_start = self.unix_time;
-            self.unix_time_is_valid = false; 
-            self.state = State::StateRenting;
+            assert!(lending_fee.amount() >= self.max_lending_cost_amount, "need more to cover borrowing the cost");
             self.collat_vault.put(collat.take(self.collat_amount));
-            self.lending_fee_vault.put(lending_fee.take(self.cost_amount_per_hour * self.max_borrow_time_in_hours));
+            self.lending_fee_vault.put(lending_fee.take(self.max_lending_cost_amount));
+            self.reserved_fees += self.max_lending_cost_amount;
+
+            let term_end = self.unix_time + Self::hours_to_ms(self.max_borrow_time_in_hours);
+            let loan_id = NonFungibleId::from_u64(self.loan_count);
+            self.loan_count += 1;
+            self.loans.insert(loan_id.clone(), Loan {
+                term_start: self.unix_time,
+                term_end: term_end,
+                term_fee: self.max_lending_cost_amount,
+                renewals: 0,
+                state: LoanState::LoanRenting,
+            });
             let component_address : ComponentAddress = Runtime::actor().component_address().unwrap();
-            let borrower_badge = create_borrower_badge(component_address);
-            self.remove_from_central_repository();
-            (self.token_vault.take_all(),collat,lending_fee, borrower_badge)
+            let loan_nft = self.admin_badge.authorize(|| {
+                borrow_resource_manager!(self.loan_nft).mint_non_fungible(&loan_id, LoanNft { offer: component_address, term_end: term_end })
+            });
+
+            let mut tokens: Vec<Bucket> = Vec::new();
+            for (address, quantity) in self.lot.iter() {
+                tokens.push(self.token_vaults.get_mut(address).unwrap().take(*quantity));
+            }
+            self.update_central_repository();
+            (tokens, collat, lending_fee, loan_nft)
         }
     }
 }
\ No newline at end of file
@@ -18,9 +18,12 @@ export function buildTransactionNewToken(accountAddress, amount, name, symbol){
   ].join("\n");
 }
 
+// The offer lends a basket of tokens split in `lots` identical lots
 export function buildNewOffer(accountAddress, offer){
+  const buckets = offer.tokens.map((token, i) => 'Bucket("offerToken' + i + '")');
   let args = [
-    'Bucket("offerToken")',
+    'Vec<Bucket>(' + buckets.join(", ") + ')',
+    '' + offer.lots + 'u64',
     'Decimal("' + offer.collatAmount + '")',
     'ResourceAddress("' + offer.collatResourceAddress + '")',
     'Decimal("' + offer.costPerHour + '")',
@@ -28,13 +31,16 @@ export function buildNewOffer(accountAddress, offer){
     '' + offer.maxBorrowTime + 'u64', 
   ].join(" ");
   return [
-    'CALL_METHOD ComponentAddress("' + accountAddress + '") "withdraw_by_amount" Decimal("' + offer.tokenAmount + '") ResourceAddress("' + offer.tokenAddress + '");',
-    'TAKE_FROM_WORKTOP_BY_AMOUNT Decimal("' + offer.tokenAmount + '") ResourceAddress("' + offer.tokenAddress + '") Bucket("offerToken");',
+    ...offer.tokens.map(token =>
+      'CALL_METHOD ComponentAddress("' + accountAddress + '") "withdraw_by_amount" Decimal("' + token.tokenAmount + '") ResourceAddress("' + token.tokenAddress + '");'),
+    ...offer.tokens.map((token, i) =>
+      'TAKE_FROM_WORKTOP_BY_AMOUNT Decimal("' + token.tokenAmount + '") ResourceAddress("' + token.tokenAddress + '") ' + buckets[i] + ';'),
     'CALL_METHOD ComponentAddress("' + config.centralComponentAddress + '") "new_offer" ' + args + ';',
     'CALL_METHOD_WITH_ALL_RESOURCES ComponentAddress("' + accountAddress + '") "deposit_batch";'
   ].join("\n");
 }
 
+// Rents one lot of the offer, the fee of a full term is paid upfront
 export function buildAcceptOffer(accountAddress, offer){
   return [
     'CALL_METHOD ComponentAddress("' + accountAddress + '") "withdraw_by_amount" Decimal("' + offer.collatAmount + '") ResourceAddress("' + offer.collatResourceAddress + '");',
@@ -48,43 +54,59 @@ export function buildAcceptOffer(accountAddress, offer){
   ].join("\n");
 }
 
-export function buildCancelOffer(accountAddress, offer){
+// The lender methods only need a proof of the lender badge
+function buildLenderCall(accountAddress, offer, method, args){
   return [
-    'CALL_METHOD ComponentAddress("' + accountAddress + '") "withdraw_by_amount" Decimal("1") ResourceAddress("' + offer.badgeAddress + '");',
-    'TAKE_FROM_WORKTOP_BY_AMOUNT Decimal("1") ResourceAddress("' + offer.badgeAddress + '") Bucket("bucket1");',
-    'CALL_METHOD ComponentAddress("' + offer.address + '") "cancel" Bucket("bucket1");',
+    'CALL_METHOD ComponentAddress("' + accountAddress + '") "create_proof_by_amount" Decimal("1") ResourceAddress("' + offer.badgeAddress + '");',
+    'POP_FROM_AUTH_ZONE Proof("lenderProof");',
+    'CALL_METHOD ComponentAddress("' + offer.address + '") "' + method + '" ' + ['Proof("lenderProof")', ...args].join(" ") + ';',
     'CALL_METHOD_WITH_ALL_RESOURCES ComponentAddress("' + accountAddress + '") "deposit_batch";'
   ].join("\n");
 }
 
-export function buildSeizeCollateral(accountAddress, offer){
-  return [
-    'CALL_METHOD ComponentAddress("' + accountAddress + '") "withdraw_by_amount" Decimal("1") ResourceAddress("' + offer.badgeAddress + '");',
-    'TAKE_FROM_WORKTOP_BY_AMOUNT Decimal("1") ResourceAddress("' + offer.badgeAddress + '") Bucket("badgeBucket");',
-    'CALL_METHOD ComponentAddress("' + offer.address + '") "default" Bucket("badgeBucket");',
-    'CALL_METHOD_WITH_ALL_RESOURCES ComponentAddress("' + accountAddress + '") "deposit_batch";'
-  ].join("\n");
+export function buildCancelOffer(accountAddress, offer){
+  return buildLenderCall(accountAddress, offer, "cancel", []);
+}
+
+export function buildSeizeCollateral(accountAddress, offer, loan){
+  return buildLenderCall(accountAddress, offer, "default", ['NonFungibleId("' + loan.id + '")']);
 }
 
 export function buildSettleOffer(accountAddress, offer){
+  return buildLenderCall(accountAddress, offer, "settle", []);
+}
+
+export function buildCollectFees(accountAddress, offer){
+  return buildLenderCall(accountAddress, offer, "collect_fees", []);
+}
+
+// Renews one of the loans of the account for a new term, the loan NFT stays in the account
+export function buildRenewLoan(accountAddress, offer){
+  const fee = offer.maxBorrowTime * offer.costPerHour;
   return [
-    'CALL_METHOD ComponentAddress("' + accountAddress + '") "withdraw_by_amount" Decimal("1") ResourceAddress("' + offer.badgeAddress + '");',
-    'TAKE_FROM_WORKTOP_BY_AMOUNT Decimal("1") ResourceAddress("' + offer.badgeAddress + '") Bucket("bucket1");',
-    'CALL_METHOD ComponentAddress("' + offer.address + '") "settle" Bucket("bucket1");',
+    'CALL_METHOD ComponentAddress("' + accountAddress + '") "create_proof_by_amount" Decimal("1") ResourceAddress("' + offer.loanNftAddress + '");',
+    'POP_FROM_AUTH_ZONE Proof("loanProof");',
+    'CALL_METHOD ComponentAddress("' + accountAddress + '") "withdraw_by_amount" Decimal("' + fee + '") ResourceAddress("' + offer.feeResourceAddress + '");',
+    'TAKE_FROM_WORKTOP_BY_AMOUNT Decimal("' + fee + '") ResourceAddress("' + offer.feeResourceAddress + '") Bucket("feeBucket");',
+    'CALL_METHOD ComponentAddress("' + offer.address + '") "renew" Proof("loanProof") Bucket("feeBucket");',
     'CALL_METHOD_WITH_ALL_RESOURCES ComponentAddress("' + accountAddress + '") "deposit_batch";'
   ].join("\n");
 }
 
+// Gives one lot back with one of the loan NFTs of the account
 export function buildReturnAssets(accountAddress, offer){
+  const buckets = offer.lot.map((token, i) => 'Bucket("assetBucket' + i + '")');
   return [
-    // Take the badge and the tokens that need to be returned
-    'CALL_METHOD ComponentAddress("' + accountAddress + '") "withdraw_by_amount" Decimal("1") ResourceAddress("' + offer.badgeAddress + '");',
-    'CALL_METHOD ComponentAddress("' + accountAddress + '") "withdraw_by_amount" Decimal("' + offer.tokenAmount + '") ResourceAddress("' + offer.tokenAddress + '");',
+    // Take the loan NFT and the tokens of one lot that need to be returned
+    'CALL_METHOD ComponentAddress("' + accountAddress + '") "withdraw_by_amount" Decimal("1") ResourceAddress("' + offer.loanNftAddress + '");',
+    ...offer.lot.map(token =>
+      'CALL_METHOD ComponentAddress("' + accountAddress + '") "withdraw_by_amount" Decimal("' + token.tokenAmount + '") ResourceAddress("' + token.tokenAddress + '");'),
     // Place it in the worktop so it can be used 
-    'TAKE_FROM_WORKTOP_BY_AMOUNT Decimal("1") ResourceAddress("' + offer.badgeAddress + '") Bucket("badgeBucket");',
-    'TAKE_FROM_WORKTOP ResourceAddress("' + offer.tokenAddress + '") Bucket("assetBucket");',
+    'TAKE_FROM_WORKTOP_BY_AMOUNT Decimal("1") ResourceAddress("' + offer.loanNftAddress + '") Bucket("loanBucket");',
+    ...offer.lot.map((token, i) =>
+      'TAKE_FROM_WORKTOP_BY_AMOUNT Decimal("' + token.tokenAmount + '") ResourceAddress("' + token.tokenAddress + '") ' + buckets[i] + ';'),
     // Call the component method 
-    'CALL_METHOD ComponentAddress("' + offer.address + '") "return_asset" Bucket("assetBucket") Bucket("badgeBucket");',
+    'CALL_METHOD ComponentAddress("' + offer.address + '") "return_asset" Vec<Bucket>(' + buckets.join(", ") + ') Bucket("loanBucket");',
     // Return the collateral and in some cases a rest of the fee
     'CALL_METHOD_WITH_ALL_RESOURCES ComponentAddress("' + accountAddress + '") "deposit_batch";'
   ].join("\n");
@@ -70,29 +70,86 @@ export function getComponentAddress(struct){
   return struct.value.slice(expectStart.length, -2);
 }
 
+export function getNonFungibleId(struct){
+  assert(struct.type == "NonFungibleId", "Expect a NonFungibleId type, got a '"+struct.type+"'");
+  let expectStart = 'NonFungibleId("';
+  assert(struct.value.startsWith(expectStart), "the non fungible id value should start with: " + expectStart);
+  return struct.value.slice(expectStart.length, -2);
+}
+
+// The elements of a HashMap are its keys and values one after the other
+export function getHashMapEntries(struct){
+  assert(struct.type == "HashMap", "Expect a HashMap type, got a '"+struct.type+"'");
+  let entries = [];
+  for(let i = 0; i < struct.elements.length; i += 2)
+    entries.push([struct.elements[i], struct.elements[i + 1]]);
+  return entries;
+}
+
+// Reads the OfferInfo listed by the central component for an offer
+function readOfferInfo(address, struct){
+  let values = struct.fields;
+  return {
+    address: address,
+    lot: values[0].elements.map(t => ({
+      tokenAddress: getResourceAddress(t.elements[0]),
+      tokenAmount: getDecimal(t.elements[1]),
+    })),
+    lotsAvailable: getU64(values[1]),
+    activeLoans: getU64(values[2]),
+    collatAmount: getDecimal(values[3]),
+    collatResourceAddress: getResourceAddress(values[4]),
+    costPerHour: getDecimal(values[5]),
+    feeResourceAddress: getResourceAddress(values[6]),
+    maxBorrowTime: getU64(values[7]),
+  };
+}
+
+// Reads the offers map of the central component
+export function readOffersFromCentralState(stateStr){
+  const state = JSON.parse(stateStr);
+  return getHashMapEntries(state.fields[0]).map(([key, value]) => readOfferInfo(getComponentAddress(key), value));
+}
+
+// Reads the state of a SecurityLending component
 export function readOfferFromState(stateStr){
   const state = JSON.parse(stateStr);
   let values = state.fields;
   console.log("The state is:", values);
+  let loans = getHashMapEntries(values[13]).map(([key, value]) => ({
+    id: getNonFungibleId(key),
+    termStart: getU64(value.fields[0]),
+    termEnd: getU64(value.fields[1]),
+    renewals: getU64(value.fields[3]),
+    state: value.fields[4].name,
+  }));
+  let activeLoans = loans.filter(l => l.state == "LoanRenting").length;
+  let offerState = values[6].name;
   return {
-    tokenAddress: getResourceAddress(values[0]), 
-    tokenAmount: getDecimal(values[1]),
-    collatAmount: getDecimal(values[2]),
-    costPerHour: getDecimal(values[3]),
-    maxBorrowTime: getU64(values[4]),
-    unixBorrowStart: getU64(values[5]),
-    collatResourceAddress: getResourceAddress(values[7]), 
-    feeResourceAddress:  getResourceAddress(values[8]),
-    state: values[6].name
+    lot: getHashMapEntries(values[1]).map(([key, value]) => ({
+      tokenAddress: getResourceAddress(key),
+      tokenAmount: getDecimal(value),
+    })),
+    lotsAvailable: offerState == "StateOpen" ? parseInt(getU64(values[2])) - activeLoans : 0,
+    activeLoans: activeLoans,
+    collatAmount: getDecimal(values[3]),
+    costPerHour: getDecimal(values[4]),
+    maxBorrowTime: getU64(values[5]),
+    state: offerState,
+    feeResourceAddress: getResourceAddress(values[7]),
+    collatResourceAddress: getResourceAddress(values[8]),
+    loans: loans,
+    loanNftAddress: getResourceAddress(values[14]),
   };
 }
 
 export async function enrichTokens(offer){
-  let tokenPromise = tokenCache.getMetaInfo(offer.tokenAddress);
+  let tokenPromises = offer.lot.map(token => tokenCache.getMetaInfo(token.tokenAddress));
   let collatResourcePromise = tokenCache.getMetaInfo(offer.collatResourceAddress);
   let feeResourcePromise = tokenCache.getMetaInfo(offer.feeResourceAddress);
   
-  offer.tokenMetaInfo = await tokenPromise;
+  for(let i = 0; i < offer.lot.length; i++)
+    offer.lot[i].tokenMetaInfo = await tokenPromises[i];
   offer.collatResourceMetaInfo = await collatResourcePromise;
   offer.feeResourceMetaInfo = await feeResourcePromise;
 }
@@ -135,11 +192,11 @@ function searchToken(address, metainfo, search){
 function searchOffer(offer, search){
   if(offer.address == search)
     return true;
-  if(searchToken(offer.tokenAddress, offer.tokenMetaInfo))
+  if(offer.lot.some(token => searchToken(token.tokenAddress, token.tokenMetaInfo, search)))
     return true;
-  if(searchToken(offer.collatResourceAddress, offer.collatResourceMetaInfo))
+  if(searchToken(offer.collatResourceAddress, offer.collatResourceMetaInfo, search))
     return true;
-  if(searchToken(offer.feeResourceAddress, offer.feeResourceMetaInfo))
+  if(searchToken(offer.feeResourceAddress, offer.feeResourceMetaInfo, search))
     return true;
 }
 
@@ -1,6 +1,8 @@
+pub mod transaction;
 mod utils;
 
-use radix_engine::model::Instruction;
+use radix_engine::model::{Instruction, Transaction};
+use scrypto::crypto::{EcdsaPrivateKey, EcdsaPublicKey, EcdsaSignature};
 use wasm_bindgen::prelude::*;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
@@ -29,3 +31,67 @@ pub fn compile_with_nonce(manifest: &str, nonce: u64) -> Result<Vec<u8>, String>
     transaction.instructions.push(Instruction::Nonce { nonce });
     Ok(scrypto::buffer::scrypto_encode(&transaction))
 }
+
+/// Compiles the manifest with the given nonce and signs it, like `compile_with_nonce`
+/// followed by `sign`.
+#[wasm_bindgen]
+pub fn compile_and_sign(manifest: &str, nonce: u64, private_key: &[u8]) -> Result<Vec<u8>, String> {
+    let transaction = compile_with_nonce(manifest, nonce)?;
+    sign(&transaction, private_key)
+}
+
+/// Turns a compiled transaction back into manifest text. The nonce and the
+/// signatures are not part of the manifest and are left out.
+#[wasm_bindgen]
+pub fn decompile(transaction: &[u8]) -> Result<String, String> {
+    utils::set_panic_hook();
+    let transaction = transaction::decode(transaction)?;
+    let manifest = Transaction {
+        instructions: transaction
+            .instructions
+            .into_iter()
+            .filter(|i| !matches!(i, Instruction::Nonce { .. } | Instruction::End { .. }))
+            .collect(),
+    };
+    transaction_manifest::decompile(&manifest).map_err(|e| format!("{:?}", e))
+}
+
+#[wasm_bindgen]
+pub fn sign(transaction: &[u8], private_key: &[u8]) -> Result<Vec<u8>, String> {
+    utils::set_panic_hook();
+    let transaction = transaction::decode(transaction)?;
+    let private_key = EcdsaPrivateKey::from_bytes(private_key).map_err(|e| format!("{:?}", e))?;
+    Ok(transaction::encode(&transaction::sign(&transaction, &private_key)))
+}
+
+#[wasm_bindgen]
+pub fn add_signature(transaction: &[u8], public_key: &[u8], signature: &[u8]) -> Result<Vec<u8>, String> {
+    utils::set_panic_hook();
+    let transaction = transaction::decode(transaction)?;
+    let public_key = EcdsaPublicKey::from_bytes(public_key).map_err(|e| format!("{:?}", e))?;
+    let signature = EcdsaSignature::from_bytes(signature).map_err(|e| format!("{:?}", e))?;
+    Ok(transaction::encode(&transaction::add_signature(&transaction, public_key, signature)))
+}
+
+/// Returns the bytes to sign, i.e. the transaction without its signatures.
+#[wasm_bindgen]
+pub fn signing_payload(transaction: &[u8]) -> Result<Vec<u8>, String> {
+    utils::set_panic_hook();
+    let transaction = transaction::decode(transaction)?;
+    Ok(transaction::payload(&transaction))
+}
+
+#[wasm_bindgen]
+pub fn verify(transaction: &[u8]) -> Result<bool, String> {
+    utils::set_panic_hook();
+    let transaction = transaction::decode(transaction)?;
+    Ok(transaction::verify(&transaction))
+}
+
+/// Hex encoded hash of the transaction, signatures included.
+#[wasm_bindgen]
+pub fn hash(transaction: &[u8]) -> Result<String, String> {
+    utils::set_panic_hook();
+    let transaction = transaction::decode(transaction)?;
+    Ok(transaction::hash(&transaction).to_string())
+}
@@ -0,0 +1,84 @@
+//! Signing and hashing of compiled transactions, shared by the wasm bindings
+//! and by native Rust callers.
+//!
+//! The signed payload is the SBOR encoding of the transaction without its
+//! `End` instruction, the same bytes `compile_with_nonce` returns. Signatures
+//! are appended to the `End` instruction, which is created on first signature.
+
+use radix_engine::model::{Instruction, Transaction};
+use scrypto::buffer::{scrypto_decode, scrypto_encode};
+use scrypto::crypto::{hash as sha256, EcdsaPrivateKey, EcdsaPublicKey, EcdsaSignature, EcdsaVerifier, Hash};
+
+pub fn decode(transaction: &[u8]) -> Result<Transaction, String> {
+    scrypto_decode(transaction).map_err(|e| format!("{:?}", e))
+}
+
+pub fn encode(transaction: &Transaction) -> Vec<u8> {
+    scrypto_encode(transaction)
+}
+
+/// Returns the transaction without its signatures.
+pub fn unsigned(transaction: &Transaction) -> Transaction {
+    Transaction {
+        instructions: transaction
+            .instructions
+            .iter()
+            .filter(|i| !matches!(i, Instruction::End { .. }))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Returns the bytes signers have to sign.
+pub fn payload(transaction: &Transaction) -> Vec<u8> {
+    scrypto_encode(&unsigned(transaction))
+}
+
+pub fn signatures(transaction: &Transaction) -> Vec<(EcdsaPublicKey, EcdsaSignature)> {
+    transaction
+        .instructions
+        .iter()
+        .filter_map(|i| match i {
+            Instruction::End { signatures } => Some(signatures.clone()),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+/// Appends a signature produced elsewhere, e.g. by the browser extension.
+pub fn add_signature(
+    transaction: &Transaction,
+    public_key: EcdsaPublicKey,
+    signature: EcdsaSignature,
+) -> Transaction {
+    let mut all = signatures(transaction);
+    all.push((public_key, signature));
+
+    let mut signed = unsigned(transaction);
+    signed
+        .instructions
+        .push(Instruction::End { signatures: all });
+    signed
+}
+
+pub fn sign(transaction: &Transaction, private_key: &EcdsaPrivateKey) -> Transaction {
+    let signature = private_key.sign(&payload(transaction));
+    add_signature(transaction, private_key.public_key(), signature)
+}
+
+/// Checks every attached signature against the payload. A transaction without
+/// signatures does not verify.
+pub fn verify(transaction: &Transaction) -> bool {
+    let message = payload(transaction);
+    let signatures = signatures(transaction);
+    !signatures.is_empty()
+        && signatures
+            .iter()
+            .all(|(public_key, signature)| EcdsaVerifier::verify(&message, public_key, signature))
+}
+
+/// Hash of the full transaction, signatures included.
+pub fn hash(transaction: &Transaction) -> Hash {
+    sha256(scrypto_encode(transaction))
+}
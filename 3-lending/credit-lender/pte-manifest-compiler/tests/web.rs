@@ -27,3 +27,44 @@ fn test_compile_with_nonce() {
         ])
     );
 }
+
+const TEST_PRIVATE_KEY: [u8; 32] = [1; 32];
+
+#[wasm_bindgen_test]
+fn test_decompile() {
+    let transaction = compile_with_nonce("CLEAR_AUTH_ZONE;", 1).unwrap();
+    let manifest = decompile(&transaction).unwrap();
+    assert_eq!(compile_with_nonce(&manifest, 1), Ok(transaction));
+}
+
+#[wasm_bindgen_test]
+fn test_sign_and_verify() {
+    let transaction = compile_with_nonce("CLEAR_AUTH_ZONE;", 1).unwrap();
+    assert_eq!(verify(&transaction), Ok(false));
+
+    let signed = sign(&transaction, &TEST_PRIVATE_KEY).unwrap();
+    assert_eq!(verify(&signed), Ok(true));
+    assert_eq!(signing_payload(&signed), Ok(transaction.clone()));
+    assert_eq!(compile_and_sign("CLEAR_AUTH_ZONE;", 1, &TEST_PRIVATE_KEY), Ok(signed.clone()));
+
+    // The signature no longer matches once the payload changes
+    let mut tampered = signed.clone();
+    let nonce_index = transaction.len() - 8;
+    tampered[nonce_index] = 2;
+    assert_eq!(verify(&tampered), Ok(false));
+}
+
+#[wasm_bindgen_test]
+fn test_hash() {
+    let transaction = compile_with_nonce("CLEAR_AUTH_ZONE;", 1).unwrap();
+    let signed = sign(&transaction, &TEST_PRIVATE_KEY).unwrap();
+
+    // The hex encoded hash of the compiled bytes, signatures included
+    assert_eq!(hash(&transaction), Ok(scrypto::crypto::hash(&transaction).to_string()));
+    assert_eq!(hash(&signed), Ok(scrypto::crypto::hash(&signed).to_string()));
+    assert_eq!(hash(&signed).unwrap().len(), 64);
+    assert_ne!(hash(&transaction), hash(&signed));
+
+    // Stripping the signatures gives back the unsigned hash
+    assert_eq!(hash(&signing_payload(&signed).unwrap()), hash(&transaction));
+}
@@ -5,7 +5,7 @@ use crate::collateral_pool::*;
 use crate::user_management::*;
 use crate::pseudopriceoracle::*;
 use crate::loan_auction::*;
-use crate::structs::{User, FlashLoan, Loan, AuctionAuth};
+use crate::structs::{User, FlashLoan, Loan, AuctionAuth, InterestRateModel, CreditTier};
 
 blueprint! {
     /// This is the main component for this protocol. It can be considered as a router, taken inspiration from Omar's "RaDEX"
@@ -42,15 +42,33 @@ blueprint! {
         flash_loan_auth_vault: Vault,
         // Flash loan resource address
         flash_loan_address: ResourceAddress,
+        // Fee charged on flash loans before the credit tier discount of the user
+        flash_loan_fee: Decimal,
         // Data structure for the loan NFTs with a Health Factor below 1.
         bad_loans: HashMap<NonFungibleId, ResourceAddress>,
         loan_auction_address: Option<ComponentAddress>,
+        // Resource address of the admin badge which is allowed to change the protocol parameters
+        admin_badge_address: ResourceAddress,
     }
 
     impl DegenFi {
         pub fn new(
-        ) -> ComponentAddress 
+        ) -> (ComponentAddress, Bucket)
         {
+            // Creates the admin badge which is allowed to change the protocol parameters
+            let admin_badge = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "DegenFi Admin Badge")
+                .metadata("symbol", "DFA")
+                .metadata("description", "Admin authority to change the DegenFi protocol parameters")
+                .initial_supply(1);
+
+            let access_rules: AccessRules = AccessRules::new()
+                .method("set_interest_rate_model", rule!(require(admin_badge.resource_address())))
+                .method("set_credit_tiers", rule!(require(admin_badge.resource_address())))
+                .method("set_flash_loan_fee", rule!(require(admin_badge.resource_address())))
+                .default(rule!(allow_all));
+
             // Creates badge to authorizie to mint/burn flash loan
             let flash_loan_token = ResourceBuilder::new_fungible()
                 .divisibility(DIVISIBILITY_NONE)
@@ -110,7 +128,7 @@ blueprint! {
                 .burnable(rule!(require(degen_badge.resource_address())), LOCKED)
                 .initial_supply(1000);
 
-            return Self {
+            let degenfi: ComponentAddress = Self {
                 lending_pools: HashMap::new(),
                 lending_pool_address: HashMap::new(),
                 collateral_pools: HashMap::new(),
@@ -127,11 +145,16 @@ blueprint! {
                 sbt_address: Vec::new(),
                 flash_loan_auth_vault: Vault::with_bucket(flash_loan_token),
                 flash_loan_address: flash_loan_address,
+                flash_loan_fee: Decimal::zero(),
                 bad_loans: HashMap::new(),
                 loan_auction_address: None,
+                admin_badge_address: admin_badge.resource_address(),
             }
             .instantiate()
+            .add_access_check(access_rules)
             .globalize();
+
+            return (degenfi, admin_badge);
         }
 
         /// Creates a new user for the lending protocol.
@@ -574,7 +597,13 @@ blueprint! {
             let access_badge_token2 = self.access_auth_vault.authorize(|| borrow_resource_manager!(self.access_badge_address).mint(Decimal::one()));
             
             // Instantiates the lending pool and collateral pool.
-            let lending_pool: ComponentAddress = LendingPool::new(user_management, pseudopriceoracle, deposit_amount, access_badge_token);
+            let lending_pool: ComponentAddress = LendingPool::new(
+                user_management, 
+                pseudopriceoracle, 
+                deposit_amount, 
+                access_badge_token, 
+                InterestRateModel::default()
+            );
             let collateral_pool: ComponentAddress = CollateralPool::new(user_management, lending_pool, token_address, access_badge_token2);
             
             // Retrieves User Management Component
@@ -997,8 +1026,8 @@ blueprint! {
         /// Allows user to perform a flash loan.
         ///
         /// This method is used to allow users to perform a flash loan. A transient token is created to record the amount
-        /// that was borrowed. The transient token must be burnt for the transaction to complete. Currently, there is no
-        /// fee for performing flash loans. 
+        /// that was borrowed. The transient token must be burnt for the transaction to complete. The full flash loan fee
+        /// is charged, users can have it discounted by their credit tier with `flash_borrow_with_sbt`.
         /// 
         /// This method performs a number of checks before the borrow is made:
         /// 
@@ -1019,6 +1048,56 @@ blueprint! {
             token_requested: ResourceAddress,
             amount: Decimal
         ) -> (Bucket, Bucket, Bucket)
+        {
+            self.issue_flash_loan(token_requested, amount, Decimal::zero())
+        }
+
+        /// Allows user to perform a flash loan with the flash loan fee discount of their credit tier.
+        ///
+        /// This method is used to allow users to perform a flash loan in the same way as `flash_borrow`, except that
+        /// the flash loan fee is discounted based on the credit tier of the user's SBT.
+        /// 
+        /// This method performs a number of checks before the borrow is made:
+        /// 
+        /// * **Check 1:** Checks that the user belongs to this protocol.
+        /// 
+        /// * **Check 2:** Checks that there does not already exist a lending pool for given token.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `user_auth` (Proof) - A proof that proves that the borrower is a user that belongs to this protocol.
+        /// * `token_requested` (ResourceAddress) - This is the token address of the requested asset to borrow.
+        /// * `amount` (Decimal) - This is the amount that the borrower wishes to borrow from the pool.
+        /// 
+        /// # Returns:
+        /// 
+        /// * `Bucket` - Returns a bucket of the borrowed funds from the pool.
+        /// * `Bucket` - The transient token representing the flash loan.
+        /// * `Bucket` - The Degen Tokens received for interacting with the protocol.
+        pub fn flash_borrow_with_sbt(
+            &mut self,
+            user_auth: Proof,
+            token_requested: ResourceAddress,
+            amount: Decimal
+        ) -> (Bucket, Bucket, Bucket)
+        {
+            // Checks if user belongs to this protocol.
+            assert_eq!(self.sbt_address.contains(&user_auth.resource_address()), true, "User does not belong to this protocol.");
+
+            let user_id = self.get_user(&user_auth);
+            let user_management: UserManagement = self.user_management_address.into();
+            let credit_tier = user_management.get_credit_tier(user_id);
+            self.issue_flash_loan(token_requested, amount, credit_tier.flash_loan_fee_discount)
+        }
+
+        /// Withdraws the flash loan from the lending pool and mints the transient token recording the amount due,
+        /// which is the amount borrowed plus the flash loan fee less the given discount.
+        fn issue_flash_loan(
+            &mut self,
+            token_requested: ResourceAddress,
+            amount: Decimal,
+            fee_discount: Decimal
+        ) -> (Bucket, Bucket, Bucket)
         {
             // Attempting to get the lending pool component associated with the provided address pair.
             let optional_lending_pool: Option<&LendingPool> = self.lending_pools.get(&token_requested);
@@ -1026,13 +1105,16 @@ blueprint! {
                 Some (lending_pool) => { // If it matches it means that the lending pool exists.
                     let return_borrow: Bucket = self.access_badge_vault.authorize(||
                         lending_pool.flash_borrow(amount));
+                    let fee_charged = amount * self.flash_loan_fee * (Decimal::one() - fee_discount);
+                    info!("[DegenFi]: Flash loan fee charged: {:?}", fee_charged);
                     // Mints the transient token
                     let transient_token = self.flash_loan_auth_vault.authorize(|| {
                         borrow_resource_manager!(self.flash_loan_address)
                         .mint_non_fungible(
                             &NonFungibleId::random(),
                             FlashLoan {
-                                amount_due: amount,
+                                amount_due: amount + fee_charged,
+                                fee: fee_charged,
                                 asset: token_requested,
                                 borrow_count: 1,
                             },
@@ -1314,8 +1396,10 @@ blueprint! {
             let optional_lending_pool: Option<&LendingPool> = self.lending_pools.get(&repay_amount.resource_address());
             match optional_lending_pool {
                 Some (lending_pool) => { // If it matches it means that the lending pool exists.
+                    // Anything repaid above the amount borrowed is income of the pool
+                    let fee = repay_amount.amount() - (flash_loan_data.amount_due - flash_loan_data.fee);
                     self.access_badge_vault.authorize(|| 
-                        lending_pool.flash_repay(repay_amount));
+                        lending_pool.flash_repay(repay_amount, fee));
                     self.flash_loan_auth_vault.authorize(|| flash_loan.burn());
                     let degen_token = self.degen_token_vault.take(1);
                     degen_token
@@ -1523,6 +1607,121 @@ blueprint! {
             }
         }
 
+        /// Allows user to check the borrow and supply rates of the pool.
+        ///
+        /// This method is used to allow users check the yearly borrow rate and supply rate of the pool 
+        /// as calculated by the interest rate model of the pool at its current utilization rate.
+        /// Both rates are zero if there is no lending pool for the given token.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `token_requested` (ResourceAddress) - This is the token address of the requested asset.
+        /// 
+        /// # Returns:
+        /// 
+        /// * `Decimal` - The borrow rate of the pool.
+        /// * `Decimal` - The supply rate of the pool.
+        pub fn check_interest_rates(
+            &mut self,
+            token_requested: ResourceAddress
+        ) -> (Decimal, Decimal)
+        {
+            // Attempting to get the lending pool component associated with the provided address pair.
+            let optional_lending_pool: Option<&LendingPool> = self.lending_pools.get(&token_requested);
+            match optional_lending_pool {
+                Some (lending_pool) => { 
+                    let borrow_rate = lending_pool.interest_calc();
+                    let supply_rate = lending_pool.check_supply_rate();
+                    info!("The borrow rate of this pool is {:?}", borrow_rate);
+                    return (borrow_rate, supply_rate)
+                }
+                None => {
+                    info!("[DegenFi]: Pool for {:?} doesn't exist.", token_requested);
+                    return (Decimal::zero(), Decimal::zero())
+                }
+            }
+        }
+
+        /// Replaces the interest rate model of a lending pool.
+        ///
+        /// This method is used by the admin to change the base rate, slopes, optimal utilization or reserve
+        /// factor of a pool. Interest is accrued at the old rate before the new model takes effect.
+        /// 
+        /// This method performs a number of checks before the model is replaced:
+        /// 
+        /// * **Check 1:** Checks that the lending pool of the given token exists.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `token_requested` (ResourceAddress) - This is the token address of the lending pool.
+        /// * `interest_rate_model` (InterestRateModel) - The new interest rate model of the pool.
+        /// 
+        /// # Returns:
+        /// 
+        /// This method does not return any assets.
+        pub fn set_interest_rate_model(
+            &mut self,
+            token_requested: ResourceAddress,
+            interest_rate_model: InterestRateModel
+        )
+        {
+            self.assert_pool_exists(token_requested, String::from("Set Interest Rate Model"));
+            let lending_pool: &LendingPool = self.lending_pools.get(&token_requested).unwrap();
+            self.access_badge_vault.authorize(|| 
+                lending_pool.set_interest_rate_model(interest_rate_model)
+            );
+        }
+
+        /// Replaces the credit tier table.
+        ///
+        /// This method is used by the admin to change the credit score ranges and the max LTV, liquidation threshold,
+        /// interest rate discount and flash loan fee discount of each tier. Lowering the liquidation threshold of a tier
+        /// may make existing loans liquidatable.
+        /// 
+        /// This method performs a number of checks before the table is replaced:
+        /// 
+        /// * **Check 1:** Checks that the first tier starts at a credit score of 0 and that the tiers are sorted by
+        /// increasing credit score.
+        /// 
+        /// * **Check 2:** Checks that the liquidation threshold of each tier is above its max LTV.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `credit_tiers` (Vec<CreditTier>) - The new credit tier table.
+        /// 
+        /// # Returns:
+        /// 
+        /// This method does not return any assets.
+        pub fn set_credit_tiers(
+            &mut self,
+            credit_tiers: Vec<CreditTier>
+        )
+        {
+            let user_management: UserManagement = self.user_management_address.into();
+            self.access_badge_vault.authorize(|| 
+                user_management.set_credit_tiers(credit_tiers)
+            );
+        }
+
+        /// Retrieves the credit tier table.
+        pub fn get_credit_tiers(
+            &self
+        ) -> Vec<CreditTier>
+        {
+            let user_management: UserManagement = self.user_management_address.into();
+            return user_management.get_credit_tiers();
+        }
+
+        /// Sets the fee charged on flash loans, as a fraction of the amount borrowed, before the credit tier discount.
+        pub fn set_flash_loan_fee(
+            &mut self,
+            flash_loan_fee: Decimal
+        )
+        {
+            assert!(flash_loan_fee >= Decimal::zero() && flash_loan_fee < Decimal::one(), "[DegenFi]: Flash loan fee must be between 0 and 1.");
+            self.flash_loan_fee = flash_loan_fee;
+        }
+
         /// Allows user to check the total supplied to the pool.
         ///
         /// This method is used to allow users check the total supply of the pool.
@@ -1556,6 +1755,76 @@ blueprint! {
             }
         }
 
+        /// Allows user to check the supply index of the pool.
+        ///
+        /// This method is used to allow users check how much one token supplied to the pool when it was 
+        /// created has grown through interest.
+        /// 
+        /// This method performs a number of checks before the information is pulled:
+        /// 
+        /// * **Check 1:** Checks that there exists a lending pool for given token.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `token_requested` (ResourceAddress) - This is the token address of the requested asset.
+        /// 
+        /// # Returns:
+        /// 
+        /// * `Decimal` - The supply index of the pool.
+        pub fn check_supply_index(
+            &mut self,
+            token_requested: ResourceAddress
+        ) -> Decimal
+        {
+            // Attempting to get the lending pool component associated with the provided address pair.
+            let optional_lending_pool: Option<&LendingPool> = self.lending_pools.get(&token_requested);
+            match optional_lending_pool {
+                Some (lending_pool) => { 
+                    return lending_pool.check_supply_index();
+                }
+                None => {
+                    info!("[DegenFi]: Pool for {:?} doesn't exist.", token_requested);
+                    return Decimal::zero()
+                }
+            }
+        }
+
+        /// Allows user to check the value of a supplier's deposit.
+        ///
+        /// This method is used to allow users check how much a supplier is owed by the pool, including the 
+        /// interest earned on their deposit.
+        /// 
+        /// This method performs a number of checks before the information is pulled:
+        /// 
+        /// * **Check 1:** Checks that there exists a lending pool for given token.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `user_id` (NonFungibleId) - The NonFungibleId of the supplier's SBT.
+        /// * `token_requested` (ResourceAddress) - This is the token address of the requested asset.
+        /// 
+        /// # Returns:
+        /// 
+        /// * `Decimal` - The value of the supplier's deposit.
+        pub fn check_deposit_value(
+            &mut self,
+            user_id: NonFungibleId,
+            token_requested: ResourceAddress
+        ) -> Decimal
+        {
+            // Attempting to get the lending pool component associated with the provided address pair.
+            let optional_lending_pool: Option<&LendingPool> = self.lending_pools.get(&token_requested);
+            match optional_lending_pool {
+                Some (lending_pool) => { 
+                    return lending_pool.check_deposit_value(user_id);
+                }
+                None => {
+                    info!("[DegenFi]: Pool for {:?} doesn't exist.", token_requested);
+                    return Decimal::zero()
+                }
+            }
+        }
+
         /// Allows user to check the total collateral supplied to the pool.
         ///
         /// This method is used to allow users check the total collateral supplied to the pool.
@@ -3,7 +3,7 @@ use crate::user_management::*;
 use crate::pseudopriceoracle::*;
 use crate::collateral_pool::*;
 use crate::loan_auction::*;
-use crate::structs::{User, Loan, Status, AuctionAuth};
+use crate::structs::{User, Loan, Status, AuctionAuth, InterestRateModel};
 
 blueprint! {
     /// This is the lending pool where practically all of the calculation of the pool takes place. Loan NFTs 
@@ -28,9 +28,6 @@ blueprint! {
         pseudopriceoracle_address: ComponentAddress,
         /// Access badge to call permissioned method from the UserManagement component.
         access_badge_vault: Vault,
-        /// The max amount a user can borrow of their collateral. In the future we can implement a sliding
-        /// collaterization requirement or max borrow % based on credit worthiness.
-        max_borrow: Decimal,
         /// The minimum health factor before a loan is allowed to be liquidated. Collateral can be liquidated up to 50%.
         min_health_factor: Decimal,
         /// Close factor is the amount reached before liquidators can liquidate 100% of the collateral.
@@ -45,6 +42,21 @@ blueprint! {
         loans: BTreeSet<NonFungibleId>,
         /// Creates a list of Loan NFTs are bad so users can query and sort through.
         bad_loans: HashMap<NonFungibleId, ResourceAddress>,
+        /// The interest rate model of this pool. The borrow rate is derived from the utilization rate every time
+        /// interest is accrued.
+        interest_rate_model: InterestRateModel,
+        /// Tracks how much one unit of debt taken at the creation of the pool would be worth now. Loan NFTs record the
+        /// index they were last updated at so that their interest can be accrued between calls.
+        borrow_index: Decimal,
+        /// The epoch at which interest was last accrued into the borrow index.
+        last_accrual_epoch: u64,
+        /// The share of the interest paid by borrowers that is kept by the pool as determined by the reserve factor.
+        reserves: Decimal,
+        /// Tracks how much one token supplied at the creation of the pool would be worth now. It grows with the
+        /// suppliers' share of the interest paid by borrowers.
+        supply_index: Decimal,
+        /// The supply index each supplier's deposit balance was last settled at.
+        supplier_indexes: HashMap<NonFungibleId, Decimal>,
     }
 
     impl LendingPool {
@@ -71,6 +83,9 @@ blueprint! {
         /// the User Management component called the `register_resource` method which registers the transient token minted from
         /// this pool.
         /// 
+        /// * `interest_rate_model` (InterestRateModel) - The interest rate model used to calculate the borrow and supply rates
+        /// of this pool.
+        /// 
         /// # Returns:
         /// 
         /// * `ComponentAddress` - The ComponentAddress of the newly created LendingPool.
@@ -79,7 +94,8 @@ blueprint! {
             user_component_address: ComponentAddress,
             pseudopriceoracle_address: ComponentAddress,
             initial_funds: Bucket, 
-            access_badge: Bucket
+            access_badge: Bucket,
+            interest_rate_model: InterestRateModel
         ) -> ComponentAddress 
         {
 
@@ -92,6 +108,7 @@ blueprint! {
                 .method("flash_borrow", rule!(require(access_badge.resource_address())))
                 .method("flash_repay", rule!(require(access_badge.resource_address())))
                 .method("auction_repay", rule!(require(access_badge.resource_address())))
+                .method("set_interest_rate_model", rule!(require(access_badge.resource_address())))
                 .default(rule!(allow_all));
 
             assert_ne!(
@@ -104,6 +121,8 @@ blueprint! {
                 "[Pool Creation]: Can't deposit an empty bucket."
             ); 
 
+            interest_rate_model.assert_valid();
+
             let user_management_address: ComponentAddress = user_component_address;
             let pseudopriceoracle_address: ComponentAddress = pseudopriceoracle_address;
 
@@ -144,7 +163,6 @@ blueprint! {
                 user_management_address: user_management_address,
                 pseudopriceoracle_address: pseudopriceoracle_address,
                 access_badge_vault: Vault::with_bucket(access_badge),
-                max_borrow: dec!("0.75"),
                 min_health_factor: dec!("1.0"),
                 close_factor: dec!("0.5"),
                 collateral_pool: None,
@@ -152,6 +170,12 @@ blueprint! {
                 loan_address: loan_nft_address,
                 loans: BTreeSet::new(),
                 bad_loans: HashMap::new(),
+                interest_rate_model: interest_rate_model,
+                borrow_index: Decimal::one(),
+                last_accrual_epoch: Runtime::current_epoch(),
+                reserves: Decimal::zero(),
+                supply_index: Decimal::one(),
+                supplier_indexes: HashMap::new(),
             }
             .instantiate()
             .add_access_check(access_rules)
@@ -159,20 +183,25 @@ blueprint! {
             return lending_pool;
         }
 
+        /// Brings the loan NFT up to date.
+        /// 
+        /// Accrues the interest of the pool and of the given loan, then updates the collateral value and the Health Factor
+        /// of the loan at the current price.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `loan_id` (NonFungibleId) - The NonFungibleId of the loan to update.
+        /// 
+        /// # Returns:
+        /// 
+        /// This method does not return any assets.
         pub fn update_loan(
             &mut self,
             loan_id: NonFungibleId,
         )
         {
-            let mut loan_data = self.call_resource_mananger(&loan_id);
-            let remaining_balance = loan_data.remaining_balance;
-            let collateral_address = loan_data.collateral;
-            let pseudopriceoracle: PseudoPriceOracle = self.pseudopriceoracle_address.into();
-            let price = pseudopriceoracle.get_price(collateral_address);
-            let collateral_amount = loan_data.collateral_amount;
-            loan_data.health_factor = ( ( collateral_amount * price ) * dec!("0.75") ) / ( remaining_balance );
-            loan_data.collateral_amount_usd = collateral_amount * price;
-            self.authorize_update(&loan_id, loan_data);
+            self.accrue_interest();
+            self.accrue_loan(&loan_id);
         }
 
         /// Returns the ResourceAddress of the loan NFTs so the collateral pool component can access the NFT data.
@@ -217,6 +246,8 @@ blueprint! {
             deposit_amount: Bucket
         ) 
         {
+            self.accrue_interest();
+
             let token_address: ResourceAddress = deposit_amount.resource_address(); 
             // Asserts that the bucket is not empty.
             assert!(
@@ -230,6 +261,9 @@ blueprint! {
             // Takes the amount passed through in the bucket.
             let amount = deposit_amount.amount();
 
+            // Credits any interest earned on the existing deposit before adding to it.
+            self.settle_supplier(&user_id, token_address);
+
             // Authorizes to increase the deposit balance of the SBT user.
             self.access_badge_vault.authorize(|| {
                     user_management.add_deposit_balance(user_id.clone(), token_address, amount)
@@ -248,6 +282,9 @@ blueprint! {
             deposit_amount: Bucket
         )
         {
+            // The liquidator repays part of the loan so the borrow counter decreases.
+            self.reduce_borrow_amount(deposit_amount.amount());
+
             // Deposits the loan repayment from liquidator.
             self.vaults.get_mut(&deposit_amount.resource_address()).unwrap().put(deposit_amount);
         }
@@ -286,11 +323,17 @@ blueprint! {
 
             let amount = deposit_amount.amount();
 
+            self.accrue_interest();
+            self.settle_supplier(&user_id, token_address);
+
             self.access_badge_vault.authorize(|| {
                 user_management.convert_collateral_to_deposit(user_id, token_address, amount)
                 }
             );
 
+            // Adding to supplied amount
+            self.supplied_amount += amount;
+
             // Deposits collateral
             self.vaults.get_mut(&deposit_amount.resource_address()).unwrap().put(deposit_amount);
         }
@@ -403,8 +446,8 @@ blueprint! {
         /// 
         /// This method performs a number of checks before the borrow is made:
         /// 
-        /// * **Check 1:** Checks that the borrow amount must be less than or equals to 75% of your collateral. Which is
-        /// currently the simple default borrow amount.
+        /// * **Check 1:** Checks that the borrow amount must be less than or equals to the max LTV of the user's credit
+        /// tier. Users without credit history can borrow up to 75% of their collateral.
         /// 
         /// # Arguments:
         /// 
@@ -425,6 +468,9 @@ blueprint! {
             borrow_amount: Decimal
         ) -> (Bucket, Bucket) 
         {
+            // Accrues interest up to now before the utilization rate changes.
+            self.accrue_interest();
+
             // Retreieves User Management component
             let user_management: UserManagement = self.user_management_address.into();
             // Retrieves SBT resource address
@@ -438,12 +484,12 @@ blueprint! {
             let pseudopriceoracle: PseudoPriceOracle = self.pseudopriceoracle_address.into();
             let price = pseudopriceoracle.get_price(collateral_address);
             let collateral_value = collateral_amount * price;
-            // Assert max borrow limit
-            let max_borrow = self.max_borrow;
-            let collateralization_modifier = user_management.collateralization_modifier(user_id.clone());
-            let modified_max_collateralization = max_borrow + collateralization_modifier;
-            assert!(borrow_amount <= collateral_value * modified_max_collateralization, 
-                "You have hit your max borrow. Your collateralization requirement is {:?}", modified_max_collateralization);
+            // Assert max borrow limit, which depends on the credit tier of the user
+            let credit_tier = user_management.get_credit_tier(user_id.clone());
+            let max_ltv = credit_tier.max_ltv;
+            let liquidation_threshold = credit_tier.liquidation_threshold;
+            assert!(borrow_amount <= collateral_value * max_ltv, 
+                "You have hit your max borrow. Your max LTV is {:?}", max_ltv);
 
             // Checks open loan positions
             assert_ne!(sbt_data.open_loans.contains_key(&token_address), true, "Existing loan position for {:?} already exist", token_address);
@@ -452,22 +498,19 @@ blueprint! {
             let fee = self.origination_fees;
             let fee_charged = borrow_amount * fee;
 
-            // Updates tracking data for the lending pool
+            // Updates tracking data for the lending pool. The origination fee is owed to the pool as well.
             self.fees_collected += fee_charged;
-            self.borrow_amount += borrow_amount;
+            self.borrow_amount += borrow_amount + fee_charged;
 
             let interest_rate = self.interest_calc();
 
-            let modifier = user_management.interest_modifier(user_id.clone());
+            let modifier = credit_tier.interest_discount;
 
-            let modified_interest_rate = interest_rate - modifier;
+            let modified_interest_rate = if interest_rate > modifier { interest_rate - modifier } else { Decimal::zero() };
 
-            // Calculate interest expense
-            let interest_expense = borrow_amount * modified_interest_rate;
+            let remaining_amount = borrow_amount + fee_charged;
 
-            let remaining_amount = borrow_amount + interest_expense + fee_charged;
-
-            let health_factor = ( ( collateral_amount * price ) * dec!("0.75") ) / ( remaining_amount );
+            let health_factor = ( ( collateral_amount * price ) * liquidation_threshold ) / ( remaining_amount );
 
             // Mint loan NFT
             let loan_nft = self.loan_issuer_badge.authorize(|| {
@@ -481,12 +524,15 @@ blueprint! {
                         collateral: collateral_address,
                         principal_loan_amount: borrow_amount,
                         interest_rate: modified_interest_rate,
+                        interest_discount: modifier,
                         origination_fee: fee,
                         origination_fee_charged: fee_charged,
                         owner: user_id.clone(),
                         remaining_balance: remaining_amount,
-                        interest_expense: interest_expense,
+                        // Interest is not charged upfront, it accrues through the borrow index from now on.
+                        interest_expense: Decimal::zero(),
                         last_update: Runtime::current_epoch(),
+                        borrow_index: self.borrow_index,
                         collateral_amount: collateral_amount,
                         collateral_amount_usd: collateral_value,
                         health_factor: health_factor,
@@ -510,7 +556,6 @@ blueprint! {
             info!("[Loan NFT]: Remaining Balance: {:?}", remaining_amount);
             info!("[Loan NFT]: Collateral amount: {:?}", collateral_amount);
             info!("[Loan NFT]: Health Factor: {:?}", health_factor);
-            info!("[Loan NFT]: Interest Expense: {:?}", interest_expense);
 
             // Commits state
             self.access_badge_vault.authorize(|| {
@@ -530,7 +575,7 @@ blueprint! {
             let addresses: Vec<ResourceAddress> = self.addresses();
             let return_borrow_amount: Bucket = self.withdraw(addresses[0], borrow_amount);
 
-            info!("You were able to increase your max collateralization by {:?} due to your credit!", collateralization_modifier);
+            info!("Your credit tier allows you to borrow up to {:?} of your collateral!", max_ltv);
             info!("You were able to reduce your interest rate by {:?} percent due to your credit!", modifier);
             info!(
                 "Your original interest rate was {:?}",
@@ -553,8 +598,8 @@ blueprint! {
         /// 
         /// This method performs a number of checks before the borrow is made:
         /// 
-        /// * **Check 1:** Checks that the borrow amount must be less than or equals to 75% of your collateral. Which is
-        /// currently the simple default borrow amount.
+        /// * **Check 1:** Checks that the borrow amount must be less than or equals to the max LTV of the user's credit
+        /// tier. Users without credit history can borrow up to 75% of their collateral.
         /// 
         /// * **Check 2:** Checks that the loan requested to top off is currently an open position.
         /// 
@@ -577,6 +622,10 @@ blueprint! {
             borrow_amount: Decimal
         ) -> Bucket 
         {
+            // Accrues the interest owed on the loan so far before it is topped off.
+            self.accrue_interest();
+            self.accrue_loan(&loan_id);
+
             // Retrieves the User Management component
             let user_management: UserManagement = self.user_management_address.into();
             // Retrieves the SBT resource address
@@ -598,8 +647,10 @@ blueprint! {
             let price = pseudopriceoracle.get_price(collateral_address);
             let collateral_value = collateral_amount * price;
 
-            // Asserts the max borrow percentage
-            assert!((loan_balance + borrow_amount) <= collateral_value * self.max_borrow, "Borrow amount must be less than or equals to 50% of your collateral.");
+            // Asserts the max borrow percentage, which depends on the credit tier of the user
+            let max_ltv = user_management.get_credit_tier(user_id.clone()).max_ltv;
+            assert!((loan_balance + borrow_amount) <= collateral_value * max_ltv, 
+                "You have hit your max borrow. Your max LTV is {:?}", max_ltv);
 
             // Checks for open loan positions of this asset
             assert_eq!(sbt_data.open_loans.contains_key(&token_address), true, "Must have an open loan position of {:?}", token_address);
@@ -612,42 +663,29 @@ blueprint! {
             let fee_charged = borrow_amount * fee;
             // Takes the origination fee from the borrow request
 
-            // Updates tracking data for the lending pool
+            // Updates tracking data for the lending pool. The origination fee is owed to the pool as well.
             self.fees_collected += fee_charged;
-            self.borrow_amount += borrow_amount;
+            self.borrow_amount += borrow_amount + fee_charged;
 
             // Calculate interest rate. Interest rate will be modified based on new utilization rate and modifier based on user credit score.
             let interest_rate = self.interest_calc();
 
-            // Calculate modifier based on user credit score
-            let modifier = user_management.interest_modifier(user_id.clone());
-
-            let modified_interest_rate = interest_rate - modifier;
-
-            // Calculate interest expense
-            let interest_expense = borrow_amount * modified_interest_rate;
-
             // Change loan NFT data
             // Get the resource manager
             let mut loan_data = self.call_resource_mananger(&loan_id);
             // Asserts that loan status must be current.
             assert_eq!(loan_data.loan_status, Status::Current, "Loan status must be current.");
-            // Take the previous interest rate
-            let previous_interest_rate = loan_data.interest_rate;
-            // Blend the two interest rate
-            let blended_interest_rate =  previous_interest_rate + modified_interest_rate / dec!("2.0");
-            // Change the interest rate on the loan /nft
-            loan_data.interest_rate = blended_interest_rate;
+            // The whole balance accrues at the pool rate from now on, so the rate shown on the loan NFT is simply the current one.
+            let modifier = loan_data.interest_discount;
+            loan_data.interest_rate = if interest_rate > modifier { interest_rate - modifier } else { Decimal::zero() };
             // Increase borrow balance on the loan NFT
-            loan_data.remaining_balance += borrow_amount + interest_expense + fee_charged;
-            // Increase interest expense on the loan NFT
-            loan_data.interest_expense += interest_expense;
+            loan_data.remaining_balance += borrow_amount + fee_charged;
             // Checks whether if the health factor of the loan is greater than one.
             assert!(loan_data.health_factor >= Decimal::one(), "Loan factor must be greater than one.");
 
             // Authorize to increase borrow balance of the user
             self.access_badge_vault.authorize(|| {
-                user_management.increase_borrow_balance(user_id, token_address, borrow_amount)
+                user_management.increase_borrow_balance(user_id, token_address, borrow_amount + fee_charged)
                 }
             );
 
@@ -690,7 +728,8 @@ blueprint! {
         /// Allows user to repay the flash loan borrow.
         ///
         /// This method is used to allow users to repay their flash loan. The amount repaid must
-        /// equal what was recorded in the flash loan token data structure.
+        /// equal what was recorded in the flash loan token data structure. The flash loan fee is booked
+        /// the same way as borrow interest, split between the suppliers and the reserves.
         /// 
         /// This method does not perform any checks, but has Access Rules enforced. Can only be callable
         /// by the DegenFi component.
@@ -698,6 +737,7 @@ blueprint! {
         /// # Arguments:
         /// 
         /// * `repay_amount` (Bucket) - The bucket that contains the asset to be repaid.
+        /// * `fee` (Decimal) - The part of the repayment that is the flash loan fee.
         /// 
         /// # Returns:
         /// 
@@ -705,9 +745,13 @@ blueprint! {
         pub fn flash_repay(
             &mut self,
             repay_amount: Bucket,
+            fee: Decimal
         )
         {
+            self.accrue_interest();
             self.vaults.get_mut(&repay_amount.resource_address()).unwrap().put(repay_amount);
+            self.fees_collected += fee;
+            self.book_income(fee);
         }
 
         /// Converts the user's supply deposit to collateral.
@@ -742,6 +786,9 @@ blueprint! {
             let pool_resource_address = self.vaults.contains_key(&token_address);
             assert!(pool_resource_address == true, "Requested asset must be the same as the lending pool.");
 
+            self.accrue_interest();
+            self.settle_supplier(&user_id, token_address);
+
             let user_management: UserManagement = self.user_management_address.into();      
 
             // Gets the user badge ResourceAddress
@@ -755,6 +802,10 @@ blueprint! {
             let addresses: Vec<ResourceAddress> = self.addresses();
             // Creating a bucket to remove deposit supply from the lending pool to transfer to collateral pool
             let collateral_amount: Bucket = self.withdraw(addresses[0], deposit_collateral);
+
+            // Removing from supplied amount
+            self.supplied_amount -= deposit_collateral;
+
             let collateral_pool: CollateralPool = self.collateral_pool.unwrap().into();
             self.access_badge_vault.authorize(|| 
                 collateral_pool.convert_from_deposit(user_id, token_address, collateral_amount));
@@ -788,6 +839,8 @@ blueprint! {
             redeem_amount: Decimal
         ) -> Bucket 
         {
+            self.accrue_interest();
+
             // Retrieves the User Management component.
             let user_management: UserManagement = self.user_management_address.into();
             // Retrieves the SBT resource address.
@@ -806,6 +859,9 @@ blueprint! {
                 assert!(check_paid_off != Status::Current, "Must pay off loans before redeeming.");
             }
             
+            // Credits any interest earned so that it can be redeemed along with the deposit.
+            self.settle_supplier(&user_id, token_address);
+
             // Reduce deposit balance of the user.
             self.access_badge_vault.authorize(|| {
                 user_management.decrease_deposit_balance(user_id, token_address, redeem_amount)
@@ -816,6 +872,9 @@ blueprint! {
             let addresses: Vec<ResourceAddress> = self.addresses();
             let bucket: Bucket = self.withdraw(addresses[0], redeem_amount);
 
+            // Removing from supplied amount. Settled interest is already part of it, so only the redeemed amount leaves.
+            self.supplied_amount -= redeem_amount;
+
             return bucket;
         }
         
@@ -855,6 +914,9 @@ blueprint! {
             let loans = &self.loans;
             // Asserts that the loan exists.
             assert!(loans.contains(&loan_id) == true, "Requested loan repayment does not exist.");
+            // Accrues the interest owed on the loan so far.
+            self.accrue_interest();
+            self.accrue_loan(&loan_id);
             // Retrieves the User Management component.
             let user_management: UserManagement = self.user_management_address.into();
             // Converts to decimal amount.
@@ -907,18 +969,8 @@ blueprint! {
             // Update remaining balance (includes interest expense and origination fee)
             loan_data.remaining_balance -= amount;
 
-            // Takes interest expense amount
-            let interest_expense = loan_data.interest_expense; 
-            // Takes origination fee
-            let origination_fee = loan_data.origination_fee;
-            // Update fee tracker.
-            self.fees_collected += origination_fee + interest_expense;
-
-            // Takes separates interest expense from principal loan amount
-            let principal_repay_amount = repay_amount.amount() - interest_expense - origination_fee;
-
-            // Decrease borrow counter (excludes interest expense)
-            self.borrow_amount -= principal_repay_amount;
+            // Decrease borrow counter. Accrued interest and fees are part of the borrow counter so the whole repayment counts.
+            self.reduce_borrow_amount(amount);
             
             if loan_data.remaining_balance <= Decimal::zero() {
                 // Change loan status to paid off
@@ -1003,6 +1055,9 @@ blueprint! {
             let loans = &self.loans;
             // Asserts that the loan exists.
             assert!(loans.contains(&loan_id) == true, "Requested loan repayment does not exist.");
+            // Accrues the interest owed on the loan so far.
+            self.accrue_interest();
+            self.accrue_loan(&loan_id);
             // Retrieves the User Management component.
             let user_management: UserManagement = self.user_management_address.into();
             // Converts to decimal amount.
@@ -1055,18 +1110,8 @@ blueprint! {
             // Update remaining balance (includes interest expense and origination fee)
             loan_data.remaining_balance -= amount;
 
-            // Takes interest expense amount
-            let interest_expense = loan_data.interest_expense; 
-            // Takes origination fee
-            let origination_fee = loan_data.origination_fee;
-            // Update fee tracker.
-            self.fees_collected += origination_fee + interest_expense;
-
-            // Takes separates interest expense from principal loan amount
-            let principal_repay_amount = repay_amount.amount() - interest_expense - origination_fee;
-
-            // Decrease borrow counter (excludes interest expense)
-            self.borrow_amount -= principal_repay_amount;
+            // Decrease borrow counter. Accrued interest and fees are part of the borrow counter so the whole repayment counts.
+            self.reduce_borrow_amount(amount);
             
             if loan_data.remaining_balance <= Decimal::zero() {
                 // Change loan status to paid off
@@ -1149,9 +1194,11 @@ blueprint! {
             &mut self
         ) 
         {
-            let loan_list = &self.loans;
-            let check_loans = loan_list.iter();
-            for loans in check_loans {
+            // Health factors are only meaningful once the interest owed so far has been accrued.
+            self.accrue_interest();
+            let loan_list: Vec<NonFungibleId> = self.loans.iter().cloned().collect();
+            for loans in loan_list.iter() {
+                self.accrue_loan(&loans);
                 let health_factor = self.check_health_factor(&loans);
                 if health_factor < self.min_health_factor {
                     self.bad_loans.insert(loans.clone(), self.loan_address);
@@ -1322,10 +1369,9 @@ blueprint! {
 
         /// Caclulates interest rate.
         ///
-        /// This is a method used to calculate the interest rate applied to the loan. It's
-        /// a simple calculation for now for demonstration purposes. The basic idea is that the more
-        /// demand there are to borrow from the pool (utilization rate) the higher the interest rate
-        /// will be.
+        /// This is a method used to calculate the yearly borrow rate of the pool from its interest rate model. 
+        /// The more demand there are to borrow from the pool (utilization rate) the higher the interest rate
+        /// will be, with a steeper increase once the utilization rate is past the optimal utilization of the model.
         /// 
         /// This method does not perform any checks.
         /// 
@@ -1337,24 +1383,320 @@ blueprint! {
         /// 
         /// `Decimal` - The interest rate returned.
         pub fn interest_calc(
-            &mut self
+            &self
         ) -> Decimal 
         {
-            let utilization_rate = self.check_utilization_rate();
-
-            let interest = if utilization_rate > dec!(".90") {
-                dec!("0.12")
-            } else if utilization_rate > dec!(".80") && utilization_rate <= dec!(".90") {
-                dec!("0.11")
-            } else if utilization_rate > dec!(".70") && utilization_rate <= dec!(".80") {
-                dec!("0.10")
-            } else if utilization_rate > dec!(".60") && utilization_rate <= dec!(".70") {
-                dec!("0.09")
-            } else {
-                dec!("0.08")
+            let utilization_rate = self.utilization_rate();
+            return self.interest_rate_model.borrow_rate(utilization_rate)
+        }
+
+        /// Allows user to check the supply rate of the pool.
+        ///
+        /// This method is used to calculate the yearly rate earned by suppliers of the pool. It is the borrow
+        /// rate spread across the whole supply, net of the reserve factor.
+        /// 
+        /// This method does not perform any checks.
+        /// 
+        /// # Arguments:
+        /// 
+        /// This method does not require any arguments to be passed through.
+        /// 
+        /// # Returns:
+        /// 
+        /// `Decimal` - The supply rate returned.
+        pub fn check_supply_rate(
+            &self
+        ) -> Decimal 
+        {
+            let utilization_rate = self.utilization_rate();
+            let supply_rate = self.interest_rate_model.supply_rate(utilization_rate);
+            info!("The supply rate of this pool is {:?}", supply_rate);
+            return supply_rate
+        }
+
+        /// Allows user to check the borrow index and reserves of the pool.
+        ///
+        /// This method does not perform any checks.
+        /// 
+        /// # Arguments:
+        /// 
+        /// This method does not require any arguments to be passed through.
+        /// 
+        /// # Returns:
+        /// 
+        /// * `Decimal` - The borrow index of the pool.
+        /// * `Decimal` - The reserves kept by the pool.
+        pub fn check_borrow_index(
+            &self
+        ) -> (Decimal, Decimal)
+        {
+            info!("The borrow index of this pool is {:?}", self.borrow_index);
+            info!("The reserves of this pool are {:?}", self.reserves);
+            return (self.borrow_index, self.reserves)
+        }
+
+        /// Replaces the interest rate model of the pool.
+        ///
+        /// Interest is accrued at the rate of the old model up to the current epoch before the new model
+        /// takes effect so that existing loans are not charged the new rate retroactively.
+        /// 
+        /// This method performs a number of checks before the model is replaced:
+        /// 
+        /// * **Check 1:** Checks that the parameters of the new model are valid.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `interest_rate_model` (InterestRateModel) - The new interest rate model of the pool.
+        /// 
+        /// # Returns:
+        /// 
+        /// This method does not return any assets.
+        pub fn set_interest_rate_model(
+            &mut self,
+            interest_rate_model: InterestRateModel
+        )
+        {
+            interest_rate_model.assert_valid();
+            self.accrue_interest();
+            self.interest_rate_model = interest_rate_model;
+        }
+
+        /// Credits a supplier with the interest earned on their deposit since it was last settled.
+        ///
+        /// This helper function brings the deposit balance of the supplier's SBT in line with the supply index
+        /// so that the interest can be redeemed or converted to collateral along with the deposit. Deposits 
+        /// that have never been settled are treated as having been made when the pool was created.
+        /// 
+        /// This method does not perform any checks.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `user_id` (&NonFungibleId) - A reference to the supplier's SBT NonFungibleId.
+        /// * `token_address` (ResourceAddress) - This is the token address of the lending pool.
+        /// 
+        /// # Returns:
+        /// 
+        /// This method does not return any assets.
+        fn settle_supplier(
+            &mut self,
+            user_id: &NonFungibleId,
+            token_address: ResourceAddress
+        )
+        {
+            let interest = self.unsettled_interest(user_id, token_address);
+            if interest > Decimal::zero() {
+                let user_management: UserManagement = self.user_management_address.into();
+                self.access_badge_vault.authorize(|| {
+                    user_management.add_deposit_balance(user_id.clone(), token_address, interest)
+                    }
+                );
+            }
+            self.supplier_indexes.insert(user_id.clone(), self.supply_index);
+        }
+
+        /// Calculates the interest a supplier has earned since their deposit was last settled.
+        /// 
+        /// This method does not perform any checks.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `user_id` (&NonFungibleId) - A reference to the supplier's SBT NonFungibleId.
+        /// * `token_address` (ResourceAddress) - This is the token address of the lending pool.
+        /// 
+        /// # Returns:
+        /// 
+        /// * `Decimal` - The unsettled interest.
+        fn unsettled_interest(
+            &self,
+            user_id: &NonFungibleId,
+            token_address: ResourceAddress
+        ) -> Decimal
+        {
+            let deposit_balance = self.deposit_balance(user_id, token_address);
+            let settled_index = self.supplier_indexes.get(user_id).cloned().unwrap_or(Decimal::one());
+            deposit_balance * (self.supply_index / settled_index - Decimal::one())
+        }
+
+        /// Retrieves the deposit balance recorded on a supplier's SBT.
+        /// 
+        /// This method does not perform any checks.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `user_id` (&NonFungibleId) - A reference to the supplier's SBT NonFungibleId.
+        /// * `token_address` (ResourceAddress) - This is the token address of the lending pool.
+        /// 
+        /// # Returns:
+        /// 
+        /// * `Decimal` - The deposit balance, or zero if the supplier has never deposited.
+        fn deposit_balance(
+            &self,
+            user_id: &NonFungibleId,
+            token_address: ResourceAddress
+        ) -> Decimal
+        {
+            let user_management: UserManagement = self.user_management_address.into();
+            let resource_manager = borrow_resource_manager!(user_management.get_sbt());
+            let sbt_data: User = resource_manager.get_non_fungible_data(user_id);
+            sbt_data.deposit_balance.get(&token_address).cloned().unwrap_or(Decimal::zero())
+        }
+
+        /// Accrues the interest of the pool into the borrow index.
+        ///
+        /// This helper function grows the borrow index by the borrow rate for every epoch that has passed 
+        /// since the last accrual. The interest is added to the borrow counter and split between the suppliers 
+        /// and the reserves according to the reserve factor. The suppliers' share grows the supply index. It is 
+        /// called before every interaction with the pool.
+        /// 
+        /// This method does not perform any checks.
+        /// 
+        /// # Arguments:
+        /// 
+        /// This method does not request any arguments to be passed.
+        /// 
+        /// # Returns:
+        /// 
+        /// This method does not return any assets.
+        fn accrue_interest(
+            &mut self
+        )
+        {
+            let current_epoch = Runtime::current_epoch();
+            if current_epoch <= self.last_accrual_epoch {
+                return
+            }
+
+            let elapsed_epochs = current_epoch - self.last_accrual_epoch;
+            let borrow_rate = self.interest_calc();
+            let interest_factor = borrow_rate * Decimal::from(elapsed_epochs) / Decimal::from(self.interest_rate_model.epochs_per_year);
+
+            let interest_accrued = self.borrow_amount * interest_factor;
+
+            self.borrow_amount += interest_accrued;
+            self.book_income(interest_accrued);
+            self.borrow_index += self.borrow_index * interest_factor;
+            self.last_accrual_epoch = current_epoch;
+        }
+
+        /// Splits income of the pool between the suppliers and the reserves.
+        ///
+        /// This helper function adds the reserve factor of the income to the reserves and the rest to the supplied
+        /// amount, growing the supply index by the suppliers' share.
+        /// 
+        /// This method does not perform any checks.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `income` (Decimal) - The interest or fees earned by the pool.
+        /// 
+        /// # Returns:
+        /// 
+        /// This method does not return any assets.
+        fn book_income(
+            &mut self,
+            income: Decimal
+        )
+        {
+            let reserves_accrued = income * self.interest_rate_model.reserve_factor;
+
+            if self.supplied_amount > Decimal::zero() {
+                self.supply_index += self.supply_index * (income - reserves_accrued) / self.supplied_amount;
+            }
+            self.supplied_amount += income - reserves_accrued;
+            self.reserves += reserves_accrued;
+        }
+
+        /// Accrues the interest of a loan NFT.
+        ///
+        /// This helper function brings the balance of the loan in line with the borrow index of the pool. Any 
+        /// interest discount the borrower earned through their credit score is deducted from the growth of the
+        /// index for the epochs since the loan was last updated. The SBT borrow balance, the collateral value and 
+        /// the Health Factor of the loan are updated along with it.
+        /// 
+        /// This method does not perform any checks.
+        /// 
+        /// # Arguments:
+        /// 
+        /// `loan_id` (&NonFungibleId) - A reference to the loan NFT's NonFungibleId.
+        /// 
+        /// # Returns:
+        /// 
+        /// This method does not return any assets.
+        fn accrue_loan(
+            &mut self,
+            loan_id: &NonFungibleId
+        )
+        {
+            let mut loan_data = self.call_resource_mananger(loan_id);
+            if loan_data.loan_status == Status::PaidOff {
+                return
+            }
+
+            let current_epoch = Runtime::current_epoch();
+            let elapsed_epochs = current_epoch - loan_data.last_update;
+            let epochs_per_year = Decimal::from(self.interest_rate_model.epochs_per_year);
+
+            // Growth of the pool's index since the last update of the loan, less the borrower's discount.
+            let index_growth = self.borrow_index / loan_data.borrow_index - Decimal::one();
+            let discount = loan_data.interest_discount * Decimal::from(elapsed_epochs) / epochs_per_year;
+            let loan_growth = if index_growth > discount { index_growth - discount } else { Decimal::zero() };
+
+            let interest_accrued = loan_data.remaining_balance * loan_growth;
+            let interest_forgone = loan_data.remaining_balance * index_growth - interest_accrued;
+
+            // The pool accrued interest on the full index so the discounted interest is taken back out of it. The
+            // supply index is lowered by the same share so the suppliers' balances add up to the supplied amount.
+            if interest_forgone > Decimal::zero() {
+                let reserves_forgone = interest_forgone * self.interest_rate_model.reserve_factor;
+                self.reduce_borrow_amount(interest_forgone);
+                if self.supplied_amount > Decimal::zero() {
+                    self.supply_index -= self.supply_index * (interest_forgone - reserves_forgone) / self.supplied_amount;
+                }
+                self.supplied_amount -= interest_forgone - reserves_forgone;
+                self.reserves -= reserves_forgone;
+            }
+
+            let interest_rate = self.interest_calc();
+            loan_data.interest_rate = if interest_rate > loan_data.interest_discount { 
+                interest_rate - loan_data.interest_discount 
+            } else { 
+                Decimal::zero() 
             };
+            loan_data.remaining_balance += interest_accrued;
+            loan_data.interest_expense += interest_accrued;
+            loan_data.borrow_index = self.borrow_index;
+            loan_data.last_update = current_epoch;
+
+            // Updates the collateral value and the Health Factor of the loan, against the liquidation threshold of the
+            // borrower's current credit tier.
+            let user_management: UserManagement = self.user_management_address.into();
+            let liquidation_threshold = user_management.get_credit_tier(loan_data.owner.clone()).liquidation_threshold;
+            let pseudopriceoracle: PseudoPriceOracle = self.pseudopriceoracle_address.into();
+            let price = pseudopriceoracle.get_price(loan_data.collateral);
+            loan_data.collateral_amount_usd = loan_data.collateral_amount * price;
+            if loan_data.remaining_balance > Decimal::zero() {
+                loan_data.health_factor = ( loan_data.collateral_amount_usd * liquidation_threshold ) / ( loan_data.remaining_balance );
+            }
+
+            let owner = loan_data.owner.clone();
+            let asset = loan_data.asset;
+            self.authorize_update(loan_id, loan_data);
 
-            return interest
+            if interest_accrued > Decimal::zero() {
+                self.access_badge_vault.authorize(|| {
+                    user_management.increase_borrow_balance(owner, asset, interest_accrued)
+                    }
+                );
+            }
+        }
+
+        /// Decreases the borrow counter of the pool without letting it go below zero.
+        fn reduce_borrow_amount(
+            &mut self,
+            amount: Decimal
+        )
+        {
+            self.borrow_amount = if amount >= self.borrow_amount { Decimal::zero() } else { self.borrow_amount - amount };
         }
 
         /// Checks the Health Factor of the loan.
@@ -1421,12 +1763,24 @@ blueprint! {
             &mut self
         ) -> Decimal
         {
-            let borrow_amount = self.borrow_amount;
-            let liquidity_amount: Decimal = borrow_amount / self.supplied_amount;
+            self.accrue_interest();
+            let liquidity_amount: Decimal = self.utilization_rate();
             info!("The utilization rate of this pool is {:?}", liquidity_amount);
             return liquidity_amount
         }
 
+        /// Calculates the utilization rate of the pool, capped at 100%.
+        fn utilization_rate(
+            &self
+        ) -> Decimal
+        {
+            if self.supplied_amount == Decimal::zero() {
+                return Decimal::zero()
+            }
+            let utilization_rate = self.borrow_amount / self.supplied_amount;
+            if utilization_rate > Decimal::one() { Decimal::one() } else { utilization_rate }
+        }
+
         /// Allows user to check the total supplied to the pool.
         ///
         /// This method is used to allow users check the total supply of the pool.
@@ -1447,6 +1801,55 @@ blueprint! {
             info!("The total supplied in this pool is {:?}", self.supplied_amount);
             return self.supplied_amount
         }
+
+        /// Allows user to check the supply index of the pool.
+        ///
+        /// This method is used to allow users check how much one token supplied to the pool when it was
+        /// created has grown through interest. Interest accrued since the pool was last interacted with is
+        /// not included.
+        /// 
+        /// This method does not perform any checks.
+        /// 
+        /// # Arguments:
+        /// 
+        /// This method does not request any arguments to be passed.
+        /// 
+        /// # Returns:
+        /// 
+        /// * `Decimal` - The supply index of the pool.
+        pub fn check_supply_index(
+            &self
+        ) -> Decimal
+        {
+            info!("The supply index of this pool is {:?}", self.supply_index);
+            return self.supply_index
+        }
+
+        /// Allows user to check the value of a supplier's deposit.
+        ///
+        /// This method is used to allow users check how much a supplier is owed by the pool, including the
+        /// interest earned up to the current supply index. Redeeming it is subject to the pool's liquidity.
+        /// 
+        /// This method does not perform any checks.
+        /// 
+        /// # Arguments:
+        /// 
+        /// * `user_id` (NonFungibleId) - The NonFungibleId of the supplier's SBT.
+        /// 
+        /// # Returns:
+        /// 
+        /// * `Decimal` - The value of the supplier's deposit.
+        pub fn check_deposit_value(
+            &self,
+            user_id: NonFungibleId
+        ) -> Decimal
+        {
+            let token_address: ResourceAddress = self.addresses()[0];
+            let deposit_value = self.deposit_balance(&user_id, token_address)
+                + self.unsettled_interest(&user_id, token_address);
+            info!("The deposit value of this supplier is {:?}", deposit_value);
+            return deposit_value
+        }
         
         /// Allows user to check the total borrowed from the pool.
         ///
@@ -39,7 +39,9 @@ pub struct Loan {
     pub asset: ResourceAddress,
     pub collateral: ResourceAddress,
     pub principal_loan_amount: Decimal,
+    #[scrypto(mutable)]
     pub interest_rate: Decimal,
+    pub interest_discount: Decimal,
     pub origination_fee: Decimal,
     pub origination_fee_charged: Decimal,
     #[scrypto(mutable)]
@@ -51,6 +53,8 @@ pub struct Loan {
     #[scrypto(mutable)]
     pub last_update: u64,
     #[scrypto(mutable)]
+    pub borrow_index: Decimal,
+    #[scrypto(mutable)]
     pub collateral_amount: Decimal,
     #[scrypto(mutable)]
     pub collateral_amount_usd: Decimal,
@@ -70,6 +74,7 @@ pub enum Status {
 #[derive(NonFungibleData, Debug)]
 pub struct FlashLoan {
     pub amount_due: Decimal,
+    pub fee: Decimal,
     pub asset: ResourceAddress,
     pub borrow_count: u8,
 }
@@ -80,4 +85,156 @@ pub struct AuctionAuth {
     pub amount_due: Decimal,
     pub collateral_due: Decimal,
     pub collateral_address: ResourceAddress,
-}
\ No newline at end of file
+}
+
+/// The interest rate model used by a lending pool. It is a continuous "kinked" model: the borrow rate starts at
+/// `base_rate` and increases linearly with utilization by `slope_low` until utilization reaches `optimal_utilization`.
+/// Past the kink, the rate increases by the much steeper `slope_high` to encourage repayments and new deposits. All
+/// rates are expressed per year and converted to per-epoch rates using `epochs_per_year` when interest is accrued.
+/// A share of the interest paid by borrowers, the `reserve_factor`, is kept by the pool as reserves and the rest is
+/// passed on to the suppliers, which is how the supply rate is derived from the borrow rate.
+#[derive(Describe, Encode, Decode, TypeId, Debug, Clone)]
+pub struct InterestRateModel {
+    pub base_rate: Decimal,
+    pub slope_low: Decimal,
+    pub slope_high: Decimal,
+    pub optimal_utilization: Decimal,
+    pub reserve_factor: Decimal,
+    pub epochs_per_year: u64,
+}
+
+/// The model that new lending pools are created with: 2% base rate, 10% slope up to 80% utilization and 100%
+/// slope above it, with 10% of the interest kept as reserves.
+impl Default for InterestRateModel {
+    fn default() -> Self {
+        Self {
+            base_rate: dec!("0.02"),
+            slope_low: dec!("0.10"),
+            slope_high: dec!("1.00"),
+            optimal_utilization: dec!("0.80"),
+            reserve_factor: dec!("0.10"),
+            epochs_per_year: 8760,
+        }
+    }
+}
+
+impl InterestRateModel {
+    /// Asserts that the parameters of the model are sensible.
+    pub fn assert_valid(&self) {
+        assert!(self.base_rate >= Decimal::zero(), "[Interest Rate Model]: Base rate can't be negative.");
+        assert!(
+            self.slope_low >= Decimal::zero() && self.slope_high >= self.slope_low,
+            "[Interest Rate Model]: The slope above the kink must be greater than or equal to the slope below it."
+        );
+        assert!(
+            self.optimal_utilization > Decimal::zero() && self.optimal_utilization < Decimal::one(),
+            "[Interest Rate Model]: Optimal utilization must be between 0 and 1."
+        );
+        assert!(
+            self.reserve_factor >= Decimal::zero() && self.reserve_factor < Decimal::one(),
+            "[Interest Rate Model]: Reserve factor must be between 0 and 1."
+        );
+        assert!(self.epochs_per_year > 0, "[Interest Rate Model]: Epochs per year must be greater than zero.");
+    }
+
+    /// Calculates the yearly borrow rate for the given utilization rate.
+    pub fn borrow_rate(&self, utilization_rate: Decimal) -> Decimal {
+        if utilization_rate <= self.optimal_utilization {
+            self.base_rate + utilization_rate * self.slope_low
+        } else {
+            let excess_utilization = utilization_rate - self.optimal_utilization;
+            self.base_rate + self.optimal_utilization * self.slope_low + excess_utilization * self.slope_high
+        }
+    }
+
+    /// Calculates the yearly supply rate for the given utilization rate. Suppliers earn the interest paid by the
+    /// borrowers, spread across the whole supply and net of the reserve factor.
+    pub fn supply_rate(&self, utilization_rate: Decimal) -> Decimal {
+        self.borrow_rate(utilization_rate) * utilization_rate * (Decimal::one() - self.reserve_factor)
+    }
+}
+
+/// A tier of the credit tier table. Users are placed in the tier with the highest `min_score` that their SBT credit
+/// score reaches. The tier sets how much a user may borrow against their collateral (`max_ltv`), the liquidation
+/// threshold used for the Health Factor of their loans, the discount on the interest rate of new loans and the share of
+/// the flash loan fee that is waived. The liquidation threshold sits above the max LTV so that a loan taken out at the
+/// max LTV has some room before it can be liquidated.
+#[derive(Describe, Encode, Decode, TypeId, Debug, Clone)]
+pub struct CreditTier {
+    pub min_score: u64,
+    pub max_ltv: Decimal,
+    pub liquidation_threshold: Decimal,
+    pub interest_discount: Decimal,
+    pub flash_loan_fee_discount: Decimal,
+}
+
+impl CreditTier {
+    /// The tier table the protocol starts with. Users with no credit history can borrow up to 75% of their collateral
+    /// and every 100 points of credit score raise the max LTV by 3% and the interest rate discount by 1%. Loans can be
+    /// liquidated once they pass 5% above the max LTV.
+    pub fn default_table() -> Vec<CreditTier> {
+        vec![
+            CreditTier { min_score: 0, max_ltv: dec!("0.75"), liquidation_threshold: dec!("0.80"), interest_discount: dec!("0.0"), flash_loan_fee_discount: dec!("0.0") },
+            CreditTier { min_score: 100, max_ltv: dec!("0.78"), liquidation_threshold: dec!("0.83"), interest_discount: dec!("0.01"), flash_loan_fee_discount: dec!("0.25") },
+            CreditTier { min_score: 200, max_ltv: dec!("0.81"), liquidation_threshold: dec!("0.86"), interest_discount: dec!("0.02"), flash_loan_fee_discount: dec!("0.50") },
+            CreditTier { min_score: 300, max_ltv: dec!("0.84"), liquidation_threshold: dec!("0.89"), interest_discount: dec!("0.03"), flash_loan_fee_discount: dec!("0.75") },
+        ]
+    }
+
+    /// Asserts that the tier table is sensible. The first tier must start at a credit score of 0 so that every user has
+    /// a tier, and the tiers must be sorted by strictly increasing `min_score`.
+    pub fn assert_valid_table(tiers: &Vec<CreditTier>) {
+        assert!(!tiers.is_empty(), "[Credit Tiers]: The tier table can't be empty.");
+        assert_eq!(tiers[0].min_score, 0, "[Credit Tiers]: The first tier must start at a credit score of 0.");
+        for (i, tier) in tiers.iter().enumerate() {
+            if i > 0 {
+                assert!(tier.min_score > tiers[i - 1].min_score, "[Credit Tiers]: Tiers must be sorted by increasing credit score.");
+            }
+            assert!(
+                tier.max_ltv > Decimal::zero() && tier.max_ltv < Decimal::one(),
+                "[Credit Tiers]: Max LTV must be between 0 and 1."
+            );
+            assert!(
+                tier.liquidation_threshold > tier.max_ltv && tier.liquidation_threshold < Decimal::one(),
+                "[Credit Tiers]: Liquidation threshold must be between the max LTV and 1."
+            );
+            assert!(
+                tier.interest_discount >= Decimal::zero() && tier.interest_discount < Decimal::one(),
+                "[Credit Tiers]: Interest rate discount must be between 0 and 1."
+            );
+            assert!(
+                tier.flash_loan_fee_discount >= Decimal::zero() && tier.flash_loan_fee_discount <= Decimal::one(),
+                "[Credit Tiers]: Flash loan fee discount must be between 0 and 1."
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scrypto::prelude::*;
+    use crate::structs::CreditTier;
+
+    #[test]
+    fn test_default_table_is_valid() {
+        let tiers = CreditTier::default_table();
+        CreditTier::assert_valid_table(&tiers);
+        assert!(tiers.iter().all(|tier| tier.liquidation_threshold > tier.max_ltv));
+    }
+
+    #[test]
+    #[should_panic(expected = "Liquidation threshold must be between the max LTV and 1.")]
+    fn test_liquidation_threshold_at_max_ltv_is_rejected() {
+        let mut tiers = CreditTier::default_table();
+        tiers[1].liquidation_threshold = tiers[1].max_ltv;
+        CreditTier::assert_valid_table(&tiers);
+    }
+
+    #[test]
+    #[should_panic(expected = "Liquidation threshold must be between the max LTV and 1.")]
+    fn test_liquidation_threshold_of_one_is_rejected() {
+        let mut tiers = CreditTier::default_table();
+        tiers[0].liquidation_threshold = dec!("1");
+        CreditTier::assert_valid_table(&tiers);
+    }
+}
@@ -1,6 +1,6 @@
 
 use scrypto::prelude::*;
-use crate::structs::{User};
+use crate::structs::{User, CreditTier};
 
 blueprint! {
     /// This is the User Management component. Everything here deals with the SBT data management. From increasing 
@@ -16,6 +16,9 @@ blueprint! {
         user_record: HashMap<NonFungibleId, User>,
         /// Keeps a record of wallet addresses to ensure that maps 1 SBT to 1 Wallet.
         account_record: Vec<ComponentAddress>,
+        /// The credit tier table, sorted by increasing credit score. It maps the credit score of the SBT to the max LTV,
+        /// interest rate discount and flash loan fee discount the user is entitled to.
+        credit_tiers: Vec<CreditTier>,
     }
 
     /// Instantiates the User Management component. This is instantiated through the main router component. 
@@ -45,6 +48,7 @@ blueprint! {
             .method("close_loan", rule!(require(access_badge_address)))
             .method("convert_deposit_to_collateral", rule!(require(access_badge_address)))
             .method("convert_collateral_to_deposit", rule!(require(access_badge_address)))
+            .method("set_credit_tiers", rule!(require(access_badge_address)))
             .default(rule!(allow_all));
 
             // Badge that will be stored in the component's vault to provide authorization to update the User NFT.
@@ -67,6 +71,7 @@ blueprint! {
                 sbt_address: sbt_data,
                 user_record: HashMap::new(),
                 account_record: Vec::new(),
+                credit_tiers: CreditTier::default_table(),
             }
             .instantiate()
             .add_access_check(access_rules)
@@ -790,71 +795,75 @@ blueprint! {
             self.authorize_update(&user_id, sbt_data);
         }
 
-        /// Modifies interest rate based on credit score.
+        /// Retrieves the credit tier of the user.
         /// 
-        /// This is just a (very) basic implementation to reward good borrowing habits by
-        /// rewarding users who demonstrate a good borrowing track record with interest coupons. 
+        /// This is just a (very) basic implementation to reward good borrowing habits by rewarding users who
+        /// demonstrate a good borrowing track record. The user is placed in the highest tier of the credit tier
+        /// table that their credit score reaches, which determines their max LTV, interest rate discount and
+        /// flash loan fee discount.
         /// 
-        /// This method does not perform any checks
+        /// This method does not perform any checks.
         /// 
         /// # Arguments:
         /// 
-        /// * `user_id` (NonFungibleId) - The NonFungibleId that identifies the specific NFT which represents the user. It is used 
-        /// to update the data of the NFT.
+        /// * `user_id` (NonFungibleId) - The NonFungibleId that identifies the specific SBT which represents the user.
         /// 
         /// # Returns:
         /// 
-        /// * `Decimal` - The awarded interest rate to be discounted by.
-        pub fn interest_modifier(
+        /// * `CreditTier` - The credit tier of the user.
+        pub fn get_credit_tier(
             &self,
             user_id: NonFungibleId
-        ) -> Decimal 
+        ) -> CreditTier
         {
             let sbt_data = self.call_resource_mananger(&user_id);
-            let credit_score = sbt_data.credit_score;
-            if credit_score >= 100 && credit_score < 200 {
-                return dec!(".01")
-            } else if credit_score >= 200 && credit_score < 300 {
-                return dec!(".02")
-            } else if credit_score >= 300 {
-                return dec!(".03")
-            } else {
-                return dec!("0.0")
-            }
+            self.credit_tier_for_score(sbt_data.credit_score)
         }
 
-        /// Modifies collaterization rate based on credit score.
+        /// Retrieves the credit tier table.
+        pub fn get_credit_tiers(
+            &self
+        ) -> Vec<CreditTier>
+        {
+            return self.credit_tiers.clone();
+        }
+
+        /// Replaces the credit tier table.
         /// 
-        /// This is just a (very) basic implementation to reward good borrowing habits by
-        /// rewarding users who demonstrate a good borrowing track record with modification 
-        /// is collaterization requirement. 
+        /// This method performs a few checks before the table is replaced:
         /// 
-        /// This method does not perform any checks
+        /// * **Check 1:** Checks that the first tier starts at a credit score of 0.
+        /// 
+        /// * **Check 2:** Checks that the tiers are sorted by increasing credit score and that the LTV and
+        /// discounts are between 0 and 1.
         /// 
         /// # Arguments:
         /// 
-        /// * `user_id` (NonFungibleId) - The NonFungibleId that identifies the specific SBT which represents the user. It is used 
-        /// to update the data of the SBT.
+        /// * `credit_tiers` (Vec<CreditTier>) - The new credit tier table.
         /// 
         /// # Returns:
         /// 
-        /// * `Decimal` - The awarded modification in collateralizaiton rate.
-        pub fn collateralization_modifier(
+        /// This method does not return any assets.
+        pub fn set_credit_tiers(
+            &mut self,
+            credit_tiers: Vec<CreditTier>
+        )
+        {
+            CreditTier::assert_valid_table(&credit_tiers);
+            self.credit_tiers = credit_tiers;
+        }
+
+        /// Finds the highest tier that the credit score reaches. The first tier always starts at 0.
+        fn credit_tier_for_score(
             &self,
-            user_id: NonFungibleId
-        ) -> Decimal 
+            credit_score: u64
+        ) -> CreditTier
         {
-            let sbt_data = self.call_resource_mananger(&user_id);
-            let credit_score = sbt_data.credit_score;
-            if credit_score >= 100 && credit_score < 200 {
-                return dec!(".03")
-            } else if credit_score >= 200 && credit_score < 300 {
-                return dec!(".06")
-            } else if credit_score >= 300 {
-                return dec!(".09")
-            } else {
-                return dec!("0.0")
-            }
+            let tier = self.credit_tiers.iter()
+                .filter(|tier| credit_score >= tier.min_score)
+                .last()
+                .unwrap();
+            return tier.clone();
         }
 
         /// Allows user to add to their credit score.
@@ -910,8 +919,10 @@ blueprint! {
             let closed_loans = sbt_data.closed_loans;
             let defaults = sbt_data.defaults;
             let paid_off = sbt_data.paid_off;
+            let credit_tier = self.credit_tier_for_score(credit_score);
 
             info!("[User SBT]: Credit Score: {:?}", credit_score);
+            info!("[User SBT]: Credit Tier: {:?}", credit_tier);
             info!("[User SBT]: Deposit Balance: {:?}", deposit_balance);
             info!("[User SBT]: Collateral Balance: {:?}", collateral_balance);
             info!("[User SBT]: Borrow Balance: {:?}", borrow_balance);
@@ -0,0 +1,295 @@
+use radix_engine::ledger::*;
+use radix_engine::model::Receipt;
+use radix_engine::transaction::*;
+use scrypto::prelude::*;
+
+struct User {
+    pk: EcdsaPublicKey,
+    sk: EcdsaPrivateKey,
+    account: ComponentAddress,
+}
+
+struct TestEnv {
+    supplier: User,
+    borrower: User,
+    depositor: User,
+    degenfi: ComponentAddress,
+    sbt: ResourceAddress,
+    usd: ResourceAddress,
+}
+
+fn new_user(executor: &mut TransactionExecutor<InMemorySubstateStore>, degenfi: ComponentAddress) -> User {
+    let (pk, sk, account) = executor.new_account();
+    let transaction = TransactionBuilder::new()
+        .call_method(degenfi, "new_user", args![account])
+        .call_method_with_all_resources(account, "deposit_batch")
+        .build(executor.get_nonce([pk]))
+        .sign([&sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+    User { pk, sk, account }
+}
+
+/// Calls a DegenFi method that takes the user's SBT proof and a bucket of the given resource
+fn call_with_bucket(
+    executor: &mut TransactionExecutor<InMemorySubstateStore>,
+    env: &TestEnv,
+    user: &User,
+    method: &str,
+    resource: ResourceAddress,
+    amount: Decimal,
+) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(env.sbt, user.account)
+        .withdraw_from_account_by_amount(amount, resource, user.account)
+        .create_proof_from_auth_zone(env.sbt, |builder, proof_id| {
+            builder.take_from_worktop(resource, |builder, bucket_id| {
+                builder.call_method(env.degenfi, method, args![Proof(proof_id), Bucket(bucket_id)])
+            })
+        })
+        .call_method_with_all_resources(user.account, "deposit_batch")
+        .build(executor.get_nonce([user.pk]))
+        .sign([&user.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+/// Creates the USD and XRD pools, supplies 100 USD from a second user and has a third user borrow
+/// 5000 USD against 10000 XRD, leaving the USD pool about half utilized
+fn setup(executor: &mut TransactionExecutor<InMemorySubstateStore>) -> TestEnv {
+    let (pk, sk, account) = executor.new_account();
+    let package = executor.publish_package(compile_package!()).unwrap();
+
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    metadata.insert("symbol".to_string(), "USD".to_string());
+    let transaction = TransactionBuilder::new()
+        .new_token_fixed(metadata, dec!("1000000"))
+        .call_method_with_all_resources(account, "deposit_batch")
+        .build(executor.get_nonce([pk]))
+        .sign([&sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let usd = receipt.new_resource_addresses[0];
+
+    let transaction = TransactionBuilder::new()
+        .call_function(package, "DegenFi", "new", args![])
+        .call_method_with_all_resources(account, "deposit_batch")
+        .build(executor.get_nonce([pk]))
+        .sign([&sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let degenfi = receipt.new_component_addresses[0];
+    let sbt = receipt.new_resource_addresses[8];
+
+    let transaction = TransactionBuilder::new()
+        .call_method(degenfi, "new_user", args![account])
+        .call_method_with_all_resources(account, "deposit_batch")
+        .build(executor.get_nonce([pk]))
+        .sign([&sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+    let supplier = User { pk, sk, account };
+    let borrower = new_user(executor, degenfi);
+    let depositor = new_user(executor, degenfi);
+
+    let env = TestEnv { supplier, borrower, depositor, degenfi, sbt, usd };
+
+    assert!(call_with_bucket(executor, &env, &env.supplier, "new_lending_pool", usd, dec!("10000")).result.is_ok());
+    assert!(call_with_bucket(executor, &env, &env.supplier, "new_lending_pool", RADIX_TOKEN, dec!("1000")).result.is_ok());
+
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(dec!("100"), usd, env.supplier.account)
+        .call_method_with_all_resources(env.depositor.account, "deposit_batch")
+        .build(executor.get_nonce([env.supplier.pk]))
+        .sign([&env.supplier.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+    assert!(call_with_bucket(executor, &env, &env.depositor, "deposit_supply", usd, dec!("100")).result.is_ok());
+
+    assert!(call_with_bucket(executor, &env, &env.borrower, "deposit_collateral", RADIX_TOKEN, dec!("10000")).result.is_ok());
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(env.sbt, env.borrower.account)
+        .create_proof_from_auth_zone(env.sbt, |builder, proof_id| {
+            builder.call_method(env.degenfi, "borrow", args![Proof(proof_id), usd, RADIX_TOKEN, dec!("5000")])
+        })
+        .call_method_with_all_resources(env.borrower.account, "deposit_batch")
+        .build(executor.get_nonce([env.borrower.pk]))
+        .sign([&env.borrower.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+
+    env
+}
+
+/// Calls a DegenFi method that takes the user's SBT proof followed by the given arguments
+fn call_with_proof(
+    executor: &mut TransactionExecutor<InMemorySubstateStore>,
+    env: &TestEnv,
+    user: &User,
+    method: &str,
+    mut args: Vec<Vec<u8>>,
+) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(env.sbt, user.account)
+        .create_proof_from_auth_zone(env.sbt, |builder, proof_id| {
+            let mut method_args = args![Proof(proof_id)];
+            method_args.append(&mut args);
+            builder.call_method(env.degenfi, method, method_args)
+        })
+        .call_method_with_all_resources(user.account, "deposit_batch")
+        .build(executor.get_nonce([user.pk]))
+        .sign([&user.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn redeem(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, amount: Decimal) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(env.sbt, env.depositor.account)
+        .create_proof_from_auth_zone(env.sbt, |builder, proof_id| {
+            builder.call_method(env.degenfi, "redeem", args![Proof(proof_id), env.usd, amount])
+        })
+        .call_method_with_all_resources(env.depositor.account, "deposit_batch")
+        .build(executor.get_nonce([env.depositor.pk]))
+        .sign([&env.depositor.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn get_decimal(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, method: &str) -> Decimal {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.degenfi, method, args![env.usd])
+        .build(executor.get_nonce([env.supplier.pk]))
+        .sign([&env.supplier.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    scrypto_decode(&receipt.outputs[0].raw[..]).unwrap()
+}
+
+#[test]
+fn test_redeem_includes_accrued_interest() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // Nothing has accrued yet, so only the deposit can be redeemed
+    assert!(redeem(&mut executor, &env, dec!("101")).result.is_err());
+
+    // After a year at about 7% on half of the pool the deposit has earned about 3% for the suppliers
+    executor.substate_store_mut().set_epoch(8760);
+    assert!(redeem(&mut executor, &env, dec!("102")).result.is_ok());
+    assert!(redeem(&mut executor, &env, dec!("2")).result.is_err());
+}
+
+#[test]
+fn test_redeem_does_not_double_count_supplied_amount() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    executor.substate_store_mut().set_epoch(8760);
+    let supplied_before = get_decimal(&mut executor, &env, "check_total_supplied");
+    let index_before = get_decimal(&mut executor, &env, "check_supply_index");
+
+    // The redeem accrues the suppliers' interest once and removes only the redeemed amount
+    assert!(redeem(&mut executor, &env, dec!("50")).result.is_ok());
+    let supplied_after = get_decimal(&mut executor, &env, "check_total_supplied");
+    let index_after = get_decimal(&mut executor, &env, "check_supply_index");
+    assert!(index_after > index_before);
+    let expected = supplied_before * index_after / index_before - dec!("50");
+    assert!(supplied_after - expected < dec!("0.000001"));
+    assert!(expected - supplied_after < dec!("0.000001"));
+
+    // Redeeming again in the same epoch settles no further interest
+    assert!(redeem(&mut executor, &env, dec!("1")).result.is_ok());
+    assert_eq!(get_decimal(&mut executor, &env, "check_total_supplied"), supplied_after - dec!("1"));
+}
+
+fn set_xrd_price(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, price: Decimal) {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.degenfi, "set_price", args![RADIX_TOKEN, price])
+        .build(executor.get_nonce([env.supplier.pk]))
+        .sign([&env.supplier.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+}
+
+fn bad_loans(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv) -> HashMap<NonFungibleId, ResourceAddress> {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.degenfi, "bad_loans", args![])
+        .build(executor.get_nonce([env.supplier.pk]))
+        .sign([&env.supplier.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    scrypto_decode(&receipt.outputs[0].raw[..]).unwrap()
+}
+
+#[test]
+fn test_loan_is_liquidatable_only_above_liquidation_threshold() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // The borrower owes 5050 including the origination fee. Without credit history the max LTV is 75% and the
+    // liquidation threshold is 80%.
+    set_xrd_price(&mut executor, &env, dec!("0.68"));
+    assert!(bad_loans(&mut executor, &env).is_empty(), "74% LTV is within the max LTV");
+
+    set_xrd_price(&mut executor, &env, dec!("0.66"));
+    assert!(bad_loans(&mut executor, &env).is_empty(), "76% LTV is above the max LTV but below the liquidation threshold");
+
+    set_xrd_price(&mut executor, &env, dec!("0.63"));
+    assert_eq!(bad_loans(&mut executor, &env).len(), 1, "80.2% LTV is above the liquidation threshold");
+}
+
+fn interest_rates(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv) -> (Decimal, Decimal) {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.degenfi, "check_interest_rates", args![env.usd])
+        .build(executor.get_nonce([env.supplier.pk]))
+        .sign([&env.supplier.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    scrypto_decode(&receipt.outputs[0].raw[..]).unwrap()
+}
+
+fn assert_close(actual: Decimal, expected: Decimal) {
+    assert!(actual - expected < dec!("0.000001") && expected - actual < dec!("0.000001"), "{}, {}", actual, expected);
+}
+
+#[test]
+fn test_loans_accrue_at_the_pool_borrow_rate() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    let (borrow_rate, _) = interest_rates(&mut executor, &env);
+    assert_eq!(get_decimal(&mut executor, &env, "check_total_borrowed"), dec!("5050"));
+
+    // Looking for bad loans accrues the pool and every loan, here over exactly one year
+    executor.substate_store_mut().set_epoch(8760);
+    assert!(bad_loans(&mut executor, &env).is_empty());
+    assert_close(get_decimal(&mut executor, &env, "check_total_borrowed"), dec!("5050") * (Decimal::one() + borrow_rate));
+}
+
+#[test]
+fn test_discount_is_taken_out_of_the_supply_index_too() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // A credit score of 300 gives the depositor a 3% interest rate discount on a new loan of 2000
+    assert!(call_with_proof(&mut executor, &env, &env.depositor, "set_credit_score", args![300u64]).result.is_ok());
+    assert!(call_with_bucket(&mut executor, &env, &env.depositor, "deposit_collateral", RADIX_TOKEN, dec!("10000")).result.is_ok());
+    assert!(call_with_proof(&mut executor, &env, &env.depositor, "borrow", args![env.usd, RADIX_TOKEN, dec!("2000")]).result.is_ok());
+
+    let (borrow_rate, _) = interest_rates(&mut executor, &env);
+    assert!(borrow_rate > dec!("0.03"));
+    let supplied_before = get_decimal(&mut executor, &env, "check_total_supplied");
+    let index_before = get_decimal(&mut executor, &env, "check_supply_index");
+
+    executor.substate_store_mut().set_epoch(8760);
+    assert!(bad_loans(&mut executor, &env).is_empty());
+
+    // The pool accrued a year of interest on all 7070 borrowed but the depositor's loan gave back 3% of 2020
+    let borrowed = get_decimal(&mut executor, &env, "check_total_borrowed");
+    assert_close(borrowed, dec!("7070") * (Decimal::one() + borrow_rate) - dec!("2020") * dec!("0.03"));
+
+    // Interest and the forgone discount move the supplied amount and the supply index together, so the
+    // suppliers' balances still add up to the supplied amount
+    let supplied_after = get_decimal(&mut executor, &env, "check_total_supplied");
+    let index_after = get_decimal(&mut executor, &env, "check_supply_index");
+    assert!(supplied_after > supplied_before);
+    assert_close(supplied_after * index_before, supplied_before * index_after);
+}
@@ -96,6 +96,55 @@
 //! with the lenders, using these mechanisms to assist in those
 //! proceedings.
 //!
+//! # Reputation
+//!
+//! If the catalog creator of our Participants catalog has authorized
+//! our admin badge as a reporter (see
+//! [Participants::authorize_reporter][par]) we report every
+//! installment paid, whether on time or late, every loan entering
+//! formal arrears and every loan repaid in full to the catalog. This
+//! builds the reputation of the borrower, the facilitator and the
+//! borrower's sponsor and endorsers.
+//!
+//! [par]: crate::participants::blueprint::Participants::authorize_reporter
+//!
+//! # Restructuring
+//!
+//! When a borrower runs into trouble it is often better for everyone
+//! to change the terms of the loan than to leave it in arrears. Any
+//! lender, or the borrower, can propose a restructuring of the loan:
+//!
+//! - extending the term, which spreads the outstanding installments
+//! over a larger number of smaller installments (see
+//! propose_extend_term),
+//!
+//! - reducing the rate, which lowers the amount paid per installment
+//! (see propose_reduce_rate),
+//!
+//! - or converting the outstanding debt into a new installment plan
+//! starting from the current epoch (see propose_installment_plan).
+//!
+//! Lenders then vote on the proposal with the approve_restructuring
+//! and reject_restructuring methods. Votes are weighted by the share
+//! of the principal each lender pledged, and a proposal passes as
+//! soon as lenders holding more than half of the principal approve
+//! it. It is rejected as soon as lenders holding at least half of the
+//! principal reject it. A proposal which passes is immediately
+//! applied to the Loan NFT. Note that restructuring does not clear
+//! the formal arrears flag, this is still done with
+//! approve_clear_arrears.
+//!
+//! # Selling a loan share
+//!
+//! A lender who voted against a restructuring proposal that passed
+//! anyway is not obliged to stay in the new deal. He can offer his
+//! share of the loan for sale with offer_loan_share, at a price of
+//! his choosing in the loan token, and another Participant can take
+//! over the share by calling buy_loan_share. The buyer steps into
+//! the seller's place in the lender list and receives all future
+//! installments for that share; the seller finds the proceeds of the
+//! sale among his lender rewards.
+//!
 //! ---
 //! 
 //! **Front-end design advice:** In addition to Participant-based
@@ -108,7 +157,64 @@
 
 use scrypto::prelude::*;
 
-use crate::participants::Participant;
+use crate::participants::{catalog_of, Participant, RepaymentEvent};
+
+/// A change to the terms of a loan that the lenders can vote on.
+#[derive(Debug, Clone, PartialEq, TypeId, Encode, Decode, Describe)]
+pub enum Restructuring {
+    /// Adds this many installments to the loan. The amount still
+    /// owed is spread evenly over the new number of remaining
+    /// installments.
+    ExtendTerm { additional_installments: u64 },
+
+    /// Lowers the amount paid on each remaining installment.
+    ReduceRate { amount_per_installment: Decimal },
+
+    /// Replaces the remaining installments with a new plan whose
+    /// first installment is due epochs_per_installment epochs after
+    /// the plan is adopted.
+    InstallmentPlan {
+        installments: u64,
+        epochs_per_installment: u64,
+        amount_per_installment: Decimal,
+    },
+}
+
+/// The outcome of a restructuring proposal.
+#[derive(Debug, Clone, Copy, PartialEq, TypeId, Encode, Decode, Describe)]
+pub enum ProposalState {
+    /// Still collecting votes.
+    Open,
+    /// Approved by a majority of the principal and applied to the
+    /// loan.
+    Passed,
+    /// Rejected by at least half of the principal.
+    Rejected,
+}
+
+/// A restructuring proposal and the votes cast on it so far.
+#[derive(Debug, Clone, TypeId, Encode, Decode, Describe)]
+pub struct RestructuringProposal {
+    /// The Participant id of whoever made the proposal.
+    proposer: NonFungibleId,
+
+    /// The epoch the proposal was made in.
+    proposal_epoch: u64,
+
+    /// The change to the loan terms being proposed.
+    restructuring: Restructuring,
+
+    /// Where the proposal stands.
+    state: ProposalState,
+
+    /// Lenders who approve the proposal.
+    approvals: HashSet<NonFungibleId>,
+
+    /// Lenders who reject the proposal. This is kept after the
+    /// proposal has passed as it entitles these lenders to sell their
+    /// share of the loan.
+    rejections: HashSet<NonFungibleId>,
+}
 
 /// This is the NFT data for a Loan. It is used for managing the loan
 /// after its principal has been paid out and repayments are expected
@@ -148,12 +254,24 @@ struct Loan {
     /// The Participant id of the borrower.
     borrower_id: NonFungibleId,
 
+    /// The borrower's sponsor at the time the loan was accepted, if
+    /// any. Repayment events on this loan are recorded against them.
+    sponsor: Option<NonFungibleId>,
+
+    /// Everyone endorsing the borrower at the time the loan was
+    /// accepted. Repayment events on this loan are recorded against
+    /// them.
+    endorsers: HashSet<NonFungibleId>,
+
     /// Overivew of the lenders to the loan and how much of the
-    /// principal each contributed.
+    /// principal each contributed. This changes when a lender sells
+    /// his share of the loan.
+    #[scrypto(mutable)]
     lenders: HashMap<NonFungibleId, Decimal>,
 
     /// How many installments in total the borrower is expected to
-    /// pay.
+    /// pay. This changes when the loan is restructured.
+    #[scrypto(mutable)]
     installment_total_count: u64,
 
     /// How many of the installments are still to be paid.
@@ -161,10 +279,28 @@ struct Loan {
     installments_remaining: u64,
 
     /// The number of epochs between each installment.
+    #[scrypto(mutable)]
     epochs_per_installment: u64,
 
     /// The amount of tokens to pay on each installment.
+    #[scrypto(mutable)]
     amount_per_installment: Decimal,
+
+    /// The epoch the current payment schedule started. This is the
+    /// loan start epoch unless the loan has been converted into an
+    /// installment plan.
+    #[scrypto(mutable)]
+    schedule_start_epoch: u64,
+
+    /// How many installments had already been paid when the current
+    /// payment schedule started.
+    #[scrypto(mutable)]
+    schedule_installments_paid: u64,
+
+    /// All restructuring proposals ever made for this loan. A
+    /// proposal's id is its index in this list.
+    #[scrypto(mutable)]
+    restructuring_proposals: Vec<RestructuringProposal>,
 }
 
 
@@ -203,6 +339,11 @@ blueprint! {
         /// Vaults holding funds taken as fees, with one vault per
         /// token type.
         facilitator_rewards: HashMap<ResourceAddress, Vault>,
+
+        /// Loan shares currently offered for sale. The outer map is
+        /// by Loan id and the inner map is from the selling lender's
+        /// Participant id to his asking price.
+        share_offers: HashMap<NonFungibleId, HashMap<NonFungibleId, Decimal>>,
     }
 
     impl LoanAcceptor {
@@ -299,6 +440,7 @@ blueprint! {
                     admin_badge: Vault::with_bucket(badge),
                     lender_rewards: HashMap::new(),
                     facilitator_rewards: HashMap::new(),
+                    share_offers: HashMap::new(),
                 }.instantiate().globalize();
 
             // All methods that require access control in this blueprint
@@ -342,6 +484,14 @@ blueprint! {
                        "Unsupported Requestor instance");
 
             let loan_nfid: NonFungibleId = NonFungibleId::random();
+            let loan_start_epoch = Runtime::current_epoch();
+            let (sponsor, endorsers) =
+                match catalog_of(self.participants_nft_addr) {
+                    Some(catalog) => borrow_component!(catalog)
+                        .call::<(Option<NonFungibleId>, HashSet<NonFungibleId>)>(
+                            "read_backers", args!(borrower_id.clone())),
+                    None => (None, HashSet::new()),
+                };
             let loan_nft: Bucket = self.admin_badge.authorize(||
                 borrow_resource_manager!(self.loan_nft_address)
                     .mint_non_fungible(
@@ -349,17 +499,22 @@ blueprint! {
                         Loan {
                             lenders,
                             borrower_id,
+                            sponsor,
+                            endorsers,
                             in_arrears: false,
                             arrears_votes: HashSet::new(),
                             loan_amount,
                             loan_token,
                             loan_purpose_summary,
                             loan_purpose_url,
-                            loan_start_epoch: Runtime::current_epoch(),
+                            loan_start_epoch,
                             installment_total_count: installments,
                             installments_remaining: installments,
                             epochs_per_installment,
                             amount_per_installment,
+                            schedule_start_epoch: loan_start_epoch,
+                            schedule_installments_paid: 0,
+                            restructuring_proposals: Vec::new(),
                         }
                     )
             );
@@ -410,10 +565,12 @@ blueprint! {
                        "All installments are already paid");
             assert_eq!(loan_data.loan_token, payment.resource_address(),
                        "Wrong token type");
-            if self.check_enter_arrears(&loan_data) {
+            let late = self.check_enter_arrears(&loan_data);
+            if late {
                 if !loan_data.in_arrears {
                     // Old votes are no longer valid: this is a new arrears situation
                     loan_data.arrears_votes.clear();
+                    self.report_repayment(&loan_data, RepaymentEvent::InArrears);
                 }
                 loan_data.in_arrears = true;
                 info!("This loan is in arrears")
@@ -460,6 +617,11 @@ blueprint! {
             }
 
             loan_data.installments_remaining -= 1;
+            self.report_repayment(&loan_data,
+                                  if late { RepaymentEvent::Late } else { RepaymentEvent::OnTime });
+            if loan_data.installments_remaining == 0 {
+                self.report_repayment(&loan_data, RepaymentEvent::Completed);
+            }
             self.save_loan_data(&loan_nfid, loan_data);
 
             assert!(installment.is_empty());
@@ -643,10 +805,273 @@ blueprint! {
                 // Old votes are no longer valid: this is a new arrears situation
                 loan_data.arrears_votes.clear();
                 loan_data.in_arrears = true;
+                self.report_repayment(&loan_data, RepaymentEvent::InArrears);
                 self.save_loan_data(&loan_nfid, loan_data);
             }
         }
 
+        /// Proposes extending the term of a loan.
+        ///
+        /// If passed, additional_installments are added to the loan
+        /// and the amount still owed is spread evenly over all the
+        /// remaining installments, making each of them smaller.
+        ///
+        /// Returns the id of the new proposal. If the proposer is a
+        /// lender his approval is counted immediately.
+        ///
+        /// ---
+        ///
+        /// **Access control:** The participant proof must be one of
+        /// the lenders in the named loan, or its borrower.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanacceptor/propose_extend_term.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanacceptor/propose_extend_term.rtm")]
+        /// ```
+        pub fn propose_extend_term(&mut self, participant: Proof, loan_nfid: NonFungibleId,
+                                   additional_installments: u64) -> u64 {
+            assert!(additional_installments > 0,
+                    "Must add at least one installment");
+            self.int_propose(participant, loan_nfid,
+                             Restructuring::ExtendTerm { additional_installments })
+        }
+
+        /// Proposes reducing the rate of a loan.
+        ///
+        /// If passed, each remaining installment will be for the new
+        /// amount_per_installment, which must be lower than the
+        /// current one.
+        ///
+        /// Returns the id of the new proposal. If the proposer is a
+        /// lender his approval is counted immediately.
+        ///
+        /// ---
+        ///
+        /// **Access control:** The participant proof must be one of
+        /// the lenders in the named loan, or its borrower.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanacceptor/propose_reduce_rate.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanacceptor/propose_reduce_rate.rtm")]
+        /// ```
+        pub fn propose_reduce_rate(&mut self, participant: Proof, loan_nfid: NonFungibleId,
+                                   amount_per_installment: Decimal) -> u64 {
+            let (_, _, loan_data) =
+                self.retrieve_loan_from_id(loan_nfid.clone());
+            assert!(amount_per_installment > Decimal::zero(),
+                    "Installments must be positive");
+            assert!(amount_per_installment < loan_data.amount_per_installment,
+                    "This is not a reduction");
+            self.int_propose(participant, loan_nfid,
+                             Restructuring::ReduceRate { amount_per_installment })
+        }
+
+        /// Proposes converting the outstanding debt of a loan into a
+        /// new installment plan.
+        ///
+        /// If passed, the remaining installments are replaced by
+        /// the given number of installments of amount_per_installment
+        /// each, with the first one due epochs_per_installment epochs
+        /// after the plan was adopted. This is the tool for getting a
+        /// borrower who has fallen behind back on a schedule he can
+        /// keep.
+        ///
+        /// Returns the id of the new proposal. If the proposer is a
+        /// lender his approval is counted immediately.
+        ///
+        /// ---
+        ///
+        /// **Access control:** The participant proof must be one of
+        /// the lenders in the named loan, or its borrower.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanacceptor/propose_installment_plan.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanacceptor/propose_installment_plan.rtm")]
+        /// ```
+        pub fn propose_installment_plan(&mut self, participant: Proof, loan_nfid: NonFungibleId,
+                                        installments: u64,
+                                        epochs_per_installment: u64,
+                                        amount_per_installment: Decimal) -> u64 {
+            assert!(installments > 0,
+                    "Must have at least one installment");
+            assert!(epochs_per_installment > 0,
+                    "Installments cannot all be due at once");
+            assert!(amount_per_installment > Decimal::zero(),
+                    "Installments must be positive");
+            self.int_propose(participant, loan_nfid,
+                             Restructuring::InstallmentPlan {
+                                 installments,
+                                 epochs_per_installment,
+                                 amount_per_installment })
+        }
+
+        /// Lenders use this method to vote for a restructuring
+        /// proposal.
+        ///
+        /// If this brings the approving lenders' share of the
+        /// principal above one half the proposal passes and is
+        /// applied to the loan straight away.
+        ///
+        /// A lender who previously rejected the proposal can change
+        /// his mind with this method while the proposal is open.
+        ///
+        /// ---
+        ///
+        /// **Access control:** The lender proof must be one of the
+        /// lenders in the named loan.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanacceptor/approve_restructuring.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanacceptor/approve_restructuring.rtm")]
+        /// ```
+        pub fn approve_restructuring(&mut self, lender: Proof, loan_nfid: NonFungibleId,
+                                     proposal_id: u64) {
+            self.int_vote(lender, loan_nfid, proposal_id, true);
+        }
+
+        /// Lenders use this method to vote against a restructuring
+        /// proposal.
+        ///
+        /// If this brings the rejecting lenders' share of the
+        /// principal to one half or more the proposal is rejected.
+        ///
+        /// Should the proposal pass regardless, having voted against
+        /// it allows the lender to sell his share of the loan, see
+        /// [LoanAcceptor::offer_loan_share].
+        ///
+        /// ---
+        ///
+        /// **Access control:** The lender proof must be one of the
+        /// lenders in the named loan.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanacceptor/reject_restructuring.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanacceptor/reject_restructuring.rtm")]
+        /// ```
+        pub fn reject_restructuring(&mut self, lender: Proof, loan_nfid: NonFungibleId,
+                                    proposal_id: u64) {
+            self.int_vote(lender, loan_nfid, proposal_id, false);
+        }
+
+        /// Offers a lender's share of a loan for sale.
+        ///
+        /// Only a lender who voted against a restructuring proposal
+        /// which passed regardless can sell his share. The price is
+        /// in the loan's token. Calling this again replaces the
+        /// price of an existing offer.
+        ///
+        /// ---
+        ///
+        /// **Access control:** The lender proof must be one of the
+        /// lenders in the named loan.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanacceptor/offer_loan_share.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanacceptor/offer_loan_share.rtm")]
+        /// ```
+        pub fn offer_loan_share(&mut self, lender: Proof, loan_nfid: NonFungibleId,
+                                price: Decimal) {
+            let (loan_nfid, _, loan_data) =
+                self.retrieve_loan_from_id(loan_nfid);
+            let (lender_nfid, _, _) =
+                self.check_and_retrieve_participant(lender);
+            assert!(loan_data.lenders.contains_key(&lender_nfid),
+                    "You are not a lender to this loan");
+            assert!(LoanAcceptor::int_was_outvoted(&loan_data, &lender_nfid),
+                    "Only lenders outvoted on a restructuring can sell their share");
+            assert!(price >= Decimal::zero(),
+                    "Price cannot be negative");
+
+            self.share_offers.entry(loan_nfid).or_insert(HashMap::new())
+                .insert(lender_nfid, price);
+        }
+
+        /// Withdraws a lender's offer to sell his share of a loan.
+        ///
+        /// ---
+        ///
+        /// **Access control:** The lender proof must be the lender
+        /// who made the offer.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanacceptor/cancel_loan_share_offer.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanacceptor/cancel_loan_share_offer.rtm")]
+        /// ```
+        pub fn cancel_loan_share_offer(&mut self, lender: Proof, loan_nfid: NonFungibleId) {
+            let (lender_nfid, _, _) =
+                self.check_and_retrieve_participant(lender);
+            let removed = self.share_offers.get_mut(&loan_nfid)
+                .and_then(|offers| offers.remove(&lender_nfid));
+            assert!(removed.is_some(), "You have no offer on this loan");
+        }
+
+        /// Buys a lender's share of a loan.
+        ///
+        /// The buyer pays the asking price, which goes to the
+        /// seller's lender rewards, and takes over the seller's
+        /// pledge in the loan. If the buyer already is a lender to
+        /// the loan the two pledges are merged. Any votes the seller
+        /// has cast on open proposals or on clearing arrears are
+        /// discarded.
+        ///
+        /// We return any change that remains after making the
+        /// payment.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Any Participant other than the
+        /// borrower can buy a share.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanacceptor/buy_loan_share.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanacceptor/buy_loan_share.rtm")]
+        /// ```
+        pub fn buy_loan_share(&mut self, buyer: Proof, loan_nfid: NonFungibleId,
+                              seller_nfid: NonFungibleId, mut payment: Bucket) -> Bucket {
+            let (loan_nfid, _, mut loan_data) =
+                self.retrieve_loan_from_id(loan_nfid);
+            let (buyer_nfid, _, _) =
+                self.check_and_retrieve_participant(buyer);
+            assert_ne!(loan_data.borrower_id, buyer_nfid,
+                       "The borrower cannot buy into his own loan");
+            assert_ne!(seller_nfid, buyer_nfid,
+                       "You cannot buy your own share");
+            assert_eq!(loan_data.loan_token, payment.resource_address(),
+                       "Wrong token type");
+            let price = self.share_offers.get_mut(&loan_nfid)
+                .and_then(|offers| offers.remove(&seller_nfid))
+                .expect("This share is not for sale");
+
+            // Pay the seller
+            let seller_rewards = self.lender_rewards.entry(seller_nfid.clone())
+                .or_insert(HashMap::new());
+            seller_rewards.entry(loan_data.loan_token)
+                .or_insert(Vault::new(loan_data.loan_token))
+                .put(payment.take(price));
+
+            // Hand the pledge over to the buyer
+            let pledge = loan_data.lenders.remove(&seller_nfid).unwrap();
+            *loan_data.lenders.entry(buyer_nfid).or_insert(Decimal::zero()) += pledge;
+            loan_data.arrears_votes.remove(&seller_nfid);
+            for proposal in loan_data.restructuring_proposals.iter_mut() {
+                if proposal.state == ProposalState::Open {
+                    proposal.approvals.remove(&seller_nfid);
+                    proposal.rejections.remove(&seller_nfid);
+                }
+            }
+            self.save_loan_data(&loan_nfid, loan_data);
+
+            payment // return the change
+        }
+
         /// Checks if the loan is currently in arrears.
         ///
         /// Returns true if the loan is currently in formal or
@@ -748,6 +1173,57 @@ blueprint! {
             loan_data.arrears_votes
         }
         
+        /// Reads a restructuring proposal.
+        ///
+        /// Returns a tuple with the following data (in order):
+        ///
+        /// 0. The proposed restructuring
+        /// 1. The state of the proposal
+        /// 2. The share of the principal that approves it
+        /// 3. The share of the principal that rejects it
+        ///
+        /// The shares are fractions between zero and one.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, anyone can call this
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanacceptor/read_restructuring_proposal.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanacceptor/read_restructuring_proposal.rtm")]
+        /// ```
+        pub fn read_restructuring_proposal(&self, loan_nfid: NonFungibleId, proposal_id: u64)
+                                           -> (Restructuring, ProposalState, Decimal, Decimal) {
+            let (_, _, loan_data) =
+                self.retrieve_loan_from_id(loan_nfid);
+            let proposal = loan_data.restructuring_proposals.get(proposal_id as usize)
+                .expect("No such proposal");
+            (proposal.restructuring.clone(),
+             proposal.state,
+             LoanAcceptor::pledge_weight(&loan_data, &proposal.approvals) / loan_data.loan_amount,
+             LoanAcceptor::pledge_weight(&loan_data, &proposal.rejections) / loan_data.loan_amount)
+        }
+
+        /// Reads the loan shares currently offered for sale.
+        ///
+        /// Returns a map where the selling lenders' Participant ids
+        /// are keys and the value is their asking price.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, anyone can call this
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanacceptor/read_loan_share_offers.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanacceptor/read_loan_share_offers.rtm")]
+        /// ```
+        pub fn read_loan_share_offers(&self, loan_nfid: NonFungibleId)
+                                      -> HashMap<NonFungibleId, Decimal> {
+            self.share_offers.get(&loan_nfid).cloned().unwrap_or(HashMap::new())
+        }
+
         /// Retrieves the Participant address of a loan's borrower.
         ///
         /// ---
@@ -856,10 +1332,49 @@ blueprint! {
             self.loan_nft_address
         }
 
+        /// Retrieves the resource address of our admin badge.
+        ///
+        /// The catalog creator passes this to
+        /// [Participants::authorize_reporter][par] to have us report
+        /// repayment events to the catalog.
+        ///
+        /// [par]: crate::participants::blueprint::Participants::authorize_reporter
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, anyone can call this
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanacceptor/read_admin_badge_addr.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanacceptor/read_admin_badge_addr.rtm")]
+        /// ```
+        pub fn read_admin_badge_addr(&self) -> ResourceAddress {
+            self.admin_badge.resource_address()
+        }
+
         //
         // Internal utility methods follow
         //
 
+        /// Reports a repayment event to our Participants catalog, if
+        /// it tracks reputation and has authorized us as a reporter.
+        fn report_repayment(&self, loan_data: &Loan, event: RepaymentEvent) {
+            if let Some(catalog) = catalog_of(self.participants_nft_addr) {
+                let catalog = borrow_component!(catalog);
+                if catalog.call::<bool>("is_reporter",
+                                        args!(self.admin_badge.resource_address())) {
+                    catalog.call::<()>("record_repayment",
+                                       args!(self.admin_badge.create_proof(),
+                                             loan_data.borrower_id.clone(),
+                                             self.facilitator.clone(),
+                                             loan_data.sponsor.clone(),
+                                             loan_data.endorsers.clone(),
+                                             event));
+                }
+            }
+        }
+
         /// Checks if this loan either is or should currently be in arrears.
         fn int_should_be_in_arrears(&self, loan_data: &Loan) -> bool {
             loan_data.in_arrears ||
@@ -871,12 +1386,138 @@ blueprint! {
         /// the loan ought to go into arrears. Does not take into account
         /// that the loan may already be paid in full.
         fn check_enter_arrears(&self, loan_data: &Loan) -> bool {
-            loan_data.loan_start_epoch
-                + ((loan_data.installment_total_count - loan_data.installments_remaining) + 1)
+            loan_data.schedule_start_epoch
+                + ((loan_data.installment_total_count - loan_data.installments_remaining
+                    - loan_data.schedule_installments_paid) + 1)
                 * loan_data.epochs_per_installment
                 < Runtime::current_epoch()
         }
 
+        /// Adds a new restructuring proposal to the loan, with the
+        /// proposer's approval if he is a lender. Returns the id of
+        /// the new proposal.
+        fn int_propose(&mut self, participant: Proof, loan_nfid: NonFungibleId,
+                       restructuring: Restructuring) -> u64
+        {
+            let (loan_nfid, _, mut loan_data) =
+                self.retrieve_loan_from_id(loan_nfid);
+            let (participant_nfid, _, _) =
+                self.check_and_retrieve_participant(participant);
+            let is_lender = loan_data.lenders.contains_key(&participant_nfid);
+            assert!(is_lender || loan_data.borrower_id == participant_nfid,
+                    "Only lenders and the borrower can propose a restructuring");
+            assert_ne!(0, loan_data.installments_remaining,
+                       "All installments are already paid");
+
+            let mut approvals = HashSet::new();
+            if is_lender {
+                approvals.insert(participant_nfid.clone());
+            }
+            loan_data.restructuring_proposals.push(RestructuringProposal {
+                proposer: participant_nfid,
+                proposal_epoch: Runtime::current_epoch(),
+                restructuring,
+                state: ProposalState::Open,
+                approvals,
+                rejections: HashSet::new(),
+            });
+            let proposal_id = (loan_data.restructuring_proposals.len() - 1) as u64;
+            LoanAcceptor::tally_proposal(&mut loan_data, proposal_id);
+            self.save_loan_data(&loan_nfid, loan_data);
+            proposal_id
+        }
+
+        /// Records a lender's vote on a restructuring proposal.
+        fn int_vote(&mut self, lender: Proof, loan_nfid: NonFungibleId,
+                    proposal_id: u64, approve: bool)
+        {
+            let (loan_nfid, _, mut loan_data) =
+                self.retrieve_loan_from_id(loan_nfid);
+            let (lender_nfid, _, _) =
+                self.check_and_retrieve_participant(lender);
+            assert!(loan_data.lenders.contains_key(&lender_nfid),
+                    "You are not a lender to this loan");
+            let proposal = loan_data.restructuring_proposals.get_mut(proposal_id as usize)
+                .expect("No such proposal");
+            assert_eq!(ProposalState::Open, proposal.state,
+                       "This proposal is no longer open");
+
+            if approve {
+                proposal.rejections.remove(&lender_nfid);
+                proposal.approvals.insert(lender_nfid);
+            } else {
+                proposal.approvals.remove(&lender_nfid);
+                proposal.rejections.insert(lender_nfid);
+            }
+            LoanAcceptor::tally_proposal(&mut loan_data, proposal_id);
+            self.save_loan_data(&loan_nfid, loan_data);
+        }
+
+        /// Sums up the pledges of the lenders in a vote set.
+        fn pledge_weight(loan_data: &Loan, voters: &HashSet<NonFungibleId>) -> Decimal {
+            voters.iter()
+                .filter_map(|voter| loan_data.lenders.get(voter))
+                .fold(Decimal::zero(), |total, pledge| total + *pledge)
+        }
+
+        /// Decides an open proposal if enough votes are in, applying
+        /// it to the loan if it passed.
+        fn tally_proposal(loan_data: &mut Loan, proposal_id: u64) {
+            let proposal = loan_data.restructuring_proposals[proposal_id as usize].clone();
+            if proposal.state != ProposalState::Open { return; }
+
+            let approved = LoanAcceptor::pledge_weight(loan_data, &proposal.approvals);
+            let rejected = LoanAcceptor::pledge_weight(loan_data, &proposal.rejections);
+            if approved * dec!("2") > loan_data.loan_amount {
+                LoanAcceptor::apply_restructuring(loan_data, &proposal.restructuring);
+                loan_data.restructuring_proposals[proposal_id as usize].state =
+                    ProposalState::Passed;
+            } else if rejected * dec!("2") >= loan_data.loan_amount {
+                loan_data.restructuring_proposals[proposal_id as usize].state =
+                    ProposalState::Rejected;
+            }
+        }
+
+        /// Changes the loan terms according to a restructuring that
+        /// has been voted through.
+        fn apply_restructuring(loan_data: &mut Loan, restructuring: &Restructuring) {
+            assert_ne!(0, loan_data.installments_remaining,
+                       "All installments are already paid");
+            match restructuring {
+                Restructuring::ExtendTerm { additional_installments } => {
+                    let outstanding = loan_data.amount_per_installment
+                        * loan_data.installments_remaining;
+                    loan_data.installment_total_count += additional_installments;
+                    loan_data.installments_remaining += additional_installments;
+                    loan_data.amount_per_installment =
+                        outstanding / loan_data.installments_remaining;
+                },
+                Restructuring::ReduceRate { amount_per_installment } => {
+                    loan_data.amount_per_installment = *amount_per_installment;
+                },
+                Restructuring::InstallmentPlan { installments,
+                                                 epochs_per_installment,
+                                                 amount_per_installment } => {
+                    let installments_paid = loan_data.installment_total_count
+                        - loan_data.installments_remaining;
+                    loan_data.schedule_start_epoch = Runtime::current_epoch();
+                    loan_data.schedule_installments_paid = installments_paid;
+                    loan_data.installment_total_count = installments_paid + installments;
+                    loan_data.installments_remaining = *installments;
+                    loan_data.epochs_per_installment = *epochs_per_installment;
+                    loan_data.amount_per_installment = *amount_per_installment;
+                },
+            }
+        }
+
+        /// Checks if the lender rejected a restructuring proposal
+        /// that passed regardless.
+        fn int_was_outvoted(loan_data: &Loan, lender_nfid: &NonFungibleId) -> bool {
+            loan_data.restructuring_proposals.iter()
+                .any(|p| p.state == ProposalState::Passed
+                     && p.rejections.contains(lender_nfid))
+        }
+
         /// Produces a resource manager and participant from a
         /// Participant id; also returns the id itself.
         fn retrieve_participant_from_id(&self, nfid: NonFungibleId) 
@@ -163,6 +163,13 @@
 //! promises to pay in each installment. This is in the same token as
 //! the loan itself.
 //!
+//! minimum_reputation and minimum_endorsement_weight: Optional limits
+//! on the reputation and endorsement weight the borrower must have
+//! (see [crate::participants] for how these are earned). They are
+//! checked when the request is made and again when it is turned into
+//! a loan, so lenders can rely on them. Setting either requires a
+//! Participants catalog which tracks reputation.
+//!
 //! ---
 //!
 //! **Future configuration possibilities:** In future we might offer
@@ -225,7 +232,7 @@
 
 use scrypto::prelude::*;
 
-use crate::participants::Participant;
+use crate::participants::{catalog_of, Participant};
 
 /// This is the NFT data for a loan request. It is used for
 /// negotiating the loan with prospective lenders.
@@ -285,6 +292,12 @@ struct LoanRequest {
 
     /// The Participant id of the borrower who created this request.
     borrower_id: NonFungibleId,
+
+    /// The reputation the borrower must have, if any.
+    minimum_reputation: Option<Decimal>,
+
+    /// The endorsement weight the borrower must have, if any.
+    minimum_endorsement_weight: Option<Decimal>,
 }
 
 blueprint! {
@@ -464,6 +477,9 @@ blueprint! {
         /// loan. These parameters map to the same in the
         /// [LoanRequest] struct.
         ///
+        /// The method panics if the borrower does not meet the
+        /// reputation requirements given.
+        ///
         /// The method returns a bucket with the LoanRequest NFT
         /// created, and the id of that NFT.
         ///
@@ -488,7 +504,10 @@ blueprint! {
                             promise_installments: u64,
                             promise_amount_per_installment: Decimal,
                             loan_purpose_summary: String,
-                            loan_purpose_url: String) -> (Bucket, NonFungibleId) {
+                            loan_purpose_url: String,
+                            minimum_reputation: Option<Decimal>,
+                            minimum_endorsement_weight: Option<Decimal>)
+                            -> (Bucket, NonFungibleId) {
             assert!(self.loan_acceptor.is_some(),
                     "Loan acceptor is not set");
             assert!(request_amount > Decimal::zero(),
@@ -496,6 +515,8 @@ blueprint! {
 
             let (borrower_id, _, _) =
                 self.check_and_retrieve_participant(borrower);
+            self.check_reputation(&borrower_id, minimum_reputation,
+                                  minimum_endorsement_weight);
 
             let nfid: NonFungibleId = NonFungibleId::random();
             let nft: Bucket = self.admin_badge.authorize(||
@@ -517,6 +538,8 @@ blueprint! {
                             loan_purpose_summary,
                             loan_purpose_url,
                             borrower_id,
+                            minimum_reputation,
+                            minimum_endorsement_weight,
                         }
                     )
             );
@@ -695,6 +718,8 @@ blueprint! {
                        "This is not your loan");
             assert!(!request_data.cancelled,
                     "This loan request has been cancelled by the borrower");
+            self.check_reputation(&borrower_id, request_data.minimum_reputation,
+                                  request_data.minimum_endorsement_weight);
 
             let pledge_map = self.principals.get_mut(&reqid).unwrap();
             assert!(request_data.request_amount <= LoanRequestor::calc_total_pledge(&pledge_map),
@@ -888,6 +913,29 @@ blueprint! {
              data.promise_amount_per_installment)
         }
 
+        /// Reads the reputation requirements of a loan request.
+        ///
+        /// Returns, in order:
+        ///
+        /// 0. The minimum reputation of the borrower, if any
+        /// 1. The minimum endorsement weight of the borrower, if any
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, anyone can call this
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/loanrequestor/read_reputation_requirements.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/loanrequestor/read_reputation_requirements.rtm")]
+        /// ```
+        pub fn read_reputation_requirements(&self, request_id: NonFungibleId)
+                                            -> (Option<Decimal>, Option<Decimal>) {
+            let (_, _, data) =
+                self.retrieve_request_from_id(request_id);
+            (data.minimum_reputation, data.minimum_endorsement_weight)
+        }
+
         /// Retrieve LoanRequest NFT resource address for this
         /// LoanRequestor instance.
         ///
@@ -950,6 +998,30 @@ blueprint! {
             total
         }
 
+        /// Asserts that the borrower meets the reputation
+        /// requirements given, as reported by our Participants
+        /// catalog.
+        fn check_reputation(&self, borrower_id: &NonFungibleId,
+                            minimum_reputation: Option<Decimal>,
+                            minimum_endorsement_weight: Option<Decimal>) {
+            if minimum_reputation.is_none() && minimum_endorsement_weight.is_none() {
+                return;
+            }
+            let catalog = catalog_of(self.participants_nft_address)
+                .expect("Our Participants catalog does not track reputation");
+            let (reputation, endorsement_weight) =
+                borrow_component!(catalog).call::<(Decimal, Decimal)>(
+                    "read_reputation", args!(borrower_id.clone()));
+            if let Some(minimum) = minimum_reputation {
+                assert!(reputation >= minimum,
+                        "Borrower reputation is too low");
+            }
+            if let Some(minimum) = minimum_endorsement_weight {
+                assert!(endorsement_weight >= minimum,
+                        "Borrower endorsement weight is too low");
+            }
+        }
+
         /// Produces a resource manager and LoanRequest NFT data from
         /// a LoanRequest id; also returns the id itself.
         fn retrieve_request_from_id(&self, non_fungible_id: NonFungibleId) 
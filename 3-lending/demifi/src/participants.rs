@@ -70,7 +70,101 @@
 //! backing it at the moment and so it should be considered purely
 //! advisory information.
 //!
+//! # Reputation
+//!
+//! Endorsements and sponsorships are only worth as much as the
+//! judgement behind them, and so the catalog keeps score of how the
+//! loans of its Participants actually turn out. Every Participant NFT
+//! holds a repayment record for each of the roles the Participant can
+//! have in a loan: as the borrower, as the facilitator, as the
+//! sponsor of the borrower and as one of the borrower's endorsers.
+//!
+//! The records are updated by the LoanAcceptor instances (see
+//! [crate::loanacceptor]) that the catalog creator has authorized as
+//! reporters with [Participants::authorize_reporter]. Each installment
+//! paid on time or late, each loan entering formal arrears and each
+//! loan paid in full is reported and counted against everyone
+//! involved with that loan.
+//!
+//! A Participant's reputation is the score of his borrower record
+//! plus half the score of his other records, where an installment
+//! paid on time is worth 1 point, a fully repaid loan 5 points, a
+//! late installment -2 points and a loan going into arrears -10
+//! points. So if you endorse someone who then defaults on his loan,
+//! your own reputation suffers for it.
+//!
+//! A Participant's endorsement weight is the sum of the positive
+//! reputations of those who endorse or sponsor him. Endorsements from
+//! Participants without a track record therefore carry no weight.
+//!
+//! Loan requests can be made conditional on a minimum reputation or
+//! endorsement weight, see [crate::loanrequestor].
+//!
 use scrypto::prelude::*;
+use std::str::FromStr;
+
+/// Reputation points for an installment paid on time.
+const ON_TIME_POINTS: i64 = 1;
+/// Reputation points for a loan repaid in full.
+const COMPLETED_POINTS: i64 = 5;
+/// Reputation points for a late installment.
+const LATE_POINTS: i64 = -2;
+/// Reputation points for a loan entering formal arrears.
+const ARREARS_POINTS: i64 = -10;
+
+/// The metadata key on the Participant NFT resource which holds the
+/// address of the catalog component.
+const CATALOG_METADATA_KEY: &str = "catalog_component";
+
+/// Something that happened in the repayment of a loan.
+#[derive(Debug, Clone, Copy, PartialEq, TypeId, Encode, Decode, Describe)]
+pub enum RepaymentEvent {
+    /// An installment was paid on time.
+    OnTime,
+    /// An installment was paid late.
+    Late,
+    /// The loan entered formal arrears.
+    InArrears,
+    /// The final installment was paid.
+    Completed,
+}
+
+/// Tally of the repayment events of the loans a Participant has been
+/// involved with in one particular role.
+#[derive(Debug, Clone, Default, TypeId, Encode, Decode, Describe)]
+pub struct RepaymentRecord {
+    pub on_time: u64,
+    pub late: u64,
+    pub in_arrears: u64,
+    pub completed: u64,
+}
+
+impl RepaymentRecord {
+    fn record(&mut self, event: RepaymentEvent) {
+        match event {
+            RepaymentEvent::OnTime => self.on_time += 1,
+            RepaymentEvent::Late => self.late += 1,
+            RepaymentEvent::InArrears => self.in_arrears += 1,
+            RepaymentEvent::Completed => self.completed += 1,
+        }
+    }
+
+    fn score(&self) -> Decimal {
+        Decimal::from(ON_TIME_POINTS) * self.on_time
+            + Decimal::from(COMPLETED_POINTS) * self.completed
+            + Decimal::from(LATE_POINTS) * self.late
+            + Decimal::from(ARREARS_POINTS) * self.in_arrears
+    }
+}
+
+/// Finds the Participants component behind a Participant NFT
+/// resource. Returns None for catalogs that don't publish their
+/// address, and which therefore don't track reputation.
+pub fn catalog_of(participants_nft_addr: ResourceAddress) -> Option<ComponentAddress> {
+    borrow_resource_manager!(participants_nft_addr).metadata()
+        .get(CATALOG_METADATA_KEY)
+        .and_then(|address| ComponentAddress::from_str(address).ok())
+}
 
 /// This is the Participant NFT data.
 ///
@@ -115,6 +209,33 @@ pub struct Participant {
     /// any time.
     #[scrypto(mutable)]
     endorsing: HashSet<NonFungibleId>,
+
+    /// Repayment record of the loans we borrowed.
+    #[scrypto(mutable)]
+    as_borrower: RepaymentRecord,
+
+    /// Repayment record of the loans we facilitated.
+    #[scrypto(mutable)]
+    as_facilitator: RepaymentRecord,
+
+    /// Repayment record of the loans of Participants we sponsor.
+    #[scrypto(mutable)]
+    as_sponsor: RepaymentRecord,
+
+    /// Repayment record of the loans of Participants we endorse.
+    #[scrypto(mutable)]
+    as_endorser: RepaymentRecord,
+}
+
+impl Participant {
+    /// Our reputation, see the module documentation for how it is
+    /// calculated.
+    pub fn reputation(&self) -> Decimal {
+        self.as_borrower.score()
+            + (self.as_facilitator.score()
+               + self.as_sponsor.score()
+               + self.as_endorser.score()) / dec!("2")
+    }
 }
 
 blueprint! {
@@ -134,6 +255,15 @@ blueprint! {
         /// control over the catalog sponsorship tree etc. It is also
         /// the proof you can use that you created / own this catalog.
         catalog_creator: NonFungibleId,
+
+        /// For each Participant, the Participants currently endorsing
+        /// them. This is the reverse of the `endorsing` set kept on
+        /// each Participant NFT.
+        endorsers: HashMap<NonFungibleId, HashSet<NonFungibleId>>,
+
+        /// Badges that are allowed to report repayments and defaults
+        /// to this catalog.
+        reporters: HashSet<ResourceAddress>,
     }
 
     impl Participants {
@@ -178,6 +308,7 @@ blueprint! {
                     "Loan Participant NFT".to_string()))
                 .mintable(rule!(require(badge.resource_address())), LOCKED)
                 .updateable_non_fungible_data(rule!(require(badge.resource_address())), LOCKED)
+                .updateable_metadata(rule!(require(badge.resource_address())), LOCKED)
                 .no_initial_supply();
             let badge = Vault::with_bucket(badge);
 
@@ -197,10 +328,17 @@ blueprint! {
                     nft_serial: 0,
                     admin_badge: badge,
                     catalog_creator: nfid.clone(),
+                    endorsers: HashMap::new(),
+                    reporters: HashSet::new(),
                 }
             .instantiate()
                 .globalize();
 
+            // Publish our address on the NFT resource so that the
+            // loan blueprints can find us for reputation purposes
+            borrow_component!(participants).call::<()>(
+                "publish_catalog_address", args!());
+
             // All methods that require access control in this blueprint
             // handle this themselves through the Proof instances provided
             // to them.
@@ -405,12 +543,16 @@ blueprint! {
         /// ```text
         #[doc = include_str!("../rtm/participants/endorse.rtm")]
         /// ```
-        pub fn endorse(&self,
+        pub fn endorse(&mut self,
                        participant: Proof,
                        target: NonFungibleId)
         {
             let (participant_nfid, _, mut participant_data) =
                 self.check_and_retrieve_participant(participant);
+            assert!(target != participant_nfid,
+                    "You cannot endorse yourself");
+            self.endorsers.entry(target.clone()).or_insert(HashSet::new())
+                .insert(participant_nfid.clone());
             participant_data.endorsing.insert(target);
             self.save_participant_data(&participant_nfid, participant_data);
         }
@@ -427,12 +569,15 @@ blueprint! {
         /// ```text
         #[doc = include_str!("../rtm/participants/unendorse.rtm")]
         /// ```
-        pub fn unendorse(&self,
+        pub fn unendorse(&mut self,
                          participant: Proof,
                          target: NonFungibleId)
         {
             let (participant_nfid, _, mut participant_data) =
                 self.check_and_retrieve_participant(participant);
+            if let Some(endorsers) = self.endorsers.get_mut(&target) {
+                endorsers.remove(&participant_nfid);
+            }
             participant_data.endorsing.remove(&target);
             self.save_participant_data(&participant_nfid, participant_data);
         }
@@ -517,6 +662,231 @@ blueprint! {
             participant_data.endorsing
         }
 
+        /// Retrieves the Participants currently backing a borrower.
+        ///
+        /// Returns, in order:
+        ///
+        /// 0. The borrower's sponsor if any
+        /// 1. Everyone currently endorsing the borrower
+        ///
+        /// LoanAcceptor components call this when a loan is accepted
+        /// to snapshot whose reputation the loan will affect.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, allows anyone
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/participants/read_backers.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/participants/read_backers.rtm")]
+        /// ```
+        pub fn read_backers(&self,
+                            participant: NonFungibleId)
+                            -> (Option<NonFungibleId>, HashSet<NonFungibleId>)
+        {
+            let (participant, _, participant_data) =
+                self.retrieve_participant_from_id(participant);
+            (participant_data.sponsor,
+             self.endorsers.get(&participant).cloned().unwrap_or(HashSet::new()))
+        }
+
+        /// Writes our component address into the metadata of the
+        /// Participant NFT resource, where the loan blueprints look
+        /// for it.
+        ///
+        /// This is done automatically when the catalog is created.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Anyone can call this since it only
+        /// ever publishes our own address.
+        ///
+        /// **Transaction manifest:** This method is called during
+        /// instantiation and so no transaction manifest is provided.
+        pub fn publish_catalog_address(&self) {
+            let nft_manager = borrow_resource_manager!(self.nft_address);
+            let mut metadata = nft_manager.metadata();
+            metadata.insert(CATALOG_METADATA_KEY.to_string(),
+                            Runtime::actor().component_address().unwrap().to_string());
+            self.admin_badge.authorize(|| nft_manager.update_metadata(metadata));
+        }
+
+        /// The catalog creator calls this to allow a LoanAcceptor to
+        /// report repayment events to us.
+        ///
+        /// The reporter badge is the LoanAcceptor's admin badge, see
+        /// [LoanAcceptor::read_admin_badge_addr][rab].
+        ///
+        /// [rab]: crate::loanacceptor::blueprint::LoanAcceptor::read_admin_badge_addr
+        ///
+        /// ---
+        ///
+        /// **Access control:** Only the catalog creator can call
+        /// this.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/participants/authorize_reporter.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/participants/authorize_reporter.rtm")]
+        /// ```
+        pub fn authorize_reporter(&mut self,
+                                  creator: Proof,
+                                  reporter_badge: ResourceAddress)
+        {
+            self.check_catalog_creator(creator);
+            self.reporters.insert(reporter_badge);
+        }
+
+        /// The catalog creator calls this to stop a LoanAcceptor from
+        /// reporting repayment events to us.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Only the catalog creator can call
+        /// this.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/participants/revoke_reporter.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/participants/revoke_reporter.rtm")]
+        /// ```
+        pub fn revoke_reporter(&mut self,
+                               creator: Proof,
+                               reporter_badge: ResourceAddress)
+        {
+            self.check_catalog_creator(creator);
+            self.reporters.remove(&reporter_badge);
+        }
+
+        /// Checks whether a badge is allowed to report repayment
+        /// events.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, allows anyone.
+        ///
+        /// **Transaction manifest:** This method is mostly called by
+        /// LoanAcceptor components and so no transaction manifest is
+        /// provided.
+        pub fn is_reporter(&self, reporter_badge: ResourceAddress) -> bool {
+            self.reporters.contains(&reporter_badge)
+        }
+
+        /// Records a repayment event against everyone involved with a
+        /// loan: the borrower, the facilitator if any, and the sponsor
+        /// and endorsers the borrower had when the loan was accepted
+        /// (see [Participants::read_backers]). Backers who have since
+        /// walked away still share in the outcome of the loan, and
+        /// those who joined later do not.
+        ///
+        /// ---
+        ///
+        /// **Access control:** The reporter proof must be of a badge
+        /// authorized with [Participants::authorize_reporter].
+        ///
+        /// **Transaction manifest:** This method is only ever called
+        /// by LoanAcceptor components. Since users are not meant to
+        /// call it directly no transaction manifest is provided.
+        pub fn record_repayment(&mut self,
+                                reporter: Proof,
+                                borrower: NonFungibleId,
+                                facilitator: Option<NonFungibleId>,
+                                sponsor: Option<NonFungibleId>,
+                                endorsers: HashSet<NonFungibleId>,
+                                event: RepaymentEvent)
+        {
+            assert!(self.reporters.contains(&reporter.resource_address()),
+                    "Not an authorized reporter");
+
+            let (borrower, _, mut borrower_data) =
+                self.retrieve_participant_from_id(borrower);
+            borrower_data.as_borrower.record(event);
+            self.save_participant_data(&borrower, borrower_data);
+
+            if let Some(facilitator) = facilitator {
+                let (facilitator, _, mut data) =
+                    self.retrieve_participant_from_id(facilitator);
+                data.as_facilitator.record(event);
+                self.save_participant_data(&facilitator, data);
+            }
+            if let Some(sponsor) = sponsor {
+                let (sponsor, _, mut data) =
+                    self.retrieve_participant_from_id(sponsor);
+                data.as_sponsor.record(event);
+                self.save_participant_data(&sponsor, data);
+            }
+            for endorser in endorsers {
+                let (endorser, _, mut data) =
+                    self.retrieve_participant_from_id(endorser);
+                data.as_endorser.record(event);
+                self.save_participant_data(&endorser, data);
+            }
+        }
+
+        /// Retrieves a Participant's reputation and endorsement
+        /// weight.
+        ///
+        /// Returns, in order:
+        ///
+        /// 0. Reputation
+        /// 1. Endorsement weight, the sum of the positive reputations
+        /// of his sponsor and of everyone endorsing him
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, allows anyone
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/participants/read_reputation.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/participants/read_reputation.rtm")]
+        /// ```
+        pub fn read_reputation(&self,
+                               participant: NonFungibleId) -> (Decimal, Decimal)
+        {
+            let (participant, _, participant_data) =
+                self.retrieve_participant_from_id(participant);
+
+            let mut backers: HashSet<NonFungibleId> =
+                self.endorsers.get(&participant).cloned().unwrap_or(HashSet::new());
+            if let Some(sponsor) = participant_data.sponsor.clone() {
+                backers.insert(sponsor);
+            }
+            let mut weight = Decimal::zero();
+            for backer in backers {
+                let (_, _, backer_data) = self.retrieve_participant_from_id(backer);
+                let reputation = backer_data.reputation();
+                if reputation > Decimal::zero() {
+                    weight += reputation;
+                }
+            }
+            (participant_data.reputation(), weight)
+        }
+
+        /// Retrieves a Participant's repayment records.
+        ///
+        /// Returns, in order, the records as borrower, as
+        /// facilitator, as sponsor and as endorser.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, allows anyone
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/participants/read_repayment_records.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/participants/read_repayment_records.rtm")]
+        /// ```
+        pub fn read_repayment_records(&self,
+                                      participant: NonFungibleId)
+                                      -> (RepaymentRecord, RepaymentRecord,
+                                          RepaymentRecord, RepaymentRecord)
+        {
+            let (_, _, data) = self.retrieve_participant_from_id(participant);
+            (data.as_borrower, data.as_facilitator, data.as_sponsor, data.as_endorser)
+        }
+
         /// Retrieves the resource address of our Participant NFTs.
         ///
         /// ---
@@ -585,6 +955,10 @@ blueprint! {
                             sponsor: None,
                             expect_sponsor,
                             endorsing: HashSet::new(),
+                            as_borrower: RepaymentRecord::default(),
+                            as_facilitator: RepaymentRecord::default(),
+                            as_sponsor: RepaymentRecord::default(),
+                            as_endorser: RepaymentRecord::default(),
                         }
                     )
             );
@@ -622,6 +996,14 @@ blueprint! {
             self.retrieve_participant_from_id(nfid)
         }
 
+        /// Asserts that the Proof is of the catalog creator's
+        /// Participant NFT.
+        fn check_catalog_creator(&self, creator: Proof) {
+            let (creator_nfid, _, _) = self.check_and_retrieve_participant(creator);
+            assert_eq!(self.catalog_creator, creator_nfid,
+                       "Only the catalog creator can do this");
+        }
+
         /// Writes the Participant NFT data to the ledger.
         fn save_participant_data(&self, non_fungible_id: &NonFungibleId, data: Participant)
         {
@@ -220,6 +220,43 @@ fn do_i_endorse(component_addr: &str, endorser_nfid: &str, endorse_target_nfid:
     RE_BOOL.captures(&output).expect("Failed to parse do_i_endorse")[1].parse().unwrap()
 }
 
+/// The catalog creator allows a LoanAcceptor to report repayments,
+/// via rtm/participants/authorize_reporter.rtm
+fn authorize_reporter(account_addr: &str, participants: &ParticipantsComponent,
+                      reporter_badge: &str)
+{
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/participants/authorize_reporter.rtm")
+                .env("account", account_addr)
+                .env("component", &participants.address)
+                .env("nft_address", &participants.nft_address)
+                .env("creator_nfid", &participants.owner_nfid)
+                .env("reporter_badge", reporter_badge));
+}
+
+/// Reads a participant's reputation and endorsement weight, via
+/// rtm/participants/read_reputation.rtm
+fn read_reputation(component_addr: &str, participant_nfid: &str)
+                   -> (String, String)
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/participants/read_reputation.rtm")
+                             .env("component", component_addr)
+                             .env("participant_nfid", participant_nfid));
+
+    lazy_static! {
+        static ref RE_TUPLE: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ Tuple\(Decimal\("([-\d.]*)"\), Decimal\("([-\d.]*)"\)\)"#
+        )).unwrap();
+    }
+
+    let matches = RE_TUPLE.captures(&output).expect("Failed to parse read_reputation");
+    (matches[1].to_string(), matches[2].to_string())
+}
+
 /// A participant sponsors another via rtm/participants/sponsor.rtm
 fn sponsor(account_addr: &str, component_addr: &str,
            nft_address: &str, sponsor_nfid: &str, sponsorship_target_nfid: &str)
@@ -482,6 +519,37 @@ fn request_loan(requestor_addr: &str, account_addr: &str,
                 loan_purpose_summary: &str,
                 loan_purpose_url: &str)
                 -> String
+{
+    request_loan_with_requirements(requestor_addr, account_addr,
+                                   participants_nft_addr, borrower_nfid,
+                                   request_token, request_amount,
+                                   minimum_share,
+                                   pledge_lock_epochs,
+                                   loan_filled_lock_epochs,
+                                   promise_payment_intervals,
+                                   promise_installments,
+                                   promise_amount_per_installment,
+                                   loan_purpose_summary,
+                                   loan_purpose_url,
+                                   None, None)
+}
+
+/// Creates a loan request with reputation requirements, via
+/// rtm/loanrequestor/request_loan.rtm
+fn request_loan_with_requirements(requestor_addr: &str, account_addr: &str,
+                                  participants_nft_addr: &str, borrower_nfid: &str,
+                                  request_token: &str, request_amount: &str,
+                                  minimum_share: &str,
+                                  pledge_lock_epochs: u64,
+                                  loan_filled_lock_epochs: u64,
+                                  promise_payment_intervals: u64,
+                                  promise_installments: u64,
+                                  promise_amount_per_installment: &str,
+                                  loan_purpose_summary: &str,
+                                  loan_purpose_url: &str,
+                                  minimum_reputation: Option<&str>,
+                                  minimum_endorsement_weight: Option<&str>)
+                                  -> String
 {
     let output = run_command(Command::new("resim")
                              .arg("run")
@@ -499,7 +567,12 @@ fn request_loan(requestor_addr: &str, account_addr: &str,
                              .env("promise_installments", promise_installments.to_string())
                              .env("promise_amount_per_installment", promise_amount_per_installment)
                              .env("loan_purpose_summary", loan_purpose_summary)
-                             .env("loan_purpose_url", loan_purpose_url));
+                             .env("loan_purpose_url", loan_purpose_url)
+                             .env("minimum_reputation",
+                                  option_to_tm_string(minimum_reputation, "Decimal"))
+                             .env("minimum_endorsement_weight",
+                                  option_to_tm_string(minimum_endorsement_weight,
+                                                      "Decimal")));
 
     lazy_static! {
         static ref RE_NFID: Regex = Regex::new(concat!(
@@ -511,6 +584,30 @@ fn request_loan(requestor_addr: &str, account_addr: &str,
     RE_NFID.captures(&output).expect("Failed to parse request_loan")[1].to_string()
 }
 
+/// Reads the reputation requirements of a loan request, via
+/// rtm/loanrequestor/read_reputation_requirements.rtm
+fn read_reputation_requirements(requestor: &RequestorComponent,
+                                loanrequest_nfid: &str)
+                                -> (Option<String>, Option<String>)
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/loanrequestor/read_reputation_requirements.rtm")
+                             .env("component", &requestor.address)
+                             .env("loanrequest_nfid", loanrequest_nfid));
+    lazy_static! {
+        static ref RE_TUPLE: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n"#,
+            r#".─ Tuple\((None|Some\(Decimal\("[\d.]*"\)\)), "#,
+            r#"(None|Some\(Decimal\("[\d.]*"\)\))\)"#,
+        )).unwrap();
+    }
+    let matches = RE_TUPLE.captures(&output).expect(
+        "Failed to parse read_reputation_requirements");
+    (tm_string_to_option(&matches[1], "Decimal"),
+     tm_string_to_option(&matches[2], "Decimal"))
+}
+
 /// Pledges funds towards a loan request, via
 /// rtm/loanrequestor/pledge_loan.rtm
 fn pledge_loan(requestor_addr: &str, account_addr: &str,
@@ -1075,6 +1172,213 @@ fn read_lenders(acceptor: &AcceptorComponent, loan_nfid: &str)
     lenders
 }
 
+/// Runs one of the restructuring proposal manifests and returns the
+/// id of the new proposal
+fn run_proposal(command: &mut Command) -> u64
+{
+    let output = run_command(command);
+    lazy_static! {
+        static ref RE_ID: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n.*\n.*\n"#,
+            r#".─ (\d*)u64"#,
+        )).unwrap();
+    }
+    RE_ID.captures(&output).expect("Failed to parse proposal id")[1].parse().unwrap()
+}
+
+/// Proposes extending the term of a loan, via
+/// rtm/loanacceptor/propose_extend_term.rtm
+fn propose_extend_term(acceptor: &AcceptorComponent, account: &Account,
+                       participants: &ParticipantsComponent,
+                       participant_nfid: &str, loan_nfid: &str,
+                       additional_installments: u64) -> u64
+{
+    run_proposal(Command::new("resim")
+                 .arg("run")
+                 .arg("rtm/loanacceptor/propose_extend_term.rtm")
+                 .env("component", &acceptor.address)
+                 .env("account", &account.address)
+                 .env("participants_nft_addr", &participants.nft_address)
+                 .env("participant_nfid", participant_nfid)
+                 .env("loan_nfid", loan_nfid)
+                 .env("additional_installments", additional_installments.to_string()))
+}
+
+/// Proposes reducing the rate of a loan, via
+/// rtm/loanacceptor/propose_reduce_rate.rtm
+fn propose_reduce_rate(acceptor: &AcceptorComponent, account: &Account,
+                       participants: &ParticipantsComponent,
+                       participant_nfid: &str, loan_nfid: &str,
+                       amount_per_installment: &str) -> u64
+{
+    run_proposal(Command::new("resim")
+                 .arg("run")
+                 .arg("rtm/loanacceptor/propose_reduce_rate.rtm")
+                 .env("component", &acceptor.address)
+                 .env("account", &account.address)
+                 .env("participants_nft_addr", &participants.nft_address)
+                 .env("participant_nfid", participant_nfid)
+                 .env("loan_nfid", loan_nfid)
+                 .env("amount_per_installment", amount_per_installment))
+}
+
+/// Proposes converting a loan into an installment plan, via
+/// rtm/loanacceptor/propose_installment_plan.rtm
+fn propose_installment_plan(acceptor: &AcceptorComponent, account: &Account,
+                            participants: &ParticipantsComponent,
+                            participant_nfid: &str, loan_nfid: &str,
+                            installments: u64, epochs_per_installment: u64,
+                            amount_per_installment: &str) -> u64
+{
+    run_proposal(Command::new("resim")
+                 .arg("run")
+                 .arg("rtm/loanacceptor/propose_installment_plan.rtm")
+                 .env("component", &acceptor.address)
+                 .env("account", &account.address)
+                 .env("participants_nft_addr", &participants.nft_address)
+                 .env("participant_nfid", participant_nfid)
+                 .env("loan_nfid", loan_nfid)
+                 .env("installments", installments.to_string())
+                 .env("epochs_per_installment", epochs_per_installment.to_string())
+                 .env("amount_per_installment", amount_per_installment))
+}
+
+/// A lender votes for a restructuring proposal, via
+/// rtm/loanacceptor/approve_restructuring.rtm
+fn approve_restructuring(acceptor: &AcceptorComponent, account: &Account,
+                         participants: &ParticipantsComponent,
+                         lender_nfid: &str, loan_nfid: &str, proposal_id: u64)
+{
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/loanacceptor/approve_restructuring.rtm")
+                .env("component", &acceptor.address)
+                .env("account", &account.address)
+                .env("participants_nft_addr", &participants.nft_address)
+                .env("lender_nfid", lender_nfid)
+                .env("loan_nfid", loan_nfid)
+                .env("proposal_id", proposal_id.to_string()));
+}
+
+/// A lender votes against a restructuring proposal, via
+/// rtm/loanacceptor/reject_restructuring.rtm
+fn reject_restructuring(acceptor: &AcceptorComponent, account: &Account,
+                        participants: &ParticipantsComponent,
+                        lender_nfid: &str, loan_nfid: &str, proposal_id: u64)
+{
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/loanacceptor/reject_restructuring.rtm")
+                .env("component", &acceptor.address)
+                .env("account", &account.address)
+                .env("participants_nft_addr", &participants.nft_address)
+                .env("lender_nfid", lender_nfid)
+                .env("loan_nfid", loan_nfid)
+                .env("proposal_id", proposal_id.to_string()));
+}
+
+/// Reads the state and vote shares of a restructuring proposal, via
+/// rtm/loanacceptor/read_restructuring_proposal.rtm
+fn read_restructuring_proposal(acceptor: &AcceptorComponent,
+                               loan_nfid: &str, proposal_id: u64)
+                               -> (String, String, String)
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/loanacceptor/read_restructuring_proposal.rtm")
+                             .env("component", &acceptor.address)
+                             .env("loan_nfid", loan_nfid)
+                             .env("proposal_id", proposal_id.to_string()));
+    lazy_static! {
+        static ref RE_TUPLE: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n"#,
+            r#".─ Tuple\(.*"#,
+            r#"Enum\("(\w*)"\), "#,
+            r#"Decimal\("([\d.]*)"\), "#,
+            r#"Decimal\("([\d.]*)"\)\)"#,
+        )).unwrap();
+    }
+    let matches = RE_TUPLE.captures(&output).expect(
+        "Failed to parse read_restructuring_proposal");
+
+    (matches[1].to_string(), matches[2].to_string(), matches[3].to_string())
+}
+
+/// A lender offers his share of a loan for sale, via
+/// rtm/loanacceptor/offer_loan_share.rtm
+fn offer_loan_share(acceptor: &AcceptorComponent, account: &Account,
+                    participants: &ParticipantsComponent,
+                    lender_nfid: &str, loan_nfid: &str, price: &str)
+{
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/loanacceptor/offer_loan_share.rtm")
+                .env("component", &acceptor.address)
+                .env("account", &account.address)
+                .env("participants_nft_addr", &participants.nft_address)
+                .env("lender_nfid", lender_nfid)
+                .env("loan_nfid", loan_nfid)
+                .env("price", price));
+}
+
+/// A lender withdraws his offer to sell his share of a loan, via
+/// rtm/loanacceptor/cancel_loan_share_offer.rtm
+fn cancel_loan_share_offer(acceptor: &AcceptorComponent, account: &Account,
+                           participants: &ParticipantsComponent,
+                           lender_nfid: &str, loan_nfid: &str)
+{
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/loanacceptor/cancel_loan_share_offer.rtm")
+                .env("component", &acceptor.address)
+                .env("account", &account.address)
+                .env("participants_nft_addr", &participants.nft_address)
+                .env("lender_nfid", lender_nfid)
+                .env("loan_nfid", loan_nfid));
+}
+
+/// Buys a lender's share of a loan, via
+/// rtm/loanacceptor/buy_loan_share.rtm
+fn buy_loan_share(acceptor: &AcceptorComponent, account: &Account,
+                  participants: &ParticipantsComponent,
+                  buyer_nfid: &str, loan_nfid: &str, seller_nfid: &str,
+                  token: &str, amount: &str)
+{
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/loanacceptor/buy_loan_share.rtm")
+                .env("component", &acceptor.address)
+                .env("account", &account.address)
+                .env("participants_nft_addr", &participants.nft_address)
+                .env("buyer_nfid", buyer_nfid)
+                .env("loan_nfid", loan_nfid)
+                .env("seller_nfid", seller_nfid)
+                .env("token_resource", token)
+                .env("amount", amount));
+}
+
+/// Reads the shares of a loan that are for sale, via
+/// rtm/loanacceptor/read_loan_share_offers.rtm
+fn read_loan_share_offers(acceptor: &AcceptorComponent, loan_nfid: &str)
+                          -> HashMap<String, String>
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/loanacceptor/read_loan_share_offers.rtm")
+                             .env("component", &acceptor.address)
+                             .env("loan_nfid", loan_nfid));
+
+    lazy_static! {
+        static ref RE_ENTRY: Regex = Regex::new(concat!(
+            r#"NonFungibleId\("(\w*)"\), Decimal\("([\d.]*)"\)"#,
+        )).unwrap();
+    }
+
+    RE_ENTRY.captures_iter(&output)
+        .map(|c| (c[1].to_string(), c[2].to_string()))
+        .collect()
+}
+
 /// Queries the Participants NFT resource address, via
 /// rtm/loanacceptor/read_participants_nft_addr.rtm
 fn loan_read_participants_nft_addr(acceptor: &AcceptorComponent)
@@ -1094,6 +1398,25 @@ fn loan_read_participants_nft_addr(acceptor: &AcceptorComponent)
         "Failed to parse loan_read_participants_nft_addr")[1].to_string()
 }
 
+/// Queries the LoanAcceptor admin badge address, via
+/// rtm/loanacceptor/read_admin_badge_addr.rtm
+fn loan_read_admin_badge_addr(acceptor: &AcceptorComponent)
+                              -> String
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/loanacceptor/read_admin_badge_addr.rtm")
+                             .env("component", &acceptor.address));
+    lazy_static! {
+        static ref RE_ADDR: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n"#,
+            r#".─ ResourceAddress\("(.*)"\)"#,
+        )).unwrap();
+    }
+    RE_ADDR.captures(&output).expect(
+        "Failed to parse loan_read_admin_badge_addr")[1].to_string()
+}
+
 /// Queries the Loan NFT resource address, via
 /// rtm/loanacceptor/read_loan_nft_addr.rtm
 fn loan_read_loan_nft_addr(acceptor: &AcceptorComponent)
@@ -1425,6 +1748,19 @@ fn test_participants_scenario_1() {
                          &participants.owner_nfid, &alice_p_nfid),
             "Root participant should now be endorsing Alice");
 
+    // Alice tries to boost her own endorsement weight
+    let result = std::panic::catch_unwind(
+        || endorse(&alice.address,
+                   &participants.address,
+                   &participants.nft_address,
+                   &alice_p_nfid,
+                   &alice_p_nfid));
+    assert!(result.is_err(),
+            "Alice should not be able to endorse herself");
+    assert!(!do_i_endorse(&participants.address,
+                          &alice_p_nfid, &alice_p_nfid),
+            "Alice should not be endorsing herself");
+
     // The catalog owner sponsors Alice as well for the same reason
     sponsor(&alice.address, &participants.address, &participants.nft_address,
             &participants.owner_nfid, &alice_p_nfid);
@@ -2696,3 +3032,352 @@ pub fn test_loanacceptor_divide_by_three() {
     // didn't lose any token fractions while processing it. Which is
     // the purpose of this test.
 }
+
+/// Restructures a loan through lender votes and has an outvoted
+/// lender sell his share
+#[test]
+pub fn test_loanacceptor_restructuring() {
+    reset_sim();
+    let package_addr = publish_package();
+
+    let alice = create_account();
+    let (participants, _) = setup_catalog(&alice.address,
+                                          &package_addr);
+    let requestor =
+        instantiate_requestor(&alice.address, &package_addr, &participants.nft_address);
+    let acceptor =
+        instantiate_loan_acceptor(&alice.address, &package_addr,
+                                  &participants.nft_address,
+                                  &requestor.admin_badge_address,
+                                  None, "0");
+    set_loan_acceptor(&requestor.address, &alice.address,
+                      &requestor.config_badge_address, &acceptor.address);
+
+    let bob = create_account();
+    set_default_account(&bob);
+    let bob_p_nfid = new_participant(&participants.address,
+                                     &bob.address,
+                                     "Bob", "", "", None);
+    set_current_epoch(100);
+    let bobs_request_nfid = 
+        request_loan(&requestor.address, &bob.address,
+                     &participants.nft_address, &bob_p_nfid,
+                     RADIX_TOKEN,
+                     "3000",  // amount
+                     "100",   // minimum_share
+                     0,       // pledge lock period
+                     0,       // loan filled lock period
+                     500,     // payment intervals
+                     2,       // installments
+                     "1650",  // payment per installment
+                     "",
+                     "");
+
+    // Three lenders share the loan 50/33/17
+    let debbie = create_account();
+    set_default_account(&debbie);
+    let debbie_p_nfid = new_participant(&participants.address,
+                                        &debbie.address,
+                                        "Debbie", "", "", None);
+    pledge_loan(&requestor.address, &debbie.address,
+                &participants.nft_address, RADIX_TOKEN,
+                &debbie_p_nfid, &bobs_request_nfid,
+                "1500");
+    let eric = create_account();
+    set_default_account(&eric);
+    let eric_p_nfid = new_participant(&participants.address,
+                                      &eric.address,
+                                      "Eric", "", "", None);
+    pledge_loan(&requestor.address, &eric.address,
+                &participants.nft_address, RADIX_TOKEN,
+                &eric_p_nfid, &bobs_request_nfid,
+                "1000");
+    let fran = create_account();
+    set_default_account(&fran);
+    let fran_p_nfid = new_participant(&participants.address,
+                                      &fran.address,
+                                      "Fran", "", "", None);
+    pledge_loan(&requestor.address, &fran.address,
+                &participants.nft_address, RADIX_TOKEN,
+                &fran_p_nfid, &bobs_request_nfid,
+                "500");
+
+    set_default_account(&bob);
+    let bobs_loan_nfid =
+        start_loan(&requestor, &bob.address,
+                   &participants.nft_address, 
+                   &bob_p_nfid, &bobs_request_nfid);
+
+    // Eric proposes a lower rate, which counts as his vote
+    set_default_account(&eric);
+    let rate_proposal = propose_reduce_rate(&acceptor, &eric, &participants,
+                                            &eric_p_nfid, &bobs_loan_nfid, "1500");
+    assert_eq!(0, rate_proposal);
+    let (state, _, _) = read_restructuring_proposal(&acceptor, &bobs_loan_nfid, rate_proposal);
+    assert_eq!("Open", state, "A third of the principal is not enough");
+
+    // A rate increase is not a reduction
+    let result = std::panic::catch_unwind(
+        ||
+            propose_reduce_rate(&acceptor, &eric, &participants,
+                                &eric_p_nfid, &bobs_loan_nfid, "2000"));
+    assert!(result.is_err(),
+            "Should not be able to propose a higher rate");
+
+    // Fran votes against, Debbie for
+    set_default_account(&fran);
+    reject_restructuring(&acceptor, &fran, &participants,
+                         &fran_p_nfid, &bobs_loan_nfid, rate_proposal);
+    set_default_account(&debbie);
+    approve_restructuring(&acceptor, &debbie, &participants,
+                          &debbie_p_nfid, &bobs_loan_nfid, rate_proposal);
+    let (state, _, rejected) =
+        read_restructuring_proposal(&acceptor, &bobs_loan_nfid, rate_proposal);
+    assert_eq!("Passed", state, "Five sixths of the principal approved");
+    assert!(rejected.starts_with("0.1666"), "Fran's share should be one sixth");
+    let (_, _, _, _, _, _, amount_per_installment) =
+        read_loan_data(&acceptor, &bobs_loan_nfid);
+    assert_eq!("1500", amount_per_installment,
+               "The rate reduction should have been applied");
+
+    // Votes are closed once decided
+    set_default_account(&fran);
+    let result = std::panic::catch_unwind(
+        ||
+            reject_restructuring(&acceptor, &fran, &participants,
+                                 &fran_p_nfid, &bobs_loan_nfid, rate_proposal));
+    assert!(result.is_err(),
+            "Should not be able to vote on a passed proposal");
+
+    // Eric was in favour so he cannot sell, Fran can
+    set_default_account(&eric);
+    let result = std::panic::catch_unwind(
+        ||
+            offer_loan_share(&acceptor, &eric, &participants,
+                             &eric_p_nfid, &bobs_loan_nfid, "900"));
+    assert!(result.is_err(),
+            "Eric was not outvoted and should not be able to sell");
+    set_default_account(&fran);
+    offer_loan_share(&acceptor, &fran, &participants,
+                     &fran_p_nfid, &bobs_loan_nfid, "500");
+    cancel_loan_share_offer(&acceptor, &fran, &participants,
+                            &fran_p_nfid, &bobs_loan_nfid);
+    assert!(read_loan_share_offers(&acceptor, &bobs_loan_nfid).is_empty(),
+            "Fran's offer should be withdrawn");
+    offer_loan_share(&acceptor, &fran, &participants,
+                     &fran_p_nfid, &bobs_loan_nfid, "400");
+    assert_eq!(Some(&"400".to_string()),
+               read_loan_share_offers(&acceptor, &bobs_loan_nfid).get(&fran_p_nfid));
+
+    // Gina buys Fran out
+    let gina = create_account();
+    set_default_account(&gina);
+    let gina_p_nfid = new_participant(&participants.address,
+                                      &gina.address,
+                                      "Gina", "", "", None);
+    buy_loan_share(&acceptor, &gina, &participants,
+                   &gina_p_nfid, &bobs_loan_nfid, &fran_p_nfid,
+                   RADIX_TOKEN, "450");
+    assert_eq!("999600", get_balance(&gina, RADIX_TOKEN),
+               "Gina should have paid 400 and received her change");
+    let lenders = read_lenders(&acceptor, &bobs_loan_nfid);
+    assert_eq!(3, lenders.len());
+    assert_eq!("500", lenders[&gina_p_nfid], "Gina should have Fran's share");
+    assert!(!lenders.contains_key(&fran_p_nfid), "Fran should be out");
+    set_default_account(&fran);
+    claim_lender_rewards(&acceptor, &fran, &participants, &fran_p_nfid);
+    assert_eq!("999900", get_balance(&fran, RADIX_TOKEN),
+               "Fran should have recovered 400 of her 500");
+
+    // Bob pays the first, reduced, installment and asks for more time,
+    // but Debbie alone holds half the principal and says no
+    set_default_account(&bob);
+    pay_installment(&acceptor, &bob, &bobs_loan_nfid,
+                    RADIX_TOKEN, "1500");
+    let term_proposal = propose_extend_term(&acceptor, &bob, &participants,
+                                            &bob_p_nfid, &bobs_loan_nfid, 1);
+    assert_eq!(1, term_proposal);
+    set_default_account(&debbie);
+    reject_restructuring(&acceptor, &debbie, &participants,
+                         &debbie_p_nfid, &bobs_loan_nfid, term_proposal);
+    let (state, _, _) = read_restructuring_proposal(&acceptor, &bobs_loan_nfid, term_proposal);
+    assert_eq!("Rejected", state, "Half the principal rejected");
+
+    // Bob misses his second installment
+    set_current_epoch(1200);
+    update_arrears(&acceptor, &debbie, &participants,
+                   &debbie_p_nfid, &bobs_loan_nfid);
+    assert!(is_in_arrears(&acceptor, &bobs_loan_nfid));
+
+    // The lenders agree to a new plan
+    set_default_account(&bob);
+    let plan_proposal = propose_installment_plan(&acceptor, &bob, &participants,
+                                                 &bob_p_nfid, &bobs_loan_nfid,
+                                                 3, 500, "600");
+    set_default_account(&debbie);
+    approve_restructuring(&acceptor, &debbie, &participants,
+                          &debbie_p_nfid, &bobs_loan_nfid, plan_proposal);
+    set_default_account(&gina);
+    approve_restructuring(&acceptor, &gina, &participants,
+                          &gina_p_nfid, &bobs_loan_nfid, plan_proposal);
+    let (_, _, _, total_installments, installments_remaining,
+         epochs_per_installment, amount_per_installment) =
+        read_loan_data(&acceptor, &bobs_loan_nfid);
+    assert_eq!(4, total_installments, "One paid plus three new");
+    assert_eq!(3, installments_remaining);
+    assert_eq!(500, epochs_per_installment);
+    assert_eq!("600", amount_per_installment);
+
+    // The formal arrears flag is still there until the lenders clear it
+    assert!(is_in_arrears(&acceptor, &bobs_loan_nfid));
+    for (account, nfid) in [(&debbie, &debbie_p_nfid),
+                            (&eric, &eric_p_nfid),
+                            (&gina, &gina_p_nfid)] {
+        set_default_account(account);
+        approve_clear_arrears(&acceptor, account, &participants,
+                              nfid, &bobs_loan_nfid);
+    }
+    assert!(!is_in_arrears(&acceptor, &bobs_loan_nfid),
+            "The new plan's first installment is not due yet");
+}
+
+/// Builds a borrower's reputation through on time repayments and
+/// uses it to meet the requirements of a new loan request
+#[test]
+pub fn test_reputation() {
+    reset_sim();
+    let package_addr = publish_package();
+
+    let alice = create_account();
+    let (participants, _) = setup_catalog(&alice.address,
+                                          &package_addr);
+    let requestor =
+        instantiate_requestor(&alice.address, &package_addr, &participants.nft_address);
+    let acceptor =
+        instantiate_loan_acceptor(&alice.address, &package_addr,
+                                  &participants.nft_address,
+                                  &requestor.admin_badge_address,
+                                  None, "0");
+    set_loan_acceptor(&requestor.address, &alice.address,
+                      &requestor.config_badge_address, &acceptor.address);
+    authorize_reporter(&alice.address, &participants,
+                       &loan_read_admin_badge_addr(&acceptor));
+
+    let bob = create_account();
+    set_default_account(&bob);
+    let bob_p_nfid = new_participant(&participants.address,
+                                     &bob.address,
+                                     "Bob", "", "", None);
+    let debbie = create_account();
+    set_default_account(&debbie);
+    let debbie_p_nfid = new_participant(&participants.address,
+                                        &debbie.address,
+                                        "Debbie", "", "", None);
+    endorse(&debbie.address, &participants.address,
+            &participants.nft_address, &debbie_p_nfid, &bob_p_nfid);
+    assert_eq!(("0".to_string(), "0".to_string()),
+               read_reputation(&participants.address, &bob_p_nfid),
+               "Bob has no track record yet");
+
+    // Bob can't yet ask for a loan that requires a track record
+    set_default_account(&bob);
+    let result = std::panic::catch_unwind(
+        ||
+            request_loan_with_requirements(&requestor.address, &bob.address,
+                                           &participants.nft_address, &bob_p_nfid,
+                                           RADIX_TOKEN, "1000", "100",
+                                           0, 0, 500, 2, "550", "", "",
+                                           Some("1"), None));
+    assert!(result.is_err(),
+            "Bob should not meet a reputation requirement yet");
+
+    set_current_epoch(100);
+    let bobs_request_nfid =
+        request_loan(&requestor.address, &bob.address,
+                     &participants.nft_address, &bob_p_nfid,
+                     RADIX_TOKEN, "1000", "100",
+                     0, 0, 500, 2, "550", "", "");
+    set_default_account(&debbie);
+    pledge_loan(&requestor.address, &debbie.address,
+                &participants.nft_address, RADIX_TOKEN,
+                &debbie_p_nfid, &bobs_request_nfid,
+                "1000");
+    set_default_account(&bob);
+    let bobs_loan_nfid =
+        start_loan(&requestor, &bob.address,
+                   &participants.nft_address,
+                   &bob_p_nfid, &bobs_request_nfid);
+
+    // Paying on time earns reputation for Bob and for his endorser
+    pay_installment(&acceptor, &bob, &bobs_loan_nfid, RADIX_TOKEN, "550");
+    assert_eq!(("1".to_string(), "0.5".to_string()),
+               read_reputation(&participants.address, &bob_p_nfid));
+
+    // Backers are those Bob had when the loan was accepted, so
+    // Debbie walking away and Eve joining in now changes nothing
+    set_default_account(&debbie);
+    unendorse(&debbie.address, &participants.address,
+              &participants.nft_address, &debbie_p_nfid, &bob_p_nfid);
+    let eve = create_account();
+    set_default_account(&eve);
+    let eve_p_nfid = new_participant(&participants.address,
+                                     &eve.address,
+                                     "Eve", "", "", None);
+    endorse(&eve.address, &participants.address,
+            &participants.nft_address, &eve_p_nfid, &bob_p_nfid);
+
+    set_default_account(&bob);
+    pay_installment(&acceptor, &bob, &bobs_loan_nfid, RADIX_TOKEN, "550");
+    assert_eq!(("7".to_string(), "0".to_string()),
+               read_reputation(&participants.address, &bob_p_nfid),
+               "Completing the loan should give a bonus");
+    assert_eq!("3.5",
+               read_reputation(&participants.address, &debbie_p_nfid).0,
+               "Debbie should share in Bob's success");
+    assert_eq!("0",
+               read_reputation(&participants.address, &eve_p_nfid).0,
+               "Eve did not back the loan when it was accepted");
+
+    set_default_account(&debbie);
+    endorse(&debbie.address, &participants.address,
+            &participants.nft_address, &debbie_p_nfid, &bob_p_nfid);
+    assert_eq!(("7".to_string(), "3.5".to_string()),
+               read_reputation(&participants.address, &bob_p_nfid));
+    set_default_account(&bob);
+
+    // Now Bob can show off his track record
+    let result = std::panic::catch_unwind(
+        ||
+            request_loan_with_requirements(&requestor.address, &bob.address,
+                                           &participants.nft_address, &bob_p_nfid,
+                                           RADIX_TOKEN, "1000", "100",
+                                           0, 0, 500, 2, "550", "", "",
+                                           Some("10"), None));
+    assert!(result.is_err(),
+            "Bob's reputation is not that good yet");
+    let bobs_request_nfid =
+        request_loan_with_requirements(&requestor.address, &bob.address,
+                                       &participants.nft_address, &bob_p_nfid,
+                                       RADIX_TOKEN, "1000", "100",
+                                       0, 0, 500, 2, "550", "", "",
+                                       Some("7"), Some("3"));
+    assert_eq!((Some("7".to_string()), Some("3".to_string())),
+               read_reputation_requirements(&requestor, &bobs_request_nfid));
+
+    // Losing his endorser's backing means the loan can't start
+    set_default_account(&debbie);
+    unendorse(&debbie.address, &participants.address,
+              &participants.nft_address, &debbie_p_nfid, &bob_p_nfid);
+    pledge_loan(&requestor.address, &debbie.address,
+                &participants.nft_address, RADIX_TOKEN,
+                &debbie_p_nfid, &bobs_request_nfid,
+                "1000");
+    set_default_account(&bob);
+    let result = std::panic::catch_unwind(
+        ||
+            start_loan(&requestor, &bob.address,
+                       &participants.nft_address,
+                       &bob_p_nfid, &bobs_request_nfid));
+    assert!(result.is_err(),
+            "Bob no longer meets the endorsement weight requirement");
+}
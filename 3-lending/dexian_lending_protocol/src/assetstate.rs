@@ -1,5 +1,6 @@
 use sbor::*;
 use scrypto::prelude::*;
+use crate::interestmodel::InterestModel;
 
 #[derive(Debug, TypeId, Encode, Decode, Describe)]
 pub struct AssetState{
@@ -27,7 +28,36 @@ pub struct AssetState{
     // bonus for liquidator
     pub liquidation_bonus: Decimal,
     // last update timestamp
-    pub last_update_epoch: u64
+    pub last_update_epoch: u64,
+    // the interest model waiting for its timelock, and the epoch from which it can be applied
+    pub pending_interest_model: Option<(ComponentAddress, u64)>,
+    // max amount of the asset that can be supplied, zero means no cap
+    pub supply_cap: Decimal,
+    // max amount of the asset that can be borrowed, zero means no cap
+    pub borrow_cap: Decimal,
+    // an isolated asset can only back borrows of assets that are borrowable in isolation, up to the debt ceiling
+    pub isolated: bool,
+    // max debt that can be backed by the asset while it is isolated, in units of the borrowed stable coins
+    pub debt_ceiling: Decimal,
+    // principal currently borrowed against the asset in isolation mode, interest is not counted
+    pub isolated_debt: Decimal,
+    // whether the asset (usually a stable coin) can be borrowed against isolated collateral
+    pub borrowable_in_isolation: bool
+}
+
+// the limits that apply to a CDP, reported by the CDP digest
+#[derive(Debug, TypeId, Encode, Decode, Describe)]
+pub struct CdpLimits{
+    // the collateral of the CDP is in isolation mode
+    pub isolated: bool,
+    // debt ceiling of the isolated collateral, zero when not isolated
+    pub debt_ceiling: Decimal,
+    // principal borrowed against the isolated collateral
+    pub isolated_debt: Decimal,
+    // supply cap of the collateral asset, zero means no cap
+    pub supply_cap: Decimal,
+    // borrow cap of the borrowed asset, zero means no cap
+    pub borrow_cap: Decimal
 }
 
 #[warn(dead_code)]
@@ -88,12 +118,9 @@ impl AssetState {
         let borrow = self.get_total_borrow_with_index(current_borrow_index) + extra_borrow_amount;
         let borrow_ratio = borrow / supply;
 
-        let borrow_interest_rate = self.get_borrow_interest_rate(borrow_ratio);
-        
-        let borrow_interest = borrow * borrow_interest_rate;
-        let supply_interest = borrow_interest * (Decimal::ONE - self.insurance_ratio);
-
-        let supply_interest_rate = supply_interest / supply;
+        let interest_model = InterestModel::at(self.interest_model);
+        let borrow_interest_rate = interest_model.get_borrow_interest_rate(borrow_ratio);
+        let supply_interest_rate = interest_model.get_supply_interest_rate(borrow_ratio, self.insurance_ratio);
         (borrow_interest_rate, supply_interest_rate)
     }
 
@@ -103,16 +130,43 @@ impl AssetState {
         self.supply_interest_rate = supply_interest_rate;
     }
 
+    pub fn set_interest_model(&mut self, interest_model: ComponentAddress) {
+        // settle the interest accrued so far at the rates of the old model
+        self.update_index();
+        self.interest_model = interest_model;
+        self.pending_interest_model = None;
+        self.update_interest_rate();
+    }
+
+    pub fn assert_supply_cap(&self, extra_supply_amount: Decimal) {
+        if self.supply_cap > Decimal::ZERO {
+            let supply = self.get_total_supply_with_index(self.supply_index) + extra_supply_amount;
+            assert!(supply <= self.supply_cap, "The supply cap of the asset is reached! cap:{}, supply:{}", self.supply_cap, supply);
+        }
+    }
+
+    pub fn assert_borrow_cap(&self, extra_borrow_amount: Decimal) {
+        if self.borrow_cap > Decimal::ZERO {
+            let borrow = self.get_total_borrow_with_index(self.borrow_index) + extra_borrow_amount;
+            assert!(borrow <= self.borrow_cap, "The borrow cap of the asset is reached! cap:{}, borrow:{}", self.borrow_cap, borrow);
+        }
+    }
+
+    pub fn add_isolated_debt(&mut self, principal: Decimal) {
+        assert!(self.isolated_debt + principal <= self.debt_ceiling, "The debt ceiling of the isolated collateral is reached! ceiling:{}, debt:{}", self.debt_ceiling, self.isolated_debt);
+        self.isolated_debt += principal;
+    }
+
+    // only the principal share of a repayment is given, so the tracked debt never drops below what is still borrowed
+    pub fn reduce_isolated_debt(&mut self, principal: Decimal) {
+        self.isolated_debt = if principal >= self.isolated_debt { Decimal::ZERO } else { self.isolated_debt - principal };
+    }
+
     pub fn get_total_normalized_supply(&self) -> Decimal{
         let token_res_mgr: &ResourceManager = borrow_resource_manager!(self.token);
         token_res_mgr.total_supply()
     }
 
-    fn get_borrow_interest_rate(&self, borrow_ratio: Decimal) -> Decimal{
-        let component: &Component = borrow_component!(self.interest_model);
-        component.call::<Decimal>("get_borrow_interest_rate", args![borrow_ratio])
-    }
-
     fn get_total_supply_with_index(&self, current_supply_index: Decimal) -> Decimal{
         self.get_total_normalized_supply() * current_supply_index
     }
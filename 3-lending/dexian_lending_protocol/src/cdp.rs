@@ -16,10 +16,13 @@ pub struct CollateralDebtPosition{
     pub normalized_borrow: Decimal,
     #[scrypto(mutable)]
     pub collateral_amount: Decimal,
+    // the principal that is still owed, the interest on top of it is given by the normalized borrow
     #[scrypto(mutable)]
     pub borrow_amount: Decimal,
     #[scrypto(mutable)]
-    pub last_update_epoch: u64
+    pub last_update_epoch: u64,
+    // the debt was borrowed against isolated collateral and counts towards its debt ceiling
+    pub isolated: bool
 }
 
 blueprint! {
@@ -1,4 +1,5 @@
 use scrypto::prelude::*;
+use crate::interestmodel::supply_interest_rate;
 
 blueprint! {
     struct DefaultInterestModel{
@@ -18,5 +19,9 @@ blueprint! {
                 borrow_ratio / Decimal::from("5") + borrow_ratio * borrow_ratio / Decimal::ONE / Decimal::from("2")
             }
         }
+
+        pub fn get_supply_interest_rate(&self, borrow_ratio: Decimal, insurance_ratio: Decimal) -> Decimal{
+            supply_interest_rate(self.get_borrow_interest_rate(borrow_ratio), borrow_ratio, insurance_ratio)
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,42 @@
+use scrypto::prelude::*;
+
+/**
+* The interface of an interest model. Any component can be plugged into an asset pool as its interest model
+* as long as it exposes the following methods, both returning annual rates:
+*
+* `get_borrow_interest_rate(borrow_ratio: Decimal) -> Decimal`
+* `get_supply_interest_rate(borrow_ratio: Decimal, insurance_ratio: Decimal) -> Decimal`
+*
+* The `borrow_ratio` is the utilization of the pool (total borrow / total supply) and the `insurance_ratio` is
+* the reserve factor of the asset, the share of the borrow interest that goes into the insurance funding.
+**/
+pub struct InterestModel{
+    address: ComponentAddress
+}
+
+impl InterestModel {
+
+    pub fn at(address: ComponentAddress) -> Self {
+        Self{ address }
+    }
+
+    pub fn get_borrow_interest_rate(&self, borrow_ratio: Decimal) -> Decimal{
+        let component: &Component = borrow_component!(self.address);
+        component.call::<Decimal>("get_borrow_interest_rate", args![borrow_ratio])
+    }
+
+    pub fn get_supply_interest_rate(&self, borrow_ratio: Decimal, insurance_ratio: Decimal) -> Decimal{
+        let component: &Component = borrow_component!(self.address);
+        component.call::<Decimal>("get_supply_interest_rate", args![borrow_ratio, insurance_ratio])
+    }
+
+}
+
+/**
+* The supply interest rate implied by a borrow interest rate: the borrow interest is spread over the whole supply
+* and the insurance share is kept aside, i.e. borrow rate * borrow ratio * (1 - insurance ratio).
+* Interest models that do not need a different supply curve can use it to implement `get_supply_interest_rate`.
+**/
+pub fn supply_interest_rate(borrow_interest_rate: Decimal, borrow_ratio: Decimal, insurance_ratio: Decimal) -> Decimal {
+    borrow_interest_rate * borrow_ratio * (Decimal::ONE - insurance_ratio)
+}
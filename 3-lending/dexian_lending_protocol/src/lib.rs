@@ -1,4 +1,5 @@
 mod assetstate;
+mod interestmodel;
 mod definterestmodel;
 mod stableinterestmodel;
 mod cdp;
@@ -8,7 +9,10 @@ use scrypto::prelude::*;
 
 use assetstate::*;
 use cdp::*;
+use interestmodel::InterestModel;
 
+// about one day, assuming 35 minute epochs
+const MIN_INTEREST_MODEL_TIMELOCK: u64 = 41u64;
 
 blueprint! {
     struct LendingPool {
@@ -28,6 +32,8 @@ blueprint! {
         cdp_id_counter: u64,
         // lending pool admin badge.
         admin_badge: ResourceAddress,
+        // number of epochs a new interest model has to wait before it can be applied
+        interest_model_timelock: u64,
         // minter
         minter: Vault,
 
@@ -54,6 +60,13 @@ blueprint! {
             
             let rules = AccessRules::new()
                 .method("new_pool", rule!(require(admin_badge.resource_address())))
+                .method("propose_interest_model", rule!(require(admin_badge.resource_address())))
+                .method("cancel_interest_model", rule!(require(admin_badge.resource_address())))
+                .method("set_interest_model_timelock", rule!(require(admin_badge.resource_address())))
+                .method("set_asset_caps", rule!(require(admin_badge.resource_address())))
+                .method("set_isolation_mode", rule!(require(admin_badge.resource_address())))
+                .method("set_debt_ceiling", rule!(require(admin_badge.resource_address())))
+                .method("set_borrowable_in_isolation", rule!(require(admin_badge.resource_address())))
                 // .method("withdraw_fees", rule!(require(admin_badge.resource_address())))
                 .default(rule!(allow_all));
 
@@ -66,6 +79,7 @@ blueprint! {
                 cdp_id_counter: 0u64,
                 minter: Vault::with_bucket(minter),
                 admin_badge: admin_badge.resource_address(),
+                interest_model_timelock: MIN_INTEREST_MODEL_TIMELOCK,
                 cdp_res_addr,
                 oracle_addr
             }
@@ -82,7 +96,9 @@ blueprint! {
             liquidation_threshold: Decimal,
             liquidation_bonus: Decimal,
             insurance_ratio: Decimal, 
-            interest_model: ComponentAddress) -> ResourceAddress  {
+            interest_model: ComponentAddress,
+            isolated: bool) -> ResourceAddress  {
+            LendingPool::assert_interest_model(interest_model);
             let res_mgr = borrow_resource_manager!(asset_address);
 
             let origin_symbol = res_mgr.metadata()["symbol"].clone();
@@ -106,7 +122,14 @@ blueprint! {
                 liquidation_threshold,
                 liquidation_bonus,
                 insurance_ratio,
-                interest_model
+                interest_model,
+                pending_interest_model: None,
+                supply_cap: Decimal::ZERO,
+                borrow_cap: Decimal::ZERO,
+                isolated,
+                debt_ceiling: Decimal::ZERO,
+                isolated_debt: Decimal::ZERO,
+                borrowable_in_isolation: false
             };
 
             self.states.insert(asset_address, asset_state);
@@ -125,6 +148,7 @@ blueprint! {
             debug!("after update_index, asset_address{} indexes:{},{}", asset_address, asset_state.borrow_index, asset_state.supply_index);
 
             let amount = deposit_asset.amount();
+            asset_state.assert_supply_cap(amount);
             let vault = self.vaults.get_mut(&asset_address).unwrap();
             vault.put(deposit_asset);
 
@@ -178,6 +202,8 @@ blueprint! {
             
             let supply_index = collateral_state.supply_index;
             let ltv = collateral_state.ltv;
+            let isolated = collateral_state.isolated;
+            let collateral_asset = collateral_addr.clone();
             let supply_amount = dx_bucket.amount();
 
             let deposit_amount = LendingPool::floor(supply_amount * supply_index);
@@ -199,12 +225,18 @@ blueprint! {
             
             let borrow_asset_state = self.states.get_mut(&borrow_token).unwrap();
             borrow_asset_state.update_index();
+            borrow_asset_state.assert_borrow_cap(amount);
+            assert!(!isolated || borrow_asset_state.borrowable_in_isolation, "The collateral is isolated, only assets borrowable in isolation can be borrowed against it!");
             
             let borrow_normalized_amount = LendingPool::ceil(amount / borrow_asset_state.borrow_index);
             borrow_asset_state.normalized_total_borrow += borrow_normalized_amount;
             borrow_asset_state.update_interest_rate();
             debug!("{}, supply:{}, borrow:{}, rate:{},{}", borrow_token, borrow_asset_state.get_total_normalized_supply(), borrow_asset_state.normalized_total_borrow, borrow_asset_state.borrow_interest_rate, borrow_asset_state.supply_interest_rate);
 
+            if isolated {
+                self.states.get_mut(&collateral_asset).unwrap().add_isolated_debt(amount);
+            }
+
             let borrow_vault = self.vaults.get_mut(&borrow_token).unwrap();
             let borrow_bucket = borrow_vault.take(amount);
 
@@ -216,7 +248,8 @@ blueprint! {
                 collateral_amount: supply_amount,
                 borrow_amount: amount,
                 last_update_epoch: Runtime::current_epoch(),
-                borrow_token
+                borrow_token,
+                isolated
             };
 
             let cdp = self.minter.authorize(|| {
@@ -246,6 +279,7 @@ blueprint! {
             assert!(borrow_index > Decimal::ZERO, "borrow index error! {}", borrow_index);
             let mut normalized_amount = LendingPool::floor(repay_token.amount() / borrow_index);
             let mut repay_amount = repay_token.amount();
+            let normalized_borrow = cdp_data.normalized_borrow;
             
 
             let mut collateral_bucket: Option<Bucket> = None;
@@ -270,12 +304,25 @@ blueprint! {
             let borrow_vault = self.vaults.get_mut(&borrow_token).unwrap();
             borrow_vault.put(repay_token.take(repay_amount));
 
+            // the repayment pays off principal and interest in proportion to the outstanding debt
+            let principal_repaid = if normalized_amount == normalized_borrow {
+                cdp_data.borrow_amount
+            } else {
+                cdp_data.borrow_amount * normalized_amount / normalized_borrow
+            };
+
             cdp_data.total_repay += repay_amount;
+            cdp_data.borrow_amount -= principal_repaid;
             cdp_data.normalized_borrow -= normalized_amount;
             cdp_data.last_update_epoch = Runtime::current_epoch();
             borrow_state.normalized_total_borrow -= normalized_amount;
 
             borrow_state.update_interest_rate();
+
+            if cdp_data.isolated {
+                let collateral_asset = self.origin_asset_map.get(&cdp_data.collateral_token).unwrap();
+                self.states.get_mut(collateral_asset).unwrap().reduce_isolated_debt(principal_repaid);
+            }
             
             self.minter.authorize(|| {
                 let cdp_res_mgr: &ResourceManager = borrow_resource_manager!(cdp.resource_address());
@@ -287,7 +334,7 @@ blueprint! {
         }
 
         pub fn liquidation(&mut self, mut debt_bucket: Bucket, cdp_id: u64) -> Bucket{
-            let (collateral, debt, collateral_in_xrd, debt_in_xrd, collateral_price, _) = self.get_cdp_digest(cdp_id);
+            let (collateral, debt, collateral_in_xrd, debt_in_xrd, collateral_price, _, _) = self.get_cdp_digest(cdp_id);
             assert!(debt == debt_bucket.resource_address(), "The CDP can not support the repay by the bucket!");
             let collateral_state = self.states.get_mut(&collateral).unwrap();
             assert!(collateral_state.liquidation_threshold >= debt_in_xrd / collateral_in_xrd, "The CDP can not be liquidation yet, the timing too early!");
@@ -320,6 +367,8 @@ blueprint! {
             
             cdp_data.collateral_amount -=  normalized_collateral;
             cdp_data.normalized_borrow = Decimal::ZERO;
+            let principal_repaid = cdp_data.borrow_amount;
+            cdp_data.borrow_amount = Decimal::ZERO;
 
             debug!("repay_bucket:{}, normalized_amount:{}, normalized_borrow:{}, repay_amount:{}", repay_amount, normalized_amount, cdp_data.normalized_borrow, repay_amount);
             let borrow_vault = self.vaults.get_mut(&debt).unwrap();
@@ -328,6 +377,11 @@ blueprint! {
 
             debt_state.update_interest_rate();
 
+            if cdp_data.isolated {
+                let collateral_asset = self.origin_asset_map.get(&cdp_data.collateral_token).unwrap();
+                self.states.get_mut(collateral_asset).unwrap().reduce_isolated_debt(principal_repaid);
+            }
+
             self.minter.authorize(|| {
                 let cdp_res_mgr: &ResourceManager = borrow_resource_manager!(self.cdp_res_addr);
                 cdp_res_mgr.update_non_fungible_data(&NonFungibleId::from_u64(cdp_id)   , cdp_data);
@@ -336,7 +390,7 @@ blueprint! {
             collateral_bucket
         } 
 
-        pub fn get_cdp_digest(&self, cdp_id: u64) -> (ResourceAddress, ResourceAddress, Decimal, Decimal, Decimal, Decimal){
+        pub fn get_cdp_digest(&self, cdp_id: u64) -> (ResourceAddress, ResourceAddress, Decimal, Decimal, Decimal, Decimal, CdpLimits){
             let cdp: CollateralDebtPosition = borrow_resource_manager!(self.cdp_res_addr).get_non_fungible_data(&NonFungibleId::from_u64(cdp_id));
             let borrow_token = cdp.borrow_token;
             let collateral_token = cdp.collateral_token;
@@ -349,6 +403,14 @@ blueprint! {
             let debet_asset_price = self.get_asset_price(borrow_token.clone());
             let (collateral_supply_index, _)= collateral_state.get_current_index();
             let (_, debet_borrow_index) = debt_state.get_current_index();
+
+            let limits = CdpLimits{
+                isolated: cdp.isolated,
+                debt_ceiling: if collateral_state.isolated { collateral_state.debt_ceiling } else { Decimal::ZERO },
+                isolated_debt: collateral_state.isolated_debt,
+                supply_cap: collateral_state.supply_cap,
+                borrow_cap: debt_state.borrow_cap
+            };
             
 
             // return {
@@ -365,7 +427,8 @@ blueprint! {
                 LendingPool::ceil(cdp.normalized_borrow * debet_borrow_index * debet_asset_price),
                 LendingPool::floor(cdp.collateral_amount * collateral_supply_index * deposit_asset_price),
                 debet_asset_price,
-                deposit_asset_price
+                deposit_asset_price,
+                limits
             )
 
         }
@@ -380,6 +443,80 @@ blueprint! {
             self.states.get(&asset_addr).unwrap().get_interest_rates(Decimal::ZERO)
         }
 
+        pub fn get_interest_model(&self, asset_addr: ResourceAddress) -> (ComponentAddress, Option<(ComponentAddress, u64)>){
+            assert!(self.states.contains_key(&asset_addr), "unknown asset!");
+            let asset_state = self.states.get(&asset_addr).unwrap();
+            (asset_state.interest_model, asset_state.pending_interest_model)
+        }
+
+        /// Queue a new interest model for an asset. It can be applied once the timelock has passed,
+        /// which gives suppliers and borrowers time to react to the new rates.
+        pub fn propose_interest_model(&mut self, asset_addr: ResourceAddress, interest_model: ComponentAddress) -> u64 {
+            assert!(self.states.contains_key(&asset_addr), "unknown asset!");
+            LendingPool::assert_interest_model(interest_model);
+            let effective_epoch = Runtime::current_epoch() + self.interest_model_timelock;
+            let asset_state = self.states.get_mut(&asset_addr).unwrap();
+            asset_state.pending_interest_model = Some((interest_model, effective_epoch));
+            info!("interest model {} of {} can be applied from epoch {}", interest_model, asset_addr, effective_epoch);
+            effective_epoch
+        }
+
+        pub fn cancel_interest_model(&mut self, asset_addr: ResourceAddress) {
+            assert!(self.states.contains_key(&asset_addr), "unknown asset!");
+            let asset_state = self.states.get_mut(&asset_addr).unwrap();
+            assert!(asset_state.pending_interest_model.is_some(), "There is no pending interest model!");
+            asset_state.pending_interest_model = None;
+        }
+
+        /// Apply the pending interest model of an asset once its timelock has passed. Anyone can call it.
+        /// The interest accrued up to now is settled at the rates of the old model first.
+        pub fn apply_interest_model(&mut self, asset_addr: ResourceAddress) {
+            assert!(self.states.contains_key(&asset_addr), "unknown asset!");
+            let asset_state = self.states.get_mut(&asset_addr).unwrap();
+            assert!(asset_state.pending_interest_model.is_some(), "There is no pending interest model!");
+            let (interest_model, effective_epoch) = asset_state.pending_interest_model.unwrap();
+            assert!(Runtime::current_epoch() >= effective_epoch, "The interest model is timelocked until epoch {}!", effective_epoch);
+            asset_state.set_interest_model(interest_model);
+            debug!("{}, interest model:{}, rate:{},{}", asset_addr, interest_model, asset_state.borrow_interest_rate, asset_state.supply_interest_rate);
+        }
+
+        /// Change the timelock of interest models proposed from now on. It can not be set below the minimum of
+        /// about one day, so the admin can not swap a model in without notice.
+        pub fn set_interest_model_timelock(&mut self, timelock: u64) {
+            assert!(timelock >= MIN_INTEREST_MODEL_TIMELOCK, "The timelock can not be less than {} epochs!", MIN_INTEREST_MODEL_TIMELOCK);
+            self.interest_model_timelock = timelock;
+        }
+
+        /// Cap the amount of an asset that can be supplied and borrowed, zero removes the cap.
+        pub fn set_asset_caps(&mut self, asset_addr: ResourceAddress, supply_cap: Decimal, borrow_cap: Decimal) {
+            assert!(self.states.contains_key(&asset_addr), "unknown asset!");
+            assert!(supply_cap >= Decimal::ZERO && borrow_cap >= Decimal::ZERO, "caps can not be negative!");
+            let asset_state = self.states.get_mut(&asset_addr).unwrap();
+            asset_state.supply_cap = supply_cap;
+            asset_state.borrow_cap = borrow_cap;
+        }
+
+        /// Put an asset in or out of isolation mode: an isolated asset can only be used as collateral to borrow
+        /// assets that are borrowable in isolation, and only up to its debt ceiling in total.
+        pub fn set_isolation_mode(&mut self, asset_addr: ResourceAddress, isolated: bool) {
+            assert!(self.states.contains_key(&asset_addr), "unknown asset!");
+            self.states.get_mut(&asset_addr).unwrap().isolated = isolated;
+        }
+
+        /// Cap the total debt that can be backed by the isolated asset. The assets borrowable in isolation are stable coins
+        /// of the same peg, so the ceiling is counted in units of them.
+        pub fn set_debt_ceiling(&mut self, asset_addr: ResourceAddress, debt_ceiling: Decimal) {
+            assert!(self.states.contains_key(&asset_addr), "unknown asset!");
+            assert!(debt_ceiling >= Decimal::ZERO, "debt ceiling can not be negative!");
+            self.states.get_mut(&asset_addr).unwrap().debt_ceiling = debt_ceiling;
+        }
+
+        /// Whitelist an asset (usually a stable coin) to be borrowed against isolated collateral.
+        pub fn set_borrowable_in_isolation(&mut self, asset_addr: ResourceAddress, borrowable: bool) {
+            assert!(self.states.contains_key(&asset_addr), "unknown asset!");
+            self.states.get_mut(&asset_addr).unwrap().borrowable_in_isolation = borrowable;
+        }
+
         pub fn get_asset_price(&self, asset_addr: ResourceAddress) -> Decimal{
             let component: &Component = borrow_component!(self.oracle_addr);
             component.call::<Decimal>("get_price_quote_in_xrd", args![asset_addr])
@@ -389,6 +526,14 @@ blueprint! {
             deposit_amount * self.get_asset_price(deposit_asset) * ltv / self.get_asset_price(borrow_asset)
         }
 
+        // make sure the component implements the interest model interface
+        fn assert_interest_model(interest_model: ComponentAddress) {
+            let model = InterestModel::at(interest_model);
+            let borrow_interest_rate = model.get_borrow_interest_rate(Decimal::ZERO);
+            let supply_interest_rate = model.get_supply_interest_rate(Decimal::ZERO, Decimal::ZERO);
+            assert!(borrow_interest_rate >= Decimal::ZERO && supply_interest_rate >= Decimal::ZERO, "invalid interest model!");
+        }
+
         fn ceil(dec: Decimal) -> Decimal{
             dec.round(18u8, RoundingMode::TowardsPositiveInfinity)
         }
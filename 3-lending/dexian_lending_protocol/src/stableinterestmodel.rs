@@ -1,4 +1,5 @@
 use scrypto::prelude::*;
+use crate::interestmodel::supply_interest_rate;
 
 blueprint! {
     struct StableInterestModel{
@@ -25,5 +26,9 @@ blueprint! {
             let hundred = Decimal::from("100");
             Decimal::from("55") * x4 / hundred + Decimal::from("45") * x8 / hundred
         }
+
+        pub fn get_supply_interest_rate(&self, borrow_ratio: Decimal, insurance_ratio: Decimal) -> Decimal{
+            supply_interest_rate(self.get_borrow_interest_rate(borrow_ratio), borrow_ratio, insurance_ratio)
+        }
     }
 }
\ No newline at end of file
@@ -1,32 +1,222 @@
 use radix_engine::ledger::*;
+use radix_engine::model::Receipt;
 use radix_engine::transaction::*;
 use scrypto::prelude::*;
 
-#[test]
-fn test_hello() {
-    // Set up environment.
-    let mut ledger = InMemorySubstateStore::with_bootstrap();
-    let mut executor = TransactionExecutor::new(&mut ledger, false);
+struct TestEnv {
+    pk: EcdsaPublicKey,
+    sk: EcdsaPrivateKey,
+    account: ComponentAddress,
+    package: PackageAddress,
+    lending_pool: ComponentAddress,
+    admin_badge: ResourceAddress,
+    cdp: ResourceAddress,
+    usdt: ResourceAddress,
+    dx_xrd: ResourceAddress,
+}
+
+fn new_token(executor: &mut TransactionExecutor<InMemorySubstateStore>, pk: EcdsaPublicKey, sk: &EcdsaPrivateKey, account: ComponentAddress, symbol: &str) -> ResourceAddress {
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    metadata.insert("symbol".to_string(), symbol.to_string());
+    let transaction = TransactionBuilder::new()
+        .new_token_fixed(metadata, dec!("1000000"))
+        .call_method_with_all_resources(account, "deposit_batch")
+        .build(executor.get_nonce([pk]))
+        .sign([sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    receipt.new_resource_addresses[0]
+}
+
+/// Lists XRD as an isolated collateral with a debt ceiling of 100 and USDT, at 10 XRD, as borrowable in isolation.
+/// 10000 USDT and 10000 XRD are supplied.
+fn setup(executor: &mut TransactionExecutor<InMemorySubstateStore>) -> TestEnv {
     let (pk, sk, account) = executor.new_account();
     let package = executor.publish_package(compile_package!()).unwrap();
 
-    // Test the `instantiate_hello` function.
-    let transaction1 = TransactionBuilder::new()
-        .call_function(package, "Hello", "instantiate_hello", args![])
+    let usdt = new_token(executor, pk, &sk, account, "USDT");
+    let usdc = new_token(executor, pk, &sk, account, "USDC");
+
+    let transaction = TransactionBuilder::new()
+        .call_function(package, "PriceOracle", "new", args![usdt, dec!("10"), usdc, dec!("10")])
+        .call_function(package, "DefaultInterestModel", "new", args![])
         .build(executor.get_nonce([pk]))
         .sign([&sk]);
-    let receipt1 = executor.validate_and_execute(&transaction1).unwrap();
-    println!("{:?}\n", receipt1);
-    assert!(receipt1.result.is_ok());
-
-    // Test the `free_token` method.
-    let component = receipt1.new_component_addresses[0];
-    let transaction2 = TransactionBuilder::new()
-        .call_method(component, "free_token", args![])
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let oracle = receipt.new_component_addresses[0];
+    let interest_model = receipt.new_component_addresses[1];
+
+    let transaction = TransactionBuilder::new()
+        .call_function(package, "LendingPool", "instantiate_asset_pool", args![oracle])
         .call_method_with_all_resources(account, "deposit_batch")
         .build(executor.get_nonce([pk]))
         .sign([&sk]);
-    let receipt2 = executor.validate_and_execute(&transaction2).unwrap();
-    println!("{:?}\n", receipt2);
-    assert!(receipt2.result.is_ok());
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let lending_pool = receipt.new_component_addresses[0];
+    let admin_badge = receipt.new_resource_addresses[0];
+    let cdp = receipt.new_resource_addresses[2];
+
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(admin_badge, account)
+        .call_method(lending_pool, "new_pool", args![RADIX_TOKEN, dec!("0.6"), dec!("0.7"), dec!("0.07"), dec!("0.25"), interest_model, true])
+        .call_method(lending_pool, "new_pool", args![usdt, dec!("0.85"), dec!("0.9"), dec!("0.05"), dec!("0.1"), interest_model, false])
+        .call_method(lending_pool, "set_borrowable_in_isolation", args![usdt, true])
+        .call_method(lending_pool, "set_debt_ceiling", args![RADIX_TOKEN, dec!("100")])
+        .build(executor.get_nonce([pk]))
+        .sign([&sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let dx_xrd = receipt.new_resource_addresses[0];
+
+    let env = TestEnv { pk, sk, account, package, lending_pool, admin_badge, cdp, usdt, dx_xrd };
+    assert!(supply(executor, &env, env.usdt, dec!("10000")).result.is_ok());
+    assert!(supply(executor, &env, RADIX_TOKEN, dec!("10000")).result.is_ok());
+    env
+}
+
+fn call_as_admin(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, method: &str, args: Vec<Vec<u8>>) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(env.admin_badge, env.account)
+        .call_method(env.lending_pool, method, args)
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn call(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, method: &str, args: Vec<Vec<u8>>) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.lending_pool, method, args)
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn supply(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, asset: ResourceAddress, amount: Decimal) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(amount, asset, env.account)
+        .take_from_worktop(asset, |builder, bucket_id| {
+            builder.call_method(env.lending_pool, "supply", args![Bucket(bucket_id)])
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+/// Borrows USDT against 2000 dxXRD, which is enough collateral for 120 USDT
+fn borrow(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, amount: Decimal) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(dec!("2000"), env.dx_xrd, env.account)
+        .take_from_worktop(env.dx_xrd, |builder, bucket_id| {
+            builder.call_method(env.lending_pool, "borrow", args![Bucket(bucket_id), env.usdt, amount])
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn repay(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, cdp_id: u64, amount: Decimal) -> Receipt {
+    let mut ids = BTreeSet::new();
+    ids.insert(NonFungibleId::from_u64(cdp_id));
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(amount, env.usdt, env.account)
+        .withdraw_from_account_by_ids(&ids, env.cdp, env.account)
+        .take_from_worktop(env.usdt, |builder, repay_id| {
+            builder.take_from_worktop(env.cdp, |builder, cdp_bucket_id| {
+                builder.call_method(env.lending_pool, "repay", args![Bucket(repay_id), Bucket(cdp_bucket_id)])
+            })
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+#[test]
+fn test_borrow_above_debt_ceiling_is_rejected() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // There is enough collateral for 120 USDT, but only 100 may be borrowed against the isolated XRD
+    assert!(borrow(&mut executor, &env, dec!("110")).result.is_err());
+    assert!(borrow(&mut executor, &env, dec!("80")).result.is_ok());
+    assert!(borrow(&mut executor, &env, dec!("30")).result.is_err());
+    assert!(borrow(&mut executor, &env, dec!("20")).result.is_ok());
+}
+
+#[test]
+fn test_repaying_frees_room_under_debt_ceiling() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert!(borrow(&mut executor, &env, dec!("80")).result.is_ok());
+    assert!(borrow(&mut executor, &env, dec!("50")).result.is_err());
+
+    // Repaying half of the principal in the same epoch frees room for as much
+    assert!(repay(&mut executor, &env, 1, dec!("40")).result.is_ok());
+    assert!(borrow(&mut executor, &env, dec!("60")).result.is_ok());
+    assert!(borrow(&mut executor, &env, dec!("1")).result.is_err());
+}
+
+#[test]
+fn test_repaying_interest_does_not_free_room_under_debt_ceiling() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert!(borrow(&mut executor, &env, dec!("80")).result.is_ok());
+
+    // After a year the debt has grown above 80, so repaying 80 leaves part of the principal borrowed
+    executor.substate_store_mut().set_epoch(15017);
+    assert!(repay(&mut executor, &env, 1, dec!("80")).result.is_ok());
+    assert!(borrow(&mut executor, &env, dec!("100")).result.is_err());
+    assert!(borrow(&mut executor, &env, dec!("99")).result.is_ok());
+}
+
+#[test]
+fn test_interest_model_timelock_has_a_minimum() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert!(call_as_admin(&mut executor, &env, "set_interest_model_timelock", args![0u64]).result.is_err());
+    assert!(call_as_admin(&mut executor, &env, "set_interest_model_timelock", args![40u64]).result.is_err());
+    assert!(call_as_admin(&mut executor, &env, "set_interest_model_timelock", args![100u64]).result.is_ok());
+}
+
+#[test]
+fn test_interest_model_can_only_be_applied_after_timelock() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    let transaction = TransactionBuilder::new()
+        .call_function(env.package, "StableInterestModel", "new", args![])
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let stable_model = receipt.new_component_addresses[0];
+
+    executor.substate_store_mut().set_epoch(100);
+    assert!(call_as_admin(&mut executor, &env, "propose_interest_model", args![env.usdt, stable_model]).result.is_ok());
+    let receipt = call(&mut executor, &env, "get_interest_model", args![env.usdt]);
+    let (_, pending): (ComponentAddress, Option<(ComponentAddress, u64)>) = scrypto_decode(&receipt.outputs[0].raw[..]).unwrap();
+    assert_eq!(pending, Some((stable_model, 141)));
+
+    executor.substate_store_mut().set_epoch(140);
+    assert!(call(&mut executor, &env, "apply_interest_model", args![env.usdt]).result.is_err());
+
+    executor.substate_store_mut().set_epoch(141);
+    assert!(call(&mut executor, &env, "apply_interest_model", args![env.usdt]).result.is_ok());
+
+    let receipt = call(&mut executor, &env, "get_interest_model", args![env.usdt]);
+    let (interest_model, pending): (ComponentAddress, Option<(ComponentAddress, u64)>) = scrypto_decode(&receipt.outputs[0].raw[..]).unwrap();
+    assert_eq!(interest_model, stable_model);
+    assert!(pending.is_none());
 }
@@ -1,4 +1,4 @@
-use crate::dutch_auction::DutchAuction;
+use crate::dutch_auction::{DutchAuction, PriceDecay};
 use sbor::*;
 use scrypto::prelude::*;
 
@@ -6,30 +6,59 @@ blueprint! {
     struct LendingMarketPlace {
         /// The liquidity pool
         liquidity_pool: Vault,
-        /// the  epoch for when the liqudation period starts
+        /// number of epochs the auction of seized collateral takes to reach its ending price
         liquidation_period: u64,
         /// User state
         users: LazyMap<ResourceAddress, User>,
-        /// nft valut ( todo)
-        nft_vaults: HashMap<ResourceAddress, Vault>,
+        /// The collateral NFTs of each user, keyed by user id and then by NFT resource address
+        nft_vaults: HashMap<ResourceAddress, HashMap<ResourceAddress, Vault>>,
         /// This is the vault which stores the payment of the NFTs once it has been made. This vault may contain XRD or
         payment_vault: Vault,
         /// price for the nft to be sold
         price: Decimal,
         token_address: ResourceAddress,
-
-
+        /// number of epochs a loan can run before it can be liquidated
+        loan_period: u64,
+        /// the price the auction of seized collateral starts at
+        auction_starting_price: Decimal,
+        /// the price the auction of seized collateral ends at
+        auction_ending_price: Decimal,
+        /// the curve along which the auction price goes down
+        auction_decay: PriceDecay,
+        /// ownership badges of the auctions of seized collateral, by auction
+        auction_badges: HashMap<ComponentAddress, Vault>,
+        /// the user whose collateral each auction sells
+        auction_users: HashMap<ComponentAddress, ResourceAddress>,
+        /// auction proceeds left over once a user's loan is repaid, by user id
+        surplus_vaults: HashMap<ResourceAddress, Vault>,
     }
 
     impl LendingMarketPlace {
-        /// Creates a lending pool
-        pub fn instantiate_LendingMarketPlace(reserve_address: ResourceAddress ) -> ComponentAddress {
+        /// Creates a lending pool.
+        /// Seized collateral is sold in a dutch auction going from `auction_starting_price` down to
+        /// `auction_ending_price` along the `auction_decay` curve in `liquidation_period` epochs
+        pub fn instantiate_LendingMarketPlace(
+            reserve_address: ResourceAddress,
+            loan_period: u64,
+            liquidation_period: u64,
+            auction_starting_price: Decimal,
+            auction_ending_price: Decimal,
+            auction_decay: PriceDecay,
+        ) -> ComponentAddress {
+            // The auction parameters are checked again by each auction, but a mistake should not wait for the first
+            // liquidation to show up
+            assert!(liquidation_period > 0, "The auction must last at least one epoch.");
+            assert!(auction_ending_price >= Decimal::zero(), "The ending price can not be a negative number.");
+            assert!(
+                auction_starting_price > auction_ending_price,
+                "The starting price must be greater than the ending price."
+            );
 
             // Create a new HashMap of vaults and aggregate all of the tokens in the buckets into the vaults of this
             // HashMap. This means that if somebody passes multiple buckets of the same resource, then they would end
             // up in the same vault.
 
-            let mut nft_vaults: HashMap<ResourceAddress, Vault> = HashMap::new();
+            let nft_vaults: HashMap<ResourceAddress, HashMap<ResourceAddress, Vault>> = HashMap::new();
             // for bucket in non_fungible_tokens.into_iter() {
             //     nft_vaults
             //         .entry(bucket.resource_address())
@@ -56,13 +85,19 @@ blueprint! {
 
             Self {
                 liquidity_pool: Vault::new(reserve_address),
-                liquidation_period:50u64,
+                liquidation_period,
                 users: LazyMap::new(),
                 nft_vaults,
                 payment_vault: Vault::new(reserve_address),
                 price: dec!("30"),
                 token_address: reserve_address,
-
+                loan_period,
+                auction_starting_price,
+                auction_ending_price,
+                auction_decay,
+                auction_badges: HashMap::new(),
+                auction_users: HashMap::new(),
+                surplus_vaults: HashMap::new(),
             }
             .instantiate()
             .globalize()
@@ -94,6 +129,7 @@ blueprint! {
                     borrow_balance: Decimal::zero(),
                     deposit_last_update: Runtime::current_epoch(),
                     borrow_last_update: Runtime::current_epoch(),
+                    loan_start: Runtime::current_epoch(),
                 },
             };
 
@@ -124,8 +160,9 @@ blueprint! {
         /// Borrows 30XRD frin the pool by collateralizing the underlying assets.
         pub fn borrow(&mut self, user_auth: Proof ,non_fungible_tokens: Vec<Bucket> ) -> Bucket {
             let user_id = Self::get_user_id(user_auth);
+            let user_vaults = self.nft_vaults.entry(user_id).or_insert(HashMap::new());
             for bucket in non_fungible_tokens.into_iter() {
-                self.nft_vaults
+                user_vaults
                     .entry(bucket.resource_address())
                     .or_insert(Vault::new(bucket.resource_address()))
                     .put(bucket)
@@ -141,6 +178,7 @@ blueprint! {
         }
 
         /// Repays a loan, partially or in full.
+        /// The collateral NFTs are given back once the loan is repaid in full, even if they are being auctioned
         pub fn repay(&mut self, user_auth: Proof, mut repaid: Bucket) -> (Bucket, Vec<Bucket>) {
             let user_id = Self::get_user_id(user_auth);
 
@@ -149,18 +187,15 @@ blueprint! {
             let to_return_amount = user.on_repay(repaid.amount());
             let to_return = repaid.take(to_return_amount);
 
-            // Taking out all of the tokens from the vaults and returning them back to the caller.
-            let resource_addresses: Vec<ResourceAddress> =
-                self.nft_vaults.keys().cloned().collect();
-            let mut tokens: Vec<Bucket> = Vec::new();
-            for resource_address in resource_addresses.into_iter() {
-                tokens.push(
-                    self.nft_vaults
-                        .get_mut(&resource_address)
-                        .unwrap()
-                        .take_all(),
-                )
-            }
+            // Taking out all of the user's tokens from the vaults and returning them back to the caller. Collateral
+            // that is still up for auction is taken back from the auction.
+            let tokens: Vec<Bucket> = if user.borrow_balance.is_zero() {
+                let mut tokens = self.take_collateral(user_id);
+                tokens.append(&mut self.cancel_auctions(user_id));
+                tokens
+            } else {
+                Vec::new()
+            };
 
             // Commit state changes
             self.users.insert(user_id, user);
@@ -169,47 +204,91 @@ blueprint! {
             return (to_return, tokens);
         }
 
-        /// Liquidates one user's position, if it's under collateralized.
-        pub fn liquidate(&mut self, user_id: ResourceAddress, repaid: Bucket) -> () {
-            let mut user = self.get_user(user_id);
-
-            // Check time passed  size
-            // assert!(
-            //     repaid.amount() <= user.borrow_balance * self.max_liquidation_percent,
-            //     "Max liquidation percent exceeded."
-            // );
+        /// Liquidates one user's position once the loan period is over.
+        /// The loan period counts from the epoch the loan was taken out, partial repayments don't extend it.
+        /// The user's collateral is put up in a dutch auction, use `settle_auction` once it is sold
+        pub fn liquidate(&mut self, user_id: ResourceAddress) -> ComponentAddress {
+            let user = self.get_user(user_id);
+            assert!(user.borrow_balance > Decimal::zero(), "This user has no loan.");
+            assert!(
+                Runtime::current_epoch() > user.loan_start + self.loan_period,
+                "The loan period is not over yet."
+            );
 
+            let tokens: Vec<Bucket> = self.take_collateral(user_id);
+            assert!(!tokens.is_empty(), "This user's collateral is already being auctioned.");
 
-            let starting_price = dec!("90");
-            let ending_price = dec!("40");
-            let starting_epoch = Runtime::current_epoch() ;
-            let ending_epoch = starting_epoch + self.liquidation_period;
+            self.open_auction(user_id, tokens)
+        }
 
-            let resource_addresses: Vec<ResourceAddress> =
-                self.nft_vaults.keys().cloned().collect();
-            let mut tokens: Vec<Bucket> = Vec::new();
-            for resource_address in resource_addresses.into_iter() {
-                tokens.push(
-                    self.nft_vaults
-                        .get_mut(&resource_address)
-                        .unwrap()
-                        .take_all(),
-                )
-            }
+        /// Liquidates several users' positions at once, opening an auction for each of them
+        pub fn liquidate_batch(&mut self, user_ids: Vec<ResourceAddress>) -> Vec<ComponentAddress> {
+            user_ids.into_iter().map(|user_id| self.liquidate(user_id)).collect()
+        }
 
-            let (auction, bucket) = DutchAuction::instantiate_dutch_auction(tokens, self.token_address, starting_price, ending_price, self.liquidation_period);
+        /// Collects the proceeds of a sold auction.
+        /// The proceeds repay the user's loan into the liquidity pool, anything left over is kept for the user
+        /// to claim with `claim_surplus`. If the proceeds don't cover the loan, the rest stays owed
+        pub fn settle_auction(&mut self, auction_address: ComponentAddress) {
+            let user_id: ResourceAddress = *self
+                .auction_users
+                .get(&auction_address)
+                .expect("Unknown auction.");
+            let auction: DutchAuction = auction_address.into();
+            assert!(auction.is_sold(), "The auction has not been sold yet.");
+
+            // The badge stays in its vault, a settled auction is no longer listed in auction_users
+            self.auction_users.remove(&auction_address);
+            let mut proceeds: Bucket = self
+                .auction_badges
+                .get(&auction_address)
+                .unwrap()
+                .authorize(|| auction.withdraw_payment());
 
+            let mut user = self.get_user(user_id);
+            let repaid_amount = std::cmp::min(proceeds.amount(), user.borrow_balance);
+            user.on_repay(repaid_amount);
+            self.liquidity_pool.put(proceeds.take(repaid_amount));
+            self.users.insert(user_id, user);
 
+            let token_address = self.token_address;
+            self.surplus_vaults
+                .entry(user_id)
+                .or_insert(Vault::new(token_address))
+                .put(proceeds);
+        }
 
+        /// Puts the collateral of an auction that went unsold up for auction again.
+        /// The old auction is cancelled once it has reached its ending price and a new one starts at
+        /// `auction_starting_price`, returns the address of the new auction
+        pub fn relist_auction(&mut self, auction_address: ComponentAddress) -> ComponentAddress {
+            let user_id: ResourceAddress = *self
+                .auction_users
+                .get(&auction_address)
+                .expect("Unknown auction.");
+            let auction: DutchAuction = auction_address.into();
+            assert!(!auction.is_sold(), "The auction has been sold, use settle_auction.");
+            assert!(
+                Runtime::current_epoch() >= auction.ending_epoch(),
+                "The auction has not reached its ending price yet."
+            );
 
-            // Update user state and remove the nft badage
+            let tokens: Vec<Bucket> = self.cancel_auction(auction_address);
+            self.open_auction(user_id, tokens)
+        }
 
-            // let to_return_amount = user.on_liquidate(repaid.amount());
-            // let to_return = self.liquidity_pool.take(to_return_amount);
+        /// Withdraws the auction proceeds left over once the user's loan was repaid
+        pub fn claim_surplus(&mut self, user_auth: Proof) -> Bucket {
+            let user_id = Self::get_user_id(user_auth);
+            match self.surplus_vaults.get_mut(&user_id) {
+                Some(vault) => vault.take_all(),
+                None => Bucket::new(self.token_address),
+            }
+        }
 
-            // Commit state changes
-            self.users.insert(user_id, user);
-            // to_return
+        /// Returns the auctions of seized collateral that have not been settled yet, with the user they belong to
+        pub fn get_auctions(&self) -> HashMap<ComponentAddress, ResourceAddress> {
+            self.auction_users.clone()
         }
 
         /// Returns the current state of a user.
@@ -220,6 +299,65 @@ blueprint! {
             }
         }
 
+        /// Puts the given collateral of a user up for auction.
+        fn open_auction(&mut self, user_id: ResourceAddress, tokens: Vec<Bucket>) -> ComponentAddress {
+            let (auction, badge) = DutchAuction::instantiate_dutch_auction_with_decay(
+                tokens,
+                self.token_address,
+                self.auction_starting_price,
+                self.auction_ending_price,
+                self.liquidation_period,
+                self.auction_decay,
+            );
+            self.auction_badges.insert(auction, Vault::with_bucket(badge));
+            self.auction_users.insert(auction, user_id);
+
+            auction
+        }
+
+        /// Cancels an unsold auction and takes the collateral back out of it.
+        /// Like a settled auction, a cancelled one is no longer listed in auction_users
+        fn cancel_auction(&mut self, auction_address: ComponentAddress) -> Vec<Bucket> {
+            self.auction_users.remove(&auction_address);
+            let auction: DutchAuction = auction_address.into();
+            self.auction_badges
+                .get(&auction_address)
+                .unwrap()
+                .authorize(|| auction.cancel_sale())
+        }
+
+        /// Cancels all the unsold auctions of a user's collateral and returns the collateral.
+        fn cancel_auctions(&mut self, user_id: ResourceAddress) -> Vec<Bucket> {
+            let auctions: Vec<ComponentAddress> = self
+                .auction_users
+                .iter()
+                .filter(|(_, auction_user)| **auction_user == user_id)
+                .map(|(auction, _)| *auction)
+                .collect();
+
+            let mut tokens: Vec<Bucket> = Vec::new();
+            for auction_address in auctions {
+                let auction: DutchAuction = auction_address.into();
+                if !auction.is_sold() {
+                    tokens.append(&mut self.cancel_auction(auction_address));
+                }
+            }
+            tokens
+        }
+
+        /// Takes all the collateral NFTs of a user out of the vaults.
+        fn take_collateral(&mut self, user_id: ResourceAddress) -> Vec<Bucket> {
+            let mut tokens: Vec<Bucket> = Vec::new();
+            if let Some(user_vaults) = self.nft_vaults.get_mut(&user_id) {
+                for vault in user_vaults.values_mut() {
+                    if !vault.is_empty() {
+                        tokens.push(vault.take_all());
+                    }
+                }
+            }
+            tokens
+        }
+
         /// Parse user id from a proof.
         fn get_user_id(user_auth: Proof) -> ResourceAddress {
             assert!(user_auth.amount() > dec!("0"), "Invalid user proof");
@@ -239,6 +377,8 @@ pub struct User {
     pub borrow_balance: Decimal,
     /// Last update timestamp
     pub borrow_last_update: u64,
+    /// The epoch the current loan was taken out, repayments don't move it
+    pub loan_start: u64,
 }
 
 impl User {
@@ -295,7 +435,11 @@ impl User {
         // Increase borrow balance by interests accrued
         // let interest = self.borrow_balance * self.borrow_interest_rate * self.borrow_time_elapsed();
         // self.borrow_balance += interest;
-        // self.borrow_last_update = Runtime::current_epoch();
+        self.borrow_last_update = Runtime::current_epoch();
+        // Borrowing more doesn't extend the loan period of an open loan
+        if self.borrow_balance.is_zero() {
+            self.loan_start = Runtime::current_epoch();
+        }
 
         // // Calculate the aggregated interest of previous borrows & the new borrow
         // self.borrow_interest_rate = (self.borrow_balance * self.borrow_interest_rate
@@ -1,5 +1,35 @@
 use scrypto::prelude::*;
 
+/// The curve along which the price of a dutch auction goes down from the starting price to the ending price.
+#[derive(Debug, Clone, Copy, PartialEq, TypeId, Encode, Decode, Describe)]
+pub enum PriceDecay {
+    /// The price goes down by the same amount every epoch.
+    Linear,
+
+    /// The price is multiplied by the given factor (between zero and one) every epoch, so it falls fast at first and
+    /// slower later on. The price drops to the ending price once the ending epoch is reached.
+    Exponential(Decimal),
+
+    /// The price follows the linear curve but only goes down once every given number of epochs.
+    Stepped(u64),
+}
+
+/// Raises `base` to the power of `exponent` by repeated squaring, so the number of multiplications only grows with
+/// the number of bits of the exponent rather than with the exponent itself.
+fn pow(base: Decimal, exponent: u64) -> Decimal {
+    let mut result: Decimal = Decimal::one();
+    let mut base: Decimal = base;
+    let mut exponent: u64 = exponent;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base;
+        }
+        base = base * base;
+        exponent >>= 1;
+    }
+    result
+}
+
 blueprint! {
     /// This blueprint defines the state and logic involved in a dutch auction non-fungible token sale. People who
     /// instantiate components from this blueprint, signify their intent at selling their NFT(s) at a price which
@@ -36,6 +66,13 @@ blueprint! {
         /// This is the ending epoch. When this epoch is reached or exceeded, the price of the NFT bundle will be the
         /// `ending_price`.
         ending_epoch: u64,
+
+        /// This is the curve along which the price goes down between the starting and the ending epochs.
+        decay: PriceDecay,
+
+        /// Whether the tokens have been bought. This is tracked separately from the `payment_vault` as the tokens
+        /// may be sold for nothing once the price reaches an `ending_price` of zero.
+        sold: bool,
     }
 
     impl DutchAuction {
@@ -52,7 +89,7 @@ blueprint! {
         /// * **Check 3:** Checks that the starting price is non-negative.
         /// * **Check 4:** Checks that the ending price is non-negative.
         /// * **Check 5:** Checks that the ending price is less than the starting price.
-        /// * **Check 5:** Checks that the ending epoch has not yet passed.
+        /// * **Check 6:** Checks that the auction lasts at least one epoch.
         ///
         /// # Arguments:
         ///
@@ -78,6 +115,46 @@ blueprint! {
             starting_price: Decimal,
             ending_price: Decimal,
             relative_ending_epoch: u64,
+        ) -> (ComponentAddress, Bucket) {
+            return Self::instantiate_dutch_auction_with_decay(
+                non_fungible_tokens,
+                accepted_payment_token,
+                starting_price,
+                ending_price,
+                relative_ending_epoch,
+                PriceDecay::Linear,
+            );
+        }
+
+        /// Instantiates a new dutch auction sale for the passed NFTs with the given price curve.
+        ///
+        /// This function works just like `instantiate_dutch_auction` but lets the instantiator choose how the price
+        /// goes down over time. On top of the checks performed by `instantiate_dutch_auction` it checks that the
+        /// exponential decay factor is between zero and one and that steps last at least one epoch.
+        ///
+        /// # Arguments:
+        ///
+        /// * `non_fungible_tokens` (Vec<Bucket>) - A vector of buckets of the non-fungible tokens that the instantiator
+        /// wishes to sell.
+        /// * `accepted_payment_token` (ResourceAddress) - The resource address of the token accepted for payment.
+        /// * `starting_price` (Decimal) - The starting price of the NFT bundle sale.
+        /// * `ending_price` (Decimal) - The ending price of the NFT bundle sale.
+        /// * `relative_ending_epoch` (u64) - The number of epochs after which the price reaches the ending price.
+        /// * `decay` (PriceDecay) - The curve along which the price goes down.
+        ///
+        /// # Returns:
+        ///
+        /// This function returns a tuple which has the following format:
+        /// * `ComponentAddress` - A component address of the instantiated `DutchAuction` component.
+        /// * `Bucket` - A bucket containing an ownership badge which entitles the holder to the assets in this
+        /// component.
+        pub fn instantiate_dutch_auction_with_decay(
+            non_fungible_tokens: Vec<Bucket>,
+            accepted_payment_token: ResourceAddress,
+            starting_price: Decimal,
+            ending_price: Decimal,
+            relative_ending_epoch: u64,
+            decay: PriceDecay,
         ) -> (ComponentAddress, Bucket) {
             // Performing checks to ensure that the creation of the component can go through
             assert!(
@@ -98,14 +175,29 @@ blueprint! {
                 starting_price >= Decimal::zero(),
                 "[Instantiation]: The starting price of the tokens can not be a negative number."
             );
+            assert!(
+                ending_price >= Decimal::zero(),
+                "[Instantiation]: The ending price of the tokens can not be a negative number."
+            );
             assert!(
                 starting_price > ending_price,
                 "[Instantiation]: The starting price must be greater than the ending price."
             );
             assert!(
-                relative_ending_epoch > Runtime::current_epoch(),
-                "[Instantiation]: The ending epoch has already passed."
+                relative_ending_epoch > 0,
+                "[Instantiation]: The auction must last at least one epoch."
             );
+            match decay {
+                PriceDecay::Linear => {}
+                PriceDecay::Exponential(factor) => assert!(
+                    factor > Decimal::zero() && factor < Decimal::one(),
+                    "[Instantiation]: The decay factor must be between zero and one."
+                ),
+                PriceDecay::Stepped(step_epochs) => assert!(
+                    step_epochs > 0,
+                    "[Instantiation]: A price step must last at least one epoch."
+                ),
+            }
 
             // At this point we know that the component creation can go through.
 
@@ -150,6 +242,8 @@ blueprint! {
                 ending_price,
                 starting_epoch: Runtime::current_epoch(),
                 ending_epoch: Runtime::current_epoch() + relative_ending_epoch,
+                decay,
+                sold: false,
             }
             .instantiate()
             .add_access_check(access_rules)
@@ -165,8 +259,9 @@ blueprint! {
         ///
         /// This method performs a number of checks before the purchase goes through:
         ///
-        /// * **Check 1:** Checks that the payment was provided in the required token.
-        /// * **Check 1:** Checks that enough tokens were provided to cover the price of the NFT(s).
+        /// * **Check 1:** Checks that the tokens have not already been sold.
+        /// * **Check 2:** Checks that the payment was provided in the required token.
+        /// * **Check 3:** Checks that enough tokens were provided to cover the price of the NFT(s).
         ///
         /// # Arguments:
         ///
@@ -177,6 +272,7 @@ blueprint! {
         /// * `Vec<Bucket>` - A vector of buckets of the non-fungible tokens which were being sold.
         pub fn buy(&mut self, mut payment: Bucket) -> Vec<Bucket> {
             // Checking if the appropriate amount of the payment token was provided before approving the token sale
+            assert!(!self.sold, "[Buy]: The tokens have already been sold.");
             assert_eq!(
                 payment.resource_address(),
                 self.accepted_payment_token,
@@ -193,6 +289,7 @@ blueprint! {
 
             // Taking the price of the NFT(s) and putting it in the payment vault
             self.payment_vault.put(payment.take(self.price().1));
+            self.sold = true;
 
             // Creating a vector of buckets of all of the NFTs that the component has, then adding to it the remaining
             // tokens from the payment
@@ -289,30 +386,57 @@ blueprint! {
         /// * `ResourceAddress` - The resource address of the accepted payment token.
         /// * `Decimal` - A decimal value of the price of the NFT(s) in terms of the `accepted_payment_token`.
         pub fn price(&self) -> (ResourceAddress, Decimal) {
+            let current_epoch: u64 = Runtime::current_epoch();
+            if current_epoch >= self.ending_epoch {
+                return (self.accepted_payment_token, self.ending_price);
+            }
+
+            let elapsed_epochs: u64 = current_epoch - self.starting_epoch;
             let gradient: Decimal = (self.ending_price - self.starting_price)
                 / (self.ending_epoch - self.starting_epoch);
+            let price: Decimal = match self.decay {
+                PriceDecay::Linear => gradient * elapsed_epochs + self.starting_price,
+                PriceDecay::Exponential(factor) => self.starting_price * pow(factor, elapsed_epochs),
+                PriceDecay::Stepped(step_epochs) => {
+                    gradient * (elapsed_epochs / step_epochs * step_epochs) + self.starting_price
+                }
+            };
             return (
                 self.accepted_payment_token,
-                std::cmp::max(
-                    self.ending_price,
-                    gradient * (Runtime::current_epoch() - self.starting_epoch)
-                        + self.starting_price,
-                ),
+                std::cmp::max(self.ending_price, price),
             );
         }
 
+        /// Returns the epoch at which the price of the tokens reaches the ending price.
+        ///
+        /// # Returns:
+        ///
+        /// * `u64` - The ending epoch of this sale.
+        pub fn ending_epoch(&self) -> u64 {
+            return self.ending_epoch;
+        }
+
+        /// Returns the curve along which the price of the tokens goes down.
+        ///
+        /// # Returns:
+        ///
+        /// * `PriceDecay` - The price curve of this sale.
+        pub fn decay(&self) -> PriceDecay {
+            return self.decay;
+        }
+
         /// Checks if the NFTs have been sold or not.
         ///
-        /// This method checks whether the NFTs have been sold or not through the `payment_vault`. If the payment vault
-        /// is empty then it means that the tokens have not been sold. On the other hand, if there are funds in the
-        /// payment vault then the exchange has gone through and the tokens have been sold.
+        /// This method checks whether the NFTs have been sold or not through the `sold` flag which is set when the
+        /// tokens are bought. The `payment_vault` can not be used for this as it stays empty when the tokens are sold
+        /// at an ending price of zero, and is emptied again once the payment has been withdrawn.
         ///
         /// # Returns:
         ///
         /// * `bool` - A boolean of whether the tokens have been sold or not. Returns `true` if the tokens have been
         /// sold and `false` if they have not been sold.
         pub fn is_sold(&self) -> bool {
-            return !self.payment_vault.is_empty();
+            return self.sold;
         }
 
         /// Returns a HashMap of the NFTs being sold through this component.
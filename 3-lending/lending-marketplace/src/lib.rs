@@ -1,7 +1,7 @@
 /// market place
 mod LendingMarketPlaceV2;
 /// These are modules which contain the liqudation and lending logic.
-mod dutch_auction;
+pub mod dutch_auction;
 // mod fixed_price_sale;
 
 /// A bootstrap module with a blueprint that creates some test NFTs for us to use when testing the blueprints.
@@ -1,32 +1,304 @@
+use lending_marketplace::dutch_auction::PriceDecay;
 use radix_engine::ledger::*;
 use radix_engine::transaction::*;
 use scrypto::prelude::*;
 
-#[test]
-fn test_hello() {
-    // Set up environment.
-    let mut ledger = InMemorySubstateStore::with_bootstrap();
-    let mut executor = TransactionExecutor::new(&mut ledger, false);
+struct TestEnv {
+    pk: EcdsaPublicKey,
+    sk: EcdsaPrivateKey,
+    account: ComponentAddress,
+    package: PackageAddress,
+    component: ComponentAddress,
+    nft: ResourceAddress,
+}
+
+/// Publishes the package, creates the test NFTs and a marketplace with a 10 epoch loan period and 50 epoch auctions
+/// funded with 1000 XRD
+fn setup(executor: &mut TransactionExecutor<InMemorySubstateStore>) -> TestEnv {
     let (pk, sk, account) = executor.new_account();
     let package = executor.publish_package(compile_package!()).unwrap();
 
-    // Test the `instantiate_hello` function.
-    let transaction1 = TransactionBuilder::new()
-        .call_function(package, "Hello", "instantiate_hello", args![])
+    let transaction = TransactionBuilder::new()
+        .call_function(package, "Bootstrap", "bootstrap", args![])
+        .call_method_with_all_resources(account, "deposit_batch")
         .build(executor.get_nonce([pk]))
         .sign([&sk]);
-    let receipt1 = executor.validate_and_execute(&transaction1).unwrap();
-    println!("{:?}\n", receipt1);
-    assert!(receipt1.result.is_ok());
-
-    // Test the `free_token` method.
-    let component = receipt1.new_component_addresses[0];
-    let transaction2 = TransactionBuilder::new()
-        .call_method(component, "free_token", args![])
-        .call_method_with_all_resources(account, "deposit_batch")
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let nft = receipt.new_resource_addresses[0];
+
+    let transaction = TransactionBuilder::new()
+        .call_function(
+            package,
+            "LendingMarketPlace",
+            "instantiate_LendingMarketPlace",
+            args![RADIX_TOKEN, 10u64, 50u64, dec!("90"), dec!("40"), PriceDecay::Linear],
+        )
         .build(executor.get_nonce([pk]))
         .sign([&sk]);
-    let receipt2 = executor.validate_and_execute(&transaction2).unwrap();
-    println!("{:?}\n", receipt2);
-    assert!(receipt2.result.is_ok());
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let component = receipt.new_component_addresses[0];
+
+    let env = TestEnv { pk, sk, account, package, component, nft };
+
+    // A lender provides the liquidity
+    let lender = new_user(executor, &env);
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(lender, env.account)
+        .withdraw_from_account_by_amount(dec!("1000"), RADIX_TOKEN, env.account)
+        .create_proof_from_auth_zone(lender, |builder, proof_id| {
+            builder.take_from_worktop(RADIX_TOKEN, |builder, bucket_id| {
+                builder.call_method(env.component, "deposit", args![Proof(proof_id), Bucket(bucket_id)])
+            })
+        })
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+
+    env
+}
+
+/// Registers a user and returns their badge
+fn new_user(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv) -> ResourceAddress {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.component, "new_user", args![])
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    receipt.new_resource_addresses[0]
+}
+
+/// Borrows against all of the test NFTs
+fn borrow(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, user_badge: ResourceAddress) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(user_badge, env.account)
+        .withdraw_from_account(env.nft, env.account)
+        .create_proof_from_auth_zone(user_badge, |builder, proof_id| {
+            builder.take_from_worktop(env.nft, |builder, bucket_id| {
+                builder.call_method(env.component, "borrow", args![Proof(proof_id), vec![Bucket(bucket_id)]])
+            })
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn repay(
+    executor: &mut TransactionExecutor<InMemorySubstateStore>,
+    env: &TestEnv,
+    user_badge: ResourceAddress,
+    amount: Decimal,
+) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(user_badge, env.account)
+        .withdraw_from_account_by_amount(amount, RADIX_TOKEN, env.account)
+        .create_proof_from_auth_zone(user_badge, |builder, proof_id| {
+            builder.take_from_worktop(RADIX_TOKEN, |builder, bucket_id| {
+                builder.call_method(env.component, "repay", args![Proof(proof_id), Bucket(bucket_id)])
+            })
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn call(
+    executor: &mut TransactionExecutor<InMemorySubstateStore>,
+    env: &TestEnv,
+    method: &str,
+    args: Vec<Vec<u8>>,
+) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.component, method, args)
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+#[test]
+fn test_partial_repay_does_not_extend_loan() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+    let borrower = new_user(&mut executor, &env);
+    assert!(borrow(&mut executor, &env, borrower).result.is_ok());
+
+    // A dust repayment half way through the loan period
+    executor.substate_store_mut().set_epoch(5);
+    assert!(repay(&mut executor, &env, borrower, dec!("1")).result.is_ok());
+
+    executor.substate_store_mut().set_epoch(10);
+    assert!(call(&mut executor, &env, "liquidate", args![borrower]).result.is_err());
+
+    // The loan is due 10 epochs after it was taken out, not after the last repayment
+    executor.substate_store_mut().set_epoch(11);
+    assert!(call(&mut executor, &env, "liquidate", args![borrower]).result.is_ok());
+}
+
+#[test]
+fn test_relist_unsold_auction() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+    let borrower = new_user(&mut executor, &env);
+    assert!(borrow(&mut executor, &env, borrower).result.is_ok());
+
+    executor.substate_store_mut().set_epoch(11);
+    let receipt = call(&mut executor, &env, "liquidate", args![borrower]);
+    assert!(receipt.result.is_ok());
+    let auction = receipt.new_component_addresses[0];
+
+    // The auction runs for 50 epochs
+    executor.substate_store_mut().set_epoch(60);
+    assert!(call(&mut executor, &env, "relist_auction", args![auction]).result.is_err());
+
+    executor.substate_store_mut().set_epoch(61);
+    let receipt = call(&mut executor, &env, "relist_auction", args![auction]);
+    assert!(receipt.result.is_ok());
+    let relisted = receipt.new_component_addresses[0];
+    assert_ne!(auction, relisted);
+
+    // The old auction is no longer known to the marketplace
+    assert!(call(&mut executor, &env, "relist_auction", args![auction]).result.is_err());
+    assert!(call(&mut executor, &env, "settle_auction", args![auction]).result.is_err());
+}
+
+#[test]
+fn test_repay_cancels_auction() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+    let borrower = new_user(&mut executor, &env);
+    assert!(borrow(&mut executor, &env, borrower).result.is_ok());
+
+    executor.substate_store_mut().set_epoch(11);
+    let receipt = call(&mut executor, &env, "liquidate", args![borrower]);
+    assert!(receipt.result.is_ok());
+    let auction = receipt.new_component_addresses[0];
+
+    // Repaying the loan in full takes the collateral back out of the auction
+    assert!(repay(&mut executor, &env, borrower, dec!("30")).result.is_ok());
+    assert!(call(&mut executor, &env, "settle_auction", args![auction]).result.is_err());
+
+    // The collateral is back in the account, so it can be borrowed against again
+    assert!(borrow(&mut executor, &env, borrower).result.is_ok());
+}
+
+#[test]
+fn test_exponential_price_over_many_epochs() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    let auction_with_duration = |executor: &mut TransactionExecutor<InMemorySubstateStore>, epochs: u64| {
+        let transaction = TransactionBuilder::new()
+            .withdraw_from_account(env.nft, env.account)
+            .take_from_worktop(env.nft, |builder, bucket_id| {
+                builder.call_function(
+                    env.package,
+                    "DutchAuction",
+                    "instantiate_dutch_auction_with_decay",
+                    args![
+                        vec![Bucket(bucket_id)],
+                        RADIX_TOKEN,
+                        dec!("100"),
+                        dec!("1"),
+                        epochs,
+                        PriceDecay::Exponential(dec!("0.5"))
+                    ],
+                )
+            })
+            .call_method_with_all_resources(env.account, "deposit_batch")
+            .build(executor.get_nonce([env.pk]))
+            .sign([&env.sk]);
+        executor.validate_and_execute(&transaction).unwrap()
+    };
+
+    assert!(auction_with_duration(&mut executor, 0).result.is_err());
+
+    let receipt = auction_with_duration(&mut executor, 1_000_000);
+    assert!(receipt.result.is_ok());
+    let auction = receipt.new_component_addresses[0];
+
+    // Half way through the auction the price has long hit the ending price
+    executor.substate_store_mut().set_epoch(500_000);
+    let transaction = TransactionBuilder::new()
+        .call_method(auction, "price", args![])
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+}
+
+#[test]
+fn test_auction_sold_at_zero_ending_price_is_sold() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account(env.nft, env.account)
+        .take_from_worktop(env.nft, |builder, bucket_id| {
+            builder.call_function(
+                env.package,
+                "DutchAuction",
+                "instantiate_dutch_auction_with_decay",
+                args![vec![Bucket(bucket_id)], RADIX_TOKEN, dec!("100"), dec!("0"), 10u64, PriceDecay::Linear],
+            )
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let auction = receipt.new_component_addresses[0];
+    let ownership_badge = receipt.new_resource_addresses[0];
+
+    let is_sold = |executor: &mut TransactionExecutor<InMemorySubstateStore>| -> bool {
+        let transaction = TransactionBuilder::new()
+            .call_method(auction, "is_sold", args![])
+            .build(executor.get_nonce([env.pk]))
+            .sign([&env.sk]);
+        let receipt = executor.validate_and_execute(&transaction).unwrap();
+        assert!(receipt.result.is_ok());
+        scrypto_decode(&receipt.outputs[0].raw[..]).unwrap()
+    };
+    assert!(!is_sold(&mut executor));
+
+    // Once the auction has ended the NFTs go for nothing, which leaves the payment vault empty
+    executor.substate_store_mut().set_epoch(10);
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(dec!("1"), RADIX_TOKEN, env.account)
+        .take_from_worktop(RADIX_TOKEN, |builder, bucket_id| {
+            builder.call_method(auction, "buy", args![Bucket(bucket_id)])
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+    assert!(is_sold(&mut executor));
+
+    // The sale is final, it can be neither cancelled nor bought again
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(ownership_badge, env.account)
+        .call_method(auction, "cancel_sale", args![])
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_err());
+
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(ownership_badge, env.account)
+        .call_method(auction, "withdraw_payment", args![])
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+    assert!(is_sold(&mut executor));
 }
@@ -0,0 +1,27 @@
+use scrypto::prelude::*;
+
+// Stand-in for the TimeOracle blueprint with a time that anyone can set, used to move MescaLend pools through time in tests.
+blueprint! {
+    struct TimeOraclePlaceholder {
+        time_string: String
+    }
+
+    impl TimeOraclePlaceholder {
+        pub fn new(time: String) -> ComponentAddress {
+            Self {
+                time_string: time
+            }
+            .instantiate()
+            .globalize()
+        }
+
+        pub fn set_time(&mut self, time: String) {
+            self.time_string = time;
+        }
+
+        // Returns the unix time as a string, like TimeOracle::get_time
+        pub fn get_time(&self) -> String {
+            self.time_string.clone()
+        }
+    }
+}
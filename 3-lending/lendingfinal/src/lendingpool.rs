@@ -33,7 +33,27 @@ blueprint! {
         collateral_asset_ratio: Decimal,
 
         // This is the unix time in which the lending pool matures. 
-        maturity_time: Decimal
+        maturity_time: Decimal,
+
+        // The time oracle component that provides the current unix time. Its `get_time` method returns 
+        // the time as a string, the same interface as the TimeOracle blueprint.
+        time_oracle: ComponentAddress,
+
+        // Collateral locked by the outstanding collateralized debt NFTs. It is forfeited if not repaid before maturity.
+        locked_collateral: Decimal,
+
+        // Set once the lending pool is settled after maturity.
+        settled: bool,
+
+        // Asset tokens reserved at settlement for the bond principal and bond interest token holders 
+        // and the amount of asset tokens paid for each bond token.
+        bond_payout_vault: Vault,
+        bond_payout_ratio: Decimal,
+
+        // Forfeited collateral reserved at settlement for the insurance principal and insurance interest token holders
+        // and the amount of collateral tokens paid for each insurance token.
+        insurance_payout_vault: Vault,
+        insurance_payout_ratio: Decimal
     }
 
     impl LendingPool {
@@ -42,7 +62,7 @@ blueprint! {
         /// This method is used to instantiate a new lending pool of the two token types that were passed to this
         /// function in the two buckets. The asset token of the lending pool may be lent and borrowed while
         /// the collateral of the lending pool may be put up, and taken in case of default 
-        /// (method for lenders to take collateral when loan defaults not yet implemented). 
+        /// (insurance token holders are paid from the forfeited collateral when the pool is settled after maturity). 
         /// 
         /// This function does a number of checks before a Lending Pool is created, these checks are:
         /// 
@@ -64,6 +84,7 @@ blueprint! {
         /// to determine how much collateral factor a lender requires for their asset they supply to be borrowed, and how much
         /// collateral factor a borrower is required to put up to borrow assets. 
         /// * `maturity_time` (Decimal) - A decimal value representing a maturity date/time in unix. 
+        /// * `time_oracle` (ComponentAddress) - The address of the time oracle component used to get the current unix time.
         /// 
         /// # Returns:
         /// 
@@ -73,7 +94,8 @@ blueprint! {
         pub fn new(
             asset: Bucket, collateral: Bucket, 
             interest_rate: Decimal,
-            maturity_time: Decimal
+            maturity_time: Decimal,
+            time_oracle: ComponentAddress
         ) -> (ComponentAddress, Bucket, Bucket) {
             // Performing checks to see if this lending pool may be created or not.
             assert_ne!(
@@ -106,9 +128,16 @@ blueprint! {
             );
 
             // Calculating the amount of debt the initial liquidity provider owes and the collateral asset ratio.
-            let debt_amount: Decimal = asset.amount()+(asset.amount()*interest_rate/dec!("31556926")*maturity_time);
+            let seconds_to_maturity: Decimal = maturity_time - read_time_oracle(time_oracle);
+            assert!(
+                seconds_to_maturity > Decimal::zero(),
+                "[Pool Creation]: The maturity time of the lending pool must be in the future."
+            );
+            let debt_amount: Decimal = asset.amount()+(asset.amount()*interest_rate/dec!("31556926")*seconds_to_maturity);
             let collateral_amount: Decimal = collateral.amount();
             let collateral_asset_ratio: Decimal = collateral.amount()/asset.amount();
+            let asset_address: ResourceAddress = asset.resource_address();
+            let collateral_address: ResourceAddress = collateral.resource_address();
 
             // Creating hashmap of vaults to insert asset and collateral reserves in. 
             let mut vaults: HashMap<ResourceAddress, Vault> = HashMap::new();
@@ -223,7 +252,14 @@ blueprint! {
                 native_token_admin_badge: Vault::with_bucket(native_token_admin_badge),
                 interest_rate: interest_rate,
                 collateral_asset_ratio: collateral_asset_ratio,
-                maturity_time: maturity_time
+                maturity_time: maturity_time,
+                time_oracle: time_oracle,
+                locked_collateral: collateral_amount,
+                settled: false,
+                bond_payout_vault: Vault::new(asset_address),
+                bond_payout_ratio: Decimal::zero(),
+                insurance_payout_vault: Vault::new(collateral_address),
+                insurance_payout_ratio: Decimal::zero()
             }
             .instantiate()
             .globalize();
@@ -264,7 +300,7 @@ blueprint! {
         }
 
         // Method that provides liquidity to the lending pool by adding asset and collateral tokens.
-        pub fn add_liquidity(&mut self, mut asset: Bucket, mut collateral: Bucket, current_time: Decimal) -> (Bucket, Bucket, Bucket, Bucket) {
+        pub fn add_liquidity(&mut self, mut asset: Bucket, mut collateral: Bucket) -> (Bucket, Bucket, Bucket, Bucket) {
             // Checking if the asset and collateral tokens belong to this lending pool.
             self.assert_belongs_to_pool(asset.resource_address(), String::from("Add Liquidity"));
             self.assert_belongs_to_pool(collateral.resource_address(), String::from("Add Liquidity"));
@@ -278,7 +314,7 @@ blueprint! {
             let y = self.interest_rate;
             let z = self.collateral_asset_ratio;
             let input_collateral_asset_ratio = collateral.amount()/asset.amount();
-            let seconds_to_maturity: Decimal = self.maturity_time - current_time;
+            let seconds_to_maturity: Decimal = self.seconds_to_maturity();
 
             // Determine the maximum amount of asset and collateral tokens that may be added to the lending pool. 
             let (input_asset_amount, input_collateral_amount): (Decimal, Decimal) = if z > input_collateral_asset_ratio {
@@ -319,6 +355,7 @@ blueprint! {
                     },
                 )
             });
+            self.locked_collateral += self.collateral_asset_ratio*debt_amount;
 
             // Depositing the amount of tokens calculated into the lending pool
             self.deposit(asset.take(input_asset_amount));
@@ -341,6 +378,9 @@ blueprint! {
 
             let liquidity_token_address: ResourceAddress = self.native_token_resource_addresses["LQ"];
 
+            // After maturity liquidity providers only get what is left once the bond and insurance claims are reserved.
+            self.settle_if_matured();
+
             assert_eq!(liquidity_tokens.resource_address(), liquidity_token_address,
                 "[Remove Liquidity]: The tracking tokens given do not belong to this liquidity pool."
             );
@@ -369,8 +409,10 @@ blueprint! {
         }
 
 
+        // Returns the asset and collateral addresses, in that order. The payout vaults are used since the keys of
+        // the vaults hashmap come in no particular order.
         pub fn asset_collateral_addresses(&self) -> Vec<ResourceAddress> {
-            return self.vaults.keys().cloned().collect::<Vec<ResourceAddress>>();
+            return vec![self.bond_payout_vault.resource_address(), self.insurance_payout_vault.resource_address()];
         }
 
         // Calculate variable k of the constant product automated market maker function.
@@ -417,19 +459,33 @@ blueprint! {
             return (ymin, zmin)
         }
 
-         // Method that borrows a bucket of asset tokens from the lending pool. The lending pool distributes
-         // a Collateralized Debt Token indicating how much debt he owes when the loan matures. 
-        pub fn borrow(&mut self, borrow_amount: Decimal, collateral: Bucket, current_time: Decimal) -> (Bucket, Bucket) {
-            let addresses: Vec<ResourceAddress> = self.asset_collateral_addresses();
+        // Returns the current unix time given by the time oracle.
+        pub fn current_time(&self) -> Decimal {
+            return read_time_oracle(self.time_oracle);
+        }
 
+        // Returns the number of seconds left until the lending pool matures, asserting that it has not matured yet.
+        fn seconds_to_maturity(&self) -> Decimal {
+            let seconds_to_maturity: Decimal = self.maturity_time - self.current_time();
+            assert!(seconds_to_maturity > Decimal::zero(), "The lending pool has already matured.");
+            return seconds_to_maturity;
+        }
+
+        // Calculates the change in interest rate and collateral asset ratio, and the interest owed, when borrowing 
+        // `borrow_amount` asset tokens at a collateral asset ratio of `collateral_ratio`.
+        // Returns (dy, dz, interest).
+        fn calculate_borrow(&self, borrow_amount: Decimal, collateral_ratio: Decimal) -> (Decimal, Decimal, Decimal) {
+            let addresses: Vec<ResourceAddress> = self.asset_collateral_addresses();
 
             let dx: Decimal = borrow_amount;
-            let dz: Decimal = (collateral.amount()/borrow_amount)-self.collateral_asset_ratio; // change in collateral_asset_ratio
+            let dz: Decimal = collateral_ratio - self.collateral_asset_ratio; // change in collateral_asset_ratio
             let x: Decimal = self.vaults[&addresses[0]].amount();
             let y: Decimal = self.interest_rate;
             let z: Decimal = self.collateral_asset_ratio;
             let k: Decimal = self.k();
 
+            assert!(dx > Decimal::zero() && dx < x, "The borrow amount must be positive and below the asset reserve.");
+
             // assert dz is positive or 0.
             assert!(
                 dz >= dec!("0"), 
@@ -437,7 +493,7 @@ blueprint! {
                 therefore change in the collateral asset ratio must be either positive or 0."
             );
             
-            let (ymax, zmax) = self.calculate_borrow_ymax_zmax(dx);
+            let (_ymax, zmax) = self.calculate_borrow_ymax_zmax(dx);
 
             assert!(
                 z+dz <= zmax, 
@@ -445,25 +501,62 @@ blueprint! {
             );
 
             let dy: Decimal = (k/((x-dx)*z)) - y;
-            
-            let interest_rate_change: Decimal = dy;
+
+            let interest: Decimal = dx*(y+dy)/dec!("31556926")*self.seconds_to_maturity();
+
+            return (dy, dz, interest);
+        }
+
+        // Read-only quote for a borrow of `borrow_amount` asset tokens at a collateral asset ratio of `collateral_ratio`.
+        // Returns (interest, debt, collateral) where collateral is the exact amount of collateral tokens that will be locked.
+        pub fn quote_borrow(&self, borrow_amount: Decimal, collateral_ratio: Decimal) -> (Decimal, Decimal, Decimal) {
+            let (_dy, _dz, interest) = self.calculate_borrow(borrow_amount, collateral_ratio);
+            let debt: Decimal = borrow_amount+interest;
+            return (interest, debt, debt*collateral_ratio);
+        }
+
+         // Method that borrows a bucket of asset tokens from the lending pool. The lending pool distributes
+         // a Collateralized Debt Token indicating how much debt he owes when the loan matures. 
+         // The borrow fails if the interest or the collateral required are above the limits given by the borrower,
+         // the collateral that is not locked is returned.
+        pub fn borrow(
+            &mut self, 
+            borrow_amount: Decimal, 
+            mut collateral: Bucket, 
+            collateral_ratio: Decimal, 
+            max_interest: Decimal, 
+            max_collateral: Decimal
+        ) -> (Bucket, Bucket, Bucket) {
+            let addresses: Vec<ResourceAddress> = self.asset_collateral_addresses();
+            assert_eq!(collateral.resource_address(), addresses[1], "[Borrow]: The collateral does not belong to this pool.");
+
+            let (dy, dz, interest) = self.calculate_borrow(borrow_amount, collateral_ratio);
+            let debt: Decimal = borrow_amount+interest;
+            let collateral_amount: Decimal = debt*collateral_ratio;
+
+            assert!(interest <= max_interest, "[Borrow]: The interest {} is above the maximum interest {}.", interest, max_interest);
+            assert!(
+                collateral_amount <= max_collateral && collateral_amount <= collateral.amount(), 
+                "[Borrow]: The collateral required {} is above the maximum collateral or the collateral provided.", collateral_amount
+            );
 
             info!(
-                "x: {}, y: {}, z: {}, k: {}, dx: {}, dy: {}, dz: {}, interest_rate_change: {}", 
-                x, y, z, k, dx, dy, dz, interest_rate_change
+                "y: {}, z: {}, dx: {}, dy: {}, dz: {}, interest: {}, collateral: {}", 
+                self.interest_rate, self.collateral_asset_ratio, borrow_amount, dy, dz, interest, collateral_amount
             );
 
             // Change lending pool's interest rate
-            self.interest_rate += interest_rate_change;
+            self.interest_rate += dy;
 
             // Change lending pool's collateral asset ratio
             self.collateral_asset_ratio += dz;
 
-            let seconds_to_maturity: Decimal = self.maturity_time - current_time;
-            let interest: Decimal = dx*(y+interest_rate_change)/dec!("31556926")*seconds_to_maturity;
+            // Lock the collateral in the pool
+            self.deposit(collateral.take(collateral_amount));
+            self.locked_collateral += collateral_amount;
 
             // These are the bucket of borrowed asset tokens
-            let borrowed_tokens: Bucket = self.withdraw(self.vaults[&addresses[0]].resource_address(),borrow_amount);
+            let borrowed_tokens: Bucket = self.withdraw(addresses[0], borrow_amount);
 
             let collateralized_debt_token_resource_address = self.native_token_resource_addresses["CD"];
             let collateralized_debt_token = self.native_token_admin_badge.authorize(|| {
@@ -474,26 +567,37 @@ blueprint! {
                     // The NFT data
                     CollateralizedDebtToken {
                         name: "Collateralized Debt NFT".to_string(),
-                        debt: dx+interest,
-                        collateral_amount: (dx+interest)*(z+dz),
+                        debt: debt,
+                        collateral_amount: collateral_amount,
                     },
                 )
             });
 
-            return (borrowed_tokens, collateralized_debt_token);
+            return (borrowed_tokens, collateralized_debt_token, collateral);
         }
 
         // Method that pays back a certain amount of asset tokens and updates the data of a passed collateralized debt NFT.
-        pub fn payback_debt(&mut self, asset: Bucket, collateralized_debt_token: Bucket) -> Bucket {
+        // Debt can only be paid back before maturity, after that the collateral is forfeited. 
+        // Returns the collateral unlocked, the asset tokens above the remaining debt and the collateralized debt NFT.
+        pub fn payback_debt(&mut self, mut asset: Bucket, collateralized_debt_token: Bucket) -> (Bucket, Bucket, Bucket) {
 
             let addresses: Vec<ResourceAddress> = self.asset_collateral_addresses();
+            assert!(self.current_time() < self.maturity_time, "[Payback]: The lending pool has matured, the collateral is forfeited.");
+            assert_eq!(
+                collateralized_debt_token.resource_address(),
+                self.native_token_resource_addresses["CD"],
+                "[Payback]: Collateralized Debt Token is not from this lending pool"
+            );
             
             let non_fungible_data: CollateralizedDebtToken = collateralized_debt_token.non_fungible().data();
             let total_debt = non_fungible_data.debt;
 
-            let payback_amount = if asset.amount() < total_debt {
-                asset.amount()
-            } else if asset.amount() > total_debt {
+            // Nothing is owed on a fully repaid debt, so there is no collateral left to unlock
+            if total_debt == Decimal::zero() {
+                return (Bucket::new(addresses[1]), asset, collateralized_debt_token);
+            }
+
+            let payback_amount = if asset.amount() > total_debt {
                 total_debt
             } else {
                 asset.amount()
@@ -503,40 +607,160 @@ blueprint! {
             let collateral_back = non_fungible_data.collateral_amount*payback_percentage;
 
             let mut non_fungible_data: CollateralizedDebtToken = collateralized_debt_token.non_fungible().data();
-            non_fungible_data.debt -= asset.amount();
+            non_fungible_data.debt -= payback_amount;
             non_fungible_data.collateral_amount -= collateral_back;
             self.native_token_admin_badge.authorize(|| collateralized_debt_token.non_fungible().update_data(non_fungible_data));
 
             //Pay back the asset
-            self.deposit(asset);
+            self.deposit(asset.take(payback_amount));
+            self.locked_collateral -= collateral_back;
 
             let returned_collateral = self.withdraw(addresses[1], collateral_back);
 
-            return returned_collateral;
+            return (returned_collateral, asset, collateralized_debt_token);
 
         }
 
-        // Method that lends a bucket of asset tokens to the lending pool. The lending pool distributes
-         // Bond Principle, Bond Interest, Insurance Principal and Insurance Interest tokens to the lender.
-        pub fn lend(&mut self, lent_tokens: Bucket,interest_rate_change: Decimal, current_time: Decimal) -> (Bucket, Bucket, Bucket, Bucket) {
-            let asset_collateral_addresses: Vec<ResourceAddress> = self.asset_collateral_addresses();
+        // Settles the lending pool after maturity. This may be called by anyone, and is also called by the first claim.
+        // 
+        // The claims are paid in the following order:
+        // 1. Bond principal and bond interest token holders are paid from the asset tokens in the pool, one asset token 
+        //    per bond token, or pro rata if the repaid assets don't cover all of the bonds.
+        // 2. Insurance principal and insurance interest token holders are paid pro rata from the collateral forfeited by
+        //    the borrowers who didn't repay their debt, at most one collateral token per insurance token.
+        // 3. Liquidity providers get what is left in the pool by removing their liquidity.
+        pub fn settle(&mut self) {
+            assert!(self.current_time() >= self.maturity_time, "[Settlement]: The lending pool has not matured yet.");
+            assert!(!self.settled, "[Settlement]: The lending pool is already settled.");
+
+            let addresses: Vec<ResourceAddress> = self.asset_collateral_addresses();
+
+            let bond_supply: Decimal = borrow_resource_manager!(self.native_token_resource_addresses["BP"]).total_supply()
+                + borrow_resource_manager!(self.native_token_resource_addresses["BI"]).total_supply();
+            let asset_amount: Decimal = self.vaults[&addresses[0]].amount();
+            if bond_supply > Decimal::zero() {
+                self.bond_payout_ratio = if asset_amount >= bond_supply { Decimal::one() } else { asset_amount/bond_supply };
+                let bond_payout: Bucket = self.withdraw(addresses[0], bond_supply*self.bond_payout_ratio);
+                self.bond_payout_vault.put(bond_payout);
+            }
+
+            let insurance_supply: Decimal = borrow_resource_manager!(self.native_token_resource_addresses["IP"]).total_supply()
+                + borrow_resource_manager!(self.native_token_resource_addresses["II"]).total_supply();
+            let collateral_amount: Decimal = self.vaults[&addresses[1]].amount();
+            let forfeited_collateral: Decimal = if self.locked_collateral < collateral_amount { self.locked_collateral } else { collateral_amount };
+            if insurance_supply > Decimal::zero() {
+                self.insurance_payout_ratio = if forfeited_collateral >= insurance_supply { Decimal::one() } else { forfeited_collateral/insurance_supply };
+                let insurance_payout: Bucket = self.withdraw(addresses[1], insurance_supply*self.insurance_payout_ratio);
+                self.insurance_payout_vault.put(insurance_payout);
+            }
 
-            let dx: Decimal = lent_tokens.amount();
+            self.settled = true;
+            info!(
+                "[Settlement]: {} asset tokens per bond token, {} collateral tokens per insurance token, {} collateral forfeited", 
+                self.bond_payout_ratio, self.insurance_payout_ratio, forfeited_collateral
+            );
+        }
+
+        fn settle_if_matured(&mut self) {
+            if !self.settled && self.current_time() >= self.maturity_time {
+                self.settle();
+            }
+        }
+
+        // Redeems bond principal, bond interest, insurance principal or insurance interest tokens after maturity.
+        // The tokens are burned and the holder gets their share of the settlement: asset tokens for bond tokens and 
+        // collateral tokens for insurance tokens.
+        pub fn redeem(&mut self, tokens: Bucket) -> Bucket {
+            self.settle_if_matured();
+            assert!(self.settled, "[Redeem]: Claims can only be redeemed after maturity.");
+
+            let address: ResourceAddress = tokens.resource_address();
+            let is_bond: bool = address == self.native_token_resource_addresses["BP"] || address == self.native_token_resource_addresses["BI"];
+            let is_insurance: bool = address == self.native_token_resource_addresses["IP"] || address == self.native_token_resource_addresses["II"];
+            assert!(is_bond || is_insurance, "[Redeem]: The tokens given are not claims of this lending pool.");
+
+            let amount: Decimal = tokens.amount();
+            self.native_token_admin_badge.authorize(|| tokens.burn());
+
+            // The last redeemer gets whatever is left to avoid rounding leftovers
+            let (vault, ratio) = if is_bond {
+                (&mut self.bond_payout_vault, self.bond_payout_ratio)
+            } else {
+                (&mut self.insurance_payout_vault, self.insurance_payout_ratio)
+            };
+            let payout: Decimal = if amount*ratio > vault.amount() { vault.amount() } else { amount*ratio };
+            info!("[Redeem]: {} claim tokens redeemed for {}", amount, payout);
+            return vault.take(payout);
+        }
+
+        // Calculates the change in collateral asset ratio and the native tokens a lender receives when lending
+        // `lend_amount` asset tokens for a decrease of `interest_rate_change` in the interest rate.
+        // Returns (dz, bond principal, bond interest, insurance principal, insurance interest).
+        fn calculate_lend(&self, lend_amount: Decimal, interest_rate_change: Decimal) -> (Decimal, Decimal, Decimal, Decimal, Decimal) {
+            let addresses: Vec<ResourceAddress> = self.asset_collateral_addresses();
+
+            let dx: Decimal = lend_amount;
             let dy: Decimal = interest_rate_change;
-            let x: Decimal = self.vaults[&asset_collateral_addresses[0]].amount();
+            let x: Decimal = self.vaults[&addresses[0]].amount();
             let y: Decimal = self.interest_rate;
             let z: Decimal = self.collateral_asset_ratio;
             let k: Decimal = self.k();
 
-            let dz: Decimal = (k/((x+dx)*(y-dy))) + z; 
-            
-            let (ymin, zmin) = self.calculate_lend_ymin_zmin(dx);
+            assert!(dx > Decimal::zero(), "The lend amount must be positive.");
+
+            let (ymin, _zmin) = self.calculate_lend_ymin_zmin(dx);
+            assert!(
+                dy >= Decimal::zero() && y-dy >= ymin, 
+                "The interest rate after a lend must be equal to or above ymin"
+            );
+
+            // The collateral asset ratio decreases so that x * y * z = k holds after the lend
+            let dz: Decimal = z - (k/((x+dx)*(y-dy)));
+
+            // assert dz is positive or 0.
+            assert!(
+                dz >= Decimal::zero(),
+                "The collateral asset ratio after a lend must be at most whatever it was before, 
+                therefore change in the collateral asset ratio must be either positive or 0."
+            );
+
+            let seconds_to_maturity: Decimal = self.seconds_to_maturity();
+            let bond_interest: Decimal = dx*(y-dy)/dec!("31556926")*seconds_to_maturity;
+
+            let total_asset_amount: Decimal = x+dx;
+            let total_collateral_amount: Decimal = self.vaults[&addresses[1]].amount();
+            let insurance_principal: Decimal = dx*total_collateral_amount/total_asset_amount;
+            let insurance_interest: Decimal = insurance_principal*(y-dy)/dec!("31556926")*seconds_to_maturity;
 
-            let interest_rate_change: Decimal = dy;
+            return (dz, dx, bond_interest, insurance_principal, insurance_interest);
+        }
+
+        // Read-only quote for a lend of `lend_amount` asset tokens for a decrease of `interest_rate_change` in the interest rate.
+        // Returns the exact amounts of (bond principal, bond interest, insurance principal, insurance interest) tokens minted.
+        pub fn quote_lend(&self, lend_amount: Decimal, interest_rate_change: Decimal) -> (Decimal, Decimal, Decimal, Decimal) {
+            let (_dz, bond_principal, bond_interest, insurance_principal, insurance_interest) = 
+                self.calculate_lend(lend_amount, interest_rate_change);
+            return (bond_principal, bond_interest, insurance_principal, insurance_interest);
+        }
+
+        // Method that lends a bucket of asset tokens to the lending pool. The lending pool distributes
+         // Bond Principle, Bond Interest, Insurance Principal and Insurance Interest tokens to the lender.
+         // The lend fails if the bond tokens (principal + interest) received are below `min_bond`.
+        pub fn lend(&mut self, lent_tokens: Bucket, interest_rate_change: Decimal, min_bond: Decimal) -> (Bucket, Bucket, Bucket, Bucket) {
+            let asset_collateral_addresses: Vec<ResourceAddress> = self.asset_collateral_addresses();
+            assert_eq!(lent_tokens.resource_address(), asset_collateral_addresses[0], "[Lend]: The tokens lent do not belong to this pool.");
+
+            let (dz, bond_principal, bond_interest, insurance_principal, insurance_interest) = 
+                self.calculate_lend(lent_tokens.amount(), interest_rate_change);
+
+            assert!(
+                bond_principal+bond_interest >= min_bond, 
+                "[Lend]: The bond amount {} is below the minimum bond {}.", bond_principal+bond_interest, min_bond
+            );
 
             info!(
-                "x: {}, y: {}, z: {}, k: {}, dx: {}, dy: {}, dz: {}, interest rate change: {}", 
-                x, y, z, k, dx, dy, dz, interest_rate_change
+                "y: {}, z: {}, dx: {}, dy: {}, dz: {}", 
+                self.interest_rate, self.collateral_asset_ratio, bond_principal, interest_rate_change, dz
             );
 
             // Change lending pool's interest rate
@@ -545,7 +769,6 @@ blueprint! {
             // Change lending pool's collateral asset ratio
             self.collateral_asset_ratio -= dz;
 
-            let lent_tokens_amount = lent_tokens.amount().clone();
             self.deposit(lent_tokens);
 
             // Retrieve native token resource addresses for lenders
@@ -557,40 +780,30 @@ blueprint! {
             // Mint Bond Principal Tokens
             let bond_principal_tokens_manager: &ResourceManager = borrow_resource_manager!(bond_principal_token_address);
             let bond_principal_tokens: Bucket = self.native_token_admin_badge.authorize(|| {
-                bond_principal_tokens_manager.mint(lent_tokens_amount)
+                bond_principal_tokens_manager.mint(bond_principal)
              });
-            info!("[Lent]: {} bond principal tokens minted", lent_tokens_amount);
+            info!("[Lent]: {} bond principal tokens minted", bond_principal);
 
             // Mint Bond Interest Tokens
-            let seconds_to_maturity: Decimal = self.maturity_time - current_time;
-            let interest: Decimal = dx*(y-interest_rate_change)/dec!("31556926")*seconds_to_maturity;
-
             let bond_interest_tokens_manager: &ResourceManager = borrow_resource_manager!(bond_interest_token_address);
             let bond_interest_tokens: Bucket = self.native_token_admin_badge.authorize(|| {
-                bond_interest_tokens_manager.mint(interest)
+                bond_interest_tokens_manager.mint(bond_interest)
              });
-            info!("[Lent]: {} bond interest tokens minted", interest);
+            info!("[Lent]: {} bond interest tokens minted", bond_interest);
 
             // Mint Insurance Principal Tokens
-            let total_asset_amount: Decimal = self.vaults[&asset_collateral_addresses[0]].amount();
-            let total_collateral_amount: Decimal = self.vaults[&asset_collateral_addresses[1]].amount();
-            let ratio: Decimal = total_collateral_amount/total_asset_amount;
-            let collateral_amount: Decimal = lent_tokens_amount*ratio;
-            
             let insurance_principal_tokens_manager: &ResourceManager = borrow_resource_manager!(insurance_principal_token_address);
             let insurance_principal_tokens: Bucket = self.native_token_admin_badge.authorize(|| {
-                insurance_principal_tokens_manager.mint(collateral_amount)
+                insurance_principal_tokens_manager.mint(insurance_principal)
                 });
-            info!("[Lent]: {} bond interest tokens minted", interest);
+            info!("[Lent]: {} insurance principal tokens minted", insurance_principal);
 
             // Mint Insurance Interest Tokens
-            let insurance_interest: Decimal = collateral_amount*(y-dy)/dec!("31556926")*seconds_to_maturity;
-
             let insurance_interest_tokens_manager: &ResourceManager = borrow_resource_manager!(insurance_interest_token_address);
             let insurance_interest_tokens: Bucket = self.native_token_admin_badge.authorize(|| {
                 insurance_interest_tokens_manager.mint(insurance_interest)
                 });
-            info!("[Lent]: {} bond interest tokens minted", interest);
+            info!("[Lent]: {} insurance interest tokens minted", insurance_interest);
 
             return (bond_principal_tokens, bond_interest_tokens, insurance_principal_tokens, insurance_interest_tokens);
         }
@@ -605,4 +818,10 @@ struct CollateralizedDebtToken {
     name: String,
     debt: Decimal,
     collateral_amount: Decimal,
-}
\ No newline at end of file
+}
+
+// Reads the current unix time from a time oracle component.
+fn read_time_oracle(time_oracle: ComponentAddress) -> Decimal {
+    let time: String = borrow_component!(time_oracle).call::<String>("get_time", args![]);
+    return time.parse::<Decimal>().expect("The time oracle did not provide a valid unix time.");
+}
@@ -5,12 +5,15 @@ use crate::paircontract::*;
 blueprint!{
     struct MescaLend {
         pair_contracts: HashMap<(ResourceAddress, ResourceAddress), PairContract>,
+        // Time oracle used by all the lending pools to know how long until they mature
+        time_oracle: ComponentAddress,
     }
 
     impl MescaLend {
-        pub fn new() -> ComponentAddress {
+        pub fn new(time_oracle: ComponentAddress) -> ComponentAddress {
             return Self {
                 pair_contracts: HashMap::new(), 
+                time_oracle: time_oracle,
             }
             .instantiate()
             .globalize();
@@ -49,7 +52,7 @@ blueprint!{
 
             let addresses: (ResourceAddress,ResourceAddress) = (asset,collateral);
 
-            let pair_contract: PairContract = PairContract::new(asset, collateral).into();
+            let pair_contract: PairContract = PairContract::new(asset, collateral, self.time_oracle).into();
 
             // Adding the liquidity pool to the hashmap of all liquidity pools
             self.pair_contracts.insert(addresses,pair_contract.into());
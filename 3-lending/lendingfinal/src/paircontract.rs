@@ -5,16 +5,18 @@ blueprint!{
     struct PairContract {
         asset: ResourceAddress,
         collateral: ResourceAddress,
-        lending_pools: HashMap<Decimal, LendingPool>
+        lending_pools: HashMap<Decimal, LendingPool>,
+        time_oracle: ComponentAddress
     }
 
     impl PairContract {
 
-        pub fn new(asset: ResourceAddress, collateral: ResourceAddress) -> ComponentAddress {
+        pub fn new(asset: ResourceAddress, collateral: ResourceAddress, time_oracle: ComponentAddress) -> ComponentAddress {
             return Self {
                 asset: asset,
                 collateral: collateral,
-                lending_pools: HashMap::new()
+                lending_pools: HashMap::new(),
+                time_oracle: time_oracle
             }
             .instantiate()
             .globalize();
@@ -60,7 +62,7 @@ blueprint!{
             let (lending_pool, collateralized_debt_token, liquidity_tokens): (
                 ComponentAddress, Bucket, Bucket
             ) = LendingPool::new(
-                asset, collateral, interest_rate, maturity_time
+                asset, collateral, interest_rate, maturity_time, self.time_oracle
             );
 
             // Adding the lending pool to the hashmap of all lending pools
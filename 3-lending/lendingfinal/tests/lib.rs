@@ -1,32 +1,355 @@
 use radix_engine::ledger::*;
+use radix_engine::model::Receipt;
 use radix_engine::transaction::*;
 use scrypto::prelude::*;
 
+// Unix time at which the tests start, the lending pools mature one year later
+const START_TIME: &str = "1000000000";
+const MATURITY_TIME: &str = "1031556926";
+
+struct User {
+    pk: EcdsaPublicKey,
+    sk: EcdsaPrivateKey,
+    account: ComponentAddress,
+}
+
+struct TestEnv {
+    provider: User,
+    borrower: User,
+    time_oracle: ComponentAddress,
+    pool: ComponentAddress,
+    usd: ResourceAddress,
+    eth: ResourceAddress,
+    collateralized_debt_token: ResourceAddress,
+    bond_principal: ResourceAddress,
+    bond_interest: ResourceAddress,
+    insurance_principal: ResourceAddress,
+    insurance_interest: ResourceAddress,
+}
+
+fn new_token(executor: &mut TransactionExecutor<InMemorySubstateStore>, user: &User, symbol: &str) -> ResourceAddress {
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    metadata.insert("symbol".to_string(), symbol.to_string());
+    let transaction = TransactionBuilder::new()
+        .new_token_fixed(metadata, dec!("1000000"))
+        .call_method_with_all_resources(user.account, "deposit_batch")
+        .build(executor.get_nonce([user.pk]))
+        .sign([&user.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    receipt.new_resource_addresses[0]
+}
+
+fn transfer(executor: &mut TransactionExecutor<InMemorySubstateStore>, from: &User, to: &User, resource: ResourceAddress, amount: Decimal) {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(amount, resource, from.account)
+        .call_method_with_all_resources(to.account, "deposit_batch")
+        .build(executor.get_nonce([from.pk]))
+        .sign([&from.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+}
+
+/// Creates a USD/ETH lending pool of 1000 USD and 1000 ETH at a 10% interest rate maturing in one year,
+/// and gives the borrower 200 USD and 200 ETH
+fn setup(executor: &mut TransactionExecutor<InMemorySubstateStore>) -> TestEnv {
+    let (pk, sk, account) = executor.new_account();
+    let provider = User { pk, sk, account };
+    let (pk, sk, account) = executor.new_account();
+    let borrower = User { pk, sk, account };
+
+    let package = executor.publish_package(compile_package!()).unwrap();
+    let oracle_package = executor
+        .publish_package(compile_package!(concat!(env!("CARGO_MANIFEST_DIR"), "/dependencies/time_oracle_placeholder")))
+        .unwrap();
+
+    let transaction = TransactionBuilder::new()
+        .call_function(oracle_package, "TimeOraclePlaceholder", "new", args![START_TIME.to_string()])
+        .build(executor.get_nonce([provider.pk]))
+        .sign([&provider.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let time_oracle = receipt.new_component_addresses[0];
+
+    let usd = new_token(executor, &provider, "USD");
+    let eth = new_token(executor, &provider, "ETH");
+
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(dec!("1000"), usd, provider.account)
+        .withdraw_from_account_by_amount(dec!("1000"), eth, provider.account)
+        .take_from_worktop(usd, |builder, asset_id| {
+            builder.take_from_worktop(eth, |builder, collateral_id| {
+                builder.call_function(
+                    package, "LendingPool", "new",
+                    args![Bucket(asset_id), Bucket(collateral_id), dec!("0.1"), Decimal::from(MATURITY_TIME), time_oracle]
+                )
+            })
+        })
+        .call_method_with_all_resources(provider.account, "deposit_batch")
+        .build(executor.get_nonce([provider.pk]))
+        .sign([&provider.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let pool = receipt.new_component_addresses[0];
+    let bond_principal = receipt.new_resource_addresses[1];
+    let bond_interest = receipt.new_resource_addresses[2];
+    let insurance_principal = receipt.new_resource_addresses[3];
+    let insurance_interest = receipt.new_resource_addresses[4];
+    let collateralized_debt_token = receipt.new_resource_addresses[5];
+
+    transfer(executor, &provider, &borrower, usd, dec!("200"));
+    transfer(executor, &provider, &borrower, eth, dec!("200"));
+
+    TestEnv {
+        provider, borrower, time_oracle, pool, usd, eth, 
+        collateralized_debt_token, bond_principal, bond_interest, insurance_principal, insurance_interest
+    }
+}
+
+fn set_time(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, time: &str) {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.time_oracle, "set_time", args![time.to_string()])
+        .build(executor.get_nonce([env.provider.pk]))
+        .sign([&env.provider.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+}
+
+/// Borrows `amount` USD at a collateral asset ratio of 1, offering all of the borrower's ETH as collateral
+fn borrow(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, amount: Decimal) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(dec!("200"), env.eth, env.borrower.account)
+        .take_from_worktop(env.eth, |builder, collateral_id| {
+            builder.call_method(env.pool, "borrow", args![amount, Bucket(collateral_id), dec!("1"), dec!("100"), dec!("200")])
+        })
+        .call_method_with_all_resources(env.borrower.account, "deposit_batch")
+        .build(executor.get_nonce([env.borrower.pk]))
+        .sign([&env.borrower.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn payback_debt(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, amount: Decimal) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(amount, env.usd, env.borrower.account)
+        .withdraw_from_account(env.collateralized_debt_token, env.borrower.account)
+        .take_from_worktop(env.usd, |builder, asset_id| {
+            builder.take_from_worktop(env.collateralized_debt_token, |builder, debt_token_id| {
+                builder.call_method(env.pool, "payback_debt", args![Bucket(asset_id), Bucket(debt_token_id)])
+            })
+        })
+        .call_method_with_all_resources(env.borrower.account, "deposit_batch")
+        .build(executor.get_nonce([env.borrower.pk]))
+        .sign([&env.borrower.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+/// Lends `amount` USD from the provider's account for a decrease of `interest_rate_change` in the interest rate
+fn lend(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, amount: Decimal, interest_rate_change: Decimal, min_bond: Decimal) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(amount, env.usd, env.provider.account)
+        .take_from_worktop(env.usd, |builder, asset_id| {
+            builder.call_method(env.pool, "lend", args![Bucket(asset_id), interest_rate_change, min_bond])
+        })
+        .call_method_with_all_resources(env.provider.account, "deposit_batch")
+        .build(executor.get_nonce([env.provider.pk]))
+        .sign([&env.provider.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn quote_lend(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, amount: Decimal, interest_rate_change: Decimal) -> (Decimal, Decimal, Decimal, Decimal) {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.pool, "quote_lend", args![amount, interest_rate_change])
+        .build(executor.get_nonce([env.provider.pk]))
+        .sign([&env.provider.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    scrypto_decode(&receipt.outputs[0].raw[..]).unwrap()
+}
+
+fn get_balance(executor: &mut TransactionExecutor<InMemorySubstateStore>, user: &User, resource: ResourceAddress) -> Decimal {
+    let transaction = TransactionBuilder::new()
+        .call_method(user.account, "balance", args![resource])
+        .build(executor.get_nonce([user.pk]))
+        .sign([&user.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    scrypto_decode(&receipt.outputs[0].raw[..]).unwrap()
+}
+/// Redeems all of the provider's claim tokens of the given resource
+fn redeem(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, claim: ResourceAddress) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account(claim, env.provider.account)
+        .take_from_worktop(claim, |builder, claim_id| {
+            builder.call_method(env.pool, "redeem", args![Bucket(claim_id)])
+        })
+        .call_method_with_all_resources(env.provider.account, "deposit_batch")
+        .build(executor.get_nonce([env.provider.pk]))
+        .sign([&env.provider.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+/// Redeems a claim and returns how much of `payout` the provider received for it
+fn redeem_for(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, claim: ResourceAddress, payout: ResourceAddress) -> Decimal {
+    let before = get_balance(executor, &env.provider, payout);
+    assert!(redeem(executor, env, claim).result.is_ok());
+    assert_eq!(get_balance(executor, &env.provider, claim), Decimal::zero());
+    get_balance(executor, &env.provider, payout) - before
+}
+
+    assert!(actual - expected < dec!("0.000001") && expected - actual < dec!("0.000001"), "expected {} got {}", expected, actual);
+}
+
 #[test]
-fn test_hello() {
-    // Set up environment.
+fn test_payback_debt_unlocks_collateral() {
     let mut ledger = InMemorySubstateStore::with_bootstrap();
     let mut executor = TransactionExecutor::new(&mut ledger, false);
-    let (pk, sk, account) = executor.new_account();
-    let package = executor.publish_package(compile_package!()).unwrap();
+    let env = setup(&mut executor);
+
+    // Borrowing 100 of the 1000 USD raises the interest rate to 1/9, so a year of interest is 11.11 USD,
+    // and the debt of 111.11 USD locks as much ETH at a ratio of 1
+    assert!(borrow(&mut executor, &env, dec!("100")).result.is_ok());
+    assert_close(get_balance(&mut executor, &env.borrower, env.usd), dec!("300"));
+    assert_close(get_balance(&mut executor, &env.borrower, env.eth), dec!("200") - dec!("1000") / dec!("9"));
+
+    // Paying back half of the debt unlocks half of the collateral
+    assert!(payback_debt(&mut executor, &env, dec!("500") / dec!("9")).result.is_ok());
+    assert_close(get_balance(&mut executor, &env.borrower, env.eth), dec!("200") - dec!("500") / dec!("9"));
+
+    // Paying more than the rest of the debt only takes what is owed and unlocks the rest of the collateral
+    assert!(payback_debt(&mut executor, &env, dec!("100")).result.is_ok());
+    assert_close(get_balance(&mut executor, &env.borrower, env.usd), dec!("300") - dec!("1000") / dec!("9"));
+    assert_close(get_balance(&mut executor, &env.borrower, env.eth), dec!("200"));
+}
+
+#[test]
+fn test_payback_debt_when_fully_repaid_returns_payment() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert!(borrow(&mut executor, &env, dec!("100")).result.is_ok());
+    assert!(payback_debt(&mut executor, &env, dec!("150")).result.is_ok());
+    let usd_before = get_balance(&mut executor, &env.borrower, env.usd);
+
+    // There is nothing left to pay back, so the payment comes back untouched
+    assert!(payback_debt(&mut executor, &env, dec!("10")).result.is_ok());
+    assert_eq!(get_balance(&mut executor, &env.borrower, env.usd), usd_before);
+    assert_eq!(get_balance(&mut executor, &env.borrower, env.eth), dec!("200"));
+}
+
+#[test]
+fn test_payback_debt_after_maturity_fails() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert!(borrow(&mut executor, &env, dec!("100")).result.is_ok());
+    set_time(&mut executor, &env, MATURITY_TIME);
+    assert!(payback_debt(&mut executor, &env, dec!("150")).result.is_err());
+}
+
+#[test]
+fn test_lend_mints_the_quoted_native_tokens() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // Lending 100 USD at the current 10% rate for a year earns 10 USD of bond interest, and the insurance
+    // covers 100/1100 of the 1000 ETH in the pool
+    let (bond_principal, bond_interest, insurance_principal, insurance_interest) = quote_lend(&mut executor, &env, dec!("100"), dec!("0"));
+    assert_eq!(bond_principal, dec!("100"));
+    assert_close(bond_interest, dec!("10"));
+    assert_close(insurance_principal, dec!("1000") / dec!("11"));
+    assert_close(insurance_interest, dec!("100") / dec!("11"));
+
+    // Asking for more bonds than the lend gives fails, asking for exactly the quote succeeds
+    assert!(lend(&mut executor, &env, dec!("100"), dec!("0"), bond_principal + bond_interest + dec!("1")).result.is_err());
+    assert!(lend(&mut executor, &env, dec!("100"), dec!("0"), bond_principal + bond_interest).result.is_ok());
+    assert_eq!(get_balance(&mut executor, &env.provider, env.bond_principal), bond_principal);
+    assert_eq!(get_balance(&mut executor, &env.provider, env.bond_interest), bond_interest);
+    assert_eq!(get_balance(&mut executor, &env.provider, env.insurance_principal), insurance_principal);
+    assert_eq!(get_balance(&mut executor, &env.provider, env.insurance_interest), insurance_interest);
+}
+
+#[test]
+fn test_lend_interest_rate_change_is_bounded() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // After lending 100 of 1100 USD the interest rate may drop to at most 100/1100, a bit above 9%
+    assert!(lend(&mut executor, &env, dec!("100"), dec!("-0.01"), dec!("0")).result.is_err());
+    assert!(lend(&mut executor, &env, dec!("100"), dec!("0.01"), dec!("0")).result.is_err());
+
+    // A smaller drop lowers the bond interest of the lender accordingly
+    let (_bond_principal, bond_interest, _insurance_principal, _insurance_interest) = quote_lend(&mut executor, &env, dec!("100"), dec!("0.005"));
+    assert_close(bond_interest, dec!("9.5"));
+    assert!(lend(&mut executor, &env, dec!("100"), dec!("0.005"), dec!("0")).result.is_ok());
+    assert_close(get_balance(&mut executor, &env.provider, env.bond_interest), dec!("9.5"));
+}
+
+#[test]
+fn test_redeem_after_default_pays_bonds_pro_rata() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // 110 bond tokens and 100 insurance tokens are minted, the collateral asset ratio drops to 10/11
+    assert!(lend(&mut executor, &env, dec!("100"), dec!("0"), dec!("0")).result.is_ok());
+
+    // Borrowing 1000 of the 1100 USD raises the interest rate to 110%, the debt of 2100 USD locks 2100 ETH
+    transfer(&mut executor, &env.provider, &env.borrower, env.eth, dec!("2000"));
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(dec!("2200"), env.eth, env.borrower.account)
+        .take_from_worktop(env.eth, |builder, collateral_id| {
+            builder.call_method(env.pool, "borrow", args![dec!("1000"), Bucket(collateral_id), dec!("1"), dec!("1200"), dec!("2200")])
+        })
+        .call_method_with_all_resources(env.borrower.account, "deposit_batch")
+        .build(executor.get_nonce([env.borrower.pk]))
+        .sign([&env.borrower.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+
+    assert!(redeem(&mut executor, &env, env.bond_principal).result.is_err());
+
+    // The borrower never pays back, so only 100 USD is left for the 110 bond tokens
+    set_time(&mut executor, &env, MATURITY_TIME);
+    assert_close(redeem_for(&mut executor, &env, env.bond_principal, env.usd), dec!("1000") / dec!("11"));
+    assert_close(redeem_for(&mut executor, &env, env.bond_interest, env.usd), dec!("100") / dec!("11"));
+
+    // The forfeited collateral covers the insurance tokens one to one
+    assert_close(redeem_for(&mut executor, &env, env.insurance_principal, env.eth), dec!("1000") / dec!("11"));
+    assert_close(redeem_for(&mut executor, &env, env.insurance_interest, env.eth), dec!("100") / dec!("11"));
+}
+
+#[test]
+fn test_redeem_after_full_repayment_pays_bonds_in_full() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert!(lend(&mut executor, &env, dec!("100"), dec!("0"), dec!("0")).result.is_ok());
+
+    // The debt of 111 USD is repaid in full before maturity
+    assert!(borrow(&mut executor, &env, dec!("100")).result.is_ok());
+    assert!(payback_debt(&mut executor, &env, dec!("150")).result.is_ok());
+    assert_close(get_balance(&mut executor, &env.borrower, env.eth), dec!("200"));
+
+    set_time(&mut executor, &env, MATURITY_TIME);
+    let transaction = TransactionBuilder::new()
+        .call_method(env.pool, "settle", args![])
+        .build(executor.get_nonce([env.provider.pk]))
+        .sign([&env.provider.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+
+    // Bond tokens are paid one asset token each
+    assert_close(redeem_for(&mut executor, &env, env.bond_principal, env.usd), dec!("100"));
+    assert_close(redeem_for(&mut executor, &env, env.bond_interest, env.usd), dec!("10"));
+
+    // The pool's initial collateral is still locked against its own debt, which was never repaid either
+    assert_close(redeem_for(&mut executor, &env, env.insurance_principal, env.eth), dec!("1000") / dec!("11"));
 
-    // Test the `instantiate_hello` function.
-    let transaction1 = TransactionBuilder::new()
-        .call_function(package, "Hello", "instantiate_hello", args![])
-        .build(executor.get_nonce([pk]))
-        .sign([&sk]);
-    let receipt1 = executor.validate_and_execute(&transaction1).unwrap();
-    println!("{:?}\n", receipt1);
-    assert!(receipt1.result.is_ok());
-
-    // Test the `free_token` method.
-    let component = receipt1.new_component_addresses[0];
-    let transaction2 = TransactionBuilder::new()
-        .call_method(component, "free_token", args![])
-        .call_method_with_all_resources(account, "deposit_batch")
-        .build(executor.get_nonce([pk]))
-        .sign([&sk]);
-    let receipt2 = executor.validate_and_execute(&transaction2).unwrap();
-    println!("{:?}\n", receipt2);
-    assert!(receipt2.result.is_ok());
+    // A settled pool can't be settled again
+    let transaction = TransactionBuilder::new()
+        .call_method(env.pool, "settle", args![])
+        .build(executor.get_nonce([env.provider.pk]))
+        .sign([&env.provider.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_err());
 }
@@ -4,10 +4,13 @@ use scrypto::prelude::*;
 // This is a very rough estimate, of course
 const EPOCHS_PER_YEAR: u64 = 15_000;
 
-// Currently all position info is centralized in the contract, no data stored in the position badge.
+// Currently all position info is centralized in the contract, only the liquidation status is stored in the position badge.
 #[derive(NonFungibleData)]
 struct PositionData {
-    is_liquidated: bool
+    #[scrypto(mutable)]
+    is_liquidated: bool,
+    #[scrypto(mutable)]
+    in_auction: bool
 }
 
 #[derive(std::fmt::Debug, scrypto::Encode, scrypto::Decode, scrypto::TypeId, scrypto::Describe, Copy, Clone)]
@@ -15,6 +18,16 @@ struct PositionInfo {
     collateral_amount: Decimal,
     loan_amount: Decimal,
     start_epoch: u64,
+    auction: Option<CollateralAuction>,
+}
+
+// A Dutch auction of the collateral of an undercollateralized position. The debt of the position is frozen
+// when the auction starts, and the discount on the collateral grows every epoch until the debt is covered.
+#[derive(std::fmt::Debug, scrypto::Encode, scrypto::Decode, scrypto::TypeId, scrypto::Describe, Copy, Clone)]
+struct CollateralAuction {
+    start_epoch: u64,
+    debt_at_start: Decimal,
+    collateral_at_start: Decimal,
 }
 
 blueprint! {
@@ -28,7 +41,25 @@ blueprint! {
         interest_rate: Decimal,
         positions_counter: u64,
         is_insolvent: bool,
-        oracle_address: ComponentAddress
+        // RAI left unbacked by auctions whose collateral ran out before the debt was covered, until it is burned with settle_bad_debt.
+        bad_debt: Decimal,
+        oracle_address: ComponentAddress,
+        // Discount on the collateral price when an auction starts, how much it grows every epoch, and its cap.
+        auction_initial_discount: Decimal,
+        auction_discount_per_epoch: Decimal,
+        auction_max_discount: Decimal,
+        // Reflex index - the redemption price of RAI drifts every epoch by the redemption rate, which is set by a PI controller
+        // from the deviation of the market price of RAI from the redemption price, every `rate_update_interval` epochs.
+        redemption_price: Decimal,
+        redemption_rate: Decimal,
+        redemption_price_epoch: u64,
+        rate_update_interval: u64,
+        proportional_gain: Decimal,
+        integral_gain: Decimal,
+        error_integral: Decimal,
+        // Bound on the accumulated error in either direction, so a long deviation cannot wind the integral term up without limit.
+        integral_limit: Decimal,
+        market_price_oracle: Option<ComponentAddress>
     }
 
     impl RaiTest {
@@ -60,6 +91,9 @@ blueprint! {
 
             let rules = AccessRules::new()
                 .method("update_interest_rate", rule!(require(admin_badge.resource_address())))
+                .method("update_auction_parameters", rule!(require(admin_badge.resource_address())))
+                .method("update_controller_parameters", rule!(require(admin_badge.resource_address())))
+                .method("set_market_price_oracle", rule!(require(admin_badge.resource_address())))
                 .default(rule!(allow_all));
 
             let mut component = Self {
@@ -71,7 +105,20 @@ blueprint! {
                 interest_rate: dec!("0.05"), // TODO - variable loan interest rate. For now, placeholder 5% interest rate.
                 positions_counter: 0,
                 is_insolvent: false,
-                oracle_address: oracle
+                bad_debt: dec!(0),
+                oracle_address: oracle,
+                auction_initial_discount: dec!("0"),
+                auction_discount_per_epoch: dec!("0.01"),
+                auction_max_discount: dec!("0.30"),
+                redemption_price: dec!(1), // RAI starts with a $1 redemption price.
+                redemption_rate: dec!(0),
+                redemption_price_epoch: Runtime::current_epoch(),
+                rate_update_interval: 24,
+                proportional_gain: dec!("0.0005"),
+                integral_gain: dec!("0.00001"),
+                error_integral: dec!(0),
+                integral_limit: dec!(10),
+                market_price_oracle: None
             }.instantiate();
 
             component.add_access_check(rules);
@@ -96,7 +143,7 @@ blueprint! {
             let position_badge = self.minter.authorize(|| {
                 let resource_manager = borrow_resource_manager!(self.position_resource);
                 resource_manager.mint_non_fungible(
-                    &position_id, PositionData {is_liquidated: false}
+                    &position_id, PositionData {is_liquidated: false, in_auction: false}
                 )
             });
 
@@ -104,6 +151,7 @@ blueprint! {
                 collateral_amount: new_position_collateral.amount(),
                 loan_amount: dec!(0),
                 start_epoch: Runtime::current_epoch(),
+                auction: None,
             };
             info!("Open Position - New position id {} {:?}", position_id, position_info);
 
@@ -127,10 +175,12 @@ blueprint! {
                 .expect("unauthorized access")
                 .non_fungible_id();                                
 
-            let required_collateral_xrd_amount = RaiTest::calc_required_collateral_xrd_amount(requested_rai, self.get_xrd_price());
+            self.update_redemption_rate_if_due();
+            let required_collateral_xrd_amount = RaiTest::calc_required_collateral_xrd_amount(requested_rai, self.get_xrd_price(), self.get_redemption_price());
             let position = self.positions.get_mut(&position_id).unwrap();
 
             // If collateral is available in position, allow mint.
+            assert!(position.auction.is_none(), "Position is being auctioned - drawing RAI is not allowed");
             info!("Draw - Position ID {} {:?}", position_id, position);
             info!("Minimum collateral required to maintain requested RAI loan - {} XRD", required_collateral_xrd_amount);
             assert!(required_collateral_xrd_amount < position.collateral_amount);
@@ -169,6 +219,7 @@ blueprint! {
 
             // Calculate loan principal + interest of position, and apply payment.
             let position = self.positions.get_mut(&position_id).unwrap();
+            assert!(position.auction.is_none(), "Position is being auctioned - the debt can only be paid down by taking the collateral auction");
             let principal_and_interest = RaiTest::calc_principal_and_interest(position.loan_amount, self.interest_rate, position.start_epoch);
 
             info!("Paydown - Position ID {} {:?}, P&I {} RAI", position_id, position, principal_and_interest);
@@ -204,6 +255,8 @@ blueprint! {
             let position = self.positions.get_mut(&position_id).unwrap();
             info!("Close Position - position id - {} {:?}", position_id, position);
 
+            assert!(position.auction.is_none(), "Position is being auctioned - it can be closed once the auction is over");
+
             assert!(
                 position.loan_amount == dec!(0),
                 "Position loan balance above 0 - please close position with payment"
@@ -239,6 +292,7 @@ blueprint! {
 
             let position_id = &position_badge.non_fungible::<PositionData>().id();
             let position = self.positions.get_mut(&position_id).unwrap();
+            assert!(position.auction.is_none(), "Position is being auctioned - it can be closed once the auction is over");
             let principal_and_interest = RaiTest::calc_principal_and_interest(position.loan_amount, self.interest_rate, position.start_epoch);
 
             info!("Close Position With Payment - position id - {} {:?}", position_id, position);
@@ -300,10 +354,12 @@ blueprint! {
                 .non_fungible_id();                                
 
             let xrd_price = self.get_xrd_price();
+            let redemption_price = self.get_redemption_price();
             let position = self.positions.get_mut(&position_id).unwrap();
+            assert!(position.auction.is_none(), "Position is being auctioned - withdrawing collateral is not allowed");
             let principal_and_interest = RaiTest::calc_principal_and_interest(position.loan_amount, self.interest_rate, position.start_epoch);
 
-            let required_collateral_xrd_amount = RaiTest::calc_required_collateral_xrd_amount(principal_and_interest, xrd_price);
+            let required_collateral_xrd_amount = RaiTest::calc_required_collateral_xrd_amount(principal_and_interest, xrd_price, redemption_price);
 
             info!("Partial Withdraw Collateral - Position ID {} - {:?}", position_id, position);
             info!("Position Principal and Interest - {} RAI, minimum collateral required to maintain position - {} XRD", principal_and_interest, required_collateral_xrd_amount);
@@ -317,65 +373,138 @@ blueprint! {
             withdrawal
         }
 
-        // Callable by anyone acting as a liquidator - provide undercollateralized position id and minimum RAI P&I payment to foreclose on position collateral
-        pub fn liquidate(&mut self, position_id: NonFungibleId, rai_payment: Bucket) -> (Bucket, Bucket) {
+        // Callable by anyone acting as a liquidator - provide undercollateralized position id to start a Dutch auction of its collateral.
+        // The debt of the position is frozen, and the collateral is offered at the oracle price with a discount that grows every epoch
+        // until the debt is covered through take_collateral.
+        pub fn liquidate(&mut self, position_id: NonFungibleId) {
             assert!(
                 self.is_insolvent == false,
                 "Protocol Insolvent - locked from liquidating positions and minting/burning RAI"
             );
-            assert!(
-                rai_payment.resource_address() == self.rai_resource,
-                "The rai_payment bucket does not contain RAI"
-            );
+            self.update_redemption_rate_if_due();
             let xrd_price = self.get_xrd_price();
+            let redemption_price = self.get_redemption_price();
             
             let position = self.positions.get_mut(&position_id).unwrap();
+            assert!(position.auction.is_none(), "Position is already being auctioned");
 
             let principal_and_interest = RaiTest::calc_principal_and_interest(position.loan_amount, self.interest_rate, position.start_epoch);
-            let required_collateral_xrd_amount = RaiTest::calc_required_collateral_xrd_amount(principal_and_interest, xrd_price);
+            let required_collateral_xrd_amount = RaiTest::calc_required_collateral_xrd_amount(principal_and_interest, xrd_price, redemption_price);
 
-            assert!(principal_and_interest < required_collateral_xrd_amount);
+            assert!(position.collateral_amount < required_collateral_xrd_amount, "Position is not undercollateralized");
             info!("Position id {} being liquidated, p&i is {} and required collateral xrd is {}, position only contains {} xrd collateral", 
                 position_id, principal_and_interest, required_collateral_xrd_amount, position.collateral_amount);
 
-            assert!(principal_and_interest < rai_payment.amount(), 
-                "Liquidation payment not enough to pay off debt");
-        
-            // If the liquidator can pay off the undercollateralized position, 
-            // let them payoff the position debt and send them the collateral along with any overpayment
-            let liquidated_collateral = self.pooled_collateral_vault.take(position.collateral_amount);
+            // Freeze the debt and start the auction.
+            position.loan_amount = principal_and_interest;
+            position.start_epoch = Runtime::current_epoch();
+            position.auction = Some(CollateralAuction {
+                start_epoch: Runtime::current_epoch(),
+                debt_at_start: principal_and_interest,
+                collateral_at_start: position.collateral_amount,
+            });
 
-            // Payoff the debt and burn the RAI payment from supply.
-            let remaining_rai_payment = self.burn_rai_payment(rai_payment, principal_and_interest);
+            info!("Started collateral auction for position id {} {:?}", position_id, position);
 
-            // Delete the position.
-            self.positions.remove(&position_id);
-            // Update the original position badge - either burn it, or lock it into soulbound token to create a position liquidation history.
-            // Since recallable access control is not yet implemented, just update the PositionData of the position badge to reflect that the position has been liquidated.
-            self.minter.authorize(|| {
-                let position_manager = borrow_resource_manager!(self.position_resource);
-                position_manager.update_non_fungible_data(&position_id, 
-                    PositionData {
-                        is_liquidated: true,
-                    })
-            });
+            self.update_position_data(&position_id, false, true);
+        }
+
+        // Callable by anyone - buy up to `collateral_amount` XRD from the auction of a position at the current auction price.
+        // The RAI paid pays down the debt of the position, so the amount taken is capped to what is needed to cover the debt. 
+        // Once the debt is covered, the auction ends and the remaining collateral is returned to the position owner through close_position.
+        pub fn take_collateral(&mut self, position_id: NonFungibleId, collateral_amount: Decimal, rai_payment: Bucket) -> (Bucket, Bucket) {
+            assert!(
+                rai_payment.resource_address() == self.rai_resource,
+                "The rai_payment bucket does not contain RAI"
+            );
+            self.update_redemption_rate_if_due();
+            let auction_price = self.get_auction_price(position_id.clone());
 
-            info!("Liquidate position id {} successful, releasing {} XRD collateral and returning {} RAI overpayment to the liquidator", 
-                position_id, liquidated_collateral.amount(), remaining_rai_payment.amount());
+            let position = self.positions.get_mut(&position_id).unwrap();
+            assert!(position.auction.is_some(), "Position is not being auctioned");
+
+            // Partial takes are allowed, but never more than the collateral left or than what covers the debt.
+            let mut take_amount = if collateral_amount < position.collateral_amount { collateral_amount } else { position.collateral_amount };
+            let mut cost = take_amount * auction_price;
+            if cost > position.loan_amount {
+                cost = position.loan_amount;
+                take_amount = cost / auction_price;
+            }
+            assert!(rai_payment.amount() >= cost, "Payment not enough to take {} XRD at {} RAI per XRD", take_amount, auction_price);
+
+            position.collateral_amount -= take_amount;
+            position.loan_amount -= cost;
+            let debt_covered = position.loan_amount == dec!(0);
+            let collateral_exhausted = position.collateral_amount == dec!(0);
+
+            info!("Took {} XRD from the auction of position id {} for {} RAI, {:?}", take_amount, position_id, cost, position);
+
+            if debt_covered || collateral_exhausted {
+                if !debt_covered {
+                    // The collateral did not cover the debt. The RAI minted for the rest stays in circulation, so record it as bad debt.
+                    info!("!! Auction of position id {} ended with {} RAI bad debt", position_id, position.loan_amount);
+                    self.bad_debt += position.loan_amount;
+                    position.loan_amount = dec!(0);
+                }
+                position.auction = None;
+                info!("Auction of position id {} ended, {} XRD collateral left to the owner", position_id, position.collateral_amount);
+                self.update_position_data(&position_id, true, false);
+            }
 
-            // After each liquidation, check protocol solvency.
+            let taken_collateral = self.pooled_collateral_vault.take(take_amount);
+            let remaining_rai_payment = self.burn_rai_payment(rai_payment, cost);
+
+            // After each auction take, check protocol solvency.
             self.check_protocol_solvency();
 
-            (liquidated_collateral, remaining_rai_payment)
+            (taken_collateral, remaining_rai_payment)
+        }
+
+        // Callable by anyone - burn RAI to cover the bad debt left by auctions, returning any RAI beyond it.
+        pub fn settle_bad_debt(&mut self, rai_payment: Bucket) -> Bucket {
+            assert!(
+                rai_payment.resource_address() == self.rai_resource,
+                "The rai_payment bucket does not contain RAI"
+            );
+            let settled_amount = if rai_payment.amount() < self.bad_debt { rai_payment.amount() } else { self.bad_debt };
+            self.bad_debt -= settled_amount;
+            info!("Settled {} RAI of bad debt, {} RAI bad debt left", settled_amount, self.bad_debt);
+
+            self.burn_rai_payment(rai_payment, settled_amount)
+        }
+
+        pub fn get_bad_debt(&self) -> Decimal {
+            self.bad_debt
+        }
+
+        // Current price in RAI of one XRD of collateral in the auction of a position.
+        pub fn get_auction_price(&self, position_id: NonFungibleId) -> Decimal {
+            let position = self.positions.get(&position_id).unwrap();
+            let auction = position.auction.expect("Position is not being auctioned");
+            self.calc_auction_price(auction.start_epoch)
+        }
+
+        // Allow the admin badge holder to change how fast the discount of collateral auctions grows.
+        pub fn update_auction_parameters(&mut self, initial_discount: Decimal, discount_per_epoch: Decimal, max_discount: Decimal) {
+            assert!(
+                initial_discount >= dec!(0) && discount_per_epoch >= dec!(0) && initial_discount <= max_discount && max_discount < dec!(1),
+                "Auction discounts must satisfy 0 <= initial discount <= max discount < 1"
+            );
+            self.auction_initial_discount = initial_discount;
+            self.auction_discount_per_epoch = discount_per_epoch;
+            self.auction_max_discount = max_discount;
+            info!("Updated auction parameters - initial discount {}, discount per epoch {}, max discount {}", initial_discount, discount_per_epoch, max_discount)
         }
 
         // When protocol is undercollateralized where locked collateral XRD value < RAI supply value, freeze opening and closing positions and only allow redemptions
         pub fn check_protocol_solvency(&mut self) {
             let rai_manager = borrow_resource_manager!(self.rai_resource);
             let total_rai_supply = rai_manager.total_supply();
+            let total_rai_value = total_rai_supply * self.get_redemption_price();
             let pooled_collateral_value = self.calc_xrd_value(self.pooled_collateral_vault.amount());
-            info!("Collateral pool xrd amount: {} XRD price: {} Pool value: {} Total RAI supply: {}", self.pooled_collateral_vault.amount(), self.get_xrd_price(), pooled_collateral_value, total_rai_supply);
-            if total_rai_supply > pooled_collateral_value {
+            info!("Collateral pool xrd amount: {} XRD price: {} Pool value: {} Total RAI supply: {} RAI value at redemption price: {}", 
+                self.pooled_collateral_vault.amount(), self.get_xrd_price(), pooled_collateral_value, total_rai_supply, total_rai_value);
+            if total_rai_value > pooled_collateral_value {
                 self.is_insolvent = true;
                 info!("!! Protocol is insolvent !! Freezing liquidations and new positions, redemptions against collateral pool allowed now");
             } else {
@@ -396,12 +525,16 @@ blueprint! {
                 rai_to_redeem.resource_address() == self.rai_resource,
                 "The rai_to_redeem bucket does not contain RAI"
             );
+            self.update_redemption_rate_if_due();
             let rai_manager = borrow_resource_manager!(self.rai_resource);
             let total_rai_supply = rai_manager.total_supply();
             info!("Total RAI supply outstanding: {}", total_rai_supply);
             let percentage_of_total = rai_to_redeem.amount() / total_rai_supply;
 
-            let collateral_redemption_amount = percentage_of_total * self.pooled_collateral_vault.amount();
+            // RAI is redeemed at the redemption price, but never for more than its share of the collateral pool.
+            let pro_rata_amount = percentage_of_total * self.pooled_collateral_vault.amount();
+            let redemption_value_amount = rai_to_redeem.amount() * self.get_redemption_price() / self.get_xrd_price();
+            let collateral_redemption_amount = if redemption_value_amount < pro_rata_amount { redemption_value_amount } else { pro_rata_amount };
 
             let redemption_collateral = self.pooled_collateral_vault.take(collateral_redemption_amount);
 
@@ -411,7 +544,7 @@ blueprint! {
                 rai_manager.burn(rai_to_redeem);
             });
             
-            info!("Redeem - {}% of RAI supply redemption, returning {}% of collateral pool - {} xrd", percentage_of_total*100, percentage_of_total*100, redemption_collateral.amount());
+            info!("Redeem - {}% of RAI supply redemption at redemption price {}, returning {} xrd", percentage_of_total*100, self.get_redemption_price(), redemption_collateral.amount());
 
             redemption_collateral
         }
@@ -423,13 +556,22 @@ blueprint! {
         // liquidation and allow manual inspection for liquidation.
         pub fn print_all_positions(&self) {
             let xrd_price = self.get_xrd_price();
-            info!("xrd price ${}", xrd_price);
+            let redemption_price = self.get_redemption_price();
+            info!("xrd price ${}, RAI redemption price ${}, bad debt {} RAI", xrd_price, redemption_price, self.bad_debt);
             for position_id in self.positions.keys() {
                 let position = self.positions.get(position_id).unwrap();
+                if let Some(auction) = position.auction {
+                    let discount = self.calc_auction_discount(auction.start_epoch);
+                    info!(
+                        "position_id {} {:?}, IN AUCTION - remaining debt {} RAI, collateral left {} XRD, discount {}, auction price {} RAI per XRD",
+                        position_id, position, position.loan_amount, position.collateral_amount, discount, self.calc_auction_price(auction.start_epoch)
+                    );
+                    continue;
+                }
                 trace!("a");
                 let principal_and_interest = RaiTest::calc_principal_and_interest(position.loan_amount, self.interest_rate, position.start_epoch);
                 trace!("b");
-                let required_collateral_amount = RaiTest::calc_required_collateral_xrd_amount(principal_and_interest, self.get_xrd_price());
+                let required_collateral_amount = RaiTest::calc_required_collateral_xrd_amount(principal_and_interest, xrd_price, redemption_price);
                 trace!("c");
                 let required_collateral_value = required_collateral_amount * xrd_price;
                 trace!("d");
@@ -453,6 +595,10 @@ blueprint! {
         // In this fashion, historical interest accrued at previous interest rates is included in calculations as the interest rate varies.
         pub fn update_interest_rate(&mut self, new_interest_rate: Decimal) {
             for (position_id, position) in self.positions.iter_mut() {
+                // The debt of positions being auctioned is frozen.
+                if position.auction.is_some() {
+                    continue;
+                }
                 let principal_and_interest = RaiTest::calc_principal_and_interest(position.loan_amount, self.interest_rate, position.start_epoch);
                 position.loan_amount = principal_and_interest;
                 position.start_epoch = Runtime::current_epoch();
@@ -462,6 +608,89 @@ blueprint! {
             info!("Updated interest rate - new interest rate {}", self.interest_rate)
         }
 
+        // Current redemption price of RAI, drifted by the redemption rate since the last update of the controller.
+        // The drift is floored just above -100% per epoch, a rate of -1 or below would take the price to zero or flip its sign.
+        pub fn get_redemption_price(&self) -> Decimal {
+            let number_of_epochs = (Runtime::current_epoch() - self.redemption_price_epoch).try_into().expect("error converting epochs u64 to i64");
+            let redemption_rate = if self.redemption_rate < dec!("-0.99") { dec!("-0.99") } else { self.redemption_rate };
+            self.redemption_price * (dec!(1) + redemption_rate).powi(number_of_epochs)
+        }
+
+        pub fn get_redemption_rate(&self) -> Decimal {
+            self.redemption_rate
+        }
+
+        // Callable by anyone - run the PI controller once `rate_update_interval` epochs have passed since its last run.
+        pub fn update_redemption_rate(&mut self) {
+            assert!(
+                Runtime::current_epoch() - self.redemption_price_epoch >= self.rate_update_interval,
+                "Redemption rate can only be updated every {} epochs", self.rate_update_interval
+            );
+            self.update_redemption_rate_if_due();
+        }
+
+        pub fn get_error_integral(&self) -> Decimal {
+            self.error_integral
+        }
+
+        // Allow the admin badge holder to tune the PI controller. The gains turn the relative price error into a redemption rate per epoch,
+        // and the integral limit caps the accumulated error the integral term works from.
+        pub fn update_controller_parameters(&mut self, proportional_gain: Decimal, integral_gain: Decimal, integral_limit: Decimal, rate_update_interval: u64) {
+            assert!(rate_update_interval > 0, "Rate update interval must be at least one epoch");
+            assert!(integral_limit >= dec!(0), "Integral limit must not be negative");
+            // Settle the redemption price at the current rate before the controller changes.
+            self.redemption_price = self.get_redemption_price();
+            self.redemption_price_epoch = Runtime::current_epoch();
+            self.proportional_gain = proportional_gain;
+            self.integral_gain = integral_gain;
+            self.integral_limit = integral_limit;
+            self.error_integral = Self::clamp_error_integral(self.error_integral, integral_limit);
+            self.rate_update_interval = rate_update_interval;
+            info!("Updated controller parameters - Kp {}, Ki {}, integral limit {}, update interval {} epochs", proportional_gain, integral_gain, integral_limit, rate_update_interval)
+        }
+
+        // Allow the admin badge holder to set the component providing the market price of RAI. It must expose `get_price`, like the OraclePlaceholder.
+        pub fn set_market_price_oracle(&mut self, market_price_oracle: ComponentAddress) {
+            self.market_price_oracle = Some(market_price_oracle);
+            info!("Market price oracle for RAI set to {}", market_price_oracle)
+        }
+
+        // Move the redemption price forward and compute the new redemption rate from the market price of RAI.
+        // A market price below the redemption price gives a positive rate, making RAI more expensive to owe and more attractive
+        // to hold, which pushes the market price back up - and the other way around.
+        fn update_redemption_rate_if_due(&mut self) {
+            let current_epoch = Runtime::current_epoch();
+            if current_epoch - self.redemption_price_epoch < self.rate_update_interval {
+                return;
+            }
+            let redemption_price = self.get_redemption_price();
+            self.redemption_price = redemption_price;
+            self.redemption_price_epoch = current_epoch;
+
+            if let Some(market_price_oracle) = self.market_price_oracle {
+                let oracle: OraclePlaceholder = market_price_oracle.into();
+                let market_price = oracle.get_price();
+                let error = (redemption_price - market_price) / redemption_price;
+                self.error_integral = Self::clamp_error_integral(self.error_integral + error, self.integral_limit);
+                self.redemption_rate = self.proportional_gain * error + self.integral_gain * self.error_integral;
+                info!("Redemption price {} market price {} error {} - new redemption rate {} per epoch", redemption_price, market_price, error, self.redemption_rate);
+            }
+        }
+
+        fn clamp_error_integral(error_integral: Decimal, integral_limit: Decimal) -> Decimal {
+            if error_integral > integral_limit {
+                integral_limit
+            } else if error_integral < -integral_limit {
+                -integral_limit
+            } else {
+                error_integral
+            }
+        }
+
+        fn calc_auction_price(&self, auction_start_epoch: u64) -> Decimal {
+            self.get_xrd_price() / self.get_redemption_price() * (dec!(1) - self.calc_auction_discount(auction_start_epoch))
+        }
+
         fn get_xrd_price(&self) -> Decimal {
             let oracle: OraclePlaceholder = self.oracle_address.into();
             oracle.get_price()
@@ -471,8 +700,25 @@ blueprint! {
             xrd_amount * self.get_xrd_price()
         }
 
-        fn calc_required_collateral_xrd_amount(loan_amount: Decimal, xrd_price: Decimal) -> Decimal {
-            let required_collateral_value = loan_amount * dec!("1.50");
+        fn calc_auction_discount(&self, auction_start_epoch: u64) -> Decimal {
+            let elapsed_epochs = Runtime::current_epoch() - auction_start_epoch;
+            let discount = self.auction_initial_discount + self.auction_discount_per_epoch * elapsed_epochs;
+            if discount > self.auction_max_discount { self.auction_max_discount } else { discount }
+        }
+
+        fn update_position_data(&self, position_id: &NonFungibleId, is_liquidated: bool, in_auction: bool) {
+            self.minter.authorize(|| {
+                let position_manager = borrow_resource_manager!(self.position_resource);
+                position_manager.update_non_fungible_data(position_id, 
+                    PositionData {
+                        is_liquidated,
+                        in_auction,
+                    })
+            });
+        }
+
+        fn calc_required_collateral_xrd_amount(loan_amount: Decimal, xrd_price: Decimal, redemption_price: Decimal) -> Decimal {
+            let required_collateral_value = loan_amount * redemption_price * dec!("1.50");
             required_collateral_value / xrd_price
         }
 
@@ -1,32 +1,272 @@
 use radix_engine::ledger::*;
-use radix_engine::transaction::*;
+use radix_engine::transaction::TransactionReceipt;
+use scrypto::core::NetworkDefinition;
 use scrypto::prelude::*;
+use scrypto_unit::*;
+use transaction::builder::ManifestBuilder;
+
+struct TestEnv {
+    public_key: EcdsaSecp256k1PublicKey,
+    account: ComponentAddress,
+    oracle: ComponentAddress,
+    market_oracle: ComponentAddress,
+    rai_test: ComponentAddress,
+    admin_badge: ResourceAddress,
+    position_resource: ResourceAddress,
+    rai_resource: ResourceAddress,
+}
+
+// Publishes the oracle placeholder and RaiTest with a second placeholder for the RAI market price, and opens position 0 with 100 XRD drawing 5 RAI at the default XRD price of $0.10
+fn setup(test_runner: &mut TestRunner<TypedInMemorySubstateStore>) -> TestEnv {
+    let (public_key, _private_key, account) = test_runner.new_account();
+    let oracle_package = test_runner.compile_and_publish(concat!(env!("CARGO_MANIFEST_DIR"), "/dependencies/oracle_placeholder"));
+    let package = test_runner.compile_and_publish(this_package!());
+
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .call_function(oracle_package, "OraclePlaceholder", "new", args!())
+        .call_function(oracle_package, "OraclePlaceholder", "new", args!())
+        .build();
+    let receipt = test_runner.execute_manifest_ignoring_fee(manifest, vec![public_key.into()]);
+    receipt.expect_commit_success();
+    let oracle = receipt.expect_commit().entity_changes.new_component_addresses[0];
+    let market_oracle = receipt.expect_commit().entity_changes.new_component_addresses[1];
+
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .call_function(package, "RaiTest", "new", args!(oracle))
+        .call_method(account, "deposit_batch", args!(Expression::entire_worktop()))
+        .build();
+    let receipt = test_runner.execute_manifest_ignoring_fee(manifest, vec![public_key.into()]);
+    receipt.expect_commit_success();
+    let result = receipt.expect_commit();
+    let rai_test = result.entity_changes.new_component_addresses[0];
+    let admin_badge = result.entity_changes.new_resource_addresses[0];
+    let position_resource = result.entity_changes.new_resource_addresses[2];
+    let rai_resource = result.entity_changes.new_resource_addresses[3];
+
+    let env = TestEnv { public_key, account, oracle, market_oracle, rai_test, admin_badge, position_resource, rai_resource };
+
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .withdraw_from_account_by_amount(dec!("100"), RADIX_TOKEN, account)
+        .take_from_worktop(RADIX_TOKEN, |builder, bucket_id| {
+            builder.call_method(rai_test, "open_position", args!(Bucket(bucket_id)))
+        })
+        .call_method(account, "deposit_batch", args!(Expression::entire_worktop()))
+        .build();
+    execute(test_runner, &env, manifest).expect_commit_success();
+
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .create_proof_from_account(position_resource, account)
+        .pop_from_auth_zone(|builder, proof_id| {
+            builder.call_method(rai_test, "draw", args!(Proof(proof_id), dec!("5")))
+        })
+        .call_method(account, "deposit_batch", args!(Expression::entire_worktop()))
+        .build();
+    execute(test_runner, &env, manifest).expect_commit_success();
+
+    env
+}
+
+fn execute(test_runner: &mut TestRunner<TypedInMemorySubstateStore>, env: &TestEnv, manifest: transaction::model::TransactionManifest) -> TransactionReceipt {
+    test_runner.execute_manifest_ignoring_fee(manifest, vec![env.public_key.into()])
+}
+
+fn set_price(test_runner: &mut TestRunner<TypedInMemorySubstateStore>, env: &TestEnv, oracle: ComponentAddress, price: Decimal) {
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .call_method(oracle, "set_price", args!(price))
+        .build();
+    execute(test_runner, env, manifest).expect_commit_success();
+}
+
+fn set_xrd_price(test_runner: &mut TestRunner<TypedInMemorySubstateStore>, env: &TestEnv, price: Decimal) {
+    set_price(test_runner, env, env.oracle, price);
+}
+
+fn call_as_admin(test_runner: &mut TestRunner<TypedInMemorySubstateStore>, env: &TestEnv, method: &str, args: Vec<u8>) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .create_proof_from_account(env.admin_badge, env.account)
+        .call_method(env.rai_test, method, args)
+        .build();
+    execute(test_runner, env, manifest)
+}
+
+fn update_redemption_rate(test_runner: &mut TestRunner<TypedInMemorySubstateStore>, env: &TestEnv) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .call_method(env.rai_test, "update_redemption_rate", args!())
+        .build();
+    execute(test_runner, env, manifest)
+}
+
+fn liquidate(test_runner: &mut TestRunner<TypedInMemorySubstateStore>, env: &TestEnv) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .call_method(env.rai_test, "liquidate", args!(NonFungibleId::from_u64(0)))
+        .build();
+    execute(test_runner, env, manifest)
+}
+
+// Buys up to `collateral_amount` XRD from the auction of position 0, offering `rai_amount` RAI
+fn take_collateral(test_runner: &mut TestRunner<TypedInMemorySubstateStore>, env: &TestEnv, collateral_amount: Decimal, rai_amount: Decimal) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .withdraw_from_account_by_amount(rai_amount, env.rai_resource, env.account)
+        .take_from_worktop(env.rai_resource, |builder, bucket_id| {
+            builder.call_method(env.rai_test, "take_collateral", args!(NonFungibleId::from_u64(0), collateral_amount, Bucket(bucket_id)))
+        })
+        .call_method(env.account, "deposit_batch", args!(Expression::entire_worktop()))
+        .build();
+    execute(test_runner, env, manifest)
+}
+
+fn close_position(test_runner: &mut TestRunner<TypedInMemorySubstateStore>, env: &TestEnv) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .withdraw_from_account(env.position_resource, env.account)
+        .take_from_worktop(env.position_resource, |builder, bucket_id| {
+            builder.call_method(env.rai_test, "close_position", args!(Bucket(bucket_id)))
+        })
+        .call_method(env.account, "deposit_batch", args!(Expression::entire_worktop()))
+        .build();
+    execute(test_runner, env, manifest)
+}
+
+fn get_decimal(test_runner: &mut TestRunner<TypedInMemorySubstateStore>, env: &TestEnv, method: &str, args: Vec<u8>) -> Decimal {
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .call_method(env.rai_test, method, args)
+        .build();
+    let receipt = execute(test_runner, env, manifest);
+    receipt.expect_commit_success();
+    receipt.output::<Decimal>(1)
+}
+
+fn get_balance(test_runner: &mut TestRunner<TypedInMemorySubstateStore>, env: &TestEnv, resource: ResourceAddress) -> Decimal {
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .call_method(env.account, "balance", args!(resource))
+        .build();
+    execute(test_runner, env, manifest).output::<Decimal>(1)
+}
+
+#[test]
+fn test_auction_partial_takes_return_leftover_collateral_to_owner() {
+    let mut store = TypedInMemorySubstateStore::with_bootstrap();
+    let mut test_runner = TestRunner::new(false, &mut store);
+    let env = setup(&mut test_runner);
+
+    // 100 XRD at $0.10 backs 5 RAI with room to spare
+    liquidate(&mut test_runner, &env).expect_commit_failure();
+
+    // At $0.06 the position needs 125 XRD, the auction starts at the oracle price and is 10% off after 10 epochs
+    set_xrd_price(&mut test_runner, &env, dec!("0.06"));
+    liquidate(&mut test_runner, &env).expect_commit_success();
+    test_runner.set_current_epoch(10);
+    assert_eq!(get_decimal(&mut test_runner, &env, "get_auction_price", args!(NonFungibleId::from_u64(0))), dec!("0.054"));
+
+    // A partial take pays down part of the debt and keeps the auction going
+    take_collateral(&mut test_runner, &env, dec!("50"), dec!("5")).expect_commit_success();
+    assert_eq!(get_balance(&mut test_runner, &env, env.rai_resource), dec!("2.3"));
+    close_position(&mut test_runner, &env).expect_commit_failure();
+
+    // Asking for more than covers the debt only takes what is needed, and the rest goes back to the owner
+    take_collateral(&mut test_runner, &env, dec!("100"), dec!("2.3")).expect_commit_success();
+    assert_eq!(get_balance(&mut test_runner, &env, env.rai_resource), dec!("0"));
+    assert_eq!(get_decimal(&mut test_runner, &env, "get_bad_debt", args!()), dec!("0"));
+
+    let xrd_before = get_balance(&mut test_runner, &env, RADIX_TOKEN);
+    close_position(&mut test_runner, &env).expect_commit_success();
+    let leftover = get_balance(&mut test_runner, &env, RADIX_TOKEN) - xrd_before;
+    assert!(leftover > dec!("7.40") && leftover < dec!("7.41"), "leftover collateral {}", leftover);
+}
+
+#[test]
+fn test_auction_shortfall_is_recorded_as_bad_debt_and_settled() {
+    let mut store = TypedInMemorySubstateStore::with_bootstrap();
+    let mut test_runner = TestRunner::new(false, &mut store);
+    let env = setup(&mut test_runner);
+
+    // At $0.02 and the maximum 30% discount all 100 XRD only cover 1.4 of the 5 RAI owed
+    set_xrd_price(&mut test_runner, &env, dec!("0.02"));
+    liquidate(&mut test_runner, &env).expect_commit_success();
+    test_runner.set_current_epoch(30);
+    take_collateral(&mut test_runner, &env, dec!("100"), dec!("5")).expect_commit_success();
+    assert_eq!(get_balance(&mut test_runner, &env, env.rai_resource), dec!("3.6"));
+    assert_eq!(get_decimal(&mut test_runner, &env, "get_bad_debt", args!()), dec!("3.6"));
+
+    // The RAI still in circulation is burned against the bad debt, any excess is returned
+    let manifest = ManifestBuilder::new(&NetworkDefinition::simulator())
+        .withdraw_from_account_by_amount(dec!("3.6"), env.rai_resource, env.account)
+        .take_from_worktop(env.rai_resource, |builder, bucket_id| {
+            builder.call_method(env.rai_test, "settle_bad_debt", args!(Bucket(bucket_id)))
+        })
+        .call_method(env.account, "deposit_batch", args!(Expression::entire_worktop()))
+        .build();
+    execute(&mut test_runner, &env, manifest).expect_commit_success();
+    assert_eq!(get_decimal(&mut test_runner, &env, "get_bad_debt", args!()), dec!("0"));
+    assert_eq!(get_balance(&mut test_runner, &env, env.rai_resource), dec!("0"));
+
+    // The position has no debt left and can be closed
+    close_position(&mut test_runner, &env).expect_commit_success();
+}
+
+#[test]
+fn test_error_integral_is_clamped_to_integral_limit() {
+    let mut store = TypedInMemorySubstateStore::with_bootstrap();
+    let mut test_runner = TestRunner::new(false, &mut store);
+    let env = setup(&mut test_runner);
+
+    call_as_admin(&mut test_runner, &env, "set_market_price_oracle", args!(env.market_oracle)).expect_commit_success();
+    call_as_admin(&mut test_runner, &env, "update_controller_parameters", args!(dec!("0.0005"), dec!("0.00001"), dec!("-1"), 1u64)).expect_commit_failure();
+    call_as_admin(&mut test_runner, &env, "update_controller_parameters", args!(dec!("0.0005"), dec!("0.00001"), dec!("2"), 1u64)).expect_commit_success();
+
+    // RAI trades at $0.50 against a redemption price of about $1, an error of about 0.5 every epoch
+    set_price(&mut test_runner, &env, env.market_oracle, dec!("0.5"));
+    update_redemption_rate(&mut test_runner, &env).expect_commit_failure();
+    for epoch in 1..=3 {
+        test_runner.set_current_epoch(epoch);
+        update_redemption_rate(&mut test_runner, &env).expect_commit_success();
+    }
+    let integral = get_decimal(&mut test_runner, &env, "get_error_integral", args!());
+    assert!(integral > dec!("1.5") && integral < dec!("1.51"), "error integral {}", integral);
+
+    // The integral stops at the limit however long the deviation lasts
+    for epoch in 4..=10 {
+        test_runner.set_current_epoch(epoch);
+        update_redemption_rate(&mut test_runner, &env).expect_commit_success();
+    }
+    assert_eq!(get_decimal(&mut test_runner, &env, "get_error_integral", args!()), dec!("2"));
+    let rate = get_decimal(&mut test_runner, &env, "get_redemption_rate", args!());
+    assert!(rate > dec!("0") && rate < dec!("0.0005") * dec!("0.51") + dec!("0.00001") * dec!("2"), "redemption rate {}", rate);
+
+    // Once RAI trades above the redemption price the integral unwinds from the limit rather than from the whole accumulated error
+    set_price(&mut test_runner, &env, env.market_oracle, dec!("4"));
+    test_runner.set_current_epoch(11);
+    update_redemption_rate(&mut test_runner, &env).expect_commit_success();
+    let integral = get_decimal(&mut test_runner, &env, "get_error_integral", args!());
+    assert!(integral < dec!("0") && integral > dec!("-2"), "error integral {}", integral);
+    assert!(get_decimal(&mut test_runner, &env, "get_redemption_rate", args!()) < dec!("0"));
+
+    test_runner.set_current_epoch(12);
+    update_redemption_rate(&mut test_runner, &env).expect_commit_success();
+    assert_eq!(get_decimal(&mut test_runner, &env, "get_error_integral", args!()), dec!("-2"));
+
+    // Lowering the limit clamps the integral already accumulated
+    call_as_admin(&mut test_runner, &env, "update_controller_parameters", args!(dec!("0.0005"), dec!("0.00001"), dec!("1"), 1u64)).expect_commit_success();
+    assert_eq!(get_decimal(&mut test_runner, &env, "get_error_integral", args!()), dec!("-1"));
+}
 
 #[test]
-fn test_hello() {
-    // Set up environment.
-    let mut ledger = InMemorySubstateStore::with_bootstrap();
-    let mut executor = TransactionExecutor::new(&mut ledger, false);
-    let (pk, sk, account) = executor.new_account();
-    let package = executor.publish_package(compile_package!()).unwrap();
-
-    // Test the `instantiate_hello` function.
-    let transaction1 = TransactionBuilder::new()
-        .call_function(package, "Hello", "instantiate_hello", args![])
-        .build(executor.get_nonce([pk]))
-        .sign([&sk]);
-    let receipt1 = executor.validate_and_execute(&transaction1).unwrap();
-    println!("{:?}\n", receipt1);
-    assert!(receipt1.result.is_ok());
-
-    // Test the `free_token` method.
-    let component = receipt1.new_component_addresses[0];
-    let transaction2 = TransactionBuilder::new()
-        .call_method(component, "free_token", args![])
-        .call_method_with_all_resources(account, "deposit_batch")
-        .build(executor.get_nonce([pk]))
-        .sign([&sk]);
-    let receipt2 = executor.validate_and_execute(&transaction2).unwrap();
-    println!("{:?}\n", receipt2);
-    assert!(receipt2.result.is_ok());
+fn test_redemption_price_stays_positive_below_minus_one_rate() {
+    let mut store = TypedInMemorySubstateStore::with_bootstrap();
+    let mut test_runner = TestRunner::new(false, &mut store);
+    let env = setup(&mut test_runner);
+
+    call_as_admin(&mut test_runner, &env, "set_market_price_oracle", args!(env.market_oracle)).expect_commit_success();
+    call_as_admin(&mut test_runner, &env, "update_controller_parameters", args!(dec!("2"), dec!("0"), dec!("10"), 1u64)).expect_commit_success();
+
+    // RAI trades at $4 against a redemption price of $1, an error of -3 and a rate of -6 per epoch
+    set_price(&mut test_runner, &env, env.market_oracle, dec!("4"));
+    test_runner.set_current_epoch(1);
+    update_redemption_rate(&mut test_runner, &env).expect_commit_success();
+    assert_eq!(get_decimal(&mut test_runner, &env, "get_redemption_rate", args!()), dec!("-6"));
+
+    // The price drifts down by 99% per epoch rather than turning negative
+    test_runner.set_current_epoch(2);
+    assert_eq!(get_decimal(&mut test_runner, &env, "get_redemption_price", args!()), dec!("0.01"));
+    test_runner.set_current_epoch(3);
+    assert_eq!(get_decimal(&mut test_runner, &env, "get_redemption_price", args!()), dec!("0.0001"));
 }
@@ -1,6 +1,42 @@
 use sbor::*;
 use scrypto::prelude::*;
 
+/// An income share agreement: instead of posting collateral, the student pays back a share of their income
+#[derive(NonFungibleData)]
+pub struct IncomeShareAgreement {
+    /// The user badge of the student
+    pub student: ResourceAddress,
+    /// The amount lent to the student
+    pub principal: Decimal,
+    /// The share of each reported income the student has to pay
+    pub income_share: Decimal,
+    /// The most the student will ever pay
+    pub payment_cap: Decimal,
+    /// Epoch at which the agreement started
+    pub start_epoch: u64,
+    /// Epoch at which the agreement ends, no income is reported after it
+    pub end_epoch: u64,
+    /// Total paid so far
+    #[scrypto(mutable)]
+    pub total_paid: Decimal,
+    /// Amount owed on reported income that has not been paid yet
+    #[scrypto(mutable)]
+    pub amount_due: Decimal,
+}
+
+/// The terms of the income share agreements a pool offers
+#[derive(Debug, Clone, TypeId, Encode, Decode, Describe, PartialEq, Eq)]
+pub struct IsaTerms {
+    /// The badge of the trusted party reporting the students' income
+    pub attestor_badge: ResourceAddress,
+    /// The share of each reported income the student has to pay
+    pub income_share: Decimal,
+    /// Payments are capped at this multiple of the principal
+    pub payment_cap_multiple: Decimal,
+    /// Number of epochs an agreement lasts
+    pub term_epochs: u64,
+}
+
 blueprint! {
     struct StudentLend {
         /// The liquidity pool
@@ -19,11 +55,74 @@ blueprint! {
         deposit_interest_rate: Decimal,
         /// The (stable) interest rate of loans, per epoch
         borrow_interest_rate: Decimal,
+        /// The income share agreement terms, if the pool offers them
+        isa_terms: Option<IsaTerms>,
+        /// Badge used to mint and update the income share agreement NFTs
+        admin_badge: Vault,
+        /// Resource address of the income share agreement NFTs
+        isa_nft_address: ResourceAddress,
+        /// The user badges minted by this pool, only these identify a user
+        user_badges: HashSet<ResourceAddress>,
+        /// The largest principal the attestor approved for each student's next agreement
+        isa_approvals: HashMap<ResourceAddress, Decimal>,
+        /// The latest agreement of each student
+        active_isas: HashMap<ResourceAddress, NonFungibleId>,
     }
 
     impl StudentLend {
         /// Creates a lending pool, with single collateral.
         pub fn instantiate_studentlend(reserve_address: ResourceAddress, the_borrow_rate: Decimal) -> ComponentAddress {
+            Self::instantiate_pool(reserve_address, the_borrow_rate, None)
+        }
+
+        /// Creates a lending pool which also offers uncollateralized income share agreements.
+        /// Students pay `income_share` of the income reported by the holder of `attestor_badge`,
+        /// up to `payment_cap_multiple` times the principal, for `term_epochs` epochs
+        pub fn instantiate_studentlend_with_isa(
+            reserve_address: ResourceAddress,
+            the_borrow_rate: Decimal,
+            attestor_badge: ResourceAddress,
+            income_share: Decimal,
+            payment_cap_multiple: Decimal,
+            term_epochs: u64,
+        ) -> ComponentAddress {
+            assert!(
+                income_share > Decimal::zero() && income_share <= Decimal::one(),
+                "Income share must be between 0 and 1"
+            );
+            assert!(payment_cap_multiple > Decimal::zero(), "Payment cap must be positive");
+            assert!(term_epochs > 0, "Term must be at least one epoch");
+
+            Self::instantiate_pool(
+                reserve_address,
+                the_borrow_rate,
+                Some(IsaTerms {
+                    attestor_badge,
+                    income_share,
+                    payment_cap_multiple,
+                    term_epochs,
+                }),
+            )
+        }
+
+        fn instantiate_pool(
+            reserve_address: ResourceAddress,
+            the_borrow_rate: Decimal,
+            isa_terms: Option<IsaTerms>,
+        ) -> ComponentAddress {
+            let admin_badge = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Student Loan Admin Badge")
+                .initial_supply(1);
+
+            // Like the user badge, agreements are soulbound so the student can't get rid of the obligation
+            let isa_nft_address = ResourceBuilder::new_non_fungible()
+                .metadata("name", "Student Loan Income Share Agreement")
+                .mintable(rule!(require(admin_badge.resource_address())), LOCKED)
+                .updateable_non_fungible_data(rule!(require(admin_badge.resource_address())), LOCKED)
+                .restrict_withdraw(rule!(deny_all), LOCKED)
+                .no_initial_supply();
+
             Self {
                 liquidity_pool: Vault::new(reserve_address),
                 min_collateral_ratio: dec!("0.01"),
@@ -33,23 +132,31 @@ blueprint! {
                 users: LazyMap::new(),
                 deposit_interest_rate: dec!("0.01"),
                 borrow_interest_rate: the_borrow_rate,
+                isa_terms,
+                admin_badge: Vault::with_bucket(admin_badge),
+                isa_nft_address,
+                user_badges: HashSet::new(),
+                isa_approvals: HashMap::new(),
+                active_isas: HashMap::new(),
             }
             .instantiate()
             .globalize()
         }
 
         /// Registers a new user
-        pub fn new_user(&self) -> Bucket {
-            ResourceBuilder::new_fungible()
+        pub fn new_user(&mut self) -> Bucket {
+            let badge = ResourceBuilder::new_fungible()
                 .divisibility(DIVISIBILITY_NONE)
                 .restrict_withdraw( rule!(deny_all), LOCKED )
                 .metadata("name", "Student Loan User Badge")
-                .initial_supply(1)
+                .initial_supply(1);
+            self.user_badges.insert(badge.resource_address());
+            badge
         }
 
         /// Deposits into the liquidity pool and start earning interest.
         pub fn deposit(&mut self, user_auth: Proof, reserve_tokens: Bucket) {
-            let user_id = Self::get_user_id(user_auth);
+            let user_id = self.get_user_id(user_auth);
             let amount = reserve_tokens.amount();
 
             // Update user state
@@ -76,7 +183,7 @@ blueprint! {
 
         /// Redeems the underlying assets, partially or in full.
         pub fn redeem(&mut self, user_auth: Proof, amount: Decimal) -> Bucket {
-            let user_id = Self::get_user_id(user_auth);
+            let user_id = self.get_user_id(user_auth);
 
             // Update user state
             let mut user = self.get_user(user_id);
@@ -96,7 +203,7 @@ blueprint! {
 
         /// Borrows the specified amount from lending pool
         pub fn borrow(&mut self, user_auth: Proof, requested: Decimal) -> Bucket {
-            let user_id = Self::get_user_id(user_auth);
+            let user_id = self.get_user_id(user_auth);
 
             assert!(
                 requested <= self.liquidity_pool.amount() * self.max_borrow_percent,
@@ -116,7 +223,7 @@ blueprint! {
 
         /// Repays a loan, partially or in full.
         pub fn repay(&mut self, user_auth: Proof, mut repaid: Bucket) -> Bucket {
-            let user_id = Self::get_user_id(user_auth);
+            let user_id = self.get_user_id(user_auth);
 
             // Update user state
             let mut user = self.get_user(user_id);
@@ -130,6 +237,7 @@ blueprint! {
         }
 
         /// Liquidates one user's position, if it's under collateralized.
+        /// Income share agreements are not collateralized and can't be liquidated
         pub fn liquidate(&mut self, user_id: ResourceAddress, repaid: Bucket) -> Bucket {
             let mut user = self.get_user(user_id);
 
@@ -159,6 +267,121 @@ blueprint! {
             to_return
         }
 
+        /// Approves a student for an income share agreement of up to `max_principal`.
+        /// Only the attestor can approve, and the approval is used up by the next agreement
+        pub fn approve_isa(&mut self, attestor_auth: Proof, student: ResourceAddress, max_principal: Decimal) {
+            self.check_attestor(attestor_auth);
+            assert!(self.user_badges.contains(&student), "User not found");
+            assert!(max_principal > Decimal::zero(), "Nothing approved");
+
+            self.isa_approvals.insert(student, max_principal);
+        }
+
+        /// Lends the requested amount under an income share agreement, no collateral is needed.
+        /// The attestor must have approved the student for at least this amount, and the student's
+        /// previous agreement must be complete. Returns the borrowed tokens and the agreement NFT
+        pub fn borrow_isa(&mut self, user_auth: Proof, requested: Decimal) -> (Bucket, Bucket) {
+            let terms = self.isa_terms.clone().expect("Income share agreements are not offered");
+            let user_id = self.get_user_id(user_auth);
+
+            assert!(requested > Decimal::zero(), "Nothing requested");
+            assert!(
+                requested <= self.liquidity_pool.amount() * self.max_borrow_percent,
+                "Max borrow percent exceeded"
+            );
+            if let Some(isa_id) = self.active_isas.get(&user_id) {
+                assert!(
+                    self.is_isa_complete(isa_id.clone()),
+                    "The student already has an active agreement"
+                );
+            }
+            let approved = self.isa_approvals.remove(&user_id).expect("The student is not approved");
+            assert!(requested <= approved, "Requested more than approved");
+
+            let agreement = IncomeShareAgreement {
+                student: user_id,
+                principal: requested,
+                income_share: terms.income_share,
+                payment_cap: requested * terms.payment_cap_multiple,
+                start_epoch: Runtime::current_epoch(),
+                end_epoch: Runtime::current_epoch() + terms.term_epochs,
+                total_paid: Decimal::zero(),
+                amount_due: Decimal::zero(),
+            };
+            let isa_id = NonFungibleId::random();
+            let isa_nft_address = self.isa_nft_address;
+            let isa = self.admin_badge.authorize(|| {
+                borrow_resource_manager!(isa_nft_address)
+                    .mint_non_fungible(&isa_id, agreement)
+            });
+            self.active_isas.insert(user_id, isa_id);
+
+            (self.liquidity_pool.take(requested), isa)
+        }
+
+        /// Records income earned by a student, making the income share of it due.
+        /// Only the attestor can report income, and only during the agreement's term.
+        /// Nothing more becomes due once the payment cap is reached
+        pub fn report_income(&mut self, attestor_auth: Proof, isa_id: NonFungibleId, income: Decimal) {
+            self.check_attestor(attestor_auth);
+            assert!(income >= Decimal::zero(), "Income can't be negative");
+
+            let mut agreement = self.isa_data(&isa_id);
+            assert!(Runtime::current_epoch() < agreement.end_epoch, "The agreement has ended");
+
+            let remaining_cap = agreement.payment_cap - agreement.total_paid - agreement.amount_due;
+            agreement.amount_due += std::cmp::min(income * agreement.income_share, remaining_cap);
+
+            self.update_isa(&isa_id, agreement);
+        }
+
+        /// Pays what is due on an income share agreement, anything paid above that is returned.
+        /// Amounts that became due before the agreement ended stay payable after it
+        pub fn pay_isa(&mut self, isa_id: NonFungibleId, mut payment: Bucket) -> Bucket {
+            let mut agreement = self.isa_data(&isa_id);
+
+            let paid = payment.take(std::cmp::min(payment.amount(), agreement.amount_due));
+            agreement.amount_due -= paid.amount();
+            agreement.total_paid += paid.amount();
+
+            self.update_isa(&isa_id, agreement);
+            self.liquidity_pool.put(paid);
+            payment
+        }
+
+        /// Returns the state of an income share agreement:
+        /// (student, principal, payment cap, end epoch, total paid, amount due)
+        pub fn get_isa(&self, isa_id: NonFungibleId) -> (ResourceAddress, Decimal, Decimal, u64, Decimal, Decimal) {
+            let agreement = self.isa_data(&isa_id);
+            (
+                agreement.student,
+                agreement.principal,
+                agreement.payment_cap,
+                agreement.end_epoch,
+                agreement.total_paid,
+                agreement.amount_due,
+            )
+        }
+
+        /// Returns true once nothing more can be asked of the student: the agreement ended or
+        /// the payment cap was reached, and nothing is left due
+        pub fn is_isa_complete(&self, isa_id: NonFungibleId) -> bool {
+            let agreement = self.isa_data(&isa_id);
+            agreement.amount_due.is_zero()
+                && (Runtime::current_epoch() >= agreement.end_epoch
+                    || agreement.total_paid >= agreement.payment_cap)
+        }
+
+        /// Returns the income share agreement terms, if the pool offers them
+        pub fn get_isa_terms(&self) -> Option<IsaTerms> {
+            self.isa_terms.clone()
+        }
+
+        /// Returns the resource address of the income share agreement NFTs
+        pub fn get_isa_nft_address(&self) -> ResourceAddress {
+            self.isa_nft_address
+        }
+
         /// Returns the current state of a user.
         pub fn get_user(&self, user_id: ResourceAddress) -> User {
             match self.users.get(&user_id) {
@@ -177,9 +400,34 @@ blueprint! {
             self.borrow_interest_rate = rate;
         }
 
-        /// Parse user id from a proof.
-        fn get_user_id(user_auth: Proof) -> ResourceAddress {
-            assert!(user_auth.amount() > dec!("0"), "Invalid user proof");
+        /// Loads the state of an income share agreement.
+        fn isa_data(&self, isa_id: &NonFungibleId) -> IncomeShareAgreement {
+            borrow_resource_manager!(self.isa_nft_address).get_non_fungible_data(isa_id)
+        }
+
+        /// Saves the state of an income share agreement.
+        fn update_isa(&self, isa_id: &NonFungibleId, agreement: IncomeShareAgreement) {
+            let isa_nft_address = self.isa_nft_address;
+            self.admin_badge.authorize(|| {
+                borrow_resource_manager!(isa_nft_address).update_non_fungible_data(isa_id, agreement)
+            });
+        }
+
+        /// Checks that a proof is of the attestor badge.
+        fn check_attestor(&self, attestor_auth: Proof) {
+            let terms = self.isa_terms.clone().expect("Income share agreements are not offered");
+            assert!(
+                attestor_auth.resource_address() == terms.attestor_badge && attestor_auth.amount() > dec!("0"),
+                "Invalid attestor proof"
+            );
+        }
+
+        /// Parse user id from a proof of a user badge minted by this pool.
+        fn get_user_id(&self, user_auth: Proof) -> ResourceAddress {
+            assert!(
+                self.user_badges.contains(&user_auth.resource_address()) && user_auth.amount() > dec!("0"),
+                "Invalid user proof"
+            );
             user_auth.resource_address()
         }
     }
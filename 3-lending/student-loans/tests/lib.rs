@@ -0,0 +1,167 @@
+use radix_engine::ledger::*;
+use radix_engine::transaction::*;
+use scrypto::prelude::*;
+
+struct TestEnv {
+    pk: EcdsaPublicKey,
+    sk: EcdsaPrivateKey,
+    account: ComponentAddress,
+    component: ComponentAddress,
+    attestor_badge: ResourceAddress,
+}
+
+/// Publishes the package, creates the attestor badge and an ISA pool funded with 10000 XRD
+fn setup(executor: &mut TransactionExecutor<InMemorySubstateStore>) -> TestEnv {
+    let (pk, sk, account) = executor.new_account();
+    let package = executor.publish_package(compile_package!()).unwrap();
+
+    let transaction = TransactionBuilder::new()
+        .new_badge_fixed(HashMap::new(), dec!("1"))
+        .call_method_with_all_resources(account, "deposit_batch")
+        .build(executor.get_nonce([pk]))
+        .sign([&sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let attestor_badge = receipt.new_resource_addresses[0];
+
+    let transaction = TransactionBuilder::new()
+        .call_function(
+            package,
+            "StudentLend",
+            "instantiate_studentlend_with_isa",
+            args![RADIX_TOKEN, dec!("0.01"), attestor_badge, dec!("0.1"), dec!("2"), 100u64],
+        )
+        .build(executor.get_nonce([pk]))
+        .sign([&sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let component = receipt.new_component_addresses[0];
+
+    let env = TestEnv { pk, sk, account, component, attestor_badge };
+
+    // A lender provides the liquidity
+    let lender = new_user(executor, &env);
+    assert!(deposit(executor, &env, lender, dec!("10000")).result.is_ok());
+
+    env
+}
+
+/// Registers a user and returns their badge
+fn new_user(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv) -> ResourceAddress {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.component, "new_user", args![])
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    receipt.new_resource_addresses[0]
+}
+
+fn deposit(
+    executor: &mut TransactionExecutor<InMemorySubstateStore>,
+    env: &TestEnv,
+    user_badge: ResourceAddress,
+    amount: Decimal,
+) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(user_badge, env.account)
+        .withdraw_from_account_by_amount(amount, RADIX_TOKEN, env.account)
+        .create_proof_from_auth_zone(user_badge, |builder, proof_id| {
+            builder.take_from_worktop(RADIX_TOKEN, |builder, bucket_id| {
+                builder.call_method(env.component, "deposit", args![Proof(proof_id), Bucket(bucket_id)])
+            })
+        })
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn approve_isa(
+    executor: &mut TransactionExecutor<InMemorySubstateStore>,
+    env: &TestEnv,
+    student: ResourceAddress,
+    max_principal: Decimal,
+) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(env.attestor_badge, env.account)
+        .create_proof_from_auth_zone(env.attestor_badge, |builder, proof_id| {
+            builder.call_method(env.component, "approve_isa", args![Proof(proof_id), student, max_principal])
+        })
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn borrow_isa(
+    executor: &mut TransactionExecutor<InMemorySubstateStore>,
+    env: &TestEnv,
+    user_badge: ResourceAddress,
+    requested: Decimal,
+) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(user_badge, env.account)
+        .create_proof_from_auth_zone(user_badge, |builder, proof_id| {
+            builder.call_method(env.component, "borrow_isa", args![Proof(proof_id), requested])
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+#[test]
+fn test_isa_requires_pool_user_badge() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // A badge not minted by new_user doesn't identify a student
+    let transaction = TransactionBuilder::new()
+        .new_badge_fixed(HashMap::new(), dec!("1"))
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    let fake_badge = receipt.new_resource_addresses[0];
+
+    assert!(approve_isa(&mut executor, &env, fake_badge, dec!("100")).result.is_err());
+    assert!(borrow_isa(&mut executor, &env, fake_badge, dec!("100")).result.is_err());
+    assert!(deposit(&mut executor, &env, fake_badge, dec!("100")).result.is_err());
+}
+
+#[test]
+fn test_isa_requires_approval() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+    let student = new_user(&mut executor, &env);
+
+    // Not approved yet
+    assert!(borrow_isa(&mut executor, &env, student, dec!("100")).result.is_err());
+
+    // Approved, but not for this much
+    assert!(approve_isa(&mut executor, &env, student, dec!("100")).result.is_ok());
+    assert!(borrow_isa(&mut executor, &env, student, dec!("200")).result.is_err());
+
+    // Within the approval
+    assert!(borrow_isa(&mut executor, &env, student, dec!("100")).result.is_ok());
+}
+
+#[test]
+fn test_isa_one_active_agreement() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+    let student = new_user(&mut executor, &env);
+
+    assert!(approve_isa(&mut executor, &env, student, dec!("100")).result.is_ok());
+    assert!(borrow_isa(&mut executor, &env, student, dec!("100")).result.is_ok());
+
+    // The approval is used up
+    assert!(borrow_isa(&mut executor, &env, student, dec!("100")).result.is_err());
+
+    // Even when approved again, the first agreement is still active
+    assert!(approve_isa(&mut executor, &env, student, dec!("100")).result.is_ok());
+    assert!(borrow_isa(&mut executor, &env, student, dec!("100")).result.is_err());
+}
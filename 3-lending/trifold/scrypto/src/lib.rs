@@ -29,16 +29,64 @@ pub struct LenderBadge {
     pub deposit_amount: Decimal
 }
 
+/// The circuit breakers lnXRD holders can vote for, from the least to the most restrictive.
+#[derive(Describe, Encode, Decode, TypeId, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CircuitBreaker {
+    /// No new borrowers or loans, lenders can only withdraw through the pro rata recovery flow
+    PauseBorrows,
+    /// No new deposits
+    PauseDeposits,
+    /// Both of the above, and lenders can only withdraw through the pro rata recovery flow
+    Lockdown,
+}
+
+/// The state of a circuit breaker.
+/// The breaker trips when the lnXRD locked in votes for it reaches `threshold` of the lnXRD supply,
+/// and stays active until `expiry_epoch` unless it is renewed.
+#[derive(Describe, Encode, Decode, TypeId, Debug, Clone)]
+pub struct BreakerState {
+    /// The amount of lnXRD locked in votes for this breaker
+    pub votes: Decimal,
+    /// The share of the lnXRD supply needed to trip the breaker
+    pub threshold: Decimal,
+    /// The number of epochs the breaker stays active once tripped or renewed
+    pub duration: u64,
+    /// The epoch at which the breaker expires, 0 if it was never tripped
+    pub expiry_epoch: u64,
+}
+
+/// A vote for a circuit breaker, redeemable for the lnXRD locked with it.
+#[derive(NonFungibleData)]
+pub struct LockdownVote {
+    pub breaker: CircuitBreaker,
+    pub amount: Decimal,
+}
+
+/// The lnXRD left over from a recovery withdrawal, returned once borrows are no longer frozen.
+#[derive(NonFungibleData)]
+pub struct RecoveryClaim {
+    pub amount: Decimal,
+}
+
 
 blueprint! {
     /// A system that manages the borrowers and their loans.
     struct Trifold {
         /// The main vault where liquidity is stored.
         main_vault: Vault,
-        /// A stable vault which stores the amount of token deposited to calculate the amount to cash out
-        stable_vault: Vault,
-        /// the emergency lockdown vault
+        /// The amount currently lent out to borrowers, including the interest settled on partial repayments
+        total_loaned: Decimal,
+        /// the emergency lockdown vault, holding the lnXRD locked in circuit breaker votes
         emergency_vault: Vault,
+        /// The state of each circuit breaker
+        circuit_breakers: HashMap<CircuitBreaker, BreakerState>,
+        /// The share of each lnXRD that can be redeemed through the recovery flow,
+        /// the liquidity of the pool when borrows were last frozen
+        recovery_ratio: Decimal,
+        /// The lnXRD left over from recovery withdrawals, held until borrows are no longer frozen
+        recovery_vault: Vault,
+        /// The nft that claims the lnXRD left over from a recovery withdrawal
+        recovery_claim: ResourceAddress,
         /// The badge that approves all protected actions
         internal_badge: Vault,
         /// The admin badge to approve borrowers
@@ -47,9 +95,9 @@ blueprint! {
         approved_borrower_badge: ResourceAddress,
         /// a nft that approves a loan
         approved_loan_badge: ResourceAddress,
-        /// The virtual token that represents the loan amount
+        /// The interest bearing deposit receipt, redeemable for a share of the pool at the current exchange rate
         virtual_token: ResourceAddress,
-        /// The lockdown token
+        /// The lockdown vote nft
         lockdown_token: ResourceAddress,
         /// The virtual token that represents the size of the loan that borrowers can request
         karma_token: ResourceAddress,
@@ -79,6 +127,7 @@ blueprint! {
             let virtual_token: ResourceAddress = ResourceBuilder::new_fungible()
                 .metadata("name", "Loaned Radix")
                 .metadata("symbol", "lnXRD")
+                .metadata("description", "Interest bearing deposit receipt, redeemable for XRD at the current exchange rate")
                 .mintable(rule!(require(internal_badge.resource_address())), LOCKED)
                 .burnable(rule!(require(internal_badge.resource_address())), LOCKED)
                 .no_initial_supply();
@@ -90,13 +139,33 @@ blueprint! {
                 .burnable(rule!(require(internal_badge.resource_address())), LOCKED)
                 .no_initial_supply();
 
-            let lockdown_token: ResourceAddress = ResourceBuilder::new_fungible()
+            let lockdown_token: ResourceAddress = ResourceBuilder::new_non_fungible()
                 .metadata("name", "Lockdown Token")
                 .metadata("symbol", "LDT")
                 .mintable(rule!(require(internal_badge.resource_address())), LOCKED)
                 .burnable(rule!(require(internal_badge.resource_address())), LOCKED)
                 .no_initial_supply();
 
+            let recovery_claim: ResourceAddress = ResourceBuilder::new_non_fungible()
+                .metadata("name", "Recovery Claim")
+                .mintable(rule!(require(internal_badge.resource_address())), LOCKED)
+                .burnable(rule!(require(internal_badge.resource_address())), LOCKED)
+                .no_initial_supply();
+
+            let mut circuit_breakers = HashMap::new();
+            for (breaker, threshold) in [
+                (CircuitBreaker::PauseBorrows, dec!("0.25")),
+                (CircuitBreaker::PauseDeposits, dec!("0.25")),
+                (CircuitBreaker::Lockdown, dec!("0.5")),
+            ] {
+                circuit_breakers.insert(breaker, BreakerState {
+                    votes: dec!(0),
+                    threshold,
+                    duration: 300,
+                    expiry_epoch: 0,
+                });
+            }
+
             let approved_borrower_badge = ResourceBuilder::new_non_fungible()
                 .metadata("name", "Approved Borrower Badge")
                 .mintable(rule!(require(internal_badge.resource_address())), LOCKED)
@@ -126,12 +195,17 @@ blueprint! {
 
             let auth =  AccessRules::new()
                 .method("approve_borrower", rule!(require(admin_badge)))
+                .method("set_circuit_breaker", rule!(require(admin_badge)))
                 .default(rule!(allow_all));
 
             (Self {
                 main_vault: Vault::new(RADIX_TOKEN),
-                stable_vault: Vault::new(virtual_token),
+                total_loaned: dec!(0),
                 emergency_vault: Vault::new(virtual_token),
+                circuit_breakers,
+                recovery_ratio: dec!(1),
+                recovery_vault: Vault::new(virtual_token),
+                recovery_claim,
                 internal_badge: Vault::with_bucket(internal_badge),
                 admin_badge: admin_badge,
                 virtual_token,
@@ -149,7 +223,8 @@ blueprint! {
 
         /// Deposit some amount of RADIX into the main vault.
         /// This will increase the supply of the main vault.
-        /// This will also increase the supply of the stable vault.
+        /// The lender receives lnXRD at the current exchange rate, so that
+        /// earlier lenders keep the interest they have already earned.
         /// 
         /// # Arguments
         /// * `xrd` - The RADIX to deposit.
@@ -157,57 +232,57 @@ blueprint! {
         /// # Returns
         /// The lnXRD to redeem for the deposit.
         pub fn deposit(&mut self, xrd: Bucket) -> Bucket {
-            assert!(self.is_in_safe_mode(), "The system is under lockdown");
+            assert!(!self.deposits_paused(), "Deposits are paused");
             assert_eq!(xrd.resource_address(), RADIX_TOKEN, "The tokens must be XRD");
+            let exchange_rate = self.get_exchange_rate();
+            // new lnXRD can't be priced against a pool that is worth nothing while lnXRD is outstanding
+            assert!(exchange_rate > dec!(0), "The pool has no value left, deposits are not accepted");
             
-            let amount_to_mint = xrd.amount();
+            let amount_to_mint = xrd.amount() / exchange_rate;
             
             self.main_vault.put(xrd);
 
-            let lnxrd: Bucket = self.internal_badge.authorize(|| -> Bucket {
+            self.internal_badge.authorize(|| -> Bucket {
                 // mint tokens to give to the lender
-                let token: Bucket = borrow_resource_manager!(self.virtual_token).mint(amount_to_mint);
-                // mint an identitical amount of tokens to store as log.
-                self.stable_vault.put(borrow_resource_manager!(self.virtual_token).mint(amount_to_mint));
-                token
-            });
-
-
-
-
-            lnxrd
+                borrow_resource_manager!(self.virtual_token).mint(amount_to_mint)
+            })
         }
 
         /// Withdraw depositted RADIX from the main vault along with any profits.
         /// This will decrease the supply of the main vault.
         /// 
         /// # Arguments
-        /// * `lnxrd` - The lnXRD to redeem for profits.
+        /// * `lnxrd` - The lnXRD to redeem at the current exchange rate.
         /// 
         /// # Returns
         /// The withdrawn RADIX.
         pub fn withdraw(&mut self, lnxrd: Bucket) -> Bucket {
-            assert!(self.is_in_safe_mode(), "The system is under lockdown");
+            assert!(!self.borrows_paused(), "Borrows are frozen, use the recovery withdrawal");
             assert!(lnxrd.resource_address() == self.virtual_token, "The tokens must be lnXRD");
             
-            let amount= lnxrd.amount();
-
-            // propertionally determine how much xrd to reward, considering that interest has been earned.
-            let amount_of_stable_token = self.stable_vault.amount();
-            let amount_of_xrd = self.main_vault.amount();
+            let amount_of_xrd_to_withdraw = lnxrd.amount() * self.get_exchange_rate();
 
-            let amount_of_xrd_to_withdraw = amount_of_xrd / amount_of_stable_token * amount;
-
-            assert!(amount_of_xrd_to_withdraw <= amount_of_xrd, "Not enough liquidity to withdraw right now, check back later.");
+            assert!(amount_of_xrd_to_withdraw <= self.main_vault.amount(), "Not enough liquidity to withdraw right now, check back later.");
             
             self.internal_badge.authorize(|| {
                 lnxrd.burn();
-                self.stable_vault.take(amount).burn();
             });
             
             self.main_vault.take(amount_of_xrd_to_withdraw)
         }
 
+        /// The amount of XRD one lnXRD can be redeemed for.
+        /// The pool is worth the liquidity in the main vault plus the amount lent out,
+        /// so the rate rises as borrowers repay interest.
+        /// It starts at one before any lnXRD has been minted, and is zero if the pool
+        /// has lost all of its value while lnXRD is still outstanding.
+        pub fn get_exchange_rate(&self) -> Decimal {
+            let supply = borrow_resource_manager!(self.virtual_token).total_supply();
+            if supply == dec!(0) {
+                return dec!(1);
+            }
+            (self.main_vault.amount() + self.total_loaned) / supply
+        }
 
         /// Take a loan from the pool
         //&/ You need to have a borrower badge to request a loan.
@@ -219,7 +294,7 @@ blueprint! {
         /// # Returns
         /// The loan and any remaining karma
         pub fn borrow(&mut self, karma: Bucket, borrower_badge: Proof) -> Bucket {
-            assert!(self.is_in_safe_mode(), "The system is under lockdown");
+            assert!(!self.borrows_paused(), "Borrows are paused");
             assert!(karma.resource_address() == self.karma_token, "The tokens must be KARMA");
 
             let amount = karma.amount();
@@ -244,13 +319,14 @@ blueprint! {
                 borrower_badge.non_fungible().update_data(borrower_badge_data);
             });
 
-
+            self.total_loaned += amount;
 
             self.main_vault.take(amount)
         }
 
         /// Repay a loan to the pool
         /// You need to have a borrower badge to repay a loan.
+        /// Repayments are accepted even when circuit breakers are active.
         /// 
         /// # Arguments
         /// * `xrd` - The XRD to repay.
@@ -259,7 +335,6 @@ blueprint! {
         /// # Returns
         /// Any karma earned and any leftover XRD.
         pub fn repay(&mut self, mut xrd: Bucket, borrower_badge: Proof) -> (Bucket, Bucket) {
-            assert!(self.is_in_safe_mode(), "The system is under lockdown");
             assert!(xrd.resource_address() == RADIX_TOKEN, "The tokens must be XRD");
             // make sure the borrower badge is valid
             assert_eq!(borrower_badge.resource_address(), self.approved_borrower_badge, "The borrower badge is not valid");
@@ -278,6 +353,9 @@ blueprint! {
 
             let amount_of_xrd = xrd.amount();
 
+            // the loan is settled at the amount owed, so the interest paid goes to the lenders
+            self.total_loaned -= borrower_badge_data.current_loan;
+
             if amount_of_xrd >= amount_owed {
                 self.main_vault.put(xrd.take(amount_owed));
                 self.internal_badge.authorize(|| {
@@ -287,6 +365,7 @@ blueprint! {
                 });
             } else {
                 self.main_vault.put(xrd.take(amount_of_xrd));
+                self.total_loaned += amount_owed - amount_of_xrd;
                 self.internal_badge.authorize(|| {
                     borrower_badge_data.current_loan = amount_owed - amount_of_xrd;
                     borrower_badge_data.loan_start_epoch = Runtime::current_epoch();
@@ -307,7 +386,7 @@ blueprint! {
 
         
         pub fn approve_borrower(&mut self, borrower: ComponentAddress, name: String, website: String) -> Bucket {
-            assert!(self.is_in_safe_mode(), "The system is under lockdown");
+            assert!(!self.borrows_paused(), "Borrows are paused");
             self.internal_badge.authorize(|| {
                 borrow_resource_manager!(self.approved_borrower_badge).mint_non_fungible(
                     &NonFungibleId::random(),
@@ -326,40 +405,220 @@ blueprint! {
         pub fn get_info(&self) -> String {
             let info = json::object!{
                 virtual_token: self.virtual_token.to_string(),
+                exchange_rate: self.get_exchange_rate().to_string(),
                 karma_token: self.karma_token.to_string(),
                 approved_borrower_badge: self.approved_borrower_badge.to_string(),
                 admin_badge: self.admin_badge.to_string(),
                 lockdown_token: self.lockdown_token.to_string(),
+                recovery_claim: self.recovery_claim.to_string(),
             };
 
             info.dump()
         }
 
+        /// Vote for a full lockdown.
+        /// 
+        /// # Arguments
+        /// * `vote` - The lnXRD to lock as a vote.
+        /// 
+        /// # Returns
+        /// The lockdown vote nft, to withdraw the vote later.
         pub fn lockdown_vote(&mut self, vote: Bucket) -> Bucket {
+            self.circuit_breaker_vote(vote, CircuitBreaker::Lockdown)
+        }
+
+        /// Vote for a circuit breaker by locking lnXRD.
+        /// The breaker trips once the locked lnXRD reaches its threshold of the lnXRD supply.
+        /// 
+        /// # Arguments
+        /// * `vote` - The lnXRD to lock as a vote.
+        /// * `breaker` - The circuit breaker to vote for.
+        /// 
+        /// # Returns
+        /// The lockdown vote nft, to withdraw the vote later.
+        pub fn circuit_breaker_vote(&mut self, vote: Bucket, breaker: CircuitBreaker) -> Bucket {
             assert!(vote.resource_address() == self.virtual_token, "The tokens must be lnXRD");
             let vote_amount = vote.amount();
+            self.emergency_vault.put(vote);
+            self.circuit_breakers.get_mut(&breaker).unwrap().votes += vote_amount;
+
+            if !self.is_active(breaker) && self.has_enough_votes(breaker) {
+                self.trip(breaker);
+            }
+
             self.internal_badge.authorize(|| {
-                self.emergency_vault.put(vote);
-            });
-            self.internal_badge.authorize(|| {
-                borrow_resource_manager!(self.lockdown_token).mint(vote_amount)
+                borrow_resource_manager!(self.lockdown_token).mint_non_fungible(
+                    &NonFungibleId::random(),
+                    LockdownVote {
+                        breaker,
+                        amount: vote_amount,
+                    }
+                )
             })
         }
 
-        pub fn withdraw_lockdown(&mut self, vote: Bucket) -> Bucket {
+        /// Renew a circuit breaker for another `duration` epochs.
+        /// Anyone can renew a breaker, as long as the votes for it still reach its threshold.
+        /// 
+        /// # Arguments
+        /// * `breaker` - The circuit breaker to renew.
+        pub fn renew_circuit_breaker(&mut self, breaker: CircuitBreaker) {
+            assert!(self.has_enough_votes(breaker), "Not enough votes to renew the circuit breaker");
+            self.trip(breaker);
+        }
+
+        /// Withdraw lockdown votes, redeeming the locked lnXRD at the current exchange rate.
+        /// While borrows are frozen the lnXRD is redeemed pro rata, like `recovery_withdraw`.
+        /// 
+        /// # Arguments
+        /// * `vote` - The lockdown vote nfts received for the votes.
+        /// 
+        /// # Returns
+        /// The redeemed XRD, and the lnXRD that could not be redeemed,
+        /// or a recovery claim for it while borrows are frozen.
+        pub fn withdraw_lockdown(&mut self, vote: Bucket) -> (Bucket, Bucket) {
             assert!(vote.resource_address() == self.lockdown_token, "The tokens must be lockdown tokens");
-            let vote_amount = vote.amount();
+            let mut vote_amount = dec!(0);
+            for lockdown_vote in vote.non_fungibles::<LockdownVote>() {
+                let data = lockdown_vote.data();
+                self.circuit_breakers.get_mut(&data.breaker).unwrap().votes -= data.amount;
+                vote_amount += data.amount;
+            }
             self.internal_badge.authorize(|| {
                 vote.burn();
             });
+            let lnxrd = self.emergency_vault.take(vote_amount);
+
+            self.redeem(lnxrd)
+        }
+
+        /// Withdraw XRD pro rata while borrows are frozen.
+        /// Every lender can redeem the same share of their lnXRD, the share of the pool that was liquid
+        /// when borrows were frozen, so that withdrawals do not become a race for the remaining liquidity.
+        /// The rest of the lnXRD is held until borrows are no longer frozen, so it can't be redeemed twice.
+        /// 
+        /// # Arguments
+        /// * `lnxrd` - The lnXRD to redeem.
+        /// 
+        /// # Returns
+        /// The redeemed XRD, and a recovery claim for the lnXRD that could not be redeemed.
+        pub fn recovery_withdraw(&mut self, lnxrd: Bucket) -> (Bucket, Bucket) {
+            assert!(self.borrows_paused(), "Borrows are not frozen, use the normal withdrawal");
+            assert!(lnxrd.resource_address() == self.virtual_token, "The tokens must be lnXRD");
+            self.redeem(lnxrd)
+        }
+
+        /// Take back the lnXRD left over from recovery withdrawals once borrows are no longer frozen.
+        /// 
+        /// # Arguments
+        /// * `claim` - The recovery claims.
+        /// 
+        /// # Returns
+        /// The lnXRD held for the claims.
+        pub fn redeem_recovery_claim(&mut self, claim: Bucket) -> Bucket {
+            assert!(!self.borrows_paused(), "Borrows are still frozen");
+            assert!(claim.resource_address() == self.recovery_claim, "The tokens must be recovery claims");
+            let mut amount = dec!(0);
+            for recovery_claim in claim.non_fungibles::<RecoveryClaim>() {
+                amount += recovery_claim.data().amount;
+            }
             self.internal_badge.authorize(|| {
-                self.emergency_vault.take(vote_amount)
-            })
-            
+                claim.burn();
+            });
+            self.recovery_vault.take(amount)
+        }
+
+        /// Change the threshold and duration of a circuit breaker.
+        /// 
+        /// # Arguments
+        /// * `breaker` - The circuit breaker to change.
+        /// * `threshold` - The share of the lnXRD supply needed to trip the breaker.
+        /// * `duration` - The number of epochs the breaker stays active once tripped or renewed.
+        pub fn set_circuit_breaker(&mut self, breaker: CircuitBreaker, threshold: Decimal, duration: u64) {
+            assert!(threshold > dec!(0) && threshold <= dec!(1), "The threshold must be between 0 and 1");
+            assert!(duration > 0, "The duration must be at least one epoch");
+            let state = self.circuit_breakers.get_mut(&breaker).unwrap();
+            state.threshold = threshold;
+            state.duration = duration;
         }
+
+        /// Get the state of a circuit breaker.
+        pub fn get_circuit_breaker(&self, breaker: CircuitBreaker) -> BreakerState {
+            self.circuit_breakers.get(&breaker).unwrap().clone()
+        }
+
+        /// Whether a circuit breaker is currently active.
+        pub fn is_active(&self, breaker: CircuitBreaker) -> bool {
+            Runtime::current_epoch() < self.circuit_breakers.get(&breaker).unwrap().expiry_epoch
+        }
+
+        /// Whether the system is free of a full lockdown.
         pub fn is_in_safe_mode(&self) -> bool {
-            self.emergency_vault.amount() > (self.stable_vault.amount() / 2)
+            !self.is_active(CircuitBreaker::Lockdown)
+        }
+
+        fn borrows_paused(&self) -> bool {
+            self.is_active(CircuitBreaker::PauseBorrows) || self.is_active(CircuitBreaker::Lockdown)
+        }
+
+        fn deposits_paused(&self) -> bool {
+            self.is_active(CircuitBreaker::PauseDeposits) || self.is_active(CircuitBreaker::Lockdown)
+        }
+
+        fn has_enough_votes(&self, breaker: CircuitBreaker) -> bool {
+            let state = self.circuit_breakers.get(&breaker).unwrap();
+            let supply = borrow_resource_manager!(self.virtual_token).total_supply();
+            state.votes > dec!(0) && state.votes >= supply * state.threshold
+        }
+
+        /// Activate the breaker for its duration.
+        /// If borrows were not frozen yet, the liquidity of the pool is recorded for the recovery flow.
+        fn trip(&mut self, breaker: CircuitBreaker) {
+            let freezes_borrows = breaker != CircuitBreaker::PauseDeposits;
+            if freezes_borrows && !self.borrows_paused() {
+                let liquidity = self.main_vault.amount();
+                self.recovery_ratio = if liquidity + self.total_loaned == dec!(0) {
+                    dec!(1)
+                } else {
+                    liquidity / (liquidity + self.total_loaned)
+                };
+            }
+            let state = self.circuit_breakers.get_mut(&breaker).unwrap();
+            state.expiry_epoch = Runtime::current_epoch() + state.duration;
         }
 
+        /// Redeem lnXRD at the current exchange rate, as much as the liquidity allows.
+        /// While borrows are frozen only the recovery ratio of the lnXRD is redeemed,
+        /// and the rest is exchanged for a recovery claim.
+        fn redeem(&mut self, mut lnxrd: Bucket) -> (Bucket, Bucket) {
+            let exchange_rate = self.get_exchange_rate();
+            let mut amount_to_redeem = lnxrd.amount();
+            if self.borrows_paused() {
+                amount_to_redeem *= self.recovery_ratio;
+            }
+            if amount_to_redeem * exchange_rate > self.main_vault.amount() {
+                amount_to_redeem = self.main_vault.amount() / exchange_rate;
+            }
+            let xrd = self.main_vault.take(amount_to_redeem * exchange_rate);
+            self.internal_badge.authorize(|| {
+                lnxrd.take(amount_to_redeem).burn();
+            });
+
+            if !self.borrows_paused() {
+                return (xrd, lnxrd);
+            }
+            let amount_held = lnxrd.amount();
+            self.recovery_vault.put(lnxrd);
+            let claim = self.internal_badge.authorize(|| {
+                borrow_resource_manager!(self.recovery_claim).mint_non_fungible(
+                    &NonFungibleId::random(),
+                    RecoveryClaim {
+                        amount: amount_held,
+                    }
+                )
+            });
+
+            (xrd, claim)
+        }
     }
 }
\ No newline at end of file
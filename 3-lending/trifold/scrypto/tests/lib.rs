@@ -1,32 +1,239 @@
 use radix_engine::ledger::*;
+use radix_engine::model::Receipt;
 use radix_engine::transaction::*;
 use scrypto::prelude::*;
+use trifold::{BreakerState, CircuitBreaker};
 
-#[test]
-fn test_hello() {
-    // Set up environment.
-    let mut ledger = InMemorySubstateStore::with_bootstrap();
-    let mut executor = TransactionExecutor::new(&mut ledger, false);
+struct TestEnv {
+    pk: EcdsaPublicKey,
+    sk: EcdsaPrivateKey,
+    account: ComponentAddress,
+    component: ComponentAddress,
+    lnxrd: ResourceAddress,
+    admin_badge: ResourceAddress,
+    lockdown_token: ResourceAddress,
+    recovery_claim: ResourceAddress,
+}
+
+/// Instantiates Trifold and reads the resource addresses from `get_info`.
+fn setup(executor: &mut TransactionExecutor<InMemorySubstateStore>) -> TestEnv {
     let (pk, sk, account) = executor.new_account();
     let package = executor.publish_package(compile_package!()).unwrap();
 
-    // Test the `instantiate_hello` function.
-    let transaction1 = TransactionBuilder::new()
-        .call_function(package, "Hello", "instantiate_hello", args![])
+    let transaction = TransactionBuilder::new()
+        .call_function(package, "Trifold", "instantiate", args![])
+        .call_method_with_all_resources(account, "deposit_batch")
         .build(executor.get_nonce([pk]))
         .sign([&sk]);
-    let receipt1 = executor.validate_and_execute(&transaction1).unwrap();
-    println!("{:?}\n", receipt1);
-    assert!(receipt1.result.is_ok());
-
-    // Test the `free_token` method.
-    let component = receipt1.new_component_addresses[0];
-    let transaction2 = TransactionBuilder::new()
-        .call_method(component, "free_token", args![])
-        .call_method_with_all_resources(account, "deposit_batch")
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let component = receipt.new_component_addresses[0];
+
+    let transaction = TransactionBuilder::new()
+        .call_method(component, "get_info", args![])
         .build(executor.get_nonce([pk]))
         .sign([&sk]);
-    let receipt2 = executor.validate_and_execute(&transaction2).unwrap();
-    println!("{:?}\n", receipt2);
-    assert!(receipt2.result.is_ok());
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let info: String = scrypto_decode(&receipt.outputs[0].raw[..]).unwrap();
+    let info = json::parse(&info).unwrap();
+    let resource = |name: &str| ResourceAddress::from_str(info[name].as_str().unwrap()).unwrap();
+    let lnxrd = resource("virtual_token");
+    let admin_badge = resource("admin_badge");
+    let lockdown_token = resource("lockdown_token");
+    let recovery_claim = resource("recovery_claim");
+
+    TestEnv { pk, sk, account, component, lnxrd, admin_badge, lockdown_token, recovery_claim }
+}
+
+/// Sends `amount` of `resource` from the account to a method taking a single bucket.
+fn call_with_bucket(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, method: &str, resource: ResourceAddress, amount: Decimal) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(amount, resource, env.account)
+        .take_from_worktop(resource, |builder, bucket_id| {
+            builder.call_method(env.component, method, args![Bucket(bucket_id)])
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn deposit(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, amount: Decimal) -> Receipt {
+    call_with_bucket(executor, env, "deposit", RADIX_TOKEN, amount)
+}
+
+fn withdraw(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, amount: Decimal) -> Receipt {
+    call_with_bucket(executor, env, "withdraw", env.lnxrd, amount)
+}
+
+/// Locks `amount` lnXRD in a vote for the circuit breaker.
+fn vote(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, breaker: CircuitBreaker, amount: Decimal) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(amount, env.lnxrd, env.account)
+        .take_from_worktop(env.lnxrd, |builder, bucket_id| {
+            builder.call_method(env.component, "circuit_breaker_vote", args![Bucket(bucket_id), breaker])
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn call(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, method: &str, args: Vec<Vec<u8>>) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(env.admin_badge, env.account)
+        .call_method(env.component, method, args)
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn circuit_breaker(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, breaker: CircuitBreaker) -> BreakerState {
+    let receipt = call(executor, env, "get_circuit_breaker", args![breaker]);
+    assert!(receipt.result.is_ok());
+    scrypto_decode(&receipt.outputs[1].raw[..]).unwrap()
+}
+
+fn exchange_rate(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv) -> Decimal {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.component, "get_exchange_rate", args![])
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    scrypto_decode(&receipt.outputs[0].raw[..]).unwrap()
+}
+
+#[test]
+fn test_first_deposit_mints_lnxrd_one_to_one() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert_eq!(exchange_rate(&mut executor, &env), dec!("1"));
+    assert!(deposit(&mut executor, &env, dec!("1000")).result.is_ok());
+    assert_eq!(exchange_rate(&mut executor, &env), dec!("1"));
+
+    // Exactly 1000 lnXRD was minted
+    assert!(withdraw(&mut executor, &env, dec!("1001")).result.is_err());
+    assert!(withdraw(&mut executor, &env, dec!("1000")).result.is_ok());
+    assert!(withdraw(&mut executor, &env, dec!("1")).result.is_err());
+}
+
+#[test]
+fn test_withdrawing_all_lnxrd_resets_exchange_rate() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert!(deposit(&mut executor, &env, dec!("1000")).result.is_ok());
+    assert!(deposit(&mut executor, &env, dec!("500")).result.is_ok());
+    assert!(withdraw(&mut executor, &env, dec!("400")).result.is_ok());
+    assert_eq!(exchange_rate(&mut executor, &env), dec!("1"));
+
+    // With no lnXRD left the pool is empty and the next deposit starts again at one to one
+    assert!(withdraw(&mut executor, &env, dec!("1100")).result.is_ok());
+    assert_eq!(exchange_rate(&mut executor, &env), dec!("1"));
+    assert!(deposit(&mut executor, &env, dec!("200")).result.is_ok());
+    assert!(withdraw(&mut executor, &env, dec!("200")).result.is_ok());
+}
+
+#[test]
+fn test_deposit_rejects_other_tokens() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert!(deposit(&mut executor, &env, dec!("1000")).result.is_ok());
+    // lnXRD can't be deposited back for more lnXRD
+    assert!(call_with_bucket(&mut executor, &env, "deposit", env.lnxrd, dec!("100")).result.is_err());
+    // and XRD can't be passed off as lnXRD
+    assert!(call_with_bucket(&mut executor, &env, "withdraw", RADIX_TOKEN, dec!("100")).result.is_err());
+}
+
+#[test]
+fn test_pause_deposits_trips_at_threshold_and_expires() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert!(deposit(&mut executor, &env, dec!("1000")).result.is_ok());
+
+    // 20% of the lnXRD supply is below the 25% threshold
+    assert!(vote(&mut executor, &env, CircuitBreaker::PauseDeposits, dec!("200")).result.is_ok());
+    assert!(deposit(&mut executor, &env, dec!("100")).result.is_ok());
+
+    // 250 of 1100 lnXRD is still below it, 300 of 1200 reaches it
+    assert!(vote(&mut executor, &env, CircuitBreaker::PauseDeposits, dec!("50")).result.is_ok());
+    assert!(deposit(&mut executor, &env, dec!("100")).result.is_ok());
+    assert!(vote(&mut executor, &env, CircuitBreaker::PauseDeposits, dec!("50")).result.is_ok());
+    assert!(deposit(&mut executor, &env, dec!("100")).result.is_err());
+    let state = circuit_breaker(&mut executor, &env, CircuitBreaker::PauseDeposits);
+    assert_eq!(state.votes, dec!("300"));
+    assert_eq!(state.expiry_epoch, 300);
+
+    // Pausing deposits doesn't freeze borrows, so lenders still withdraw normally
+    assert!(withdraw(&mut executor, &env, dec!("100")).result.is_ok());
+
+    // The breaker expires on its own even though the votes are still locked
+    executor.substate_store_mut().set_epoch(299);
+    assert!(deposit(&mut executor, &env, dec!("100")).result.is_err());
+    executor.substate_store_mut().set_epoch(300);
+    assert!(deposit(&mut executor, &env, dec!("100")).result.is_ok());
+}
+
+#[test]
+fn test_circuit_breaker_renews_only_while_votes_reach_threshold() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert!(call(&mut executor, &env, "set_circuit_breaker", args![CircuitBreaker::PauseDeposits, dec!("0.25"), 10u64]).result.is_ok());
+    assert!(call(&mut executor, &env, "set_circuit_breaker", args![CircuitBreaker::PauseDeposits, dec!("0"), 10u64]).result.is_err());
+    assert!(call(&mut executor, &env, "set_circuit_breaker", args![CircuitBreaker::PauseDeposits, dec!("0.25"), 0u64]).result.is_err());
+
+    assert!(deposit(&mut executor, &env, dec!("1000")).result.is_ok());
+    assert!(call(&mut executor, &env, "renew_circuit_breaker", args![CircuitBreaker::PauseDeposits]).result.is_err());
+    assert!(vote(&mut executor, &env, CircuitBreaker::PauseDeposits, dec!("250")).result.is_ok());
+
+    // Anyone can renew the breaker for another 10 epochs from now
+    executor.substate_store_mut().set_epoch(9);
+    assert!(call(&mut executor, &env, "renew_circuit_breaker", args![CircuitBreaker::PauseDeposits]).result.is_ok());
+    assert_eq!(circuit_breaker(&mut executor, &env, CircuitBreaker::PauseDeposits).expiry_epoch, 19);
+    executor.substate_store_mut().set_epoch(10);
+    assert!(deposit(&mut executor, &env, dec!("100")).result.is_err());
+
+    // Once the vote is withdrawn the breaker can't be renewed and expires
+    assert!(call_with_bucket(&mut executor, &env, "withdraw_lockdown", env.lockdown_token, dec!("1")).result.is_ok());
+    assert_eq!(circuit_breaker(&mut executor, &env, CircuitBreaker::PauseDeposits).votes, dec!("0"));
+    assert!(call(&mut executor, &env, "renew_circuit_breaker", args![CircuitBreaker::PauseDeposits]).result.is_err());
+    executor.substate_store_mut().set_epoch(19);
+    assert!(deposit(&mut executor, &env, dec!("100")).result.is_ok());
+}
+
+#[test]
+fn test_lockdown_moves_withdrawals_to_recovery_until_it_expires() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    assert!(deposit(&mut executor, &env, dec!("1000")).result.is_ok());
+    assert!(call_with_bucket(&mut executor, &env, "recovery_withdraw", env.lnxrd, dec!("100")).result.is_err());
+
+    // Half of the lnXRD supply trips the lockdown, which pauses both deposits and normal withdrawals
+    assert!(vote(&mut executor, &env, CircuitBreaker::Lockdown, dec!("500")).result.is_ok());
+    assert!(deposit(&mut executor, &env, dec!("100")).result.is_err());
+    assert!(withdraw(&mut executor, &env, dec!("100")).result.is_err());
+
+    // With nothing lent out the whole pool is liquid, so the recovery withdrawal redeems in full
+    assert!(call_with_bucket(&mut executor, &env, "recovery_withdraw", env.lnxrd, dec!("100")).result.is_ok());
+    assert!(call_with_bucket(&mut executor, &env, "redeem_recovery_claim", env.recovery_claim, dec!("1")).result.is_err());
+    assert_eq!(exchange_rate(&mut executor, &env), dec!("1"));
+
+    executor.substate_store_mut().set_epoch(300);
+    assert!(call_with_bucket(&mut executor, &env, "recovery_withdraw", env.lnxrd, dec!("100")).result.is_err());
+    assert!(call_with_bucket(&mut executor, &env, "redeem_recovery_claim", env.recovery_claim, dec!("1")).result.is_ok());
+    assert!(withdraw(&mut executor, &env, dec!("400")).result.is_ok());
+    assert!(deposit(&mut executor, &env, dec!("100")).result.is_ok());
 }
@@ -1,5 +1,7 @@
 use scrypto::prelude::*;
 
+mod oracle;
+
 blueprint! {
     struct Pool {
         lp_token_address: ResourceAddress,
@@ -0,0 +1,28 @@
+use scrypto::prelude::*;
+
+blueprint! {
+    struct PriceOracle {
+        prices: HashMap<ResourceAddress, Decimal>,
+    }
+
+    impl PriceOracle {
+        // instantiates a price oracle whose prices anyone can set, for testing
+        pub fn new() -> ComponentAddress {
+            Self {
+                prices: HashMap::new(),
+            }
+            .instantiate()
+            .globalize()
+        }
+
+        // sets price of a token in denominator tokens
+        pub fn set_price(&mut self, token_address: ResourceAddress, price: Decimal) {
+            self.prices.insert(token_address, price);
+        }
+
+        // returns price of a token in denominator tokens or zero if it has no price
+        pub fn get_price(&self, token_address: ResourceAddress) -> Decimal {
+            *self.prices.get(&token_address).unwrap_or(&dec!(0))
+        }
+    }
+}
@@ -184,10 +184,20 @@ blueprint! {
             (self.bid_vault.take(bid_data.amount), self.offer_vault.take(bid_data.filled))
         }
 
+        // returns price of the first bid in bid chain or zero if there are no bids
+        pub fn get_best_price(&self) -> Decimal {
+            if self.bid_chain_head == NonFungibleId::from_u32(0) {
+                dec!(0)
+            } else {
+                let bid_manager: &ResourceManager = borrow_resource_manager!(self.bid_receipt_address);
+                bid_manager.get_non_fungible_data::<BidReceipt>(&self.bid_chain_head).price
+            }
+        }
+
         // requires admin badge to call
-        // auctions offer tokens
+        // auctions offer tokens, only filling bids with a price no higher than limit_price if given
         // returns (bought tokens, remaining offer tokens)
-        pub fn auction(&mut self, mut offer_tokens: Bucket) -> (Bucket, Bucket) {
+        pub fn auction(&mut self, mut offer_tokens: Bucket, limit_price: Option<Decimal>) -> (Bucket, Bucket) {
             let mut bid_tokens: Bucket = Bucket::new(self.bid_vault.resource_address());
             let bid_manager: &ResourceManager = borrow_resource_manager!(self.bid_receipt_address);
             
@@ -197,6 +207,11 @@ blueprint! {
                 let id: NonFungibleId = pointer.clone();
                 let mut bid_receipt_data: BidReceipt = bid_manager.get_non_fungible_data(&id);
 
+                // stop at bids priced above the limit, bid chain is ordered by price
+                if limit_price.map_or(false, |limit| bid_receipt_data.price > limit) {
+                    break;
+                }
+
                 let calc_buy: Decimal = bid_receipt_data.amount * bid_receipt_data.price;
                 if calc_buy > offer_tokens.amount() {       // part fill bid
                     bid_receipt_data.filled += offer_tokens.amount();
@@ -48,6 +48,34 @@ import! { r#"
 }
 "# }
 
+// import price oracle blueprint as a generic interface
+// get_price returns the price of a token in denominator tokens
+import! { r#"
+{
+    "package_address": "",
+    "blueprint_name": "PriceOracle",
+    "functions": [],
+    "methods": [
+        {
+        "name": "get_price",
+        "mutability": "Immutable",
+        "inputs": [
+            {
+            "type": "Custom",
+            "name": "ResourceAddress",
+            "generics": []
+            }
+        ],
+        "output": {
+            "type": "Custom",
+            "name": "Decimal",
+            "generics": []
+        }
+        }
+    ]
+}
+"# }
+
 // nft that represents a stake
 #[derive(NonFungibleData)]
 pub struct StakeReceipt {
@@ -56,6 +84,8 @@ pub struct StakeReceipt {
     #[scrypto(mutable)]
     price: Decimal,                     // price of tokens bought
     #[scrypto(mutable)]
+    sell_price: Decimal,                // price of tokens sold
+    #[scrypto(mutable)]
     reward: Decimal,                    // reward for performance 
     #[scrypto(mutable)]
     status: Status,                     // status of stake 
@@ -80,6 +110,8 @@ pub struct Investment {
     buy_auction_badge: Vault,                          // badge to authorize buy auction
     sell_auction: ComponentAddress,                    // auction used to sell tokens for the investment
     sell_auction_badge: Vault,                         // badge to authorize sell auction
+    price: Decimal,                                    // price of tokens in denominator tokens from the latest auction
+    value_price: Decimal,                              // price of tokens in denominator tokens the fund was last valued at for fees
 }
 
 #[derive(Encode, Decode, TypeId, Describe)]
@@ -103,6 +135,13 @@ blueprint! {
         stake_receipt_address: ResourceAddress,        // nft that represents a stake
         internal_badge: Vault,                         // badge used for all internal permission (minting, burning, etc.)
         fee_percent: Decimal,                          // percent of buying xrd transfered to stakers
+        management_fee_percent: Decimal,               // percent of fund token supply minted to the manager per epoch
+        performance_fee_percent: Decimal,              // percent of value gained above the high water mark minted to the manager
+        high_water_mark: Decimal,                      // highest net asset value per fund token that performance fees were charged at
+        fee_epoch: u64,                                // epoch fees were last charged
+        manager_fees: Vault,                           // fund tokens minted as fees for the manager
+        price_oracle: Option<ComponentAddress>,        // reference price source auctions are checked against
+        price_tolerance_percent: Decimal,              // percent an auction price can be worse than the reference price
         auction_delay: u64,                            // delay be before auctions are processed to allow the market to participate
         denominator_tokens: Vault,                     // denominator tokens reserve
         fund_tokens: Vault,                            // not staked fund tokens
@@ -114,14 +153,16 @@ blueprint! {
     impl Fund {
         // instantiates fund
         // returns (fund_address, admin_badge)
-        // admin_badge is used to add tokens to the fund
+        // admin_badge is used to add tokens to the fund and withdraw manager fees
         pub fn new(
             name: String, 
             token_name: String, 
             token_symbol: String, 
             denominator_token_address: ResourceAddress, 
             auction_delay: u64, 
-            fee_percent: Decimal) -> (ComponentAddress, Bucket) {
+            fee_percent: Decimal,
+            management_fee_percent: Decimal,
+            performance_fee_percent: Decimal) -> (ComponentAddress, Bucket) {
 
             // assert it is a valid delay
             assert!(
@@ -135,6 +176,18 @@ blueprint! {
                 "Invalid fee_percent value."
             );
 
+            // assert it is a valid management fee percent, at most roughly 18% a year
+            assert!(
+                management_fee_percent >= dec!(0) && management_fee_percent <= dec!("0.001"),
+                "Invalid management_fee_percent value."
+            );
+
+            // assert it is a valid performance fee percent
+            assert!(
+                performance_fee_percent >= dec!(0) && performance_fee_percent <= dec!(30),
+                "Invalid performance_fee_percent value."
+            );
+
             // mint admin badge
             let admin_badge: Bucket = ResourceBuilder::new_fungible()
                 .metadata("name", "Fund Admin Badge")
@@ -167,6 +220,8 @@ blueprint! {
             let auth: AccessRules = AccessRules::new()
                 .method("set_immutable", rule!(require(admin_badge.resource_address())))
                 .method("add_investment", rule!(require(admin_badge.resource_address())))
+                .method("withdraw_fees", rule!(require(admin_badge.resource_address())))
+                .method("set_price_oracle", rule!(require(admin_badge.resource_address())))
                 .default(rule!(allow_all));
 
             // instantiate and return
@@ -178,6 +233,13 @@ blueprint! {
                     stake_receipt_address: stake_receipt_address,
                     internal_badge: Vault::with_bucket(internal_badge),
                     fee_percent: fee_percent,
+                    management_fee_percent: management_fee_percent,
+                    performance_fee_percent: performance_fee_percent,
+                    high_water_mark: dec!(1),
+                    fee_epoch: Runtime::current_epoch(),
+                    manager_fees: Vault::new(fund_token_address),
+                    price_oracle: None,
+                    price_tolerance_percent: dec!(0),
                     auction_delay: auction_delay,
                     denominator_tokens: Vault::new(denominator_token_address),
                     fund_tokens: Vault::new(fund_token_address),
@@ -203,6 +265,26 @@ blueprint! {
             self.mutable = false;
         }
 
+        // sets the reference price source auctions are checked against or removes it if none
+        // auctions only fill bids up to price_tolerance_percent worse than the reference price
+        // the price source can always be removed, even if the fund is immutable, in case it stops working
+        pub fn set_price_oracle(&mut self, price_oracle: Option<ComponentAddress>, price_tolerance_percent: Decimal) {
+            // assert fund is mutable or the price source is being removed
+            assert!(
+                self.mutable || price_oracle.is_none(),
+                "Fund is not mutable."
+            );
+
+            // assert it is a valid tolerance
+            assert!(
+                price_tolerance_percent >= dec!(0) && price_tolerance_percent <= dec!(100),
+                "Invalid price_tolerance_percent value."
+            );
+
+            self.price_oracle = price_oracle;
+            self.price_tolerance_percent = price_tolerance_percent;
+        }
+
         // adds a investment for the fund
         pub fn add_investment(&mut self, amm_pool: ComponentAddress) {
             // assert fund is mutable
@@ -233,6 +315,8 @@ blueprint! {
                     buy_auction_badge: Vault::with_bucket(buy_auction_badge),
                     sell_auction: sell_auction,
                     sell_auction_badge: Vault::with_bucket(sell_auction_badge),
+                    price: dec!(0),
+                    value_price: dec!(0),
                 }
             );
         }
@@ -241,6 +325,8 @@ blueprint! {
         // fund buys investment tokens using the denominator tokens in proportion to stakes or keeps denominator tokens if no stakers
         // returns fund tokens
         pub fn mint(&mut self, mut denominator_tokens: Bucket) -> Bucket {
+            self.charge_fees();
+
             let denominator_amount: Decimal = denominator_tokens.amount();
 
             // if no current supply
@@ -290,6 +376,8 @@ blueprint! {
         // fund sells investment tokens for denominator tokens or takes denominator tokens from the reserve
         // returns denominator tokens
         pub fn redeem(&mut self, fund_tokens: Bucket) -> Bucket {
+            self.charge_fees();
+
             // calculate amount of the fund owned by the tokens and take entitled amount from the reserve
             let fund_token_manager: &ResourceManager = borrow_resource_manager!(self.fund_token_address);
             let amount_ownership: Decimal = fund_tokens.amount() / fund_token_manager.total_supply();
@@ -316,6 +404,8 @@ blueprint! {
         // redeems fund tokens for investment tokens
         // returns vector of tokens
         pub fn redeem_for_tokens(&mut self, fund_tokens: Bucket) -> Vec<Bucket> {
+            self.charge_fees();
+
             let mut tokens_vec: Vec<Bucket> = Vec::new();
 
             // calculate amount of the fund owned by the tokens and take entitled amount from the reserve
@@ -369,6 +459,7 @@ blueprint! {
                         investment: investment,
                         stake: stake,
                         price: dec!(0),
+                        sell_price: dec!(0),
                         reward: dec!(0),
                         status: Status::Staking,
                     }
@@ -466,6 +557,8 @@ blueprint! {
 
         // processes stakes in staking queue
         pub fn process_staking(&mut self) {
+            self.charge_fees();
+
             let stake_manager: &ResourceManager = borrow_resource_manager!(self.stake_receipt_address);
             let epoch: u64 = Runtime::current_epoch();
 
@@ -473,31 +566,43 @@ blueprint! {
             let mut idx: usize = 0;
             while idx < self.staking_queue.len() && self.staking_queue[idx].epoch + self.auction_delay < epoch {
                 let mut stake_receipt_data: StakeReceipt = stake_manager.get_non_fungible_data(&self.staking_queue[idx].stake_receipt);
+
+                // halt if the best buy price deviates too far from the reference price
+                let buy_limit: Option<Decimal> = self.get_buy_limit(&self.investments[stake_receipt_data.investment]);
+                if self.exceeds_limit(self.investments[stake_receipt_data.investment].buy_auction, buy_limit) {
+                    break;
+                }
+                let sell_limits: Vec<Option<Decimal>> = self.investments.iter().map(|investment| self.get_sell_limit(investment)).collect();
                 
                 // if currently stakers
                 let total_stake: Decimal = self.get_total_stake();
                 if total_stake > dec!(0) {
-                    // sell tokens to account for new stake
+                    // sell tokens to account for new stake, only at prices within tolerance
                     let change: Decimal = dec!(1) / total_stake - dec!(1) / (total_stake + stake_receipt_data.stake);
-                    for investment in &mut self.investments {
+                    for (investment, sell_limit) in self.investments.iter_mut().zip(sell_limits) {
                         let auction: Auction = investment.sell_auction.into();
                         let sell_amount: Decimal = change * investment.stake_pool.amount() * investment.tokens.amount();        
                         let (tokens, remainder): (Bucket, Bucket) = investment.sell_auction_badge.authorize(|| {
-                            auction.auction(investment.tokens.take(sell_amount))
+                            auction.auction(investment.tokens.take(sell_amount), sell_limit)
                         });
 
+                        let sold_amount: Decimal = sell_amount - remainder.amount();
+                        let denominator_amount: Decimal = tokens.amount();
                         self.denominator_tokens.put(tokens);
                         investment.tokens.put(remainder);
+
+                        // record price
+                        Self::record_price(investment, sold_amount, denominator_amount);
                     }
                 }
 
                 let investment: &mut Investment = &mut self.investments[stake_receipt_data.investment];
 
-                // buy tokens using available capital 
+                // buy tokens using available capital, only at prices within tolerance
                 let auction: Auction = investment.buy_auction.into();
                 let mut denominator_amount: Decimal = self.denominator_tokens.amount();
                 let (tokens, remainder): (Bucket, Bucket) = investment.buy_auction_badge.authorize(|| {
-                    auction.auction(self.denominator_tokens.take_all())
+                    auction.auction(self.denominator_tokens.take_all(), buy_limit)
                 });
 
                 // calculate price
@@ -514,6 +619,8 @@ blueprint! {
                 self.denominator_tokens.put(remainder);
 
                 if price > dec!(0) {    // auction was successful
+                    Self::record_price(investment, tokens_amount, denominator_amount);
+
                     // move stake to staking pool
                     investment.stake_pool.put(self.fund_tokens.take(stake_receipt_data.stake));
                     
@@ -539,6 +646,8 @@ blueprint! {
 
         // processes stakes in unstaking queue
         pub fn process_unstaking(&mut self) {
+            self.charge_fees();
+
             let stake_manager: &ResourceManager = borrow_resource_manager!(self.stake_receipt_address);
             let epoch: u64 = Runtime::current_epoch();
 
@@ -546,13 +655,19 @@ blueprint! {
             let mut idx: usize = 0;
             while idx < self.unstaking_queue.len() && self.unstaking_queue[idx].epoch + self.auction_delay < epoch {
                 let mut stake_receipt_data: StakeReceipt = stake_manager.get_non_fungible_data(&self.unstaking_queue[idx].stake_receipt);
+
+                // unstakes never halt so stakers can always leave, tokens are only sold at prices within tolerance
+                // and if none are sold the stake is returned without a reward
+                let sell_limit: Option<Decimal> = self.get_sell_limit(&self.investments[stake_receipt_data.investment]);
+                let buy_limits: Vec<Option<Decimal>> = self.investments.iter().map(|investment| self.get_buy_limit(investment)).collect();
+
                 let investment: &mut Investment = &mut self.investments[stake_receipt_data.investment];
 
-                // sell tokens to account for unstake
+                // sell tokens to account for unstake, only at prices within tolerance
                 let auction: Auction = investment.sell_auction.into();
                 let sell_amount: Decimal = stake_receipt_data.stake / investment.stake_pool.amount() * investment.tokens.amount();
                 let (tokens, remainder): (Bucket, Bucket) = investment.sell_auction_badge.authorize(|| {
-                    auction.auction(investment.tokens.take(sell_amount))
+                    auction.auction(investment.tokens.take(sell_amount), sell_limit)
                 });
 
                 // calculate price
@@ -567,6 +682,7 @@ blueprint! {
                 // deposit tokens
                 self.denominator_tokens.put(tokens);
                 investment.tokens.put(remainder);
+                Self::record_price(investment, tokens_amount, denominator_amount);
 
                 // remove stake from stake pool
                 let mut fund_tokens: Bucket = investment.stake_pool.take(stake_receipt_data.stake);
@@ -576,19 +692,24 @@ blueprint! {
                 let relative_value: Decimal = if total_stake > dec!(0) {
                     // buy tokens according to stake ratios
                     let mut value_sum: Decimal = dec!(0);
-                    for investment in &mut self.investments {
+                    for (investment, buy_limit) in self.investments.iter_mut().zip(buy_limits) {
                         let calculated_buy_amount = denominator_amount * investment.stake_pool.amount() / total_stake;
                         let buy_amount: Decimal = calculated_buy_amount.min(self.denominator_tokens.amount());
                         
                         if buy_amount > dec!(0) {
                             let auction: Auction = investment.buy_auction.into();
                             let (tokens, remainder): (Bucket, Bucket) = investment.buy_auction_badge.authorize(|| {
-                                auction.auction(self.denominator_tokens.take(buy_amount))
+                                auction.auction(self.denominator_tokens.take(buy_amount), buy_limit)
                             });
                             value_sum += tokens.amount() / investment.tokens.amount() * investment.stake_pool.amount();
+                            let bought_amount: Decimal = tokens.amount();
+                            let spent_amount: Decimal = buy_amount - remainder.amount();
 
                             investment.tokens.put(tokens);
                             self.denominator_tokens.put(remainder);
+
+                            // record price
+                            Self::record_price(investment, bought_amount, spent_amount);
                         }
                     }
 
@@ -628,6 +749,7 @@ blueprint! {
                 self.fund_tokens.put(fund_tokens);
 
                 // update stake receipt
+                stake_receipt_data.sell_price = price;
                 stake_receipt_data.reward = reward;
                 stake_receipt_data.status = Status::Unstaked;
 
@@ -643,6 +765,200 @@ blueprint! {
             self.unstaking_queue.drain(..idx);
         }
 
+        // charges outstanding fees
+        // returns fund tokens minted as fees for the manager
+        pub fn withdraw_fees(&mut self) -> Bucket {
+            self.charge_fees();
+
+            self.manager_fees.take_all()
+        }
+
+        // returns percent of fund token supply minted to the manager per epoch
+        pub fn get_management_fee_percent(&self) -> Decimal {
+            self.management_fee_percent
+        }
+
+        // returns percent of value gained above the high water mark minted to the manager
+        pub fn get_performance_fee_percent(&self) -> Decimal {
+            self.performance_fee_percent
+        }
+
+        // returns highest net asset value per fund token that performance fees were charged at
+        pub fn get_high_water_mark(&self) -> Decimal {
+            self.high_water_mark
+        }
+
+        // returns net asset value per fund token in denominator tokens, using the prices fees were last charged at
+        pub fn get_net_asset_value(&self) -> Decimal {
+            let total_supply: Decimal = borrow_resource_manager!(self.fund_token_address).total_supply();
+            if total_supply == dec!(0) {
+                dec!(0)
+            } else {
+                self.get_total_value() / total_supply
+            }
+        }
+
+        // returns amount of fund tokens held as fees for the manager, not including fees not yet charged
+        pub fn get_manager_fees(&self) -> Decimal {
+            self.manager_fees.amount()
+        }
+
+        // mints management fees for the epochs passed since fees were last charged
+        // mints performance fees if the net asset value is above the high water mark
+        // and no investment was valued for the first time
+        fn charge_fees(&mut self) {
+            let fund_token_manager: &ResourceManager = borrow_resource_manager!(self.fund_token_address);
+            let epoch: u64 = Runtime::current_epoch();
+            let epochs: u64 = epoch - self.fee_epoch;
+            self.fee_epoch = epoch;
+
+            // value investments at prices a single auction fill can not push up
+            // a held investment valued for the first time has no baseline the rise can be capped from
+            let value_prices: Vec<Decimal> = self.investments.iter().map(|investment| self.get_value_price(investment, epochs)).collect();
+            let mut new_baseline: bool = false;
+            for (investment, value_price) in self.investments.iter_mut().zip(value_prices) {
+                if investment.value_price == dec!(0) && value_price > dec!(0) && investment.tokens.amount() > dec!(0) {
+                    new_baseline = true;
+                }
+                investment.value_price = value_price;
+            }
+
+            // an empty fund mints at a net asset value of 1 again
+            let total_supply: Decimal = fund_token_manager.total_supply();
+            if total_supply == dec!(0) {
+                self.high_water_mark = dec!(1);
+                return;
+            }
+
+            // management fee dilutes the supply by a fixed percent per epoch
+            let management_amount: Decimal = total_supply * self.management_fee_percent * epochs / dec!(100);
+
+            // performance fee is a percent of the value gained above the high water mark
+            let total_value: Decimal = self.get_total_value();
+            let supply_after_management: Decimal = total_supply + management_amount;
+            let net_asset_value: Decimal = total_value / supply_after_management;
+            let performance_amount: Decimal = if new_baseline {
+                // no fee on an uncapped first valuation, it only raises the high water mark
+                self.high_water_mark = self.high_water_mark.max(net_asset_value);
+
+                dec!(0)
+            } else if net_asset_value > self.high_water_mark {
+                let fee_value: Decimal = (net_asset_value - self.high_water_mark) * supply_after_management * self.performance_fee_percent / dec!(100);
+
+                // mint tokens worth the fee value after dilution
+                let amount: Decimal = fee_value * supply_after_management / (total_value - fee_value);
+                self.high_water_mark = total_value / (supply_after_management + amount);
+
+                amount
+            } else {
+                dec!(0)
+            };
+
+            let mint_amount: Decimal = management_amount + performance_amount;
+            if mint_amount > dec!(0) {
+                let fee_fund_tokens: Bucket = self.internal_badge.authorize(|| {
+                    fund_token_manager.mint(mint_amount)
+                });
+
+                self.manager_fees.put(fee_fund_tokens);
+            }
+        }
+
+        // returns (price, sell_price, reward) of a stake receipt
+        pub fn get_stake_receipt_data(&self, stake_receipt: Proof) -> (Decimal, Decimal, Decimal) {
+            // assert it is a valid stake receipt
+            assert!(
+                stake_receipt.resource_address() == self.stake_receipt_address,
+                "Invalid stake receipt."
+            );
+
+            let stake_receipt_data: StakeReceipt = stake_receipt.non_fungible().data();
+
+            (stake_receipt_data.price, stake_receipt_data.sell_price, stake_receipt_data.reward)
+        }
+
+        // returns reference price source auctions are checked against
+        pub fn get_price_oracle(&self) -> Option<ComponentAddress> {
+            self.price_oracle
+        }
+
+        // returns percent an auction price can be worse than the reference price
+        pub fn get_price_tolerance_percent(&self) -> Decimal {
+            self.price_tolerance_percent
+        }
+
+        // returns price of investment tokens in denominator tokens from the reference price source
+        // returns none if there is no reference price source, and zero if it has no valid price
+        fn get_reference_price(&self, investment: &Investment) -> Option<Decimal> {
+            self.price_oracle.map(|price_oracle| {
+                let oracle: PriceOracle = price_oracle.into();
+                let price: Decimal = oracle.get_price(investment.tokens.resource_address());
+
+                price.max(dec!(0))
+            })
+        }
+
+        // returns highest buy auction price accepted, in denominator tokens per investment token
+        // a limit of zero fills no bids, which halts auctions while the reference price is not valid
+        fn get_buy_limit(&self, investment: &Investment) -> Option<Decimal> {
+            self.get_reference_price(investment)
+                .map(|price| price * (dec!(1) + self.price_tolerance_percent / dec!(100)))
+        }
+
+        // returns highest sell auction price accepted, in investment tokens per denominator token
+        // a limit of zero fills no bids, which halts auctions while the reference price is not valid
+        fn get_sell_limit(&self, investment: &Investment) -> Option<Decimal> {
+            self.get_reference_price(investment)
+                .map(|price| if price > dec!(0) {
+                    dec!(1) / price * (dec!(1) + self.price_tolerance_percent / dec!(100))
+                } else {
+                    dec!(0)
+                })
+        }
+
+        // returns price investment tokens are valued at for fees, in denominator tokens per investment token
+        // uses the reference price if it is valid, otherwise the latest auction price
+        // rising at most 1% per epoch since fees were last charged, without an earlier valuation the latest auction price is taken as is
+        fn get_value_price(&self, investment: &Investment, epochs: u64) -> Decimal {
+            match self.get_reference_price(investment) {
+                Some(price) if price > dec!(0) => price,
+                _ => if investment.value_price == dec!(0) {
+                    investment.price
+                } else {
+                    investment.price.min(investment.value_price * (dec!(1) + dec!("0.01") * epochs))
+                },
+            }
+        }
+
+        // records the price of an auction fill as the latest price of an investment
+        // fills of less than 0.1% of the investment tokens held are ignored so a dust bid can not set the price
+        fn record_price(investment: &mut Investment, tokens_amount: Decimal, denominator_amount: Decimal) {
+            if tokens_amount > dec!(0) && tokens_amount >= investment.tokens.amount() * dec!("0.001") {
+                investment.price = denominator_amount / tokens_amount;
+            }
+        }
+
+        // returns if the best bid of an auction is priced above the limit
+        fn exceeds_limit(&self, auction: ComponentAddress, limit: Option<Decimal>) -> bool {
+            match limit {
+                Some(limit) => {
+                    let auction: Auction = auction.into();
+                    auction.get_best_price() > limit
+                },
+                None => false,
+            }
+        }
+
+        // returns total value of the fund in denominator tokens, using the prices fees were last charged at
+        fn get_total_value(&self) -> Decimal {
+            let mut sum: Decimal = self.denominator_tokens.amount();
+            for investment in &self.investments {
+                sum += investment.tokens.amount() * investment.value_price;
+            }
+
+            sum
+        }
+
         // returns total amount of staked tokens
         fn get_total_stake(&self) -> Decimal {
             let mut sum: Decimal = dec!(0);
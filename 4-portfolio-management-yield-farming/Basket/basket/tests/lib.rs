@@ -0,0 +1,425 @@
+use radix_engine::ledger::*;
+use radix_engine::model::Receipt;
+use radix_engine::transaction::*;
+use scrypto::prelude::*;
+
+struct TestEnv {
+    pk: EcdsaPublicKey,
+    sk: EcdsaPrivateKey,
+    account: ComponentAddress,
+    package: PackageAddress,
+    ammdex: PackageAddress,
+    denominator: ResourceAddress,
+    token: ResourceAddress,
+    fund: ComponentAddress,
+    admin_badge: ResourceAddress,
+    fund_token: ResourceAddress,
+    stake_receipt: ResourceAddress,
+    buy_auction: ComponentAddress,
+    sell_auction: ComponentAddress,
+}
+
+fn new_token(executor: &mut TransactionExecutor<InMemorySubstateStore>, pk: EcdsaPublicKey, sk: &EcdsaPrivateKey, account: ComponentAddress, symbol: &str) -> ResourceAddress {
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    metadata.insert("symbol".to_string(), symbol.to_string());
+    let transaction = TransactionBuilder::new()
+        .new_token_fixed(metadata, dec!("1000000"))
+        .call_method_with_all_resources(account, "deposit_batch")
+        .build(executor.get_nonce([pk]))
+        .sign([sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    receipt.new_resource_addresses[0]
+}
+
+/// Creates a fund with a 20% performance fee and no management fee, investing in one token with a 1:1 pool
+fn setup(executor: &mut TransactionExecutor<InMemorySubstateStore>) -> TestEnv {
+    let (pk, sk, account) = executor.new_account();
+    let package = executor.publish_package(compile_package!()).unwrap();
+    let ammdex = executor.publish_package(compile_package!(concat!(env!("CARGO_MANIFEST_DIR"), "/../ammdex"))).unwrap();
+    let denominator = new_token(executor, pk, &sk, account, "USD");
+    let token = new_token(executor, pk, &sk, account, "TKN");
+
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(dec!("1000"), token, account)
+        .withdraw_from_account_by_amount(dec!("1000"), denominator, account)
+        .take_from_worktop(token, |builder, x_bucket| {
+            builder.take_from_worktop(denominator, |builder, y_bucket| {
+                builder.call_function(ammdex, "Pool", "new", args![Bucket(x_bucket), Bucket(y_bucket)])
+            })
+        })
+        .call_method_with_all_resources(account, "deposit_batch")
+        .build(executor.get_nonce([pk]))
+        .sign([&sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let pool = receipt.new_component_addresses[0];
+
+    let transaction = TransactionBuilder::new()
+        .call_function(
+            package,
+            "Fund",
+            "new",
+            args![
+                "Test Fund".to_string(),
+                "Test Fund Token".to_string(),
+                "TFT".to_string(),
+                denominator,
+                2u64,
+                dec!("0"),
+                dec!("0"),
+                dec!("20")
+            ],
+        )
+        .call_method_with_all_resources(account, "deposit_batch")
+        .build(executor.get_nonce([pk]))
+        .sign([&sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let fund = receipt.new_component_addresses[0];
+    let admin_badge = receipt.new_resource_addresses[0];
+    let fund_token = receipt.new_resource_addresses[2];
+    let stake_receipt = receipt.new_resource_addresses[3];
+
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(admin_badge, account)
+        .call_method(fund, "add_investment", args![pool])
+        .build(executor.get_nonce([pk]))
+        .sign([&sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let buy_auction = receipt.new_component_addresses[0];
+    let sell_auction = receipt.new_component_addresses[1];
+
+    TestEnv { pk, sk, account, package, ammdex, denominator, token, fund, admin_badge, fund_token, stake_receipt, buy_auction, sell_auction }
+}
+
+/// Spends bid_amount of the resource on a bid in the auction at the given price
+fn create_bid(
+    executor: &mut TransactionExecutor<InMemorySubstateStore>,
+    env: &TestEnv,
+    auction: ComponentAddress,
+    resource: ResourceAddress,
+    bid_amount: Decimal,
+    price: Decimal,
+) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(bid_amount, resource, env.account)
+        .take_from_worktop(resource, |builder, bucket_id| {
+            builder.call_method(auction, "create_bid", args![Bucket(bucket_id), price])
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn stake(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, amount: Decimal) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(amount, env.fund_token, env.account)
+        .take_from_worktop(env.fund_token, |builder, bucket_id| {
+            builder.call_method(env.fund, "stake", args![Bucket(bucket_id), 0usize])
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn call(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, method: &str) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(env.admin_badge, env.account)
+        .call_method(env.fund, method, args![])
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    receipt
+}
+
+fn get_decimal(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, method: &str) -> Decimal {
+    let transaction = TransactionBuilder::new()
+        .call_method(env.fund, method, args![])
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    scrypto_decode(&receipt.outputs[0].raw[..]).unwrap()
+}
+
+/// Creates a mock price oracle pricing the fund's token at the given price
+fn new_price_oracle(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, price: Decimal) -> ComponentAddress {
+    let transaction = TransactionBuilder::new()
+        .call_function(env.ammdex, "PriceOracle", "new", args![])
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let price_oracle = receipt.new_component_addresses[0];
+    set_oracle_price(executor, env, price_oracle, price);
+
+    price_oracle
+}
+
+fn set_oracle_price(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, price_oracle: ComponentAddress, price: Decimal) {
+    let transaction = TransactionBuilder::new()
+        .call_method(price_oracle, "set_price", args![env.token, price])
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+}
+
+fn set_price_oracle(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, price_oracle: Option<ComponentAddress>, tolerance: Decimal) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(env.admin_badge, env.account)
+        .call_method(env.fund, "set_price_oracle", args![price_oracle, tolerance])
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+fn mint(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, amount: Decimal) {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(amount, env.denominator, env.account)
+        .take_from_worktop(env.denominator, |builder, bucket_id| {
+            builder.call_method(env.fund, "mint", args![Bucket(bucket_id)])
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+}
+
+/// Passes the account's only stake receipt to a fund method
+fn call_with_stake_receipt(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, method: &str) -> Receipt {
+    let transaction = TransactionBuilder::new()
+        .withdraw_from_account_by_amount(dec!("1"), env.stake_receipt, env.account)
+        .take_from_worktop(env.stake_receipt, |builder, bucket_id| {
+            builder.call_method(env.fund, method, args![Bucket(bucket_id)])
+        })
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    executor.validate_and_execute(&transaction).unwrap()
+}
+
+/// Returns (price, sell_price, reward) of the account's only stake receipt
+fn get_stake_receipt_data(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv) -> (Decimal, Decimal, Decimal) {
+    let transaction = TransactionBuilder::new()
+        .create_proof_from_account(env.stake_receipt, env.account)
+        .create_proof_from_auth_zone(env.stake_receipt, |builder, proof_id| {
+            builder.call_method(env.fund, "get_stake_receipt_data", args![Proof(proof_id)])
+        })
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    scrypto_decode(&receipt.outputs[receipt.outputs.len() - 1].raw[..]).unwrap()
+}
+
+fn get_best_price(executor: &mut TransactionExecutor<InMemorySubstateStore>, env: &TestEnv, auction: ComponentAddress) -> Decimal {
+    let transaction = TransactionBuilder::new()
+        .call_method(auction, "get_best_price", args![])
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    scrypto_decode(&receipt.outputs[0].raw[..]).unwrap()
+}
+
+#[test]
+fn test_dust_fill_does_not_charge_performance_fee() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // Mint 1000 fund tokens and stake half of them, the fund buys 1000 tokens at a price of 1
+    mint(&mut executor, &env, dec!("1000"));
+    assert!(stake(&mut executor, &env, dec!("500")).result.is_ok());
+    assert!(create_bid(&mut executor, &env, env.buy_auction, env.token, dec!("1000"), dec!("1")).result.is_ok());
+
+    executor.substate_store_mut().set_epoch(3);
+    call(&mut executor, &env, "process_staking");
+
+    // A dust bid buys a tiny amount of tokens at 100 times their price
+    assert!(stake(&mut executor, &env, dec!("1")).result.is_ok());
+    assert!(create_bid(&mut executor, &env, env.sell_auction, env.denominator, dec!("0.01"), dec!("0.01")).result.is_ok());
+
+    executor.substate_store_mut().set_epoch(6);
+    call(&mut executor, &env, "process_staking");
+
+    // The dust fill does not mark up the position, only its real gain is charged
+    executor.substate_store_mut().set_epoch(7);
+    call(&mut executor, &env, "withdraw_fees");
+    assert!(get_decimal(&mut executor, &env, "get_net_asset_value") < dec!("1.001"));
+    assert!(get_decimal(&mut executor, &env, "get_high_water_mark") < dec!("1.001"));
+}
+
+#[test]
+fn test_valuation_rises_at_most_one_percent_per_epoch() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    mint(&mut executor, &env, dec!("1000"));
+    assert!(stake(&mut executor, &env, dec!("500")).result.is_ok());
+    assert!(create_bid(&mut executor, &env, env.buy_auction, env.token, dec!("1000"), dec!("1")).result.is_ok());
+
+    executor.substate_store_mut().set_epoch(3);
+    call(&mut executor, &env, "process_staking");
+    executor.substate_store_mut().set_epoch(4);
+    call(&mut executor, &env, "withdraw_fees");
+
+    // A large bid fills a sale at double the price
+    assert!(stake(&mut executor, &env, dec!("100")).result.is_ok());
+    assert!(create_bid(&mut executor, &env, env.sell_auction, env.denominator, dec!("1000"), dec!("0.5")).result.is_ok());
+
+    executor.substate_store_mut().set_epoch(7);
+    call(&mut executor, &env, "process_staking");
+
+    // One epoch later the remaining tokens are valued at 1.01 rather than 2, the fee is only on that and the realized gain
+    executor.substate_store_mut().set_epoch(8);
+    call(&mut executor, &env, "process_staking");
+    assert!(get_decimal(&mut executor, &env, "get_manager_fees") > dec!("0"));
+    assert!(get_decimal(&mut executor, &env, "get_high_water_mark") < dec!("1.18"));
+}
+
+#[test]
+fn test_first_valuation_does_not_charge_performance_fee() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // The first stake buys 1000 tokens at a price of 1, the second sells some at double that before fees are charged
+    mint(&mut executor, &env, dec!("1000"));
+    assert!(stake(&mut executor, &env, dec!("500")).result.is_ok());
+    assert!(stake(&mut executor, &env, dec!("100")).result.is_ok());
+    assert!(create_bid(&mut executor, &env, env.buy_auction, env.token, dec!("1000"), dec!("1")).result.is_ok());
+    assert!(create_bid(&mut executor, &env, env.sell_auction, env.denominator, dec!("1000"), dec!("0.5")).result.is_ok());
+
+    executor.substate_store_mut().set_epoch(3);
+    call(&mut executor, &env, "process_staking");
+
+    // The uncapped first valuation doubles the net asset value, it only becomes the high water mark
+    executor.substate_store_mut().set_epoch(4);
+    call(&mut executor, &env, "process_staking");
+    assert_eq!(get_decimal(&mut executor, &env, "get_manager_fees"), dec!("0"));
+    assert!(get_decimal(&mut executor, &env, "get_high_water_mark") > dec!("1.9"));
+
+    // Later valuations are capped from it and charge nothing until the value rises above it
+    executor.substate_store_mut().set_epoch(5);
+    call(&mut executor, &env, "process_staking");
+    assert_eq!(get_decimal(&mut executor, &env, "get_manager_fees"), dec!("0"));
+}
+
+#[test]
+fn test_price_oracle_can_be_removed_when_immutable() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+    let price_oracle = new_price_oracle(&mut executor, &env, dec!("1"));
+    let other_price_oracle = new_price_oracle(&mut executor, &env, dec!("1"));
+
+    assert!(set_price_oracle(&mut executor, &env, Some(price_oracle), dec!("5")).result.is_ok());
+    call(&mut executor, &env, "set_immutable");
+
+    // A new source can no longer be set, but a broken one can always be removed
+    assert!(set_price_oracle(&mut executor, &env, Some(other_price_oracle), dec!("5")).result.is_err());
+    assert!(set_price_oracle(&mut executor, &env, None, dec!("5")).result.is_ok());
+}
+
+#[test]
+fn test_auction_stops_at_limit_price() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // An auction of denominator tokens for tokens, owned by the test account
+    let transaction = TransactionBuilder::new()
+        .call_function(env.package, "Auction", "new", args![env.token, env.denominator])
+        .call_method_with_all_resources(env.account, "deposit_batch")
+        .build(executor.get_nonce([env.pk]))
+        .sign([&env.sk]);
+    let receipt = executor.validate_and_execute(&transaction).unwrap();
+    assert!(receipt.result.is_ok());
+    let auction = receipt.new_component_addresses[0];
+    let auction_badge = receipt.new_resource_addresses[0];
+
+    assert!(create_bid(&mut executor, &env, auction, env.token, dec!("100"), dec!("2")).result.is_ok());
+    assert!(create_bid(&mut executor, &env, auction, env.token, dec!("100"), dec!("1")).result.is_ok());
+    assert_eq!(get_best_price(&mut executor, &env, auction), dec!("1"));
+
+    let run_auction = |executor: &mut TransactionExecutor<InMemorySubstateStore>, limit_price: Option<Decimal>| {
+        let transaction = TransactionBuilder::new()
+            .create_proof_from_account(auction_badge, env.account)
+            .withdraw_from_account_by_amount(dec!("500"), env.denominator, env.account)
+            .take_from_worktop(env.denominator, |builder, bucket_id| {
+                builder.call_method(auction, "auction", args![Bucket(bucket_id), limit_price])
+            })
+            .call_method_with_all_resources(env.account, "deposit_batch")
+            .build(executor.get_nonce([env.pk]))
+            .sign([&env.sk]);
+        assert!(executor.validate_and_execute(&transaction).unwrap().result.is_ok());
+    };
+
+    // Only the bid at 1 is filled, the bid at 2 is above the limit and stays at the front of the chain
+    run_auction(&mut executor, Some(dec!("1.5")));
+    assert_eq!(get_best_price(&mut executor, &env, auction), dec!("2"));
+
+    // Without a limit the remaining bid is filled too
+    run_auction(&mut executor, None);
+    assert_eq!(get_best_price(&mut executor, &env, auction), dec!("0"));
+}
+
+#[test]
+fn test_process_staking_halts_while_best_bid_exceeds_tolerance() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+    let price_oracle = new_price_oracle(&mut executor, &env, dec!("1"));
+    assert!(set_price_oracle(&mut executor, &env, Some(price_oracle), dec!("5")).result.is_ok());
+
+    mint(&mut executor, &env, dec!("1000"));
+    assert!(stake(&mut executor, &env, dec!("500")).result.is_ok());
+    assert!(create_bid(&mut executor, &env, env.buy_auction, env.token, dec!("500"), dec!("1.1")).result.is_ok());
+
+    // The best bid asks 1.1 for a token the oracle prices at 1, more than 5% above it
+    executor.substate_store_mut().set_epoch(3);
+    call(&mut executor, &env, "process_staking");
+    assert_eq!(get_best_price(&mut executor, &env, env.buy_auction), dec!("1.1"));
+    assert!(call_with_stake_receipt(&mut executor, &env, "unstake").result.is_err(), "the stake is still queued");
+
+    // Once the reference price moves the queued stake is processed
+    set_oracle_price(&mut executor, &env, price_oracle, dec!("1.05"));
+    call(&mut executor, &env, "process_staking");
+    assert_eq!(get_best_price(&mut executor, &env, env.buy_auction), dec!("0"));
+    assert_eq!(get_stake_receipt_data(&mut executor, &env).0, dec!("1.1"));
+    assert!(call_with_stake_receipt(&mut executor, &env, "unstake").result.is_ok());
+}
+
+#[test]
+fn test_sell_price_is_written_to_stake_receipt() {
+    let mut ledger = InMemorySubstateStore::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, false);
+    let env = setup(&mut executor);
+
+    // The stake buys 1000 tokens at a price of 1
+    mint(&mut executor, &env, dec!("1000"));
+    assert!(stake(&mut executor, &env, dec!("500")).result.is_ok());
+    assert!(create_bid(&mut executor, &env, env.buy_auction, env.token, dec!("1000"), dec!("1")).result.is_ok());
+    executor.substate_store_mut().set_epoch(3);
+    call(&mut executor, &env, "process_staking");
+    assert_eq!(get_stake_receipt_data(&mut executor, &env), (dec!("1"), dec!("0"), dec!("0")));
+
+    // The unstake sells them to a bid paying 2 denominator tokens per token
+    assert!(call_with_stake_receipt(&mut executor, &env, "unstake").result.is_ok());
+    assert!(create_bid(&mut executor, &env, env.sell_auction, env.denominator, dec!("2000"), dec!("0.5")).result.is_ok());
+    executor.substate_store_mut().set_epoch(6);
+    call(&mut executor, &env, "process_unstaking");
+
+    let (price, sell_price, _) = get_stake_receipt_data(&mut executor, &env);
+    assert_eq!(price, dec!("1"));
+    assert_eq!(sell_price, dec!("2"));
+    assert!(call_with_stake_receipt(&mut executor, &env, "collect_unstaked").result.is_ok());
+}
@@ -78,6 +78,8 @@ def setup():
         'DENOMINATOR_TOKEN': xrd,
         'AUCTION_DELAY': str(20),
         'FEE_PERCENT': str(5),
+        'MANAGEMENT_FEE_PERCENT': str(0.0001),
+        'PERFORMANCE_FEE_PERCENT': str(10),
     }
     stdout = subprocess.run('resim run ./transactions/2_setup_fund.rtm', env=env, stdout=subprocess.PIPE, universal_newlines=True).stdout
     fund = stdout.split('Component: ')[1][0:54]
@@ -182,6 +184,26 @@ def process_stakes():
     }
     subprocess.run('resim run ./transactions/process_stakes.rtm', env=env)
 
+def withdraw_fees():
+    env = {
+        **os.environ,
+        'ACCOUNT': account,
+        'FUND': fund,
+        'FUND_ADMIN_BADGE': fund_admin_badge,
+    }
+    subprocess.run('resim run ./transactions/withdraw_fees.rtm', env=env)
+
+def set_price_oracle(price_oracle, tolerance):
+    env = {
+        **os.environ,
+        'ACCOUNT': account,
+        'FUND': fund,
+        'FUND_ADMIN_BADGE': fund_admin_badge,
+        'PRICE_ORACLE': 'Some(ComponentAddress("{}"))'.format(price_oracle) if price_oracle else 'None',
+        'PRICE_TOLERANCE_PERCENT': str(tolerance),
+    }
+    subprocess.run('resim run ./transactions/set_price_oracle.rtm', env=env)
+
 def amm_swap(amount, token, pool):
     env = {
         **os.environ,
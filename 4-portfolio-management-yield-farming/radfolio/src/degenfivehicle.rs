@@ -0,0 +1,221 @@
+//! This is an investment vehicle that supplies funds to a DegenFi
+//! lending pool, earning the suppliers' share of the interest that
+//! borrowers pay.
+//!
+//! DegenFi identifies its users by a soulbound SBT. The vehicle
+//! registers itself as a DegenFi user when instantiated and keeps
+//! its SBT for the rest of its life, presenting it whenever it
+//! deposits or redeems funds.
+//!
+//! Interest earned is credited to our deposit balance and so this
+//! vehicle never produces any profits to withdraw, it simply grows
+//! in value. The Degen tokens that DegenFi hands out for using the
+//! protocol are not something the Radfolio can hold, and are
+//! instead forwarded to a rewards account named at instantiation.
+//!
+//! Note that we can only ever get funds back out to the extent that
+//! the lending pool has liquidity which hasn't been lent out.
+
+use scrypto::prelude::*;
+
+blueprint! {
+
+    struct DegenfiVehicle {
+        /// The DegenFi component we supply funds through.
+        degenfi: ComponentAddress,
+
+        /// The token the Radfolio invests in, and which we supply to
+        /// DegenFi.
+        investment_token: ResourceAddress,
+
+        /// Our DegenFi user SBT.
+        sbt: Vault,
+
+        /// The non-fungible id of our SBT, which DegenFi tracks our
+        /// deposit under.
+        sbt_id: NonFungibleId,
+
+        /// The account we send our Degen token rewards to.
+        rewards_account: ComponentAddress,
+
+        /// The badge you need to present to call our restricted
+        /// methods
+        iv_control_badge: ResourceAddress,
+    }
+
+    impl DegenfiVehicle {
+
+        /// Creates an investment vehicle that supplies
+        /// `investment_token` to its DegenFi lending pool.
+        ///
+        /// The lending pool must already exist in the named DegenFi
+        /// component. DegenFi allows only one user per account and
+        /// the `rewards_account` is used to register us, so each
+        /// DegenFi vehicle needs its own rewards account.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can be called by anyone but note that
+        /// only someone who can call us with the named
+        /// `iv_control_badge` in their auth zone will be able to make
+        /// good use of us.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/vehicles/instantiate_degenfi_vehicle.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/vehicles/instantiate_degenfi_vehicle.rtm")]
+        /// ```
+        pub fn instantiate_degenfi_vehicle(
+            degenfi: ComponentAddress,
+            investment_token: ResourceAddress,
+            rewards_account: ComponentAddress,
+            iv_control_badge: ResourceAddress) -> ComponentAddress {
+
+            assert!(borrow_component!(degenfi).call::<bool>(
+                "pool_exists", args!(investment_token)),
+                    "DegenFi has no lending pool for this token");
+
+            let (sbt, degen_tokens): (Bucket, Bucket) =
+                borrow_component!(degenfi).call(
+                    "new_user", args!(rewards_account));
+            let sbt_id = sbt.non_fungible_ids().into_iter().next().unwrap();
+
+            let vehicle = Self {
+                degenfi,
+                investment_token,
+                sbt: Vault::with_bucket(sbt),
+                sbt_id,
+                rewards_account,
+                iv_control_badge,
+            };
+            vehicle.send_rewards(degen_tokens);
+
+            vehicle.instantiate()
+                .add_access_check(
+                    AccessRules::new()
+                        .default(rule!(require(iv_control_badge)))
+                        .method("read_investment_value", rule!(allow_all))
+                        .method("read_max_investable", rule!(allow_all))
+                        .method("read_sbt_id", rule!(allow_all))
+                ).globalize()
+        }
+
+        /// Supplies all the funds to the DegenFi lending pool.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can only be called with `iv_control_badge` in auth zone
+        ///
+        /// **Transaction manifest:** Not user callable, so no manifest is provided
+        pub fn add_funds(&mut self, new_funds: Bucket) -> Option<Bucket> {
+            assert!(new_funds.resource_address() == self.investment_token,
+                    "Wrong token type");
+            if new_funds.is_empty() { return Some(new_funds); }
+
+            let degen_tokens: Bucket = borrow_component!(self.degenfi).call(
+                "deposit_supply", args!(self.sbt.create_proof(), new_funds));
+            self.send_rewards(degen_tokens);
+            None
+        }
+
+        /// Redeems up to `by_amount` from the lending pool. If the
+        /// pool has lent out too much of its funds to cover this we
+        /// redeem what we can, and the Radfolio will ask again
+        /// later.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can only be called with `iv_control_badge` in auth zone
+        ///
+        /// **Transaction manifest:** Not user callable, so no manifest is provided
+        pub fn reduce_funds(&mut self, by_amount: Decimal) -> Option<Bucket> {
+            let liquidity: Decimal = borrow_component!(self.degenfi).call(
+                "check_liquidity", args!(self.investment_token));
+            let amount = std::cmp::min(
+                std::cmp::min(by_amount, self.read_investment_value()),
+                liquidity);
+            if amount <= Decimal::zero() { return None; }
+
+            Some(borrow_component!(self.degenfi).call::<Bucket>(
+                "redeem", args!(self.sbt.create_proof(), self.investment_token, amount)))
+        }
+
+        /// Interest is credited to our deposit and so we never have
+        /// any profits to withdraw.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can only be called with `iv_control_badge` in auth zone
+        ///
+        /// **Transaction manifest:** Not user callable, so no manifest is provided
+        pub fn withdraw_profits(&mut self) -> Option<Bucket> {
+            None
+        }
+
+        /// Values our deposit at the lending pool's supply index,
+        /// i.e. our deposit plus the interest credited to suppliers
+        /// up to the pool's last interaction. Interest that has
+        /// accrued since then but not yet been booked by the pool is
+        /// not included.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/investmentvehicle/read_investment_value.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/investmentvehicle/read_investment_value.rtm")]
+        /// ```
+        pub fn read_investment_value(&self) -> Decimal {
+            borrow_component!(self.degenfi).call::<Decimal>(
+                "check_deposit_value", args!(self.sbt_id.clone(), self.investment_token))
+        }
+
+        /// There is no limit to how much we can supply.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/investmentvehicle/read_max_investable.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/investmentvehicle/read_max_investable.rtm")]
+        /// ```
+        pub fn read_max_investable(&self) -> Option<Decimal> {
+            None
+        }
+
+        /// Returns the non-fungible id of our DegenFi SBT, which can
+        /// be used to look up our position in DegenFi.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/vehicles/read_sbt_id.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/vehicles/read_sbt_id.rtm")]
+        /// ```
+        pub fn read_sbt_id(&self) -> NonFungibleId {
+            self.sbt_id.clone()
+        }
+
+
+
+        // ---
+        // Internal methods follow
+
+        /// Forwards Degen tokens to our rewards account.
+        fn send_rewards(&self, degen_tokens: Bucket) {
+            if degen_tokens.is_empty() {
+                degen_tokens.drop_empty();
+            } else {
+                borrow_component!(self.rewards_account).call::<()>(
+                    "deposit", args!(degen_tokens));
+            }
+        }
+    }
+}
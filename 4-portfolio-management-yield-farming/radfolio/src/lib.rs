@@ -3,12 +3,13 @@
 //! Radfolio, a portfolio management system for the Radix ledger.
 //!
 //! Of the contained modules only radfolio is needed for the portfolio
-//! management system. The others exist for documentation and testing
-//! purposes only.
+//! management system. The radexvehicle and degenfivehicle modules
+//! provide investment vehicles for use with it, and the others exist
+//! for documentation and testing purposes only.
 //!
 //! If you're building a package for deployment on ledger we recommend
-//! you remove all but the radfolio module, to minimize the size of
-//! the binary.
+//! you remove all but the radfolio module and whichever investment
+//! vehicles you need, to minimize the size of the binary.
 //!
 //! # Understanding What This Does
 //!
@@ -52,9 +53,15 @@
 //! [InterestBearingMock]: crate::interestbearingmock::blueprint::InterestBearingMock
 //! [instantiate_interestbearing_mock]: crate::interestbearingmock::blueprint::InterestBearingMock::instantiate_interestbearing_mock
 //!
-//! In a real live system you would instead obtain or develop
-//! investment vehicles that interface towards real investment
-//! opportunities on the ledger.
+//! In a real live system you would instead use investment vehicles
+//! that interface towards real investment opportunities on the
+//! ledger. Two such are provided: the [RadexVehicle] provides
+//! liquidity to a RaDEX liquidity pool and the [DegenfiVehicle]
+//! supplies funds to a DegenFi lending pool. You can also develop
+//! your own.
+//!
+//! [RadexVehicle]: crate::radexvehicle::blueprint::RadexVehicle
+//! [DegenfiVehicle]: crate::degenfivehicle::blueprint::DegenfiVehicle
 //!
 //! # Test suite
 //!
@@ -87,7 +94,7 @@
 //! method in question, before finally depositing any funds to the
 //! user.
 //!
-//! There are four subdirectories in the `rtm` directory:
+//! There are five subdirectories in the `rtm` directory:
 //!
 //! `rtm/radfolio/` These are the manifests for the Radfolio component
 //! itself. These are the ones you will deal with the most.
@@ -97,13 +104,20 @@
 //! front-end with relevant data.
 //!
 //! `rtm/mock/` This contains a manifest for instantiating the mock
-//! investment vehicle we use in our test suite.
+//! investment vehicle we use in our test suite, and manifests for
+//! setting up the RaDEX and DegenFi components that the vehicle tests
+//! invest through.
+//!
+//! `rtm/vehicles/` Manifests for instantiating and reading the RaDEX
+//! and DegenFi investment vehicles.
 //!
 //! `rtm/participants/` Contains two manifests you will need if you
 //! want to create Participant NFTs to identify partners.
 //!
 //! All these transaction manifests are actively used in execution of
-//! the test suite provided and so they are known to be correct.
+//! the test suite provided and so they are known to be correct. The
+//! vehicle tests publish the RaDEX and DegenFi packages from their
+//! folders in this repository.
 //!
 //! # Development environment
 //!
@@ -125,4 +139,6 @@
 mod radfolio;
 mod investmentvehicle;
 mod interestbearingmock;
+mod radexvehicle;
+mod degenfivehicle;
 
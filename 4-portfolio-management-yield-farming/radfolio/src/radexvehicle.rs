@@ -0,0 +1,287 @@
+//! This is an investment vehicle that provides liquidity to a RaDEX
+//! liquidity pool, earning the swap fees that the pool charges
+//! traders.
+//!
+//! The vehicle is paired with one RaDEX pool, made up of the
+//! Radfolio's investment token and some other pair token. When
+//! funds are added we swap half of them for the pair token and
+//! deposit both halves into the pool, receiving the pool's tracking
+//! tokens in return. When funds are reduced we redeem a proportional
+//! part of our tracking tokens and swap the pair token back into the
+//! investment token.
+//!
+//! Fees earned by the pool accrue to the value of the tracking tokens
+//! and so this vehicle never produces any profits to withdraw, it
+//! simply grows in value.
+//!
+//! Note that providing liquidity exposes the fund to the price
+//! movements of the pair token (so-called impermanent loss) and that
+//! every entry and exit pays the pool's swap fee on half the amount
+//! moved.
+
+use scrypto::prelude::*;
+
+blueprint! {
+
+    struct RadexVehicle {
+        /// The RaDEX component we provide liquidity through.
+        radex: ComponentAddress,
+
+        /// The token the Radfolio invests in, and which we accept
+        /// and return.
+        investment_token: ResourceAddress,
+
+        /// The other token of the liquidity pool we provide to.
+        pair_token: ResourceAddress,
+
+        /// The tracking tokens representing our share of the
+        /// pool. This is None until we have first added funds,
+        /// since only then do we learn the tracking token's address.
+        tracking_tokens: Option<Vault>,
+
+        /// Pair tokens that the pool didn't accept when we last
+        /// added liquidity. They will be put to use next time we do
+        /// so.
+        leftover_pair: Vault,
+
+        /// The badge you need to present to call our restricted
+        /// methods
+        iv_control_badge: ResourceAddress,
+    }
+
+    impl RadexVehicle {
+
+        /// Creates an investment vehicle that provides liquidity to
+        /// the RaDEX pool of `investment_token` and `pair_token`.
+        ///
+        /// The pool must already exist in the named RaDEX component.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can be called by anyone but note that
+        /// only someone who can call us with the named
+        /// `iv_control_badge` in their auth zone will be able to make
+        /// good use of us.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/vehicles/instantiate_radex_vehicle.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/vehicles/instantiate_radex_vehicle.rtm")]
+        /// ```
+        pub fn instantiate_radex_vehicle(
+            radex: ComponentAddress,
+            investment_token: ResourceAddress,
+            pair_token: ResourceAddress,
+            iv_control_badge: ResourceAddress) -> ComponentAddress {
+
+            assert!(investment_token != pair_token,
+                    "Investment token and pair token must be different");
+            assert!(borrow_component!(radex).call::<bool>(
+                "pool_exists", args!(investment_token, pair_token)),
+                    "RaDEX has no pool for this token pair");
+
+            Self {
+                radex,
+                investment_token,
+                pair_token,
+                tracking_tokens: None,
+                leftover_pair: Vault::new(pair_token),
+                iv_control_badge,
+            }.instantiate()
+                .add_access_check(
+                    AccessRules::new()
+                        .default(rule!(require(iv_control_badge)))
+                        .method("read_investment_value", rule!(allow_all))
+                        .method("read_max_investable", rule!(allow_all))
+                        .method("read_tracking_tokens", rule!(allow_all))
+                ).globalize()
+        }
+
+        /// Swaps half of the funds for the pair token and adds both
+        /// halves as liquidity to the pool. Whatever the pool doesn't
+        /// accept of the investment token is returned, while excess
+        /// pair tokens are kept for next time.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can only be called with `iv_control_badge` in auth zone
+        ///
+        /// **Transaction manifest:** Not user callable, so no manifest is provided
+        pub fn add_funds(&mut self, mut new_funds: Bucket) -> Option<Bucket> {
+            assert!(new_funds.resource_address() == self.investment_token,
+                    "Wrong token type");
+            if new_funds.is_empty() { return Some(new_funds); }
+
+            let half = new_funds.take(new_funds.amount() / 2);
+            let mut pair = self.swap(half, self.pair_token);
+            pair.put(self.leftover_pair.take_all());
+
+            let (ret1, ret2, tracking): (Option<Bucket>, Option<Bucket>, Bucket) =
+                borrow_component!(self.radex).call(
+                    "add_liquidity", args!(new_funds, pair));
+
+            match &mut self.tracking_tokens {
+                Some(vault) => vault.put(tracking),
+                None => self.tracking_tokens = Some(Vault::with_bucket(tracking)),
+            }
+
+            let mut excess = Bucket::new(self.investment_token);
+            for bucket in [ret1, ret2].into_iter().flatten() {
+                if bucket.resource_address() == self.investment_token {
+                    excess.put(bucket);
+                } else {
+                    self.leftover_pair.put(bucket);
+                }
+            }
+            Some(excess)
+        }
+
+        /// Pulls funds out of the vehicle by redeeming the fraction
+        /// of our tracking tokens that corresponds to `by_amount` of
+        /// our current value, swapping the pair token side back to
+        /// the investment token.
+        ///
+        /// Due to the swap fee the amount returned will usually be a
+        /// little less than requested.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can only be called with `iv_control_badge` in auth zone
+        ///
+        /// **Transaction manifest:** Not user callable, so no manifest is provided
+        pub fn reduce_funds(&mut self, by_amount: Decimal) -> Option<Bucket> {
+            let value = self.read_investment_value();
+            if value.is_zero() || by_amount <= Decimal::zero() { return None; }
+
+            let exit_all = by_amount >= value;
+            let fraction = if exit_all { Decimal::one() } else { by_amount / value };
+
+            let mut funds = Bucket::new(self.investment_token);
+            let mut pair = if exit_all { self.leftover_pair.take_all() }
+            else { self.leftover_pair.take(self.leftover_pair.amount() * fraction) };
+
+            if let Some(vault) = &mut self.tracking_tokens {
+                let tracking = if exit_all { vault.take_all() }
+                else { vault.take(vault.amount() * fraction) };
+                if !tracking.is_empty() {
+                    let (out1, out2): (Bucket, Bucket) =
+                        borrow_component!(self.radex).call(
+                            "remove_liquidity", args!(tracking));
+                    for bucket in [out1, out2] {
+                        if bucket.resource_address() == self.investment_token {
+                            funds.put(bucket);
+                        } else {
+                            pair.put(bucket);
+                        }
+                    }
+                }
+            }
+
+            if !pair.is_empty() {
+                funds.put(self.swap(pair, self.investment_token));
+            } else {
+                pair.drop_empty();
+            }
+            Some(funds)
+        }
+
+        /// Swap fees are compounded into our liquidity position and
+        /// so we never have any profits to withdraw.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can only be called with `iv_control_badge` in auth zone
+        ///
+        /// **Transaction manifest:** Not user callable, so no manifest is provided
+        pub fn withdraw_profits(&mut self) -> Option<Bucket> {
+            None
+        }
+
+        /// Values our share of the pool at its current reserves. The
+        /// investment token side counts at face value, while the pair
+        /// token side (including any leftover pair tokens we hold) is
+        /// valued at what swapping it into the pool after removing
+        /// our liquidity would return before fees. This accounts for
+        /// the price impact of our own exit.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/investmentvehicle/read_investment_value.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/investmentvehicle/read_investment_value.rtm")]
+        /// ```
+        pub fn read_investment_value(&self) -> Decimal {
+            let reserves: HashMap<ResourceAddress, Decimal> =
+                borrow_component!(self.radex).call(
+                    "pool_reserves", args!(self.investment_token, self.pair_token));
+            let reserve_investment = reserves[&self.investment_token];
+            let reserve_pair = reserves[&self.pair_token];
+
+            let mut our_investment = Decimal::zero();
+            let mut our_pair = Decimal::zero();
+            if let Some(vault) = &self.tracking_tokens {
+                let total = borrow_resource_manager!(vault.resource_address()).total_supply();
+                if !total.is_zero() {
+                    let share = vault.amount() / total;
+                    our_investment = reserve_investment * share;
+                    our_pair = reserve_pair * share;
+                }
+            }
+
+            let remaining_investment = reserve_investment - our_investment;
+            let remaining_pair = reserve_pair - our_pair;
+            let pair_held = our_pair + self.leftover_pair.amount();
+            let pair_value = if pair_held.is_zero() { Decimal::zero() }
+            else { remaining_investment * pair_held / (remaining_pair + pair_held) };
+
+            our_investment + pair_value
+        }
+
+        /// There is no limit to how much liquidity we can provide.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/investmentvehicle/read_max_investable.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/investmentvehicle/read_max_investable.rtm")]
+        /// ```
+        pub fn read_max_investable(&self) -> Option<Decimal> {
+            None
+        }
+
+        /// Returns how many of the pool's tracking tokens we hold.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/vehicles/read_tracking_tokens.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/vehicles/read_tracking_tokens.rtm")]
+        /// ```
+        pub fn read_tracking_tokens(&self) -> Decimal {
+            match &self.tracking_tokens {
+                Some(vault) => vault.amount(),
+                None => Decimal::zero(),
+            }
+        }
+
+
+
+        // ---
+        // Internal methods follow
+
+        /// Swaps `tokens` into `output` through our RaDEX pool.
+        fn swap(&self, tokens: Bucket, output: ResourceAddress) -> Bucket {
+            borrow_component!(self.radex).call::<Bucket>(
+                "swap", args!(tokens, output))
+        }
+    }
+}
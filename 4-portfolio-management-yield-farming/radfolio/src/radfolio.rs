@@ -48,6 +48,29 @@
 //! [value_of_coupons()][blueprint::Radfolio::value_of_coupons]
 //! Calculates the current coin value of some number of coupons.
 //!
+//! ## Withdrawal Queue
+//!
+//! [queue_withdrawal()][blueprint::Radfolio::queue_withdrawal]
+//! Locks in coupons for redemption, receiving a redemption ticket.
+//!
+//! [claim_withdrawal()][blueprint::Radfolio::claim_withdrawal]
+//! Collects the funds for redemption tickets that have been paid.
+//!
+//! [cancel_withdrawal()][blueprint::Radfolio::cancel_withdrawal]
+//! Returns the coupons of redemption tickets that have not been paid.
+//!
+//! [process_withdrawal_queue()][blueprint::Radfolio::process_withdrawal_queue]
+//! Tries to free up funds and pay out queued redemption tickets.
+//!
+//! [read_withdrawal_queue()][blueprint::Radfolio::read_withdrawal_queue]
+//! Lists the redemption tickets waiting to be paid, in order.
+//!
+//! [read_queued_coupons()][blueprint::Radfolio::read_queued_coupons]
+//! Reports how many coupons are locked up in the withdrawal queue.
+//!
+//! [read_redemption_ticket_address()][blueprint::Radfolio::read_redemption_ticket_address]
+//! Returns the resource address of our redemption tickets.
+//!
 //! ## Collect Fees
 //!
 //! [read_partner_fees_stored()][blueprint::Radfolio::read_partner_fees_stored]
@@ -88,6 +111,32 @@
 //! [read_halted_investment_vehicles()][blueprint::Radfolio::read_halted_investment_vehicles]
 //! Retrieves a list of investment vehicles that are currently halted.
 //!
+//! ## Risk-Weighted Allocation
+//!
+//! [set_risk_weighted_allocation()][blueprint::Radfolio::set_risk_weighted_allocation]
+//! Turns risk-weighted allocation on or off.
+//!
+//! [read_risk_weighted_allocation()][blueprint::Radfolio::read_risk_weighted_allocation]
+//! Check whether we're currently using risk-weighted allocation.
+//!
+//! [set_allocation_bounds()][blueprint::Radfolio::set_allocation_bounds]
+//! Limits how much of the fund an investment vehicle can be given.
+//!
+//! [read_allocation_bounds()][blueprint::Radfolio::read_allocation_bounds]
+//! Retrieves the allocation limits of our investment vehicles.
+//!
+//! [read_vehicle_performance()][blueprint::Radfolio::read_vehicle_performance]
+//! Returns the realized yield and drawdown of an investment vehicle.
+//!
+//! [read_effective_weights()][blueprint::Radfolio::read_effective_weights]
+//! Returns the weights currently used for our investment vehicles.
+//!
+//! [read_rebalance_log_length()][blueprint::Radfolio::read_rebalance_log_length]
+//! Returns how many rebalance decisions we have recorded.
+//!
+//! [read_rebalance_log_entry()][blueprint::Radfolio::read_rebalance_log_entry]
+//! Retrieves one of our recorded rebalance decisions.
+//!
 //! ## Partner List Management
 //!
 //! [set_allow_any_partner()][blueprint::Radfolio::set_allow_any_partner]
@@ -311,6 +360,36 @@
 //!
 //! [force_fund_maintenance]: blueprint::Radfolio::force_fund_maintenance
 //!
+//! ## Withdrawal Queue
+//!
+//! A `withdraw` call can only be paid out of our free funds, and will
+//! fail if they're not sufficient. This can happen after a run of
+//! large withdrawals, or if a lot of our funds are stuck in
+//! investment vehicles that are illiquid or halted.
+//!
+//! An investor who doesn't want to wait around for free funds to
+//! refill can instead call `queue_withdrawal`. Their coupons are then
+//! locked up in the fund and they receive a redemption ticket NFT in
+//! return. Tickets are paid first-in-first-out out of our free funds,
+//! with payment happening whenever a fund maintenance cycle finds
+//! enough funds to cover the next ticket in line. While tickets are
+//! waiting to be paid, maintenance cycles will try to pull enough
+//! funds out of our investment vehicles to cover them all.
+//!
+//! The value of a ticket is the value of its coupons at the time it
+//! gets paid, so the investor shares in the fund's gains and losses
+//! until then. Withdrawal fees are charged as for a normal
+//! withdrawal. Once paid, the funds are set aside for the ticket and
+//! the investor can collect them at their leisure by calling
+//! `claim_withdrawal`.
+//!
+//! An investor who changes their mind can call `cancel_withdrawal`
+//! with a ticket that hasn't been paid yet to get their coupons back.
+//!
+//! Queued tickets are paid before any normal `withdraw` gets access
+//! to our free funds, and `withdraw` is unavailable for as long as
+//! there are tickets waiting.
+//!
 //! ## Minimum Deposit
 //!
 //! You can set a minimum deposit and if you do then any call to the
@@ -415,10 +494,174 @@
 //! Note that an investment vehicle that has been removed from the
 //! fund can be re-added later, either to put it back into use again
 //! or perhaps to recover more funds from it.
+//!
+//! ## Risk-Weighted Allocation
+//!
+//! At every full maintenance cycle Radfolio samples the
+//! `read_investment_value` of each investment vehicle. Accounting
+//! for the funds we have put in and taken out since the last sample,
+//! this tells us how one token invested in the vehicle has grown
+//! over time. From this we derive each vehicle's realized yield (per
+//! epoch) and its maximum drawdown, i.e. the worst fall it has had
+//! from a previous high.
+//!
+//! By default these numbers are for information only, and funds are
+//! distributed according to the weights you have set. If you turn on
+//! risk-weighted allocation however, each vehicle's weight is scaled
+//! by how much it has been growing per maintenance interval, and
+//! then scaled down by its maximum drawdown. So a vehicle that has
+//! made 1% per epoch with a 50 epoch maintenance interval and has
+//! never lost value will have its weight multiplied by 1.5, whereas
+//! one that has made the same profits but once lost 20% of its value
+//! will have its weight multiplied by 1.2. Vehicles we don't yet have
+//! a history for keep the weight you set.
+//!
+//! You can put limits on this by setting the minimum and maximum
+//! percentage of our invested funds each vehicle should have. If the
+//! minimums you set add up to more than 100% they can't all be met,
+//! and funds will be spread in proportion to them.
+//!
+//! A vehicle you have set to zero weight always remains at zero
+//! weight.
+//!
+//! Every full maintenance cycle records its decision in a rebalance
+//! log that anyone can read, so investors can follow how and why
+//! their funds are being moved around.
 
 
 use scrypto::prelude::*;
 
+/// A redemption ticket is given to investors who queue a withdrawal,
+/// and is used to collect the funds once the ticket has been paid.
+#[derive(NonFungibleData)]
+pub struct RedemptionTicket {
+    /// The number of coupons locked up for redemption.
+    pub coupons: Decimal,
+
+    /// The partner to credit for the withdrawal, if any.
+    pub partner: Option<NonFungibleId>,
+
+    /// The epoch the withdrawal was queued in.
+    pub queued_epoch: u64,
+
+    /// The funds set aside for this ticket, once it has been paid.
+    #[scrypto(mutable)]
+    pub payout: Option<Decimal>,
+}
+
+/// Tracks the realized performance of an investment vehicle, as seen
+/// through its `read_investment_value` over time.
+#[derive(Debug, Clone, TypeId, Encode, Decode, Describe)]
+pub struct VehiclePerformance {
+    /// The epoch of our first sample.
+    pub first_epoch: u64,
+
+    /// The epoch of our latest sample.
+    pub last_epoch: u64,
+
+    /// The investment value reported at our latest sample.
+    pub last_value: Decimal,
+
+    /// Funds we have put into the vehicle since our latest sample,
+    /// less funds we have taken out of it.
+    pub net_inflow: Decimal,
+
+    /// What one token invested at our first sample has grown into.
+    pub growth_index: Decimal,
+
+    /// The highest `growth_index` we have seen.
+    pub peak_index: Decimal,
+
+    /// The largest fall of `growth_index` from a previous peak, as a
+    /// fraction of that peak.
+    pub max_drawdown: Decimal,
+}
+
+impl VehiclePerformance {
+    /// Starts tracking a vehicle at its current investment value.
+    pub fn new(value: Decimal, epoch: u64) -> Self {
+        Self {
+            first_epoch: epoch,
+            last_epoch: epoch,
+            last_value: value,
+            net_inflow: Decimal::ZERO,
+            growth_index: Decimal::ONE,
+            peak_index: Decimal::ONE,
+            max_drawdown: Decimal::ZERO,
+        }
+    }
+
+    /// Takes a new sample of the vehicle's investment value.
+    pub fn sample(&mut self, value: Decimal, epoch: u64) {
+        let base = self.last_value + self.net_inflow;
+        if base.is_positive() {
+            self.growth_index = self.growth_index * value / base;
+        }
+        if self.growth_index > self.peak_index {
+            self.peak_index = self.growth_index;
+        } else if self.peak_index.is_positive() {
+            let drawdown = (self.peak_index - self.growth_index) / self.peak_index;
+            if drawdown > self.max_drawdown {
+                self.max_drawdown = drawdown;
+            }
+        }
+        self.last_value = value;
+        self.net_inflow = Decimal::ZERO;
+        self.last_epoch = epoch;
+    }
+
+    /// The average growth per epoch since our first sample. This is
+    /// zero until we have samples from two different epochs.
+    pub fn realized_yield(&self) -> Decimal {
+        if self.last_epoch > self.first_epoch {
+            (self.growth_index - Decimal::ONE) / (self.last_epoch - self.first_epoch)
+        } else {
+            Decimal::ZERO
+        }
+    }
+}
+
+/// One investment vehicle's part in a rebalance decision.
+#[derive(Debug, Clone, TypeId, Encode, Decode, Describe)]
+pub struct RebalanceAllocation {
+    /// The investment vehicle.
+    pub vehicle: ComponentAddress,
+
+    /// The weight configured for the vehicle by the fund's managers.
+    pub configured_weight: Decimal,
+
+    /// The weight we used for the vehicle.
+    pub weight: Decimal,
+
+    /// The vehicle's realized yield per epoch.
+    pub realized_yield: Decimal,
+
+    /// The vehicle's maximum drawdown.
+    pub max_drawdown: Decimal,
+
+    /// The vehicle's investment value once rebalancing was done.
+    pub value: Decimal,
+}
+
+/// A record of the decisions made in one full maintenance cycle.
+#[derive(Debug, Clone, TypeId, Encode, Decode, Describe)]
+pub struct RebalanceRecord {
+    /// The epoch the rebalance took place.
+    pub epoch: u64,
+
+    /// Whether risk-weighted allocation was in use.
+    pub risk_weighted: bool,
+
+    /// Our total funds once rebalancing was done.
+    pub total_funds: Decimal,
+
+    /// Our free funds once rebalancing was done.
+    pub free_funds: Decimal,
+
+    /// What we did with each non-halted investment vehicle.
+    pub allocations: Vec<RebalanceAllocation>,
+}
+
 blueprint! {
     struct Radfolio {
         /// This is the token we're investing.
@@ -497,6 +740,46 @@ blueprint! {
 
         /// The partners to allow (if we don't allow everyone).
         approved_partners: HashSet<NonFungibleId>,
+
+        /// Whether to adjust our investment weights according to the
+        /// realized performance of our investment vehicles.
+        risk_weighted_allocation: bool,
+
+        /// Minimum and maximum percentage of our invested funds to
+        /// put into each investment vehicle when using risk-weighted
+        /// allocation.
+        allocation_bounds: HashMap<ComponentAddress, (Decimal, Decimal)>,
+
+        /// Realized performance of each of our investment vehicles.
+        performance: HashMap<ComponentAddress, VehiclePerformance>,
+
+        /// The weights calculated at our last full maintenance cycle
+        /// when using risk-weighted allocation.
+        effective_weights: HashMap<ComponentAddress, Decimal>,
+
+        /// A record of every full maintenance cycle, indexed from
+        /// zero.
+        rebalance_log: LazyMap<u64, RebalanceRecord>,
+
+        /// The number of entries in `rebalance_log`.
+        rebalance_log_length: u64,
+
+        /// The resource address of our redemption ticket NFTs.
+        redemption_ticket_address: ResourceAddress,
+
+        /// The id to use for the next redemption ticket we issue.
+        next_redemption_ticket: u64,
+
+        /// Redemption tickets waiting to be paid, first in line
+        /// first.
+        redemption_queue: Vec<NonFungibleId>,
+
+        /// Coupons locked up by unpaid redemption tickets.
+        queued_coupons: Vault,
+
+        /// Funds set aside for paid redemption tickets that haven't
+        /// been claimed yet.
+        redemption_funds: Vault,
     }
 
     impl Radfolio {
@@ -604,6 +887,15 @@ blueprint! {
                     "Radfolio coupon".to_string()))
                 .initial_supply(0);
 
+            // Redemption tickets are given to investors waiting in
+            // our withdrawal queue.
+            let redemption_ticket_address = ResourceBuilder::new_non_fungible()
+                .metadata("name", "Radfolio redemption ticket")
+                .mintable(rule!(require(mint_badge.resource_address())), LOCKED)
+                .burnable(rule!(require(mint_badge.resource_address())), LOCKED)
+                .updateable_non_fungible_data(rule!(require(mint_badge.resource_address())), LOCKED)
+                .no_initial_supply();
+
             let radfolio = 
                 Self {
                     investment_token,
@@ -627,6 +919,17 @@ blueprint! {
                     partner_fees: HashMap::new(),
                     allow_any_partner: false,
                     approved_partners: HashSet::new(),
+                    risk_weighted_allocation: false,
+                    allocation_bounds: HashMap::new(),
+                    performance: HashMap::new(),
+                    effective_weights: HashMap::new(),
+                    rebalance_log: LazyMap::new(),
+                    rebalance_log_length: 0,
+                    redemption_ticket_address,
+                    next_redemption_ticket: 0,
+                    redemption_queue: Vec::new(),
+                    queued_coupons: Vault::new(coupons.resource_address()),
+                    redemption_funds: Vault::new(investment_token),
                 }
             .instantiate()
                 .add_access_check(
@@ -645,6 +948,15 @@ blueprint! {
                         // withdraw requires you to provide coupon
                         // tokens
                         .method("withdraw", rule!(allow_all))
+                        // the withdrawal queue methods require you to
+                        // provide coupons or redemption tickets
+                        .method("queue_withdrawal", rule!(allow_all))
+                        .method("claim_withdrawal", rule!(allow_all))
+                        .method("cancel_withdrawal", rule!(allow_all))
+                        .method("process_withdrawal_queue", rule!(allow_all))
+                        .method("read_withdrawal_queue", rule!(allow_all))
+                        .method("read_queued_coupons", rule!(allow_all))
+                        .method("read_redemption_ticket_address", rule!(allow_all))
                         .method("value_of_coupons", rule!(allow_all))
                         .method("read_total_funds", rule!(allow_all))
                         .method("read_total_coupons", rule!(allow_all))
@@ -668,6 +980,12 @@ blueprint! {
                         .method("read_partner_fees_stored", rule!(allow_all))
                         .method("read_investment_vehicles", rule!(allow_all))
                         .method("read_halted_investment_vehicles", rule!(allow_all))
+                        .method("read_risk_weighted_allocation", rule!(allow_all))
+                        .method("read_allocation_bounds", rule!(allow_all))
+                        .method("read_vehicle_performance", rule!(allow_all))
+                        .method("read_effective_weights", rule!(allow_all))
+                        .method("read_rebalance_log_length", rule!(allow_all))
+                        .method("read_rebalance_log_entry", rule!(allow_all))
                         // withdraw_partner_fees requires you to
                         // provide a Proof
                         .method("withdraw_partner_fees", rule!(allow_all))
@@ -869,7 +1187,12 @@ blueprint! {
         ///
         /// Will panic if insufficient free funds are available. In
         /// this case, try with a smaller number of coupons and/or
-        /// wait for more free funds to become available.
+        /// wait for more free funds to become available, or use
+        /// [Radfolio::queue_withdrawal] instead.
+        ///
+        /// Queued redemption tickets are paid before this
+        /// withdrawal, and if we can't pay them all then this
+        /// withdrawal will panic. You will need to queue behind them.
         ///
         /// Will also panic if you try to deposit the wrong coupon
         /// type and under various error conditions.
@@ -893,6 +1216,9 @@ blueprint! {
                     "The partner is not approved");
 
             self.recover_profits_from_ivs();
+            self.pay_redemption_queue();
+            assert!(self.redemption_queue.is_empty(),
+                    "There are queued withdrawals ahead of you, consider queueing yours");
             let cmgr: &ResourceManager = borrow_resource_manager!(self.coupon_address);
             // We receive a number of tokens proportional to our
             // ownership% in the coupon tokens.
@@ -901,7 +1227,10 @@ blueprint! {
             // then this call fails and the user needs to wait for
             // free_funds to refill, possibly making a smaller
             // withdrawal in the meantime.
-            let mut bucket_out = self.free_funds.take(self.value_of(coupons.amount(), cmgr));
+            let value = self.value_of(coupons.amount(), cmgr);
+            assert!(value <= self.free_funds.amount(),
+                    "Insufficient free funds, consider queueing your withdrawal");
+            let mut bucket_out = self.free_funds.take(value);
             self.charge_fees(self.withdraw_fee_bps, self.withdraw_fee_partner_bps,
                              &mut bucket_out, &partner);
             self.mint_badge.authorize(||  {
@@ -913,6 +1242,196 @@ blueprint! {
             bucket_out
         }
 
+        /// Queues a withdrawal from Radfolio. The coupons are locked
+        /// up in the fund and a redemption ticket NFT is returned.
+        ///
+        /// Redemption tickets are paid first-in-first-out as free
+        /// funds become available. This may happen immediately, in
+        /// which case the ticket can be claimed at once with
+        /// [Radfolio::claim_withdrawal]. Use
+        /// [Radfolio::cancel_withdrawal] to get the coupons back
+        /// from a ticket that hasn't been paid yet.
+        ///
+        /// Will panic if you send the wrong coupon type, no coupons,
+        /// or a partner that isn't approved.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can be called by anyone sending us
+        /// coupons of the correct type
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/queue_withdrawal.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/queue_withdrawal.rtm")]
+        /// ```
+        pub fn queue_withdrawal(&mut self, coupons: Bucket, partner: Option<NonFungibleId>)
+                                -> Bucket {
+            assert!(coupons.resource_address() == self.coupon_address,
+                    "Wrong coupon type");
+            assert!(coupons.amount().is_positive(),
+                    "Send some coupons to queue for withdrawal");
+            assert!(partner.is_none()
+                    || self.allow_any_partner
+                    || self.approved_partners.contains(partner.as_ref().unwrap()),
+                    "The partner is not approved");
+
+            let nfid = NonFungibleId::from_u64(self.next_redemption_ticket);
+            self.next_redemption_ticket += 1;
+            let tmgr: &ResourceManager = borrow_resource_manager!(self.redemption_ticket_address);
+            let ticket = self.mint_badge.authorize(|| {
+                tmgr.mint_non_fungible(&nfid,
+                                       RedemptionTicket {
+                                           coupons: coupons.amount(),
+                                           partner,
+                                           queued_epoch: Runtime::current_epoch(),
+                                           payout: None,
+                                       })
+            });
+            self.redemption_queue.push(nfid);
+            self.queued_coupons.put(coupons);
+
+            self.maintain_fund(false);
+
+            ticket
+        }
+
+        /// Collects the funds set aside for one or more paid
+        /// redemption tickets. The tickets are burnt.
+        ///
+        /// Will panic if any of the tickets hasn't been paid yet.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can be called by anyone sending us
+        /// redemption tickets
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/claim_withdrawal.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/claim_withdrawal.rtm")]
+        /// ```
+        pub fn claim_withdrawal(&mut self, tickets: Bucket) -> Bucket {
+            assert!(tickets.resource_address() == self.redemption_ticket_address,
+                    "Wrong ticket type");
+            let tmgr: &ResourceManager = borrow_resource_manager!(self.redemption_ticket_address);
+            let mut funds = Bucket::new(self.investment_token);
+            for nfid in tickets.non_fungible_ids() {
+                let ticket: RedemptionTicket = tmgr.get_non_fungible_data(&nfid);
+                let payout = ticket.payout.expect("Ticket has not been paid yet");
+                funds.put(self.redemption_funds.take(payout));
+            }
+            self.mint_badge.authorize(|| {
+                tickets.burn();
+            });
+
+            funds
+        }
+
+        /// Cancels one or more queued withdrawals, returning the
+        /// coupons that were locked up by them. The tickets are
+        /// burnt.
+        ///
+        /// Will panic if any of the tickets has already been paid,
+        /// in which case you need to use
+        /// [Radfolio::claim_withdrawal] instead.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can be called by anyone sending us
+        /// redemption tickets
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/cancel_withdrawal.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/cancel_withdrawal.rtm")]
+        /// ```
+        pub fn cancel_withdrawal(&mut self, tickets: Bucket) -> Bucket {
+            assert!(tickets.resource_address() == self.redemption_ticket_address,
+                    "Wrong ticket type");
+            let tmgr: &ResourceManager = borrow_resource_manager!(self.redemption_ticket_address);
+            let mut coupons = Bucket::new(self.coupon_address);
+            for nfid in tickets.non_fungible_ids() {
+                let ticket: RedemptionTicket = tmgr.get_non_fungible_data(&nfid);
+                assert!(ticket.payout.is_none(),
+                        "Ticket has already been paid, claim it instead");
+                self.redemption_queue.retain(|queued| *queued != nfid);
+                coupons.put(self.queued_coupons.take(ticket.coupons));
+            }
+            self.mint_badge.authorize(|| {
+                tickets.burn();
+            });
+
+            coupons
+        }
+
+        /// Runs a fund maintenance cycle, which pays out any queued
+        /// redemption tickets we have funds for and tries to free up
+        /// funds for the rest.
+        ///
+        /// A full maintenance cycle may be run if one is due, as on
+        /// a deposit or withdrawal.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/process_withdrawal_queue.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/process_withdrawal_queue.rtm")]
+        /// ```
+        pub fn process_withdrawal_queue(&mut self) {
+            self.maintain_fund(false);
+        }
+
+        /// Returns the redemption tickets that are waiting to be
+        /// paid, with the next one to be paid first.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/read_withdrawal_queue.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/read_withdrawal_queue.rtm")]
+        /// ```
+        pub fn read_withdrawal_queue(&self) -> Vec<NonFungibleId> {
+            self.redemption_queue.clone()
+        }
+
+        /// Returns the number of coupons locked up by unpaid
+        /// redemption tickets.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/read_queued_coupons.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/read_queued_coupons.rtm")]
+        /// ```
+        pub fn read_queued_coupons(&self) -> Decimal {
+            self.queued_coupons.amount()
+        }
+
+        /// Returns the resource address of our redemption tickets.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/read_redemption_ticket_address.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/read_redemption_ticket_address.rtm")]
+        /// ```
+        pub fn read_redemption_ticket_address(&self) -> ResourceAddress {
+            self.redemption_ticket_address
+        }
+
         /// Can be called by a partner to withdraw the partner fees
         /// accrued by them.
         ///
@@ -1372,7 +1891,8 @@ blueprint! {
         ///
         /// Investment vehicles that are removed will also be removed
         /// from the list of halted investment vehicles, if they were
-        /// there.
+        /// there. Their allocation bounds and performance history are
+        /// forgotten.
         ///
         /// ---
         ///
@@ -1386,6 +1906,9 @@ blueprint! {
         pub fn remove_investment_vehicles(&mut self, vehicles: HashSet<ComponentAddress>) {
             self.investments.retain(|k, _| !vehicles.contains(k));
             self.halted_investments.retain(|k| !vehicles.contains(k));
+            self.allocation_bounds.retain(|k, _| !vehicles.contains(k));
+            self.performance.retain(|k, _| !vehicles.contains(k));
+            self.effective_weights.retain(|k, _| !vehicles.contains(k));
         }
         
         /// Removes ALL investment vehicles from the fund.
@@ -1396,7 +1919,8 @@ blueprint! {
         /// rescuing such funds.
         ///
         /// The list of halted investment vehicles will also be
-        /// cleared.
+        /// cleared, as will all allocation bounds and performance
+        /// history.
         ///
         /// ---
         ///
@@ -1410,6 +1934,9 @@ blueprint! {
         pub fn clear_investment_vehicles(&mut self) {
             self.investments.clear();
             self.halted_investments.clear();
+            self.allocation_bounds.clear();
+            self.performance.clear();
+            self.effective_weights.clear();
         }
 
         /// Retrieves a list of our current investment vhicles. This
@@ -1478,6 +2005,188 @@ blueprint! {
             self.halted_investments.clone()
         }
 
+        /// Turns risk-weighted allocation on or off. Pass `true` to
+        /// this method to turn it on.
+        ///
+        /// The new setting takes effect on the next full maintenance
+        /// cycle. Call [Radfolio::force_fund_maintenance] if you want
+        /// it to take effect immediately.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can only be called with admin badge in auth zone
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/set_risk_weighted_allocation.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/set_risk_weighted_allocation.rtm")]
+        /// ```
+        pub fn set_risk_weighted_allocation(&mut self, enabled: bool) {
+            self.risk_weighted_allocation = enabled;
+            self.effective_weights.clear();
+        }
+
+        /// Returns our `risk_weighted_allocation` setting.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/read_risk_weighted_allocation.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/read_risk_weighted_allocation.rtm")]
+        /// ```
+        pub fn read_risk_weighted_allocation(&self) -> bool {
+            self.risk_weighted_allocation
+        }
+
+        /// Sets the minimum and maximum percentage of our invested
+        /// funds that an investment vehicle can be given when using
+        /// risk-weighted allocation.
+        ///
+        /// Will panic if we do not currently have this investment
+        /// vehicle, if the percentages aren't between 0 and 100
+        /// with the minimum no larger than the maximum, or if the
+        /// minimums of all our vehicles would add up to more than 100.
+        /// If the maximums add up to less than 100 the rest of our
+        /// invested funds is left in free funds.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Can only be called with admin badge in auth zone
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/set_allocation_bounds.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/set_allocation_bounds.rtm")]
+        /// ```
+        pub fn set_allocation_bounds(&mut self,
+                                     vehicle: ComponentAddress,
+                                     min_percent: Decimal,
+                                     max_percent: Decimal) {
+            assert!(self.investments.contains_key(&vehicle),
+                    "We do not use this investment vehicle");
+            assert!(!min_percent.is_negative(),
+                    "Minimum allocation cannot be negative");
+            assert!(max_percent <= dec!("100"),
+                    "Maximum allocation cannot be greater than 100%");
+            assert!(min_percent <= max_percent,
+                    "Minimum allocation cannot be greater than maximum allocation");
+            let other_minimums = self.allocation_bounds.iter()
+                .filter(|(iv, _)| **iv != vehicle)
+                .fold(Decimal::ZERO, |acc, (_, (min, _))| acc + *min);
+            assert!(other_minimums + min_percent <= dec!("100"),
+                    "Minimum allocations cannot add up to more than 100%");
+            self.allocation_bounds.insert(vehicle, (min_percent, max_percent));
+        }
+
+        /// Retrieves the allocation bounds set for our investment
+        /// vehicles. Vehicles without bounds are not included.
+        ///
+        /// The return value maps from investment vehicle address to
+        /// its minimum and maximum percentage of our invested funds.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/read_allocation_bounds.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/read_allocation_bounds.rtm")]
+        /// ```
+        pub fn read_allocation_bounds(&self) -> HashMap<ComponentAddress, (Decimal, Decimal)> {
+            self.allocation_bounds.clone()
+        }
+
+        /// Returns the realized performance of an investment vehicle
+        /// as of our last full maintenance cycle.
+        ///
+        /// The return tuple contains, in order:
+        ///
+        /// 0. The realized yield per epoch, e.g. 0.01 for 1%
+        /// 1. The maximum drawdown, e.g. 0.2 for 20%
+        /// 2. The number of epochs we have been tracking the vehicle
+        ///
+        /// Will panic if we have no performance history for the
+        /// vehicle.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/read_vehicle_performance.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/read_vehicle_performance.rtm")]
+        /// ```
+        pub fn read_vehicle_performance(&self, vehicle: ComponentAddress)
+                                        -> (Decimal, Decimal, u64) {
+            let performance = self.performance.get(&vehicle)
+                .expect("No performance history for this investment vehicle");
+            (performance.realized_yield(),
+             performance.max_drawdown,
+             performance.last_epoch - performance.first_epoch)
+        }
+
+        /// Returns the weights we are currently using for our
+        /// non-halted investment vehicles. Unless risk-weighted
+        /// allocation is in use these are the configured weights.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/read_effective_weights.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/read_effective_weights.rtm")]
+        /// ```
+        pub fn read_effective_weights(&self) -> HashMap<ComponentAddress, Decimal> {
+            let mut weights = HashMap::new();
+            for iv in self.investments.keys() {
+                if !self.halted_investments.contains(iv) {
+                    weights.insert(iv.clone(), self.weight_of(iv));
+                }
+            }
+            weights
+        }
+
+        /// Returns how many entries there are in our rebalance
+        /// log. Entries are numbered from zero and up.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/read_rebalance_log_length.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/read_rebalance_log_length.rtm")]
+        /// ```
+        pub fn read_rebalance_log_length(&self) -> u64 {
+            self.rebalance_log_length
+        }
+
+        /// Retrieves an entry from our rebalance log. There is one
+        /// entry for every full maintenance cycle we have run.
+        ///
+        /// Will panic if there is no such entry.
+        ///
+        /// ---
+        ///
+        /// **Access control:** Read only, can be called by anyone.
+        ///
+        /// **Transaction manifest:**
+        /// `rtm/radfolio/read_rebalance_log_entry.rtm`
+        /// ```text
+        #[doc = include_str!("../rtm/radfolio/read_rebalance_log_entry.rtm")]
+        /// ```
+        pub fn read_rebalance_log_entry(&self, index: u64) -> RebalanceRecord {
+            self.rebalance_log.get(&index).expect("No such rebalance log entry")
+        }
+
         /// Triggers a full fund maintenance cycle. All non-halted
         /// investment vehicles will have profits collected from them,
         /// and they will then be rebalanced towards their desired
@@ -1754,20 +2463,208 @@ blueprint! {
             for v in self.investments.keys() {
                 if !self.halted_investments.contains(v) {
                     if let Some(profits) = self.iv_withdraw_profits(v) {
+                        Radfolio::record_flow(&mut self.performance, v, -profits.amount());
                         self.free_funds.put(profits);
                     }
                 }
             }
         }
 
+        /// Pays out queued redemption tickets in order for as long as
+        /// our free funds can cover the next ticket in line. Funds
+        /// for paid tickets are set aside until they are claimed.
+        fn pay_redemption_queue(&mut self) {
+            let cmgr: &ResourceManager = borrow_resource_manager!(self.coupon_address);
+            let tmgr: &ResourceManager = borrow_resource_manager!(self.redemption_ticket_address);
+            while !self.redemption_queue.is_empty() {
+                let nfid = self.redemption_queue[0].clone();
+                let mut ticket: RedemptionTicket = tmgr.get_non_fungible_data(&nfid);
+                let value = self.value_of(ticket.coupons, cmgr);
+                if value > self.free_funds.amount() { break; }
+
+                let mut payout = self.free_funds.take(value);
+                self.charge_fees(self.withdraw_fee_bps, self.withdraw_fee_partner_bps,
+                                 &mut payout, &ticket.partner);
+                let coupons = self.queued_coupons.take(ticket.coupons);
+                ticket.payout = Some(payout.amount());
+                self.mint_badge.authorize(|| {
+                    coupons.burn();
+                    tmgr.update_non_fungible_data(&nfid, ticket);
+                });
+                self.redemption_funds.put(payout);
+                self.redemption_queue.remove(0);
+            }
+        }
+
+        /// Calculates the current value of the coupons locked up in
+        /// our withdrawal queue.
+        fn calc_queued_value(&self) -> Decimal {
+            if self.queued_coupons.is_empty() { return Decimal::ZERO; }
+            let cmgr: &ResourceManager = borrow_resource_manager!(self.coupon_address);
+            self.value_of(self.queued_coupons.amount(), cmgr)
+        }
+
+        /// Notes down funds moving into (positive `inflow`) or out of
+        /// (negative `inflow`) an investment vehicle so that they
+        /// aren't mistaken for gains or losses when we next sample
+        /// its performance.
+        fn record_flow(performance: &mut HashMap<ComponentAddress, VehiclePerformance>,
+                       iv: &ComponentAddress,
+                       inflow: Decimal) {
+            if let Some(performance) = performance.get_mut(iv) {
+                performance.net_inflow += inflow;
+            }
+        }
+
+        /// Samples the investment value of all our non-halted
+        /// investment vehicles, updating their performance history.
+        fn sample_performance(&mut self, iv_invested: &HashMap<ComponentAddress, Decimal>) {
+            let epoch = Runtime::current_epoch();
+            for (iv, value) in iv_invested {
+                match self.performance.get_mut(iv) {
+                    Some(performance) => performance.sample(*value, epoch),
+                    None => {
+                        self.performance.insert(iv.clone(),
+                                                VehiclePerformance::new(*value, epoch));
+                    }
+                }
+            }
+        }
+
+        /// Calculates the weights to use for our non-halted
+        /// investment vehicles under risk-weighted allocation.
+        ///
+        /// Each vehicle's configured weight is scaled by its expected
+        /// growth over one maintenance interval, and then by what
+        /// remains after its maximum drawdown. The resulting shares
+        /// are then held within the vehicle's allocation bounds, with
+        /// any excess or shortfall spread among the remaining
+        /// vehicles in proportion to their scores.
+        ///
+        /// The returned weights are percentages of our invested
+        /// funds. If the maximum allocations of our vehicles add up to
+        /// less than 100 so do the weights, and the rest of our
+        /// invested funds is left in free funds.
+        fn calc_risk_weights(&self) -> HashMap<ComponentAddress, Decimal> {
+            let mut scores = HashMap::new();
+            let mut total_score = Decimal::ZERO;
+            for (iv, weight) in &self.investments {
+                if self.halted_investments.contains(iv) { continue; }
+                let mut score = *weight;
+                if let Some(performance) = self.performance.get(iv) {
+                    let growth = Decimal::ONE
+                        + performance.realized_yield() * self.investment_update_interval_epochs;
+                    score = if growth.is_positive() {
+                        score * growth * (Decimal::ONE - performance.max_drawdown)
+                    } else { Decimal::ZERO };
+                }
+                total_score += score;
+                scores.insert(iv.clone(), score);
+            }
+
+            let mut weights = HashMap::new();
+            if !total_score.is_positive() {
+                for iv in scores.keys() { weights.insert(iv.clone(), Decimal::ZERO); }
+                return weights;
+            }
+
+            // Vehicles configured to zero weight stay out of use, the
+            // rest compete for the remaining percentage.
+            let mut unsettled: HashSet<ComponentAddress> = HashSet::new();
+            for (iv, score) in &scores {
+                if self.investments.get(iv).unwrap().is_zero() {
+                    weights.insert(iv.clone(), Decimal::ZERO);
+                } else {
+                    unsettled.insert(iv.clone());
+                }
+            }
+            let mut remaining = dec!("100");
+            while !unsettled.is_empty() {
+                let unsettled_score = unsettled.iter()
+                    .fold(Decimal::ZERO, |acc, iv| acc + *scores.get(iv).unwrap());
+                let mut clamped = Vec::new();
+                for iv in &unsettled {
+                    let share = if unsettled_score.is_positive() {
+                        remaining * *scores.get(iv).unwrap() / unsettled_score
+                    } else { remaining / unsettled.len() as u64 };
+                    let (min, max) = self.allocation_bounds.get(iv)
+                        .cloned().unwrap_or((Decimal::ZERO, dec!("100")));
+                    if share < min {
+                        clamped.push((iv.clone(), min));
+                    } else if share > max {
+                        clamped.push((iv.clone(), max));
+                    }
+                }
+                if clamped.is_empty() {
+                    for iv in &unsettled {
+                        let share = if unsettled_score.is_positive() {
+                            remaining * *scores.get(iv).unwrap() / unsettled_score
+                        } else { remaining / unsettled.len() as u64 };
+                        weights.insert(iv.clone(), share);
+                    }
+                    break;
+                }
+                for (iv, weight) in clamped {
+                    unsettled.remove(&iv);
+                    remaining -= weight;
+                    weights.insert(iv, weight);
+                }
+                if remaining.is_negative() { remaining = Decimal::ZERO; }
+            }
+            weights
+        }
+
+        /// Adds an entry to our rebalance log describing the current
+        /// state of our non-halted investment vehicles.
+        fn log_rebalance(&mut self) {
+            let (total_funds, iv_invested) = self.calc_iv_funds(None);
+            let mut allocations = Vec::new();
+            for (iv, value) in iv_invested {
+                let (realized_yield, max_drawdown) = match self.performance.get(&iv) {
+                    Some(performance) => (performance.realized_yield(), performance.max_drawdown),
+                    None => (Decimal::ZERO, Decimal::ZERO),
+                };
+                allocations.push(RebalanceAllocation {
+                    vehicle: iv,
+                    configured_weight: *self.investments.get(&iv).unwrap(),
+                    weight: self.weight_of(&iv),
+                    realized_yield,
+                    max_drawdown,
+                    value,
+                });
+            }
+            self.rebalance_log.insert(self.rebalance_log_length, RebalanceRecord {
+                epoch: Runtime::current_epoch(),
+                risk_weighted: self.risk_weighted_allocation,
+                total_funds,
+                free_funds: self.free_funds.amount(),
+                allocations,
+            });
+            self.rebalance_log_length += 1;
+        }
+
+        /// The weight to use for an investment vehicle. This is its
+        /// configured weight unless risk-weighted allocation is in
+        /// use and we have calculated weights for it.
+        fn weight_of(&self, iv: &ComponentAddress) -> Decimal {
+            if self.uses_risk_weights() {
+                // Vehicles added since our last full maintenance
+                // cycle wait until the next one to be given a weight
+                *self.effective_weights.get(iv).unwrap_or(&Decimal::ZERO)
+            } else {
+                *self.investments.get(iv).unwrap()
+            }
+        }
+
         /// Runs through a fund maintenance cycle. If a full cycle is
         /// forced or if certain criteria are met then a full
         /// maintenance is done. Otherwise we do a toned-down
         /// maintenance.
         ///
-        /// A full maintenance will recover profits from investment
-        /// vehicles and then rebalance them towards their configured
-        /// weights.
+        /// A full maintenance will sample the performance of our
+        /// investment vehicles, recover profits from them and then
+        /// rebalance them towards their weights. The outcome is
+        /// recorded in our rebalance log.
         ///
         /// Ideally we would do this every time a deposit or withdraw
         /// happens but these are potentially very computationally
@@ -1791,6 +2688,7 @@ blueprint! {
         /// and even this only if the current free funds are getting
         /// low.
         fn maintain_fund(&mut self, forced: bool) {
+            self.pay_redemption_queue();
             if self.investments.len() == 0 {
                 return;
             }
@@ -1802,9 +2700,12 @@ blueprint! {
             }
 
             let free_percent = (self.free_funds.amount() / total_funds) * 100;
-            let target_free_funds = total_funds * self.free_funds_target_percent / 100;
+            // We hold back enough to pay our queued redemption
+            // tickets on top of our usual free funds
+            let reserve = self.calc_queued_value();
+            let target_free_funds = total_funds * self.free_funds_target_percent / 100 + reserve;
 
-            let mut total_weight = self.calc_total_weight();
+            let mut total_weight = self.calc_weight_basis();
             
             if forced
                 || (Runtime::current_epoch() >=
@@ -1812,21 +2713,35 @@ blueprint! {
                 || free_percent > self.free_funds_target_percent * 2
             {
                 // Run a full fund update
+                // See how our vehicles have been doing, and adjust
+                // weights if we're allocating by performance
+                self.sample_performance(&iv_invested);
+                if self.risk_weighted_allocation {
+                    self.effective_weights = self.calc_risk_weights();
+                    total_weight = self.calc_weight_basis();
+                }
+
                 // Free up profits and excess funds
                 self.recover_profits_from_ivs();
                 let (total_funds, iv_invested) = self.calc_iv_funds(None);
 
-                self.trim_vehicles_to_weight(iv_invested, total_funds, total_weight);
+                self.trim_vehicles_to_weight(iv_invested, total_funds, total_weight, reserve);
+                self.pay_redemption_queue();
                 let (total_funds, iv_invested) = self.calc_iv_funds(None);
+                // Tickets we just paid no longer need their reserve
+                let target_free_funds = target_free_funds - reserve + self.calc_queued_value();
                 
                 // Add funds to bring up to weight
                 let filled_ivs =
                     self.fund_vehicles(iv_invested, total_funds, total_weight, target_free_funds);
-                if !filled_ivs.is_empty() {
+                // Risk weights are bounded percentages, so handing a
+                // filled vehicle's share to the others could push
+                // them past their maximum. It stays in free funds.
+                if !filled_ivs.is_empty() && !self.uses_risk_weights() {
                     // Some vehicles didn't accept the full funds
                     // passed to them, so do a second pass among the
                     // vehicles that are still accepting funds
-                    for iv in &filled_ivs { total_weight -= *self.investments.get(&iv).unwrap(); }
+                    for iv in &filled_ivs { total_weight -= self.weight_of(&iv); }
                     if total_weight.is_positive() {
                         let (total_funds, iv_invested) = self.calc_iv_funds(Some(filled_ivs));
                         self.fund_vehicles(iv_invested, total_funds, total_weight, target_free_funds);
@@ -1835,10 +2750,11 @@ blueprint! {
                     // returns so we stop here.
                 }
                 
+                self.log_rebalance();
                 self.last_update_epoch = Runtime::current_epoch();
             } else {
                 let minimum_percent = self.free_funds_target_percent / 2;
-                if free_percent < minimum_percent {
+                if free_percent < minimum_percent || !self.redemption_queue.is_empty() {
                     // Run a toned-down update attempting to free up
                     // some funds
 
@@ -1847,12 +2763,16 @@ blueprint! {
 
                     // First just collect any outstanding profits
                     self.recover_profits_from_ivs();
+                    self.pay_redemption_queue();
                     if total_funds.is_positive()
-                        && (self.free_funds.amount() - old_free_funds) < missing_free_funds
+                        && ((self.free_funds.amount() - old_free_funds) < missing_free_funds
+                            || !self.redemption_queue.is_empty())
                     {
                         // If that wasn't enough, ask investment
                         // vehicles to give up some of their funds
-                        self.trim_vehicles_to_weight(iv_invested, total_funds, total_weight);
+                        self.trim_vehicles_to_weight(iv_invested, total_funds, total_weight,
+                                                     reserve);
+                        self.pay_redemption_queue();
                     }
                 }
             }
@@ -1861,25 +2781,34 @@ blueprint! {
         /// Will try to reduce the funding level of any investment
         /// vehicles that are currently above their configured weight.
         ///
+        /// The `reserve` is held back from investment on top of our
+        /// free funds target.
+        ///
         /// Do not call this method with `total_invested <= 0`.
         fn trim_vehicles_to_weight(&mut self,
                                    iv_invested: HashMap<ComponentAddress, Decimal>,
                                    total_funds: Decimal,
-                                   total_weight: Decimal) {
+                                   total_weight: Decimal,
+                                   reserve: Decimal) {
             // Remove funds to bring down to weight
             for iv in self.investments.keys() {
                 if !self.halted_investments.contains(iv) {
-                    let free_funds_target = self.free_funds_target_percent * total_funds / 100;
-                    let myweight = *self.investments.get(iv).unwrap();
+                    let free_funds_target =
+                        self.free_funds_target_percent * total_funds / 100 + reserve;
+                    let myweight = self.weight_of(iv);
                     let invested = *iv_invested.get(iv).unwrap();
                     let investment_target = if myweight.is_zero() { Decimal::ZERO } else {
                         (total_funds - free_funds_target)
                             * (myweight / total_weight)
                     };
+                    // A large reserve can push the target below zero
+                    let investment_target = if investment_target.is_negative() { Decimal::ZERO }
+                                            else { investment_target };
                     let investment_overshoot = invested - investment_target;
 
                     if investment_overshoot.is_positive() {
                         if let Some(returns) = self.iv_reduce_funds(iv, investment_overshoot) {
+                            Radfolio::record_flow(&mut self.performance, iv, -returns.amount());
                             self.free_funds.put(returns);
                         }
                     }
@@ -1913,7 +2842,7 @@ blueprint! {
 
             // Now distribute excess_funds out to our vehicles
             for iv in iv_invested.keys() {
-                let myweight = *self.investments.get(iv).unwrap();
+                let myweight = self.weight_of(iv);
                 let investment_target = if myweight.is_zero() { Decimal::ZERO } else {
                     (total_funds - free_funds_amount + excess_funds)
                         * (myweight / total_weight)
@@ -1922,7 +2851,10 @@ blueprint! {
                 let investment_undershoot = investment_target - currently_invested;
                 if investment_undershoot.is_positive() {
                     let addition = self.free_funds.take(investment_undershoot);
+                    let added = addition.amount();
+                    Radfolio::record_flow(&mut self.performance, iv, added);
                     if let Some(returns) = self.iv_add_funds(iv, addition) {
+                        Radfolio::record_flow(&mut self.performance, iv, -returns.amount());
                         if !returns.amount().is_zero() {
                             filled_ivs.insert(iv.clone());
                         }
@@ -1934,13 +2866,32 @@ blueprint! {
             filled_ivs
         }
 
-        /// Calculates the total weights configured for non-halted
+        /// Whether we are currently allocating by the weights from
+        /// [calc_risk_weights][Self::calc_risk_weights] rather than
+        /// by configured weights.
+        fn uses_risk_weights(&self) -> bool {
+            self.risk_weighted_allocation && !self.effective_weights.is_empty()
+        }
+
+        /// Calculates the weight that our investment vehicles'
+        /// weights are divided by to find their share of our
+        /// invested funds.
+        ///
+        /// Risk weights are percentages of our invested funds, so
+        /// they are divided by 100. When their allocation bounds keep
+        /// them from adding up to 100 the remainder is left in free
+        /// funds. Configured weights are divided by their total.
+        fn calc_weight_basis(&self) -> Decimal {
+            if self.uses_risk_weights() { dec!("100") } else { self.calc_total_weight() }
+        }
+
+        /// Calculates the total weights in use for non-halted
         /// investment vehicles.
         fn calc_total_weight(&self) -> Decimal {
             let mut total = Decimal::ZERO;
-            for (iv, weight) in &self.investments {
+            for iv in self.investments.keys() {
                 if !self.halted_investments.contains(iv) {
-                    total += *weight;
+                    total += self.weight_of(iv);
                 }
             }
             total
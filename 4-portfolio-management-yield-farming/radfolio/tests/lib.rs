@@ -998,6 +998,118 @@ fn withdraw(radfolio: &RadfolioComponent, account: &Account,
     println!("{}", output);
 }
 
+/// Queues a withdrawal from the fund, via
+/// rtm/radfolio/queue_withdrawal.rtm
+///
+/// Note that "amount" is number of coupons to lock up.
+fn queue_withdrawal(radfolio: &RadfolioComponent, account: &Account,
+                    amount: &str, partner: Option<&str>) {
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/radfolio/queue_withdrawal.rtm")
+                .env("component", &radfolio.address)
+                .env("account", &account.address)
+                .env("amount", amount)
+                .env("coupon", &radfolio.coupon_address)
+                .env("partner", option_to_tm_string(partner, "NonFungibleId")));
+}
+
+/// Collects the funds of a paid redemption ticket, via
+/// rtm/radfolio/claim_withdrawal.rtm
+fn claim_withdrawal(radfolio: &RadfolioComponent, account: &Account,
+                    ticket_address: &str, ticket: &str) {
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/radfolio/claim_withdrawal.rtm")
+                .env("component", &radfolio.address)
+                .env("account", &account.address)
+                .env("ticket_address", ticket_address)
+                .env("ticket", ticket));
+}
+
+/// Cancels a queued withdrawal, via
+/// rtm/radfolio/cancel_withdrawal.rtm
+fn cancel_withdrawal(radfolio: &RadfolioComponent, account: &Account,
+                     ticket_address: &str, ticket: &str) {
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/radfolio/cancel_withdrawal.rtm")
+                .env("component", &radfolio.address)
+                .env("account", &account.address)
+                .env("ticket_address", ticket_address)
+                .env("ticket", ticket));
+}
+
+/// Pays out queued redemption tickets where possible, via
+/// rtm/radfolio/process_withdrawal_queue.rtm
+fn process_withdrawal_queue(radfolio: &RadfolioComponent) {
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/radfolio/process_withdrawal_queue.rtm")
+                .env("component", &radfolio.address));
+}
+
+/// Reads the redemption tickets waiting to be paid, via
+/// rtm/radfolio/read_withdrawal_queue.rtm
+fn read_withdrawal_queue(component: &RadfolioComponent) -> Vec<String>
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/radfolio/read_withdrawal_queue.rtm")
+                             .env("component", &component.address));
+
+    lazy_static! {
+        static ref RE_VEC: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ Vec<NonFungibleId>\(([^\n]*)\)"#,
+        )).unwrap();
+        static ref RE_NFID: Regex = Regex::new(r#"NonFungibleId\("(\w*)"\)"#).unwrap();
+    }
+
+    let vec = RE_VEC.captures(&output).expect(
+        "Failed to parse read_withdrawal_queue")[1].to_string();
+    RE_NFID.captures_iter(&vec).map(|c| c[1].to_string()).collect()
+}
+
+/// Reads the number of coupons in the withdrawal queue, via
+/// rtm/radfolio/read_queued_coupons.rtm
+fn read_queued_coupons(component: &RadfolioComponent) -> String
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/radfolio/read_queued_coupons.rtm")
+                             .env("component", &component.address));
+
+    lazy_static! {
+        static ref RE_COUPONS: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ Decimal\("(.*)"\)"#
+        )).unwrap();
+    }
+
+    RE_COUPONS.captures(&output).expect("Failed to parse read_queued_coupons")[1].to_string()
+}
+
+/// Finds the resource address of our redemption tickets, via
+/// rtm/radfolio/read_redemption_ticket_address.rtm
+fn read_redemption_ticket_address(component: &RadfolioComponent) -> String
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/radfolio/read_redemption_ticket_address.rtm")
+                             .env("component", &component.address));
+
+    lazy_static! {
+        static ref RE_TOK: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ ResourceAddress\("(.*)"\)"#
+        )).unwrap();
+    }
+
+    RE_TOK.captures(&output).expect(
+        "Failed to parse read_redemption_ticket_address")[1].to_string()
+}
+
 /// Forces the fund to do a full maintenance cycle, via
 /// rtm/radfolio/force_fund_maintenance.rtm
 fn force_fund_maintenance(component: &RadfolioComponent, account: &Account)
@@ -1077,6 +1189,178 @@ fn instantiate_interestbearing_mock(account: &Account, package_addr: &str,
     matches[1].to_string()
 }
 
+/// Creates a new RaDEX via
+/// rtm/mock/instantiate_radex.rtm
+///
+/// Returns the component created.
+fn instantiate_radex(package_addr: &str) -> String
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/mock/instantiate_radex.rtm")
+                             .env("package", &package_addr));
+
+    lazy_static! {
+        static ref RE_ADDR: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ ComponentAddress\("(\w*)"\)"#)).unwrap();
+    }
+
+    RE_ADDR.captures(&output).expect("Failed to parse instantiate_radex")[1].to_string()
+}
+
+/// Creates a RaDEX liquidity pool for a token pair, via
+/// rtm/mock/radex_new_liquidity_pool.rtm
+fn radex_new_liquidity_pool(radex: &str, account: &Account,
+                            token1: &str, amount1: &str,
+                            token2: &str, amount2: &str) {
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/mock/radex_new_liquidity_pool.rtm")
+                .env("component", radex)
+                .env("account", &account.address)
+                .env("token1", token1)
+                .env("amount1", amount1)
+                .env("token2", token2)
+                .env("amount2", amount2));
+}
+
+/// Creates a new DegenFi via
+/// rtm/mock/instantiate_degenfi.rtm
+///
+/// Returns a tuple containing first the component created and then
+/// the resource address of its SBT.
+fn instantiate_degenfi(account: &Account, package_addr: &str) -> (String, String)
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/mock/instantiate_degenfi.rtm")
+                             .env("account", &account.address)
+                             .env("package", &package_addr));
+
+    lazy_static! {
+        static ref RE_TUPLE: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ Tuple\(ComponentAddress\("(\w*)"\)"#)).unwrap();
+        static ref RE_RESOURCE: Regex = Regex::new(r"─ Resource: (\w*)").unwrap();
+    }
+
+    let component = RE_TUPLE.captures(&output).expect(
+        "Failed to parse instantiate_degenfi")[1].to_string();
+    // DegenFi makes seven resources of its own before its user
+    // management makes the SBT badge and then the SBT
+    let sbt = RE_RESOURCE.captures_iter(&output).nth(8).expect(
+        "Failed to parse DegenFi SBT address")[1].to_string();
+
+    (component, sbt)
+}
+
+/// Creates a DegenFi lending pool, via
+/// rtm/mock/degenfi_new_lending_pool.rtm
+///
+/// The account gets registered as a DegenFi user in the process.
+fn degenfi_new_lending_pool(degenfi: &str, account: &Account, sbt: &str,
+                            token: &str, amount: &str) {
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/mock/degenfi_new_lending_pool.rtm")
+                .env("component", degenfi)
+                .env("account", &account.address)
+                .env("sbt", sbt)
+                .env("token", token)
+                .env("amount", amount));
+}
+
+/// Creates a new RadexVehicle via
+/// rtm/vehicles/instantiate_radex_vehicle.rtm
+///
+/// Returns the component created.
+fn instantiate_radex_vehicle(package_addr: &str, radex: &str,
+                             investment_token: &str, pair_token: &str,
+                             iv_control_badge: &str) -> String
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/vehicles/instantiate_radex_vehicle.rtm")
+                             .env("package", &package_addr)
+                             .env("radex", radex)
+                             .env("investment_token", investment_token)
+                             .env("pair_token", pair_token)
+                             .env("iv_control_badge", iv_control_badge));
+
+    lazy_static! {
+        static ref RE_ADDR: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ ComponentAddress\("(\w*)"\)"#)).unwrap();
+    }
+
+    RE_ADDR.captures(&output).expect("Failed to parse instantiate_radex_vehicle")[1].to_string()
+}
+
+/// Creates a new DegenfiVehicle via
+/// rtm/vehicles/instantiate_degenfi_vehicle.rtm
+///
+/// Returns the component created.
+fn instantiate_degenfi_vehicle(package_addr: &str, degenfi: &str,
+                               investment_token: &str, rewards_account: &Account,
+                               iv_control_badge: &str) -> String
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/vehicles/instantiate_degenfi_vehicle.rtm")
+                             .env("package", &package_addr)
+                             .env("degenfi", degenfi)
+                             .env("investment_token", investment_token)
+                             .env("rewards_account", &rewards_account.address)
+                             .env("iv_control_badge", iv_control_badge));
+
+    lazy_static! {
+        static ref RE_ADDR: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ ComponentAddress\("(\w*)"\)"#)).unwrap();
+    }
+
+    RE_ADDR.captures(&output).expect("Failed to parse instantiate_degenfi_vehicle")[1].to_string()
+}
+
+/// Reads how many RaDEX tracking tokens a RadexVehicle holds, via
+/// rtm/vehicles/read_tracking_tokens.rtm
+fn read_tracking_tokens(component: &str) -> String
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/vehicles/read_tracking_tokens.rtm")
+                             .env("component", component));
+
+    lazy_static! {
+        static ref RE_DEC: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ Decimal\("(.*)"\)"#
+        )).unwrap();
+    }
+
+    RE_DEC.captures(&output).expect("Failed to parse read_tracking_tokens")[1].to_string()
+}
+
+/// Reads the id of a DegenfiVehicle's DegenFi SBT, via
+/// rtm/vehicles/read_sbt_id.rtm
+fn read_sbt_id(component: &str) -> String
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/vehicles/read_sbt_id.rtm")
+                             .env("component", component));
+
+    lazy_static! {
+        static ref RE_NFID: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ NonFungibleId\("(\w*)"\)"#
+        )).unwrap();
+    }
+
+    RE_NFID.captures(&output).expect("Failed to parse read_sbt_id")[1].to_string()
+}
+
 /// Adds a new current investment vehicle, via
 /// rtm/radfolio/add_investment_vehicle.rtm
 fn add_investment_vehicle(component: &RadfolioComponent, account: &Account,
@@ -1263,6 +1547,170 @@ fn read_halted_investment_vehicles(component: &RadfolioComponent)
 }
 
 
+/// Turns risk-weighted allocation on or off, via
+/// rtm/radfolio/set_risk_weighted_allocation.rtm
+fn set_risk_weighted_allocation(component: &RadfolioComponent,
+                                account: &Account,
+                                enabled: bool) {
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/radfolio/set_risk_weighted_allocation.rtm")
+                .env("component", &component.address)
+                .env("account", &account.address)
+                .env("admin_badge", &component.admin_address)
+                .env("enabled", enabled.to_string()));
+}
+
+/// Reads the risk-weighted allocation flag, via
+/// rtm/radfolio/read_risk_weighted_allocation.rtm
+fn read_risk_weighted_allocation(component: &RadfolioComponent) -> bool
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/radfolio/read_risk_weighted_allocation.rtm")
+                             .env("component", &component.address));
+
+    lazy_static! {
+        static ref RE_BOOL: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ (.*)"#
+        )).unwrap();
+    }
+
+    RE_BOOL.captures(&output).expect(
+        "Failed to parse read_risk_weighted_allocation")[1].parse().unwrap()
+}
+
+/// Sets the allocation bounds of an investment vehicle, via
+/// rtm/radfolio/set_allocation_bounds.rtm
+fn set_allocation_bounds(component: &RadfolioComponent, account: &Account,
+                         vehicle: &str, min_percent: &str, max_percent: &str) {
+    run_command(Command::new("resim")
+                .arg("run")
+                .arg("rtm/radfolio/set_allocation_bounds.rtm")
+                .env("component", &component.address)
+                .env("account", &account.address)
+                .env("admin_badge", &component.admin_address)
+                .env("vehicle", vehicle)
+                .env("min_percent", min_percent)
+                .env("max_percent", max_percent));
+}
+
+/// Reads the allocation bounds of our investment vehicles, via
+/// rtm/radfolio/read_allocation_bounds.rtm
+fn read_allocation_bounds(component: &RadfolioComponent)
+                          -> HashMap<String, (String, String)> {
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/radfolio/read_allocation_bounds.rtm")
+                             .env("component", &component.address));
+
+    lazy_static! {
+        static ref RE_BOUNDS: Regex = Regex::new(concat!(
+            r#"ComponentAddress\("(\w*)"\), "#,
+            r#"Tuple\(Decimal\("([.\w]*)"\), Decimal\("([.\w]*)"\)\)"#,
+        )).unwrap();
+    }
+
+    let mut bounds = HashMap::new();
+    for capture in RE_BOUNDS.captures_iter(&output) {
+        bounds.insert(capture[1].to_string(),
+                      (capture[2].to_string(), capture[3].to_string()));
+    }
+    bounds
+}
+
+/// Reads the realized performance of an investment vehicle, via
+/// rtm/radfolio/read_vehicle_performance.rtm
+///
+/// Returns a tuple of realized yield per epoch, maximum drawdown,
+/// and number of epochs tracked.
+fn read_vehicle_performance(component: &RadfolioComponent, vehicle: &str)
+                            -> (String, String, u64) {
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/radfolio/read_vehicle_performance.rtm")
+                             .env("component", &component.address)
+                             .env("vehicle", vehicle));
+
+    lazy_static! {
+        static ref RE_TUPLE: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ Tuple\(Decimal\("([.\w]*)"\), Decimal\("([.\w]*)"\), (\w*)u64\)"#,
+        )).unwrap();
+    }
+
+    let matches = RE_TUPLE.captures(&output).expect(
+        "Failed to parse read_vehicle_performance");
+    (matches[1].to_string(), matches[2].to_string(), matches[3].parse().unwrap())
+}
+
+/// Reads the weights currently in use for our investment vehicles,
+/// via rtm/radfolio/read_effective_weights.rtm
+fn read_effective_weights(component: &RadfolioComponent)
+                          -> HashMap<String, String> {
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/radfolio/read_effective_weights.rtm")
+                             .env("component", &component.address));
+
+    lazy_static! {
+        static ref RE_WEIGHT: Regex = Regex::new(concat!(
+            r#"ComponentAddress\("(\w*)"\), Decimal\("([.\w]*)"\)"#,
+        )).unwrap();
+    }
+
+    let mut weights = HashMap::new();
+    for capture in RE_WEIGHT.captures_iter(&output) {
+        weights.insert(capture[1].to_string(), capture[2].to_string());
+    }
+    weights
+}
+
+/// Reads the number of rebalance log entries, via
+/// rtm/radfolio/read_rebalance_log_length.rtm
+fn read_rebalance_log_length(component: &RadfolioComponent) -> u64
+{
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/radfolio/read_rebalance_log_length.rtm")
+                             .env("component", &component.address));
+
+    lazy_static! {
+        static ref RE_LENGTH: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ (.*)u64"#
+        )).unwrap();
+    }
+
+    RE_LENGTH.captures(&output).expect(
+        "Failed to parse read_rebalance_log_length")[1].parse().unwrap()
+}
+
+/// Reads a rebalance log entry, via
+/// rtm/radfolio/read_rebalance_log_entry.rtm
+///
+/// Returns the epoch of the entry and whether it was risk-weighted.
+fn read_rebalance_log_entry(component: &RadfolioComponent, index: u64)
+                            -> (u64, bool) {
+    let output = run_command(Command::new("resim")
+                             .arg("run")
+                             .arg("rtm/radfolio/read_rebalance_log_entry.rtm")
+                             .env("component", &component.address)
+                             .env("index", index.to_string()));
+
+    lazy_static! {
+        static ref RE_ENTRY: Regex = Regex::new(concat!(
+            r#"Instruction Outputs:\n\W*"#,
+            r#".─ Struct\((\w*)u64, (true|false),"#,
+        )).unwrap();
+    }
+
+    let matches = RE_ENTRY.captures(&output).expect(
+        "Failed to parse read_rebalance_log_entry");
+    (matches[1].parse().unwrap(), matches[2].parse().unwrap())
+}
+
 /// Changes the default account by calling "resim set-default-account ..."
 fn set_default_account(account: &Account) {
     run_command(Command::new("resim")
@@ -3155,3 +3603,465 @@ fn test_flurry_of_activity() {
     force_fund_maintenance(&radfolio, &owner);
     remove_investment_vehicles(&radfolio, &owner, vec![&*mock3].into_iter().collect());
 }
+
+
+/// Tests risk-weighted allocation, allocation bounds and the
+/// rebalance log.
+#[test]
+fn test_risk_weighted_allocation() {
+    reset_sim();
+    let user = create_account();
+    let package_addr = publish_package(None);
+
+    let radfolio = instantiate_radfolio(&user.address, &package_addr,
+                                        RADIX_TOKEN,
+                                        None, // participants nft address
+                                        "10", // free funds target %
+                                        10,   // investment update interval epochs
+                                        "100",// minimum deposit
+                                        None, // admin badge name
+                                        1,    // admin badge quantity
+                                        None, // coupon name
+                                        None, // deposit fee bps
+                                        None, // deposit fee partner bps
+                                        None, // withdraw fee bps
+                                        None, // withdraw fee partner bps
+                                        None, // mint badge name
+                                        None);// iv control badge name
+
+    let iv_control_address = read_iv_control_badge_address(&radfolio);
+
+    let xavier1 = create_account();
+    set_default_account(&xavier1);
+    let mock1 = instantiate_interestbearing_mock(&xavier1, &package_addr,
+                                                 "1", // interest per epoch
+                                                 "1000000", // treasury
+                                                 RADIX_TOKEN,
+                                                 &iv_control_address,
+                                                 None);   // max investment
+    let xavier2 = create_account();
+    set_default_account(&xavier2);
+    let mock2 = instantiate_interestbearing_mock(&xavier2, &package_addr,
+                                                 "5", // interest per epoch
+                                                 "1000000", // treasury
+                                                 RADIX_TOKEN,
+                                                 &iv_control_address,
+                                                 None);   // max investment
+    set_default_account(&user);
+
+    add_investment_vehicle(&radfolio, &user, &mock1, "1");
+    add_investment_vehicle(&radfolio, &user, &mock2, "1");
+
+    assert!(!read_risk_weighted_allocation(&radfolio),
+            "Risk-weighted allocation should start out off");
+    assert_eq!(0, read_rebalance_log_length(&radfolio),
+               "Rebalance log should start out empty");
+
+    set_current_epoch(10);
+    deposit(&radfolio, &user, "10000", None, None);
+
+    // Equal weights so each vehicle gets half of the 9000 invested
+    let investments = read_investments(&radfolio);
+    assert_delta(investments.get(&mock1).unwrap(), "4500", "mock1");
+    assert_delta(investments.get(&mock2).unwrap(), "4500", "mock2");
+    assert_eq!(1, read_rebalance_log_length(&radfolio),
+               "First maintenance should be logged");
+    assert_eq!((10, false), read_rebalance_log_entry(&radfolio, 0),
+               "First log entry should not be risk-weighted");
+
+    // Bounds must make sense and refer to vehicles we use
+    let result =
+        std::panic::catch_unwind(|| {
+            set_allocation_bounds(&radfolio, &user, &mock2, "60", "55");
+        });
+    assert!(result.is_err(), "Minimum above maximum should fail");
+    let result =
+        std::panic::catch_unwind(|| {
+            set_allocation_bounds(&radfolio, &user, &mock2, "0", "101");
+        });
+    assert!(result.is_err(), "Maximum above 100% should fail");
+    let result =
+        std::panic::catch_unwind(|| {
+            set_allocation_bounds(&radfolio, &user, &radfolio.address, "0", "50");
+        });
+    assert!(result.is_err(), "Bounds on unknown vehicle should fail");
+
+    set_allocation_bounds(&radfolio, &user, &mock2, "0", "55");
+    let bounds = read_allocation_bounds(&radfolio);
+    assert_eq!(1, bounds.len(), "Only mock2 should have bounds");
+    assert_eq!(("0".to_string(), "55".to_string()), *bounds.get(&mock2).unwrap(),
+               "mock2 bounds should be as set");
+
+    set_risk_weighted_allocation(&radfolio, &user, true);
+    assert!(read_risk_weighted_allocation(&radfolio),
+            "Risk-weighted allocation should now be on");
+
+    // After 10 epochs mock1 has grown 10% and mock2 50%
+    set_current_epoch(20);
+    force_fund_maintenance(&radfolio, &user);
+
+    let (realized_yield, drawdown, epochs) = read_vehicle_performance(&radfolio, &mock1);
+    assert_delta(&realized_yield, "0.01", "mock1 yield");
+    assert_eq!("0", drawdown, "mock1 should have no drawdown");
+    assert_eq!(10, epochs, "mock1 epochs tracked");
+    let (realized_yield, drawdown, _) = read_vehicle_performance(&radfolio, &mock2);
+    assert_delta(&realized_yield, "0.05", "mock2 yield");
+    assert_eq!("0", drawdown, "mock2 should have no drawdown");
+
+    // Scores are 1.1 and 1.5, which would give mock2 ~57.7% of
+    // invested funds but its bounds cap it at 55%.
+    let weights = read_effective_weights(&radfolio);
+    assert_delta(weights.get(&mock1).unwrap(), "45", "mock1 weight");
+    assert_delta(weights.get(&mock2).unwrap(), "55", "mock2 weight");
+
+    // Total funds are 12700 of which 11430 are invested
+    let investments = read_investments(&radfolio);
+    assert_delta(investments.get(&mock1).unwrap(), "5143.5", "mock1");
+    assert_delta(investments.get(&mock2).unwrap(), "6286.5", "mock2");
+    assert_delta("1270", &read_free_funds(&radfolio), "Free funds");
+
+    assert_eq!(2, read_rebalance_log_length(&radfolio),
+               "Second maintenance should be logged");
+    assert_eq!((20, true), read_rebalance_log_entry(&radfolio, 1),
+               "Second log entry should be risk-weighted");
+
+    // Turning it off takes us back to the configured weights
+    set_risk_weighted_allocation(&radfolio, &user, false);
+    let weights = read_effective_weights(&radfolio);
+    assert_eq!("1", weights.get(&mock1).unwrap(), "mock1 configured weight");
+    assert_eq!("1", weights.get(&mock2).unwrap(), "mock2 configured weight");
+}
+
+
+/// Tests that risk-weighted allocation keeps vehicles within their
+/// maximum allocation when the maximums add up to less than 100%.
+#[test]
+fn test_risk_weighted_allocation_with_maximums_below_100() {
+    reset_sim();
+    let user = create_account();
+    let package_addr = publish_package(None);
+
+    let radfolio = instantiate_radfolio(&user.address, &package_addr,
+                                        RADIX_TOKEN,
+                                        None, // participants nft address
+                                        "10", // free funds target %
+                                        10,   // investment update interval epochs
+                                        "100",// minimum deposit
+                                        None, // admin badge name
+                                        1,    // admin badge quantity
+                                        None, // coupon name
+                                        None, // deposit fee bps
+                                        None, // deposit fee partner bps
+                                        None, // withdraw fee bps
+                                        None, // withdraw fee partner bps
+                                        None, // mint badge name
+                                        None);// iv control badge name
+
+    let iv_control_address = read_iv_control_badge_address(&radfolio);
+
+    let xavier1 = create_account();
+    set_default_account(&xavier1);
+    let mock1 = instantiate_interestbearing_mock(&xavier1, &package_addr,
+                                                 "1", // interest per epoch
+                                                 "1000000", // treasury
+                                                 RADIX_TOKEN,
+                                                 &iv_control_address,
+                                                 None);   // max investment
+    let xavier2 = create_account();
+    set_default_account(&xavier2);
+    let mock2 = instantiate_interestbearing_mock(&xavier2, &package_addr,
+                                                 "5", // interest per epoch
+                                                 "1000000", // treasury
+                                                 RADIX_TOKEN,
+                                                 &iv_control_address,
+                                                 None);   // max investment
+    set_default_account(&user);
+
+    add_investment_vehicle(&radfolio, &user, &mock1, "1");
+    add_investment_vehicle(&radfolio, &user, &mock2, "1");
+
+    set_current_epoch(10);
+    deposit(&radfolio, &user, "10000", None, None);
+
+    // Minimums cannot claim more than all of our invested funds
+    set_allocation_bounds(&radfolio, &user, &mock1, "60", "100");
+    let result =
+        std::panic::catch_unwind(|| {
+            set_allocation_bounds(&radfolio, &user, &mock2, "50", "100");
+        });
+    assert!(result.is_err(), "Minimums above 100% in total should fail");
+
+    set_allocation_bounds(&radfolio, &user, &mock1, "0", "30");
+    set_allocation_bounds(&radfolio, &user, &mock2, "0", "40");
+    set_risk_weighted_allocation(&radfolio, &user, true);
+
+    // After 10 epochs mock1 has grown 10% and mock2 50%
+    set_current_epoch(20);
+    force_fund_maintenance(&radfolio, &user);
+
+    // Scores of 1.1 and 1.5 would give both more than their
+    // maximum, and the remaining 30% is not handed out.
+    let weights = read_effective_weights(&radfolio);
+    assert_delta(weights.get(&mock1).unwrap(), "30", "mock1 weight");
+    assert_delta(weights.get(&mock2).unwrap(), "40", "mock2 weight");
+
+    // Total funds are 12700 of which 11430 are invested, 30% of that
+    // stays in free funds on top of the 10% target
+    let investments = read_investments(&radfolio);
+    assert_delta(investments.get(&mock1).unwrap(), "3429", "mock1");
+    assert_delta(investments.get(&mock2).unwrap(), "4572", "mock2");
+    assert_delta("4699", &read_free_funds(&radfolio), "Free funds");
+}
+
+
+/// Tests queueing, paying, claiming and cancelling withdrawals.
+#[test]
+fn test_withdrawal_queue() {
+    reset_sim();
+    let user = create_account();
+    let package_addr = publish_package(None);
+
+    let radfolio = instantiate_radfolio(&user.address, &package_addr,
+                                        RADIX_TOKEN,
+                                        None, // participants nft address
+                                        "10", // free funds target %
+                                        50,   // investment update interval epochs
+                                        "100",// minimum deposit
+                                        None, // admin badge name
+                                        1,    // admin badge quantity
+                                        None, // coupon name
+                                        None, // deposit fee bps
+                                        None, // deposit fee partner bps
+                                        None, // withdraw fee bps
+                                        None, // withdraw fee partner bps
+                                        None, // mint badge name
+                                        None);// iv control badge name
+
+    let iv_control_address = read_iv_control_badge_address(&radfolio);
+    let ticket_address = read_redemption_ticket_address(&radfolio);
+
+    let xavier = create_account();
+    set_default_account(&xavier);
+    let mock = instantiate_interestbearing_mock(&xavier, &package_addr,
+                                                "0", // interest per epoch
+                                                "1000000", // treasury
+                                                RADIX_TOKEN,
+                                                &iv_control_address,
+                                                None);   // max investment
+    set_default_account(&user);
+
+    add_investment_vehicle(&radfolio, &user, &mock, "1");
+    deposit(&radfolio, &user, "10000", None, None);
+    assert_delta("1000", &read_free_funds(&radfolio), "Free funds after deposit");
+
+    // With our only vehicle halted there is no way to get at the
+    // funds invested in it
+    halt_investment_vehicles(&radfolio, &user, vec![&*mock].into_iter().collect());
+
+    queue_withdrawal(&radfolio, &user, "3000", None);
+    queue_withdrawal(&radfolio, &user, "500", None);
+
+    // The second ticket could be covered by our free funds but it
+    // must wait its turn behind the first
+    let queue = read_withdrawal_queue(&radfolio);
+    assert_eq!(2, queue.len(), "Both tickets should be queued");
+    let ticket_a = queue[0].clone();
+    let ticket_b = queue[1].clone();
+    assert_delta("3500", &read_queued_coupons(&radfolio), "Queued coupons");
+    assert_delta("1000", &read_free_funds(&radfolio), "Free funds while queued");
+    assert_delta("6500", &get_balance(&user, &radfolio.coupon_address),
+                 "Coupons left after queueing");
+
+    let result =
+        std::panic::catch_unwind(|| {
+            withdraw(&radfolio, &user, "100", None, None);
+        });
+    assert!(result.is_err(), "Withdraw should not jump the queue");
+
+    let result =
+        std::panic::catch_unwind(|| {
+            claim_withdrawal(&radfolio, &user, &ticket_address, &ticket_b);
+        });
+    assert!(result.is_err(), "Unpaid ticket should not be claimable");
+
+    // Changing our mind about the second ticket
+    cancel_withdrawal(&radfolio, &user, &ticket_address, &ticket_b);
+    assert_eq!(vec![ticket_a.clone()], read_withdrawal_queue(&radfolio),
+               "Only the first ticket should remain");
+    assert_delta("3000", &read_queued_coupons(&radfolio), "Queued coupons after cancel");
+    assert_delta("7000", &get_balance(&user, &radfolio.coupon_address),
+                 "Coupons returned by cancel");
+
+    // Once the vehicle is back the queue can be paid
+    restart_investment_vehicles(&radfolio, &user, vec![&*mock].into_iter().collect());
+    process_withdrawal_queue(&radfolio);
+
+    assert!(read_withdrawal_queue(&radfolio).is_empty(), "Queue should now be empty");
+    assert_eq!("0", read_queued_coupons(&radfolio), "No coupons should be queued");
+    assert_delta("7000", &read_total_coupons(&radfolio), "Paid coupons should be burnt");
+    assert_delta("1000", &read_free_funds(&radfolio), "Free funds after payout");
+
+    let result =
+        std::panic::catch_unwind(|| {
+            cancel_withdrawal(&radfolio, &user, &ticket_address, &ticket_a);
+        });
+    assert!(result.is_err(), "Paid ticket should not be cancellable");
+
+    let balance_before: f64 = get_balance(&user, RADIX_TOKEN).parse().unwrap();
+    claim_withdrawal(&radfolio, &user, &ticket_address, &ticket_a);
+    let balance_after: f64 = get_balance(&user, RADIX_TOKEN).parse().unwrap();
+    assert_delta("3000", &(balance_after - balance_before).to_string(),
+                 "Claimed funds");
+    let result =
+        std::panic::catch_unwind(|| {
+            claim_withdrawal(&radfolio, &user, &ticket_address, &ticket_a);
+        });
+    assert!(result.is_err(), "Ticket should be burnt after claiming");
+
+    // And normal withdrawals work again
+    withdraw(&radfolio, &user, "100", None, None);
+}
+
+/// Tests investing through a RadexVehicle, and reducing that
+/// investment when a second vehicle joins.
+#[test]
+fn test_radex_vehicle() {
+    reset_sim();
+    let user = create_account();
+    let package_addr = publish_package(None);
+    let radex_package_addr = publish_package(Some("../../1-exchanges/RaDEX"));
+
+    let pair_token = _new_token_fixed("Pair", "PAIR", "1000000");
+    let radex = instantiate_radex(&radex_package_addr);
+    radex_new_liquidity_pool(&radex, &user,
+                             RADIX_TOKEN, "100000",
+                             &pair_token, "100000");
+
+    let radfolio = instantiate_radfolio(&user.address, &package_addr,
+                                        RADIX_TOKEN,
+                                        None, // participants nft address
+                                        "10", // free funds target %
+                                        50,   // investment update interval epochs
+                                        "100",// minimum deposit
+                                        None, // admin badge name
+                                        1,    // admin badge quantity
+                                        None, // coupon name
+                                        None, // deposit fee bps
+                                        None, // deposit fee partner bps
+                                        None, // withdraw fee bps
+                                        None, // withdraw fee partner bps
+                                        None, // mint badge name
+                                        None);// iv control badge name
+
+    let iv_control_address = read_iv_control_badge_address(&radfolio);
+
+    let vehicle = instantiate_radex_vehicle(&package_addr, &radex,
+                                            RADIX_TOKEN, &pair_token,
+                                            &iv_control_address);
+    assert_eq!("0", iv_read_investment_value(&vehicle), "Vehicle should start empty");
+    assert_eq!("0", read_tracking_tokens(&vehicle), "Vehicle should start without liquidity");
+
+    add_investment_vehicle(&radfolio, &user, &vehicle, "1");
+    deposit(&radfolio, &user, "10000", None, None);
+
+    // 9000 went into the pool, half of it swapped to the pair token
+    // at a small loss to fees and slippage
+    let value_full: f64 = iv_read_investment_value(&vehicle).parse().unwrap();
+    let tracking_full: f64 = read_tracking_tokens(&vehicle).parse().unwrap();
+    assert!(value_full > 8800.0 && value_full < 9000.0,
+            "Vehicle value {} should be a little under 9000", value_full);
+    assert!(tracking_full > 0.0, "Vehicle should hold tracking tokens");
+    assert_delta(&value_full.to_string(),
+                 read_investments(&radfolio).get(&vehicle).unwrap(),
+                 "Radfolio should agree with the vehicle's value");
+
+    // A second vehicle of equal weight makes the fund pull about half
+    // of the liquidity back out
+    let xavier = create_account();
+    set_default_account(&xavier);
+    let mock = instantiate_interestbearing_mock(&xavier, &package_addr,
+                                                "0.001", // interest per epoch
+                                                "1000000", // treasury
+                                                RADIX_TOKEN,
+                                                &iv_control_address,
+                                                None);   // max investment
+    set_default_account(&user);
+    add_investment_vehicle(&radfolio, &user, &mock, "1");
+    force_fund_maintenance(&radfolio, &user);
+
+    let value_half: f64 = iv_read_investment_value(&vehicle).parse().unwrap();
+    let tracking_half: f64 = read_tracking_tokens(&vehicle).parse().unwrap();
+    assert!(value_half > 4300.0 && value_half < 4600.0,
+            "Vehicle value {} should be about half of what it was", value_half);
+    assert!(tracking_half > 0.0 && tracking_half < tracking_full,
+            "Vehicle should have redeemed some but not all of its tracking tokens");
+    assert!(iv_read_investment_value(&mock).parse::<f64>().unwrap() > 4300.0,
+            "The funds should have moved to the mock");
+}
+
+/// Tests investing through a DegenfiVehicle, and reducing that
+/// investment when a second vehicle joins.
+#[test]
+fn test_degenfi_vehicle() {
+    reset_sim();
+    let user = create_account();
+    let package_addr = publish_package(None);
+    let degenfi_package_addr = publish_package(Some("../../3-lending/degenfi"));
+
+    let (degenfi, sbt) = instantiate_degenfi(&user, &degenfi_package_addr);
+    degenfi_new_lending_pool(&degenfi, &user, &sbt, RADIX_TOKEN, "1000");
+
+    let radfolio = instantiate_radfolio(&user.address, &package_addr,
+                                        RADIX_TOKEN,
+                                        None, // participants nft address
+                                        "10", // free funds target %
+                                        50,   // investment update interval epochs
+                                        "100",// minimum deposit
+                                        None, // admin badge name
+                                        1,    // admin badge quantity
+                                        None, // coupon name
+                                        None, // deposit fee bps
+                                        None, // deposit fee partner bps
+                                        None, // withdraw fee bps
+                                        None, // withdraw fee partner bps
+                                        None, // mint badge name
+                                        None);// iv control badge name
+
+    let iv_control_address = read_iv_control_badge_address(&radfolio);
+
+    // The vehicle registers its own DegenFi user, with Degen token
+    // rewards going to Rachel
+    let rachel = create_account();
+    let vehicle = instantiate_degenfi_vehicle(&package_addr, &degenfi,
+                                              RADIX_TOKEN, &rachel,
+                                              &iv_control_address);
+    assert!(!read_sbt_id(&vehicle).is_empty(), "Vehicle should have an SBT");
+    assert_eq!("0", iv_read_investment_value(&vehicle), "Vehicle should start empty");
+
+    add_investment_vehicle(&radfolio, &user, &vehicle, "1");
+    deposit(&radfolio, &user, "10000", None, None);
+
+    // Supplying to the lending pool is lossless so all of the 9000
+    // invested shows in the deposit value
+    assert_delta("9000", &iv_read_investment_value(&vehicle), "Vehicle value");
+    assert_delta("9000", read_investments(&radfolio).get(&vehicle).unwrap(),
+                 "Radfolio investment");
+
+    // A second vehicle of equal weight makes the fund redeem half of
+    // our supply
+    let xavier = create_account();
+    set_default_account(&xavier);
+    let mock = instantiate_interestbearing_mock(&xavier, &package_addr,
+                                                "0.001", // interest per epoch
+                                                "1000000", // treasury
+                                                RADIX_TOKEN,
+                                                &iv_control_address,
+                                                None);   // max investment
+    set_default_account(&user);
+    add_investment_vehicle(&radfolio, &user, &mock, "1");
+    force_fund_maintenance(&radfolio, &user);
+
+    assert_delta("4500", &iv_read_investment_value(&vehicle), "Vehicle value after reduce");
+    assert_delta("4500", &iv_read_investment_value(&mock), "Mock value after reduce");
+    assert_delta("1000", &read_free_funds(&radfolio), "Free funds after reduce");
+}